@@ -22,10 +22,12 @@ extern "C" {
 typedef struct lol_html_HtmlRewriterBuilder lol_html_rewriter_builder_t;
 typedef struct lol_html_HtmlRewriter lol_html_rewriter_t;
 typedef struct lol_html_Doctype lol_html_doctype_t;
+typedef struct lol_html_DocumentStart lol_html_doc_start_t;
 typedef struct lol_html_DocumentEnd lol_html_doc_end_t;
 typedef struct lol_html_Comment lol_html_comment_t;
 typedef struct lol_html_TextChunk lol_html_text_chunk_t;
 typedef struct lol_html_Element lol_html_element_t;
+typedef struct lol_html_EndTag lol_html_end_tag_t;
 typedef struct lol_html_AttributesIterator lol_html_attributes_iterator_t;
 typedef struct lol_html_Attribute lol_html_attribute_t;
 typedef struct lol_html_Selector lol_html_selector_t;
@@ -103,6 +105,16 @@ typedef lol_html_rewriter_directive_t (*lol_html_element_handler_t)(
     void *user_data
 );
 
+typedef lol_html_rewriter_directive_t (*lol_html_end_tag_handler_t)(
+    lol_html_end_tag_t *end_tag,
+    void *user_data
+);
+
+typedef lol_html_rewriter_directive_t (*lol_html_doc_start_handler_t)(
+    lol_html_doc_start_t *doc_start,
+    void *user_data
+);
+
 typedef lol_html_rewriter_directive_t (*lol_html_doc_end_handler_t)(
     lol_html_doc_end_t *doc_end,
     void *user_data
@@ -161,6 +173,24 @@ void lol_html_rewriter_builder_add_document_content_handlers(
     void *doc_end_user_data
 );
 
+// Adds a document start handler to the builder.
+//
+// The handler is called before any output is produced. It can
+// optionally have associated user data which will be passed to
+// the handler on invocation along with the document start argument.
+//
+// If the handler returns LOL_HTML_STOP directive then rewriting
+// stops immediately and `write()` or `end()` of the rewriter methods
+// return an error code.
+//
+// WARNING: Pointers passed to the handler are valid only during the
+// handler execution. So they should never be leaked outside of the handler.
+void lol_html_rewriter_builder_add_document_start_handler(
+    lol_html_rewriter_builder_t *builder,
+    lol_html_doc_start_handler_t doc_start_handler,
+    void *doc_start_user_data
+);
+
 // Adds element content handlers to the builder for the
 // given CSS selector.
 //
@@ -286,6 +316,91 @@ lol_html_str_t *lol_html_doctype_public_id_get(const lol_html_doctype_t *doctype
 // Returns NULL if the doctype doesn't have a SYSTEM identifier.
 lol_html_str_t *lol_html_doctype_system_id_get(const lol_html_doctype_t *doctype);
 
+// Sets doctype's name.
+//
+// Name should be a valid UTF8-string. If `name` is NULL, the doctype
+// is serialized without the name.
+//
+// Returns 0 in case of success and -1 otherwise. The actual error message
+// can be obtained using `lol_html_take_last_error` function.
+int lol_html_doctype_name_set(
+    lol_html_doctype_t *doctype,
+    const char *name,
+    size_t name_len
+);
+
+// Sets doctype's PUBLIC identifier.
+//
+// Identifier should be a valid UTF8-string. If `public_id` is NULL, the doctype
+// is serialized without the PUBLIC identifier.
+//
+// Returns 0 in case of success and -1 otherwise. The actual error message
+// can be obtained using `lol_html_take_last_error` function.
+int lol_html_doctype_public_id_set(
+    lol_html_doctype_t *doctype,
+    const char *public_id,
+    size_t public_id_len
+);
+
+// Sets doctype's SYSTEM identifier.
+//
+// Identifier should be a valid UTF8-string. If `system_id` is NULL, the doctype
+// is serialized without the SYSTEM identifier.
+//
+// Returns 0 in case of success and -1 otherwise. The actual error message
+// can be obtained using `lol_html_take_last_error` function.
+int lol_html_doctype_system_id_set(
+    lol_html_doctype_t *doctype,
+    const char *system_id,
+    size_t system_id_len
+);
+
+// Inserts the content string before the doctype either as raw text or as HTML.
+//
+// Content should be a valid UTF8-string.
+//
+// Returns 0 in case of success and -1 otherwise. The actual error message
+// can be obtained using `lol_html_take_last_error` function.
+int lol_html_doctype_before(
+    lol_html_doctype_t *doctype,
+    const char *content,
+    size_t content_len,
+    bool is_html
+);
+
+// Inserts the content string after the doctype either as raw text or as HTML.
+//
+// Content should be a valid UTF8-string.
+//
+// Returns 0 in case of success and -1 otherwise. The actual error message
+// can be obtained using `lol_html_take_last_error` function.
+int lol_html_doctype_after(
+    lol_html_doctype_t *doctype,
+    const char *content,
+    size_t content_len,
+    bool is_html
+);
+
+// Replace the doctype with the content of the string which is interpreted
+// either as raw text or as HTML.
+//
+// Content should be a valid UTF8-string.
+//
+// Returns 0 in case of success and -1 otherwise. The actual error message
+// can be obtained using `lol_html_take_last_error` function.
+int lol_html_doctype_replace(
+    lol_html_doctype_t *doctype,
+    const char *content,
+    size_t content_len,
+    bool is_html
+);
+
+// Removes the doctype.
+void lol_html_doctype_remove(lol_html_doctype_t *doctype);
+
+// Returns `true` if the doctype has been removed.
+bool lol_html_doctype_is_removed(const lol_html_doctype_t *doctype);
+
 // Attaches custom user data to the doctype.
 //
 // The same doctype can be passed to multiple handlers if it has been
@@ -390,6 +505,14 @@ lol_html_text_chunk_content_t lol_html_text_chunk_content_get(
     const lol_html_text_chunk_t *chunk
 );
 
+// Returns the text chunk content with decoded character references.
+//
+// A character reference split between chunks is decoded as a part of the
+// chunk where it ends.
+lol_html_str_t lol_html_text_chunk_decoded_content_get(
+    const lol_html_text_chunk_t *chunk
+);
+
 // Returns `true` if the chunk is last in the current text node.
 bool lol_html_text_chunk_is_last_in_text_node(const lol_html_text_chunk_t *chunk);
 
@@ -507,6 +630,9 @@ lol_html_str_t lol_html_attribute_name_get(const lol_html_attribute_t *attribute
 // Returns the attribute value.
 lol_html_str_t lol_html_attribute_value_get(const lol_html_attribute_t *attribute);
 
+// Returns the attribute value with decoded character references.
+lol_html_str_t lol_html_attribute_decoded_value_get(const lol_html_attribute_t *attribute);
+
 // Returns the attribute value or NULL if attribute with the given name
 // doesn't exist on the element.
 //
@@ -520,6 +646,19 @@ lol_html_str_t *lol_html_element_get_attribute(
     size_t name_len
 );
 
+// Returns the attribute value with decoded character references or NULL if
+// attribute with the given name doesn't exist on the element.
+//
+// Name should be a valid UTF8-string.
+//
+// If the provided name is invalid UTF8-string the function returns NULL as well.
+// Therefore one should always check `lol_html_take_last_error` result after the call.
+lol_html_str_t *lol_html_element_get_decoded_attribute(
+    const lol_html_element_t *element,
+    const char *name,
+    size_t name_len
+);
+
 // Returns 1 if element has attribute with the given name, and 0 otherwise.
 // Returns -1 in case of an error.
 //
@@ -659,6 +798,73 @@ void lol_html_element_user_data_set(
 // Returns user data attached to the text chunk.
 void *lol_html_element_user_data_get(const lol_html_element_t *element);
 
+// Adds a handler that will be invoked once the end tag of the element is reached.
+//
+// The handler is also invoked if the element is closed implicitly. In this
+// case the handler receives the end tag that closes the element.
+//
+// Returns 0 in case of success and -1 otherwise (e.g. if the element can't
+// have an end tag). The actual error message can be obtained using
+// `lol_html_take_last_error` function.
+int lol_html_element_add_end_tag_handler(
+    lol_html_element_t *element,
+    lol_html_end_tag_handler_t end_tag_handler,
+    void *user_data
+);
+
+// End tag
+//---------------------------------------------------------------------
+
+// Returns the name of the end tag.
+lol_html_str_t lol_html_end_tag_name_get(const lol_html_end_tag_t *end_tag);
+
+// Inserts the content string before the end tag either as raw text or as HTML.
+//
+// Content should be a valid UTF8-string.
+//
+// Returns 0 in case of success and -1 otherwise. The actual error message
+// can be obtained using `lol_html_take_last_error` function.
+int lol_html_end_tag_before(
+    lol_html_end_tag_t *end_tag,
+    const char *content,
+    size_t content_len,
+    bool is_html
+);
+
+// Inserts the content string after the end tag either as raw text or as HTML.
+//
+// Content should be a valid UTF8-string.
+//
+// Returns 0 in case of success and -1 otherwise. The actual error message
+// can be obtained using `lol_html_take_last_error` function.
+int lol_html_end_tag_after(
+    lol_html_end_tag_t *end_tag,
+    const char *content,
+    size_t content_len,
+    bool is_html
+);
+
+// Removes the end tag.
+void lol_html_end_tag_remove(lol_html_end_tag_t *end_tag);
+
+// Returns `true` if the end tag has been removed.
+bool lol_html_end_tag_is_removed(const lol_html_end_tag_t *end_tag);
+
+// Inserts the content at the start of the document, either as raw text or as HTML.
+//
+// The content is inserted after the byte order mark if the input starts with it.
+//
+// The content should be a valid UTF-8 string.
+//
+// Returns 0 if successful, and -1 otherwise. The actual error message
+// can be obtained using the `lol_html_take_last_error` function.
+int lol_html_doc_start_prepend(
+    lol_html_doc_start_t *doc_start,
+    const char *content,
+    size_t content_len,
+    bool is_html
+);
+
 // Inserts the content at the end of the document, either as raw text or as HTML.
 //
 // The content should be a valid UTF-8 string.
@@ -0,0 +1,11 @@
+use super::*;
+
+#[no_mangle]
+pub extern "C" fn lol_html_doc_start_prepend(
+    document_start: *mut DocumentStart,
+    content: *const c_char,
+    content_len: size_t,
+    is_html: bool,
+) -> c_int {
+    content_insertion_fn_body! { document_start.prepend(content, content_len, is_html) }
+}
@@ -1,6 +1,9 @@
+use super::rewriter_builder::RewriterDirective;
 use super::*;
 use std::slice::Iter;
 
+type EndTagHandler = unsafe extern "C" fn(*mut EndTag, *mut c_void) -> RewriterDirective;
+
 #[no_mangle]
 pub extern "C" fn lol_html_element_tag_name_get(element: *const Element) -> Str {
     let element = to_ref!(element);
@@ -74,6 +77,13 @@ pub extern "C" fn lol_html_attribute_value_get(attribute: *const Attribute) -> S
     Str::new(attribute.value())
 }
 
+#[no_mangle]
+pub extern "C" fn lol_html_attribute_decoded_value_get(attribute: *const Attribute) -> Str {
+    let attribute = to_ref!(attribute);
+
+    Str::new(attribute.decoded_value())
+}
+
 #[no_mangle]
 pub extern "C" fn lol_html_element_get_attribute(
     element: *const Element,
@@ -86,6 +96,18 @@ pub extern "C" fn lol_html_element_get_attribute(
     Str::opt_ptr(element.get_attribute(name))
 }
 
+#[no_mangle]
+pub extern "C" fn lol_html_element_get_decoded_attribute(
+    element: *const Element,
+    name: *const c_char,
+    name_len: size_t,
+) -> *const Str {
+    let element = to_ref!(element);
+    let name = unwrap_or_ret_null! { to_str!(name, name_len) };
+
+    Str::opt_ptr(element.get_decoded_attribute(name))
+}
+
 #[no_mangle]
 pub extern "C" fn lol_html_element_has_attribute(
     element: *const Element,
@@ -208,6 +230,24 @@ pub extern "C" fn lol_html_element_is_removed(element: *mut Element) -> bool {
     to_ref_mut!(element).removed()
 }
 
+#[no_mangle]
+pub extern "C" fn lol_html_element_add_end_tag_handler(
+    element: *mut Element,
+    handler: EndTagHandler,
+    user_data: *mut c_void,
+) -> c_int {
+    let element = to_ref_mut!(element);
+
+    unwrap_or_ret_err_code! {
+        element.on_end_tag(move |end_tag| match unsafe { handler(end_tag, user_data) } {
+            RewriterDirective::Continue => Ok(()),
+            RewriterDirective::Stop => Err("The rewriter has been stopped.".into()),
+        })
+    };
+
+    0
+}
+
 #[no_mangle]
 pub extern "C" fn lol_html_element_user_data_set(element: *mut Element, user_data: *mut c_void) {
     to_ref_mut!(element).set_user_data(user_data);
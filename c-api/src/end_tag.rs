@@ -0,0 +1,36 @@
+use super::*;
+
+#[no_mangle]
+pub extern "C" fn lol_html_end_tag_name_get(end_tag: *const EndTag) -> Str {
+    Str::new(to_ref!(end_tag).name())
+}
+
+#[no_mangle]
+pub extern "C" fn lol_html_end_tag_before(
+    end_tag: *mut EndTag,
+    content: *const c_char,
+    content_len: size_t,
+    is_html: bool,
+) -> c_int {
+    content_insertion_fn_body! { end_tag.before(content, content_len, is_html) }
+}
+
+#[no_mangle]
+pub extern "C" fn lol_html_end_tag_after(
+    end_tag: *mut EndTag,
+    content: *const c_char,
+    content_len: size_t,
+    is_html: bool,
+) -> c_int {
+    content_insertion_fn_body! { end_tag.after(content, content_len, is_html) }
+}
+
+#[no_mangle]
+pub extern "C" fn lol_html_end_tag_remove(end_tag: *mut EndTag) {
+    to_ref_mut!(end_tag).remove();
+}
+
+#[no_mangle]
+pub extern "C" fn lol_html_end_tag_is_removed(end_tag: *const EndTag) -> bool {
+    to_ref!(end_tag).removed()
+}
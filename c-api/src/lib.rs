@@ -58,6 +58,17 @@ macro_rules! to_str {
     };
 }
 
+// NOTE: NULL is used to represent `None` for optional strings.
+macro_rules! to_opt_str {
+    ($data:ident, $len:ident) => {
+        if $data.is_null() {
+            Ok(None)
+        } else {
+            str::from_utf8(to_bytes!($data, $len)).map(Some)
+        }
+    };
+}
+
 macro_rules! static_c_str {
     ($s:expr) => {
         concat!($s, "\0").as_ptr() as *const c_char
@@ -119,7 +130,9 @@ macro_rules! get_user_data {
 mod comment;
 mod doctype;
 mod document_end;
+mod document_start;
 mod element;
+mod end_tag;
 mod errors;
 mod rewriter;
 mod rewriter_builder;
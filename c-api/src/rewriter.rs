@@ -56,8 +56,15 @@ pub extern "C" fn lol_html_rewriter_build(
         element_content_handlers: handlers.element,
         document_content_handlers: handlers.document,
         encoding: unwrap_or_ret_null! { encoding.try_into().or(Err(EncodingError::NonAsciiCompatibleEncoding)) },
+        detect_encoding: false,
+        transcoding: None,
+        output_encoding: None,
+        rewrite_meta_charset: false,
+        match_decoded_attribute_values: false,
         memory_settings,
         strict,
+        stop_signal: None,
+        stats: None,
     };
 
     let output_sink = ExternOutputSink::new(output_sink, output_sink_user_data);
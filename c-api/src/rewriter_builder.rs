@@ -12,6 +12,8 @@ type ElementHandler = unsafe extern "C" fn(*mut Element, *mut c_void) -> Rewrite
 type DoctypeHandler = unsafe extern "C" fn(*mut Doctype, *mut c_void) -> RewriterDirective;
 type CommentsHandler = unsafe extern "C" fn(*mut Comment, *mut c_void) -> RewriterDirective;
 type TextHandler = unsafe extern "C" fn(*mut TextChunk, *mut c_void) -> RewriterDirective;
+type DocumentStartHandler =
+    unsafe extern "C" fn(*mut DocumentStart, *mut c_void) -> RewriterDirective;
 type DocumentEndHandler = unsafe extern "C" fn(*mut DocumentEnd, *mut c_void) -> RewriterDirective;
 
 struct ExternHandler<F> {
@@ -54,6 +56,7 @@ pub struct ExternDocumentContentHandlers {
     doctype: ExternHandler<DoctypeHandler>,
     comments: ExternHandler<CommentsHandler>,
     text: ExternHandler<TextHandler>,
+    start: ExternHandler<DocumentStartHandler>,
     end: ExternHandler<DocumentEndHandler>,
 }
 
@@ -64,6 +67,7 @@ impl ExternDocumentContentHandlers {
         add_handler!(handlers, self.doctype);
         add_handler!(handlers, self.comments);
         add_handler!(handlers, self.text);
+        add_handler!(handlers, self.start);
         add_handler!(handlers, self.end);
 
         handlers
@@ -139,12 +143,32 @@ pub extern "C" fn lol_html_rewriter_builder_add_document_content_handlers(
         doctype: ExternHandler::new(doctype_handler, doctype_handler_user_data),
         comments: ExternHandler::new(comments_handler, comments_handler_user_data),
         text: ExternHandler::new(text_handler, text_handler_user_data),
+        start: ExternHandler::new(None, ptr::null_mut()),
         end: ExternHandler::new(document_end_handler, document_end_handler_user_data),
     };
 
     builder.document_content_handlers.push(handlers);
 }
 
+#[no_mangle]
+pub extern "C" fn lol_html_rewriter_builder_add_document_start_handler(
+    builder: *mut HtmlRewriterBuilder,
+    handler: Option<DocumentStartHandler>,
+    user_data: *mut c_void,
+) {
+    let builder = to_ref_mut!(builder);
+
+    let handlers = ExternDocumentContentHandlers {
+        doctype: ExternHandler::new(None, ptr::null_mut()),
+        comments: ExternHandler::new(None, ptr::null_mut()),
+        text: ExternHandler::new(None, ptr::null_mut()),
+        start: ExternHandler::new(handler, user_data),
+        end: ExternHandler::new(None, ptr::null_mut()),
+    };
+
+    builder.document_content_handlers.push(handlers);
+}
+
 #[no_mangle]
 pub extern "C" fn lol_html_rewriter_builder_add_element_content_handlers(
     builder: *mut HtmlRewriterBuilder,
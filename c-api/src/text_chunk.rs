@@ -22,6 +22,11 @@ pub extern "C" fn lol_html_text_chunk_content_get(chunk: *mut TextChunk) -> Text
     TextChunkContent::new(to_ref!(chunk))
 }
 
+#[no_mangle]
+pub extern "C" fn lol_html_text_chunk_decoded_content_get(chunk: *const TextChunk) -> Str {
+    Str::new(to_ref!(chunk).decoded_text().into_owned())
+}
+
 #[no_mangle]
 pub extern "C" fn lol_html_text_chunk_is_last_in_text_node(chunk: *mut TextChunk) -> bool {
     to_ref!(chunk).last_in_text_node()
@@ -109,6 +109,148 @@ static void test_get_user_data(void *user_data) {
     );
 }
 
+//-------------------------------------------------------------------------
+EXPECT_OUTPUT(
+    modify_doctype_output_sink,
+    "<!-- legacy --><!DOCTYPE html>&lt;/foo&gt;",
+    &EXPECTED_USER_DATA,
+    sizeof(EXPECTED_USER_DATA)
+)
+
+static lol_html_rewriter_directive_t modify_doctype(
+    lol_html_doctype_t *doctype,
+    void *user_data
+) {
+    UNUSED(user_data);
+
+    const char *name = "html";
+    const char *invalid_name = "foo bar";
+    const char *before = "<!-- legacy -->";
+    const char *after = "</foo>";
+
+    note("Set fields");
+    ok(!lol_html_doctype_name_set(doctype, name, strlen(name)));
+    ok(!lol_html_doctype_public_id_set(doctype, NULL, 0));
+    ok(!lol_html_doctype_system_id_set(doctype, NULL, 0));
+    ok(lol_html_doctype_name_set(doctype, invalid_name, strlen(invalid_name)) == -1);
+
+    lol_html_str_t *msg = lol_html_take_last_error();
+
+    str_eq(msg, "` ` character is forbidden in the doctype.");
+
+    lol_html_str_free(*msg);
+
+    note("Insert before/after");
+    ok(!lol_html_doctype_before(doctype, before, strlen(before), true));
+    ok(!lol_html_doctype_after(doctype, after, strlen(after), false));
+
+    return LOL_HTML_CONTINUE;
+}
+
+static void test_modify_doctype(void *user_data) {
+    lol_html_rewriter_builder_t *builder = lol_html_rewriter_builder_new();
+
+    lol_html_rewriter_builder_add_document_content_handlers(
+        builder,
+        &modify_doctype,
+        NULL,
+        NULL,
+        NULL,
+        NULL,
+        NULL,
+        NULL,
+        NULL
+    );
+
+    run_rewriter(
+        builder,
+        "<!DOCTYPE math SYSTEM \"http://www.w3.org/Math/DTD/mathml1/mathml.dtd\">",
+        modify_doctype_output_sink,
+        user_data
+    );
+}
+
+//-------------------------------------------------------------------------
+EXPECT_OUTPUT(
+    replace_doctype_output_sink,
+    "<!DOCTYPE html>",
+    &EXPECTED_USER_DATA,
+    sizeof(EXPECTED_USER_DATA)
+)
+
+static lol_html_rewriter_directive_t replace_doctype(
+    lol_html_doctype_t *doctype,
+    void *user_data
+) {
+    UNUSED(user_data);
+
+    const char *replacement = "<!DOCTYPE html>";
+
+    note("Replace");
+    ok(!lol_html_doctype_is_removed(doctype));
+    ok(!lol_html_doctype_replace(doctype, replacement, strlen(replacement), true));
+    ok(lol_html_doctype_is_removed(doctype));
+
+    return LOL_HTML_CONTINUE;
+}
+
+static void test_replace_doctype(void *user_data) {
+    lol_html_rewriter_builder_t *builder = lol_html_rewriter_builder_new();
+
+    lol_html_rewriter_builder_add_document_content_handlers(
+        builder,
+        &replace_doctype,
+        NULL,
+        NULL,
+        NULL,
+        NULL,
+        NULL,
+        NULL,
+        NULL
+    );
+
+    run_rewriter(builder, "<!doctype foo>", replace_doctype_output_sink, user_data);
+}
+
+//-------------------------------------------------------------------------
+EXPECT_OUTPUT(
+    remove_doctype_output_sink,
+    "<html></html>",
+    &EXPECTED_USER_DATA,
+    sizeof(EXPECTED_USER_DATA)
+)
+
+static lol_html_rewriter_directive_t remove_doctype(
+    lol_html_doctype_t *doctype,
+    void *user_data
+) {
+    UNUSED(user_data);
+
+    note("Remove");
+    lol_html_doctype_remove(doctype);
+    ok(lol_html_doctype_is_removed(doctype));
+
+    return LOL_HTML_CONTINUE;
+}
+
+static void test_remove_doctype(void *user_data) {
+    lol_html_rewriter_builder_t *builder = lol_html_rewriter_builder_new();
+
+    lol_html_rewriter_builder_add_document_content_handlers(
+        builder,
+        &remove_doctype,
+        NULL,
+        NULL,
+        NULL,
+        NULL,
+        NULL,
+        NULL,
+        NULL
+    );
+
+    run_rewriter(builder, "<!doctype html><html></html>", remove_doctype_output_sink, user_data);
+}
+
 //-------------------------------------------------------------------------
 static lol_html_rewriter_directive_t stop_rewriting(
     lol_html_doctype_t *doctype,
@@ -145,5 +287,8 @@ void test_doctype_api() {
 
     test_get_doctype_fields(&user_data);
     test_get_user_data(&user_data);
+    test_modify_doctype(&user_data);
+    test_replace_doctype(&user_data);
+    test_remove_doctype(&user_data);
     test_stop(&user_data);
 }
@@ -0,0 +1,95 @@
+#include "../../include/lol_html.h"
+#include "deps/picotest/picotest.h"
+#include "tests.h"
+#include "test_util.h"
+
+static int EXPECTED_USER_DATA = 43;
+
+//-------------------------------------------------------------------------
+EXPECT_OUTPUT(
+    prepend_to_empty_doc_output_sink,
+    "hello &amp; world<!--prepended text-->",
+    &EXPECTED_USER_DATA,
+    sizeof(EXPECTED_USER_DATA)
+);
+
+static lol_html_rewriter_directive_t prepend_to_empty_doc(
+    lol_html_doc_start_t *doc_start,
+    void *user_data
+) {
+    note("Prepend to an empty document");
+    ok(*(int*)user_data == EXPECTED_USER_DATA);
+
+    const char *prepend_html = "<!--prepended text-->";
+    ok(!lol_html_doc_start_prepend(doc_start, prepend_html, strlen(prepend_html), true));
+
+    const char *prepend_text = "hello & world";
+    ok(!lol_html_doc_start_prepend(doc_start, prepend_text, strlen(prepend_text), false));
+
+    return LOL_HTML_CONTINUE;
+}
+
+static void test_prepend_to_empty_doc(void *user_data) {
+    lol_html_rewriter_builder_t *builder = lol_html_rewriter_builder_new();
+
+    lol_html_rewriter_builder_add_document_start_handler(
+        builder,
+        prepend_to_empty_doc,
+        user_data
+    );
+
+    run_rewriter(
+        builder,
+        "",
+        prepend_to_empty_doc_output_sink,
+        user_data
+    );
+}
+
+//-------------------------------------------------------------------------
+EXPECT_OUTPUT(
+    prepend_at_start_output_sink,
+    "hello &amp; world<!--prepended text--><html><div>Hello</div></html>",
+    &EXPECTED_USER_DATA,
+    sizeof(EXPECTED_USER_DATA)
+);
+
+static lol_html_rewriter_directive_t prepend_at_start(
+    lol_html_doc_start_t *doc_start,
+    void *user_data
+) {
+    note("Prepend at the start");
+    ok(*(int*)user_data == EXPECTED_USER_DATA);
+
+    const char *prepend_html = "<!--prepended text-->";
+    ok(!lol_html_doc_start_prepend(doc_start, prepend_html, strlen(prepend_html), true));
+
+    const char *prepend_text = "hello & world";
+    ok(!lol_html_doc_start_prepend(doc_start, prepend_text, strlen(prepend_text), false));
+
+    return LOL_HTML_CONTINUE;
+}
+
+static void test_prepend_at_start(void *user_data) {
+    lol_html_rewriter_builder_t *builder = lol_html_rewriter_builder_new();
+
+    lol_html_rewriter_builder_add_document_start_handler(
+        builder,
+        prepend_at_start,
+        user_data
+    );
+
+    run_rewriter(
+        builder,
+        "<html><div>Hello</div></html>",
+        prepend_at_start_output_sink,
+        user_data
+    );
+}
+
+void document_start_api_test() {
+    int user_data = 43;
+
+    test_prepend_to_empty_doc(&user_data);
+    test_prepend_at_start(&user_data);
+}
@@ -519,6 +519,68 @@ static void test_iterate_attributes(lol_html_selector_t *selector, void *user_da
     run_rewriter(builder, "<div foo=42 bar='1337'>", output_sink_stub, user_data);
 }
 
+//-------------------------------------------------------------------------
+static lol_html_rewriter_directive_t get_decoded_attributes(
+    lol_html_element_t *element,
+    void *user_data
+) {
+    UNUSED(user_data);
+
+    const char *attr = "foo";
+
+    note("Get decoded attribute");
+    lol_html_str_t *value = lol_html_element_get_decoded_attribute(
+        element,
+        attr,
+        strlen(attr)
+    );
+
+    str_eq(value, "a&b");
+
+    lol_html_str_free(*value);
+
+    value = lol_html_element_get_decoded_attribute(element, "bar", 3);
+
+    ok(value == NULL);
+
+    note("Attribute decoded value");
+    lol_html_attributes_iterator_t *iter = lol_html_attributes_iterator_get(element);
+    const lol_html_attribute_t *attribute = lol_html_attributes_iterator_next(iter);
+
+    ok(attribute != NULL);
+
+    lol_html_str_t raw_value = lol_html_attribute_value_get(attribute);
+    lol_html_str_t decoded_value = lol_html_attribute_decoded_value_get(attribute);
+
+    str_eq(&raw_value, "a&amp;b");
+    str_eq(&decoded_value, "a&b");
+
+    lol_html_str_free(raw_value);
+    lol_html_str_free(decoded_value);
+    lol_html_attributes_iterator_free(iter);
+
+    return LOL_HTML_CONTINUE;
+}
+
+static void test_get_decoded_attributes(lol_html_selector_t *selector, void *user_data) {
+    lol_html_rewriter_builder_t *builder = lol_html_rewriter_builder_new();
+
+    int err = lol_html_rewriter_builder_add_element_content_handlers(
+        builder,
+        selector,
+        &get_decoded_attributes,
+        NULL,
+        NULL,
+        NULL,
+        NULL,
+        NULL
+    );
+
+    ok(!err);
+
+    run_rewriter(builder, "<div foo='a&amp;b'>", output_sink_stub, user_data);
+}
+
 //-------------------------------------------------------------------------
 static lol_html_rewriter_directive_t assert_element_ns_is_html(
     lol_html_element_t *element,
@@ -617,6 +679,126 @@ static void test_stop(lol_html_selector_t *selector, void *user_data) {
     ok(!err);
 }
 
+//-------------------------------------------------------------------------
+EXPECT_OUTPUT(
+    end_tag_handler_output_sink,
+    "<div>Hi<!--before--></div>&amp;after",
+    &EXPECTED_USER_DATA,
+    sizeof(EXPECTED_USER_DATA)
+);
+
+static lol_html_rewriter_directive_t modify_end_tag(
+    lol_html_end_tag_t *end_tag,
+    void *user_data
+) {
+    note("End tag user data");
+    ok(*(int*)user_data == EXPECTED_USER_DATA);
+
+    const char *before = "<!--before-->";
+    const char *after = "&after";
+
+    note("Get end tag name");
+    lol_html_str_t name = lol_html_end_tag_name_get(end_tag);
+
+    str_eq(&name, "div");
+
+    lol_html_str_free(name);
+
+    note("Insert before/after end tag");
+    ok(!lol_html_end_tag_before(end_tag, before, strlen(before), true));
+    ok(!lol_html_end_tag_after(end_tag, after, strlen(after), false));
+    ok(!lol_html_end_tag_is_removed(end_tag));
+
+    return LOL_HTML_CONTINUE;
+}
+
+static lol_html_rewriter_directive_t add_end_tag_handler(
+    lol_html_element_t *element,
+    void *user_data
+) {
+    note("Add end tag handler");
+    ok(!lol_html_element_add_end_tag_handler(element, &modify_end_tag, user_data));
+
+    return LOL_HTML_CONTINUE;
+}
+
+static void test_end_tag_handler(lol_html_selector_t *selector, void *user_data) {
+    lol_html_rewriter_builder_t *builder = lol_html_rewriter_builder_new();
+
+    int err = lol_html_rewriter_builder_add_element_content_handlers(
+        builder,
+        selector,
+        &add_end_tag_handler,
+        user_data,
+        NULL,
+        NULL,
+        NULL,
+        NULL
+    );
+
+    ok(!err);
+
+    run_rewriter(builder, "<div>Hi</div>", end_tag_handler_output_sink, user_data);
+}
+
+//-------------------------------------------------------------------------
+EXPECT_OUTPUT(
+    end_tag_handler_for_void_element_output_sink,
+    "<img>",
+    &EXPECTED_USER_DATA,
+    sizeof(EXPECTED_USER_DATA)
+);
+
+static lol_html_rewriter_directive_t end_tag_handler_stub(
+    lol_html_end_tag_t *end_tag,
+    void *user_data
+) {
+    UNUSED(end_tag);
+    UNUSED(user_data);
+
+    return LOL_HTML_CONTINUE;
+}
+
+static lol_html_rewriter_directive_t add_end_tag_handler_to_void_element(
+    lol_html_element_t *element,
+    void *user_data
+) {
+    UNUSED(user_data);
+
+    note("Add end tag handler to void element");
+    ok(lol_html_element_add_end_tag_handler(element, &end_tag_handler_stub, NULL) == -1);
+
+    lol_html_str_t *msg = lol_html_take_last_error();
+
+    str_eq(msg, "The element doesn't have an end tag.");
+
+    lol_html_str_free(*msg);
+
+    return LOL_HTML_CONTINUE;
+}
+
+static void test_end_tag_handler_for_void_element(
+    lol_html_selector_t *selector,
+    void *user_data
+) {
+    lol_html_rewriter_builder_t *builder = lol_html_rewriter_builder_new();
+
+    int err = lol_html_rewriter_builder_add_element_content_handlers(
+        builder,
+        selector,
+        &add_end_tag_handler_to_void_element,
+        NULL,
+        NULL,
+        NULL,
+        NULL,
+        NULL
+    );
+
+    ok(!err);
+
+    run_rewriter(builder, "<img>", end_tag_handler_for_void_element_output_sink, user_data);
+}
+
 void element_api_test() {
     int user_data = 43;
 
@@ -631,8 +813,11 @@ void element_api_test() {
         test_modify_element_tag_name(selector, &user_data);
         test_modify_element_user_data(selector, &user_data);
         test_iterate_attributes(selector, &user_data);
+        test_get_decoded_attributes(selector, &user_data);
         test_get_and_modify_attributes(selector, &user_data);
         test_insert_content_around_element(selector, &user_data);
+        test_end_tag_handler(selector, &user_data);
+        test_end_tag_handler_for_void_element(selector, &user_data);
 
         lol_html_selector_free(selector);
     }
@@ -60,6 +60,46 @@ static void test_insert_before_and_after_text_chunk(void *user_data) {
     run_rewriter(builder, "Hey 42", insert_after_text_chunk_output_sink, user_data);
 }
 
+//-------------------------------------------------------------------------
+static lol_html_rewriter_directive_t get_decoded_content(
+    lol_html_text_chunk_t *chunk,
+    void *user_data
+) {
+    UNUSED(user_data);
+
+    lol_html_text_chunk_content_t content = lol_html_text_chunk_content_get(chunk);
+
+    if (content.len > 0) {
+        note("Decoded content");
+        lol_html_str_t decoded = lol_html_text_chunk_decoded_content_get(chunk);
+
+        str_eq(&content, "Tom &amp; Jerry");
+        str_eq(&decoded, "Tom & Jerry");
+
+        lol_html_str_free(decoded);
+    }
+
+    return LOL_HTML_CONTINUE;
+}
+
+static void test_get_decoded_content(void *user_data) {
+    lol_html_rewriter_builder_t *builder = lol_html_rewriter_builder_new();
+
+     lol_html_rewriter_builder_add_document_content_handlers(
+        builder,
+        NULL,
+        NULL,
+        NULL,
+        NULL,
+        &get_decoded_content,
+        NULL,
+        NULL,
+        NULL
+    );
+
+    run_rewriter(builder, "Tom &amp; Jerry", output_sink_stub, user_data);
+}
+
 //-------------------------------------------------------------------------
 EXPECT_OUTPUT(
     modify_user_data_output_sink,
@@ -290,6 +330,7 @@ void test_text_chunk_api() {
     );
 
     test_insert_before_and_after_text_chunk(&user_data);
+    test_get_decoded_content(&user_data);
     test_modify_user_data(&user_data);
     test_replace_chunk(selector, &user_data);
     test_insert_after_chunk(&user_data);
@@ -0,0 +1,20 @@
+use super::*;
+use lol_html::html_content::DocumentStart as NativeDocumentStart;
+
+#[wasm_bindgen]
+pub struct DocumentStart(NativeRefWrap<NativeDocumentStart>);
+
+impl_from_native!(NativeDocumentStart --> DocumentStart);
+
+#[wasm_bindgen]
+impl DocumentStart {
+    pub fn prepend(
+        &mut self,
+        content: &str,
+        content_type: Option<ContentTypeOptions>,
+    ) -> Result<(), JsValue> {
+        self.0
+            .get_mut()
+            .map(|e| e.prepend(content, content_type.into_native()))
+    }
+}
@@ -1,4 +1,6 @@
+use super::end_tag::EndTag;
 use super::*;
+use js_sys::Function as JsFunction;
 use lol_html::html_content::{Attribute as NativeAttribute, Element as NativeElement};
 use serde::Serialize;
 use serde_wasm_bindgen::to_value as to_js_value;
@@ -59,6 +61,11 @@ impl Element {
         self.0.get().map(|e| e.get_attribute(name))
     }
 
+    #[wasm_bindgen(method, js_name=getDecodedAttribute)]
+    pub fn get_decoded_attribute(&self, name: &str) -> JsResult<Option<String>> {
+        self.0.get().map(|e| e.get_decoded_attribute(name))
+    }
+
     #[wasm_bindgen(method, js_name=hasAttribute)]
     pub fn has_attribute(&self, name: &str) -> JsResult<bool> {
         self.0.get().map(|e| e.has_attribute(name))
@@ -112,4 +119,21 @@ impl Element {
     pub fn remove_and_keep_content(&mut self) -> Result<(), JsValue> {
         self.0.get_mut().map(|e| e.remove_and_keep_content())
     }
+
+    #[wasm_bindgen(method, js_name=onEndTag)]
+    pub fn on_end_tag(&mut self, handler: JsFunction) -> JsResult<()> {
+        self.0
+            .get_mut()?
+            .on_end_tag(move |end_tag| {
+                let this = JsValue::NULL;
+                let (end_tag, anchor) = EndTag::from_native(end_tag);
+
+                let res = handler.call1(&this, &JsValue::from(end_tag));
+
+                drop(anchor);
+
+                res.map(|_| ()).map_err(|e| format!("{:?}", e).into())
+            })
+            .into_js_result()
+    }
 }
@@ -170,6 +170,8 @@ macro_rules! impl_from_native {
 mod comment;
 mod doctype;
 mod document_end;
+mod document_start;
 mod element;
+mod end_tag;
 mod html_rewriter;
 mod text_chunk;
@@ -14,6 +14,11 @@ impl TextChunk {
         self.0.get().map(|c| c.as_str().into())
     }
 
+    #[wasm_bindgen(method, getter=decodedText)]
+    pub fn decoded_text(&self) -> JsResult<String> {
+        self.0.get().map(|c| c.decoded_text().into_owned())
+    }
+
     #[wasm_bindgen(method, getter=lastInTextNode)]
     pub fn last_in_text_node(&self) -> JsResult<bool> {
         self.0.get().map(|c| c.last_in_text_node())
@@ -1,5 +1,8 @@
 #[macro_use]
-mod debug_trace;
+mod trace;
+
+#[macro_use]
+mod shared;
 
 mod align;
 mod bytes;
@@ -8,3 +11,4 @@ mod range;
 pub use self::align::Align;
 pub use self::bytes::{Bytes, HasReplacementsError};
 pub use self::range::Range;
+pub use self::shared::{MaybeSend, Shared};
@@ -0,0 +1,94 @@
+use cfg_if::cfg_if;
+
+cfg_if! {
+    if #[cfg(feature = "send")] {
+        use std::sync::{Arc, Mutex, MutexGuard};
+
+        /// A marker trait for the content handlers.
+        ///
+        /// With the `send` feature enabled it's implemented for all [`Send`] types, making the
+        /// rewriter [`Send`] as well. Otherwise it's implemented for all types.
+        ///
+        /// [`Send`]: https://doc.rust-lang.org/std/marker/trait.Send.html
+        pub trait MaybeSend: Send {}
+
+        impl<T: Send> MaybeSend for T {}
+
+        macro_rules! dyn_maybe_send {
+            ($($t:tt)+) => { dyn $($t)+ + Send };
+        }
+
+        /// Shared mutable state of the rewriter's internal components.
+        #[derive(Debug)]
+        pub struct Shared<T>(Arc<Mutex<T>>);
+
+        impl<T> Shared<T> {
+            #[inline]
+            pub fn new(value: T) -> Self {
+                Shared(Arc::new(Mutex::new(value)))
+            }
+
+            #[inline]
+            pub fn borrow(&self) -> MutexGuard<'_, T> {
+                self.borrow_mut()
+            }
+
+            #[inline]
+            pub fn borrow_mut(&self) -> MutexGuard<'_, T> {
+                self.0.lock().expect("Shared state should not be poisoned.")
+            }
+        }
+
+        impl<T> Clone for Shared<T> {
+            #[inline]
+            fn clone(&self) -> Self {
+                Shared(Arc::clone(&self.0))
+            }
+        }
+    } else {
+        use std::cell::{Ref, RefCell, RefMut};
+        use std::rc::Rc;
+
+        /// A marker trait for the content handlers.
+        ///
+        /// With the `send` feature enabled it's implemented for all [`Send`] types, making the
+        /// rewriter [`Send`] as well. Otherwise it's implemented for all types.
+        ///
+        /// [`Send`]: https://doc.rust-lang.org/std/marker/trait.Send.html
+        pub trait MaybeSend {}
+
+        impl<T> MaybeSend for T {}
+
+        macro_rules! dyn_maybe_send {
+            ($($t:tt)+) => { dyn $($t)+ };
+        }
+
+        /// Shared mutable state of the rewriter's internal components.
+        #[derive(Debug)]
+        pub struct Shared<T>(Rc<RefCell<T>>);
+
+        impl<T> Shared<T> {
+            #[inline]
+            pub fn new(value: T) -> Self {
+                Shared(Rc::new(RefCell::new(value)))
+            }
+
+            #[inline]
+            pub fn borrow(&self) -> Ref<'_, T> {
+                self.0.borrow()
+            }
+
+            #[inline]
+            pub fn borrow_mut(&self) -> RefMut<'_, T> {
+                self.0.borrow_mut()
+            }
+        }
+
+        impl<T> Clone for Shared<T> {
+            #[inline]
+            fn clone(&self) -> Self {
+                Shared(Rc::clone(&self.0))
+            }
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use cfg_if::cfg_if;
+
+// NOTE: the traces are emitted as the `tracing` events. Debug level events describe the
+// decisions made by the parser and the rewriter (parser directive and text type switches,
+// ambiguity guard state changes, selector matches and buffering), while trace level events
+// follow the parsing process in the finest detail. Without the `tracing` feature the macro
+// expands to nothing.
+cfg_if! {
+    if #[cfg(feature = "tracing")] {
+        macro_rules! trace {
+            ( @write $slice:expr ) => {
+                let _span = ::tracing::debug_span!(
+                    "write",
+                    len = $slice.len()
+                ).entered();
+
+                ::tracing::trace!(chunk = ?crate::base::Bytes::from($slice), "write");
+            };
+
+            ( @end ) => {
+                let _span = ::tracing::debug_span!("end").entered();
+            };
+
+            ( @directive $directive:expr ) => {
+                ::tracing::debug!(directive = ?$directive, "parser directive switch");
+            };
+
+            ( @text_type $text_type:expr ) => {
+                ::tracing::debug!(text_type = ?$text_type, "text type switch");
+            };
+
+            ( @ambiguity_guard $state:expr ) => {
+                ::tracing::debug!(state = ?$state, "ambiguity guard state switch");
+            };
+
+            ( @ambiguity_error $tag_name:expr ) => {
+                ::tracing::debug!(tag_name = %$tag_name, "ambiguous text type switch");
+            };
+
+            ( @selector_match $selector_idx:expr, $with_content:expr ) => {
+                ::tracing::debug!(
+                    selector_idx = $selector_idx,
+                    with_content = $with_content,
+                    "selector match"
+                );
+            };
+
+            ( @buffer $buffer:expr ) => {
+                ::tracing::debug!(
+                    len = $buffer.bytes().len(),
+                    buffered = ?crate::base::Bytes::from($buffer.bytes()),
+                    "buffering"
+                );
+            };
+
+            ( @actions $($actions:tt)+ ) => {
+                ::tracing::trace!(actions = stringify!($($actions)+), "action");
+            };
+
+            ( @chars $action_descr:expr $(, $ch:expr)* ) => {
+                ::tracing::trace!(
+                    $(ch = ?$ch.map(char::from),)*
+                    "{}",
+                    $action_descr
+                );
+            };
+
+            ( @chunk $chunk:expr ) => {
+                ::tracing::trace!(chunk = ?$chunk, "chunk");
+            };
+
+            ( @noop ) => {
+                ::tracing::trace!("noop");
+            };
+
+            ( @continue_from_bookmark $bookmark:expr, $parser_directive:expr, $chunk:expr ) => {
+                ::tracing::trace!(
+                    bookmark = ?$bookmark,
+                    directive = ?$parser_directive,
+                    "continue from bookmark"
+                );
+            };
+
+            ( @output $output:expr ) => {
+                ::tracing::trace!(output = ?$output, "output");
+            };
+        }
+    } else {
+        macro_rules! trace {
+            ( @$ty:ident $($args:tt)* ) => {};
+            ( @$ty:ident $($args:tt),* ) => {};
+        }
+    }
+}
@@ -0,0 +1,287 @@
+use super::named_character_references::{MAX_NAME_LEN, NAMED_CHARACTER_REFERENCES};
+use std::borrow::Cow;
+
+// NOTE: numeric character references in the `0x80..=0x9F` range are interpreted
+// as `windows-1252` code points.
+// See: https://html.spec.whatwg.org/multipage/parsing.html#numeric-character-reference-end-state
+static C1_REPLACEMENTS: [Option<char>; 32] = [
+    Some('\u{20AC}'),
+    None,
+    Some('\u{201A}'),
+    Some('\u{0192}'),
+    Some('\u{201E}'),
+    Some('\u{2026}'),
+    Some('\u{2020}'),
+    Some('\u{2021}'),
+    Some('\u{02C6}'),
+    Some('\u{2030}'),
+    Some('\u{0160}'),
+    Some('\u{2039}'),
+    Some('\u{0152}'),
+    None,
+    Some('\u{017D}'),
+    None,
+    None,
+    Some('\u{2018}'),
+    Some('\u{2019}'),
+    Some('\u{201C}'),
+    Some('\u{201D}'),
+    Some('\u{2022}'),
+    Some('\u{2013}'),
+    Some('\u{2014}'),
+    Some('\u{02DC}'),
+    Some('\u{2122}'),
+    Some('\u{0161}'),
+    Some('\u{203A}'),
+    Some('\u{0153}'),
+    None,
+    Some('\u{017E}'),
+    Some('\u{0178}'),
+];
+
+/// A decoded character reference.
+pub struct CharacterReference {
+    /// The length of the reference in bytes, including the leading `&`.
+    pub len: usize,
+    pub decoded: Cow<'static, str>,
+}
+
+fn numeric_character_reference_value(code_point: u32) -> char {
+    match code_point {
+        0x80..=0x9F => C1_REPLACEMENTS[(code_point - 0x80) as usize]
+            .unwrap_or_else(|| std::char::from_u32(code_point).unwrap()),
+        // NOTE: `from_u32` returns `None` for surrogates and code points
+        // outside of the Unicode range.
+        _ => match std::char::from_u32(code_point) {
+            Some('\0') | None => '\u{FFFD}',
+            Some(ch) => ch,
+        },
+    }
+}
+
+fn decode_numeric_character_reference(input: &[u8]) -> Option<CharacterReference> {
+    // NOTE: `input` starts with `&#`.
+    let (radix, digits_start) = match input.get(2) {
+        Some(b'x') | Some(b'X') => (16, 3),
+        _ => (10, 2),
+    };
+
+    let mut code_point = 0u32;
+    let mut pos = digits_start;
+
+    while let Some(digit) = input.get(pos).and_then(|&b| (b as char).to_digit(radix)) {
+        // NOTE: saturate on overflow, the value is out of the Unicode range anyway.
+        code_point = code_point
+            .saturating_mul(radix)
+            .saturating_add(digit)
+            .min(0x11_0000);
+
+        pos += 1;
+    }
+
+    if pos == digits_start {
+        return None;
+    }
+
+    if input.get(pos) == Some(&b';') {
+        pos += 1;
+    }
+
+    Some(CharacterReference {
+        len: pos,
+        decoded: numeric_character_reference_value(code_point)
+            .to_string()
+            .into(),
+    })
+}
+
+fn find_named_character_reference(name: &[u8]) -> Option<(&'static str, &'static str)> {
+    // NOTE: names consist of alphanumeric characters and an optional trailing semicolon.
+    let mut max_len = name
+        .iter()
+        .take(MAX_NAME_LEN)
+        .take_while(|b| b.is_ascii_alphanumeric())
+        .count();
+
+    if max_len < MAX_NAME_LEN && name.get(max_len) == Some(&b';') {
+        max_len += 1;
+    }
+
+    // NOTE: the longest matching name wins.
+    (1..=max_len).rev().find_map(|len| {
+        NAMED_CHARACTER_REFERENCES
+            .binary_search_by(|(n, _)| n.as_bytes().cmp(&name[..len]))
+            .ok()
+            .map(|idx| NAMED_CHARACTER_REFERENCES[idx])
+    })
+}
+
+fn decode_named_character_reference(
+    input: &[u8],
+    in_attribute: bool,
+) -> Option<CharacterReference> {
+    // NOTE: `input` starts with `&`.
+    let (name, decoded) = find_named_character_reference(&input[1..])?;
+    let len = name.len() + 1;
+
+    // NOTE: for historical reasons, references without the trailing semicolon are not
+    // decoded in attribute values if they are followed by `=` or an alphanumeric character.
+    if in_attribute && !name.ends_with(';') {
+        if let Some(&next) = input.get(len) {
+            if next == b'=' || next.is_ascii_alphanumeric() {
+                return None;
+            }
+        }
+    }
+
+    Some(CharacterReference {
+        len,
+        decoded: decoded.into(),
+    })
+}
+
+/// Decodes the character reference at the beginning of the `input` that starts with `&`.
+///
+/// Returns `None` if the `input` doesn't start with a valid character reference.
+pub fn decode_character_reference(input: &[u8], in_attribute: bool) -> Option<CharacterReference> {
+    debug_assert_eq!(input.first(), Some(&b'&'));
+
+    match input.get(1) {
+        Some(b'#') => decode_numeric_character_reference(input),
+        Some(b) if b.is_ascii_alphanumeric() => {
+            decode_named_character_reference(input, in_attribute)
+        }
+        _ => None,
+    }
+}
+
+fn decode_character_references(value: &str, in_attribute: bool) -> Cow<str> {
+    let bytes = value.as_bytes();
+    let mut decoded = String::new();
+    let mut last_flushed = 0;
+    let mut pos = 0;
+
+    while let Some(amp_pos) = memchr::memchr(b'&', &bytes[pos..]) {
+        let amp_pos = pos + amp_pos;
+
+        match decode_character_reference(&bytes[amp_pos..], in_attribute) {
+            Some(reference) => {
+                decoded.push_str(&value[last_flushed..amp_pos]);
+                decoded.push_str(&reference.decoded);
+
+                pos = amp_pos + reference.len;
+                last_flushed = pos;
+            }
+            None => pos = amp_pos + 1,
+        }
+    }
+
+    if last_flushed == 0 {
+        Cow::Borrowed(value)
+    } else {
+        decoded.push_str(&value[last_flushed..]);
+
+        Cow::Owned(decoded)
+    }
+}
+
+/// Decodes character references in the attribute `value` as specified by the HTML parsing
+/// algorithm.
+#[inline]
+pub fn decode_attribute_value(value: &str) -> Cow<str> {
+    decode_character_references(value, true)
+}
+
+/// Decodes character references in the `text` as specified by the HTML parsing algorithm.
+#[inline]
+pub fn decode_text(text: &str) -> Cow<str> {
+    decode_character_references(text, false)
+}
+
+/// Returns the length of the trailing part of the `text` that can be the beginning of a character
+/// reference that continues in the subsequent text.
+pub fn incomplete_character_reference_len(text: &str) -> usize {
+    let bytes = text.as_bytes();
+
+    let amp_pos = match memchr::memrchr(b'&', bytes) {
+        Some(pos) => pos,
+        None => return 0,
+    };
+
+    let tail = &bytes[amp_pos + 1..];
+
+    // NOTE: numeric references can have any number of leading zeroes, but we don't
+    // buffer more than the longest named reference to keep the lookbehind bounded.
+    if tail.len() >= MAX_NAME_LEN {
+        return 0;
+    }
+
+    let name = match tail.first() {
+        Some(b'#') => &tail[1..],
+        _ => tail,
+    };
+
+    if name.iter().all(u8::is_ascii_alphanumeric) {
+        bytes.len() - amp_pos
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn named_character_references() {
+        assert_eq!(decode_attribute_value("a&amp;b"), "a&b");
+        assert_eq!(decode_attribute_value("&lt;&GT;&quot;"), "<>\"");
+        assert_eq!(decode_attribute_value("&NotEqualTilde;"), "\u{2242}\u{338}");
+        assert_eq!(decode_attribute_value("&foo;&amp"), "&foo;&");
+        assert_eq!(decode_attribute_value("&;&&#;&#x;"), "&;&&#;&#x;");
+        assert_eq!(decode_attribute_value("no references"), "no references");
+    }
+
+    #[test]
+    fn legacy_named_character_references() {
+        assert_eq!(decode_attribute_value("&copy 2020"), "\u{A9} 2020");
+        assert_eq!(decode_attribute_value("&notin;&notit;"), "\u{2209}&notit;");
+        assert_eq!(decode_attribute_value("?a=1&copy=2"), "?a=1&copy=2");
+        assert_eq!(decode_attribute_value("?a=1&copyb"), "?a=1&copyb");
+        assert_eq!(decode_attribute_value("&copy;b"), "\u{A9}b");
+    }
+
+    #[test]
+    fn text() {
+        assert_eq!(decode_text("a&amp;b&copy=1&notit;"), "a&b\u{A9}=1\u{AC}it;");
+        assert_eq!(decode_text("&lt;p&gt;&nbsp;&#x27;"), "<p>\u{A0}'");
+        assert_eq!(decode_text("no references"), "no references");
+    }
+
+    #[test]
+    fn incomplete_references() {
+        assert_eq!(incomplete_character_reference_len("foo&"), 1);
+        assert_eq!(incomplete_character_reference_len("foo&am"), 3);
+        assert_eq!(incomplete_character_reference_len("foo&#x2"), 4);
+        assert_eq!(incomplete_character_reference_len("&amp;bar&#"), 2);
+        assert_eq!(incomplete_character_reference_len("foo&amp;"), 0);
+        assert_eq!(incomplete_character_reference_len("foo& bar"), 0);
+        assert_eq!(incomplete_character_reference_len("foo"), 0);
+        assert_eq!(
+            incomplete_character_reference_len(&format!("&#{}", "0".repeat(40))),
+            0
+        );
+    }
+
+    #[test]
+    fn numeric_character_references() {
+        assert_eq!(decode_attribute_value("&#65;&#x42&#X43;&#0068"), "ABCD");
+        assert_eq!(
+            decode_attribute_value("&#0;&#xD800;&#x110000;"),
+            "\u{FFFD}\u{FFFD}\u{FFFD}"
+        );
+        assert_eq!(decode_attribute_value("&#99999999999999;"), "\u{FFFD}");
+        assert_eq!(decode_attribute_value("&#x80;&#x81;"), "\u{20AC}\u{81}");
+        assert_eq!(decode_attribute_value("&#xG;&#a"), "&#xG;&#a");
+    }
+}
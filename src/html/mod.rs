@@ -1,10 +1,15 @@
 #[macro_use]
 mod tag;
 
+mod character_references;
 mod local_name;
+mod named_character_references;
 mod namespace;
 mod text_type;
 
+pub use self::character_references::{
+    decode_attribute_value, decode_text, incomplete_character_reference_len,
+};
 pub use self::local_name::{LocalName, LocalNameHash};
 pub use self::namespace::Namespace;
 pub use self::tag::*;
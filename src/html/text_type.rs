@@ -37,6 +37,9 @@ pub enum TextType {
 }
 
 impl TextType {
+    /// Returns `true` if [HTML entities] are decoded in the text of this type.
+    ///
+    /// [HTML entities]: https://developer.mozilla.org/en-US/docs/Glossary/Entity
     #[inline]
     pub fn allows_html_entities(self) -> bool {
         self == TextType::Data || self == TextType::RCData
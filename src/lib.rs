@@ -8,14 +8,19 @@
 //! can be used as a standalone library with the convenient API for a wide variety of HTML
 //! rewriting/analyzis tasks.
 //!
-//! The crate provides two main API entry points:
+//! The crate provides three main API entry points:
 //!
 //! * [`HtmlRewriter`] - a streaming HTML rewriter;
+//! * [`HtmlAnalyzer`] - a streaming HTML analyzer that doesn't produce any output;
 //! * [`rewrite_str`] - one-off HTML string rewriting function.
 //!
+//! The [`sanitizer`] module provides allowlist-based HTML sanitization on top of the rewriter.
+//!
 //! [Cloudflare Workers]: https://www.cloudflare.com/en-gb/products/cloudflare-workers/
 //! [`HtmlRewriter`]: struct.HtmlRewriter.html
+//! [`HtmlAnalyzer`]: struct.HtmlAnalyzer.html
 //! [`rewrite_str`]: fn.rewrite_str.html
+//! [`sanitizer`]: sanitizer/index.html
 
 #[macro_use]
 mod base;
@@ -33,26 +38,38 @@ mod transform_stream;
 
 use cfg_if::cfg_if;
 
+pub use self::base::MaybeSend;
 pub use self::rewriter::{
-    rewrite_str, AsciiCompatibleEncoding, DocumentContentHandlers, ElementContentHandlers,
-    HtmlRewriter, MemorySettings, RewriteStrSettings, Settings,
+    rewrite_str, AnalyzerSettings, AsciiCompatibleEncoding, CompiledSelectors,
+    DocumentContentAnalyzers, DocumentContentHandlers, ElementContentAnalyzers,
+    ElementContentHandlers, HtmlAnalyzer, HtmlRewriter, IoOutputSink, MemorySettings,
+    RewriteStrSettings, RewritingReader, RewritingStats, Settings, StopSignal, TextPattern,
+    Transcoding,
 };
 pub use self::selectors_vm::Selector;
 pub use self::transform_stream::OutputSink;
 
+#[cfg(feature = "async")]
+pub use self::rewriter::{AsyncHtmlRewriter, RewritingStream};
+
 /// The errors that can be produced by the crate's API.
 pub mod errors {
     pub use super::memory::MemoryLimitExceededError;
     pub use super::parser::ParsingAmbiguityError;
-    pub use super::rewritable_units::{AttributeNameError, CommentTextError, TagNameError};
-    pub use super::rewriter::RewritingError;
+    pub use super::rewritable_units::{
+        AttributeNameError, CommentTextError, DoctypeError, EndTagError, TagNameError,
+    };
+    pub use super::rewriter::{RewritingError, TextPatternError};
     pub use super::selectors_vm::SelectorError;
 }
 
+pub mod sanitizer;
+
 /// HTML content descriptors that can be produced and modified by a rewriter.
 pub mod html_content {
     pub use super::rewritable_units::{
-        Attribute, Comment, ContentType, Doctype, DocumentEnd, Element, TextChunk, UserData,
+        Attribute, Comment, ContentType, Doctype, DocumentEnd, DocumentStart, Element, EndTag,
+        SourceLocation, StreamingContent, TextChunk, TextNode, UserData,
     };
 
     pub use super::html::TextType;
@@ -158,7 +175,7 @@ cfg_if! {
         };
 
         pub use self::rewritable_units::{
-            EndTag, Serialize, StartTag, Token, TokenCaptureFlags, Mutations
+            Serialize, StartTag, Token, TokenCaptureFlags, Mutations
         };
 
         pub use self::base::Bytes;
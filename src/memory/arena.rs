@@ -7,6 +7,8 @@ use safemem::copy_over;
 pub struct Arena {
     limiter: SharedMemoryLimiter,
     data: Vec<u8>,
+    preallocated_size: usize,
+    accounted_size: usize,
 }
 
 impl Arena {
@@ -16,6 +18,8 @@ impl Arena {
         Arena {
             limiter,
             data: Vec::with_capacity(preallocated_size),
+            preallocated_size,
+            accounted_size: preallocated_size,
         }
     }
 
@@ -29,6 +33,7 @@ impl Arena {
             // NOTE: approximate usage, as `Vec::reserve_exact` doesn't
             // give guarantees about exact capacity value :).
             self.limiter.borrow_mut().increase_usage(additional)?;
+            self.accounted_size += additional;
 
             // NOTE: with wicely choosen preallocated size this branch should be
             // executed quite rarely. We can't afford to use double capacity
@@ -47,6 +52,20 @@ impl Arena {
         self.append(slice)
     }
 
+    /// Clears the arena and deallocates the memory it has grown by since the construction.
+    pub fn reset(&mut self) {
+        if self.accounted_size > self.preallocated_size {
+            self.limiter
+                .borrow_mut()
+                .decrease_usage(self.accounted_size - self.preallocated_size);
+
+            self.data = Vec::with_capacity(self.preallocated_size);
+            self.accounted_size = self.preallocated_size;
+        } else {
+            self.data.clear();
+        }
+    }
+
     pub fn shift(&mut self, byte_count: usize) {
         let remainder_len = self.data.len() - byte_count;
 
@@ -63,12 +82,12 @@ impl Arena {
 mod tests {
     use super::super::limiter::MemoryLimiter;
     use super::*;
-    use std::rc::Rc;
+    use crate::base::Shared;
 
     #[test]
     fn append() {
         let limiter = MemoryLimiter::new_shared(10);
-        let mut arena = Arena::new(Rc::clone(&limiter), 2);
+        let mut arena = Arena::new(Shared::clone(&limiter), 2);
 
         arena.append(&[1, 2]).unwrap();
         assert_eq!(arena.bytes(), &[1, 2]);
@@ -90,7 +109,7 @@ mod tests {
     #[test]
     fn init_with() {
         let limiter = MemoryLimiter::new_shared(5);
-        let mut arena = Arena::new(Rc::clone(&limiter), 0);
+        let mut arena = Arena::new(Shared::clone(&limiter), 0);
 
         arena.init_with(&[1]).unwrap();
         assert_eq!(arena.bytes(), &[1]);
@@ -113,10 +132,32 @@ mod tests {
         assert_eq!(err, MemoryLimitExceededError);
     }
 
+    #[test]
+    fn reset() {
+        let limiter = MemoryLimiter::new_shared(10);
+        let mut arena = Arena::new(Shared::clone(&limiter), 2);
+
+        arena.append(&[1]).unwrap();
+        arena.reset();
+        assert_eq!(arena.bytes(), &[]);
+        assert_eq!(limiter.borrow().current_usage(), 2);
+
+        arena.append(&[1, 2, 3, 4, 5, 6, 7, 8]).unwrap();
+        assert_eq!(limiter.borrow().current_usage(), 8);
+
+        arena.reset();
+        assert_eq!(arena.bytes(), &[]);
+        assert_eq!(limiter.borrow().current_usage(), 2);
+
+        arena.append(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10]).unwrap();
+        assert_eq!(arena.bytes(), &[1, 2, 3, 4, 5, 6, 7, 8, 9, 10]);
+        assert_eq!(limiter.borrow().current_usage(), 10);
+    }
+
     #[test]
     fn shift() {
         let limiter = MemoryLimiter::new_shared(10);
-        let mut arena = Arena::new(Rc::clone(&limiter), 0);
+        let mut arena = Arena::new(Shared::clone(&limiter), 0);
 
         arena.append(&[0, 1, 2, 3]).unwrap();
         arena.shift(2);
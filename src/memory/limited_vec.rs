@@ -26,6 +26,18 @@ impl<T> LimitedVec<T> {
         Ok(())
     }
 
+    pub fn extend_from_slice(&mut self, elements: &[T]) -> Result<(), MemoryLimitExceededError>
+    where
+        T: Clone,
+    {
+        self.limiter
+            .borrow_mut()
+            .increase_usage(size_of::<T>() * elements.len())?;
+
+        self.vec.extend_from_slice(elements);
+        Ok(())
+    }
+
     /// Returns the number of elements in the vector, also referred to as its 'length'.
     #[inline]
     pub fn len(&self) -> usize {
@@ -101,13 +113,13 @@ impl<T> Drop for LimitedVec<T> {
 mod tests {
     use super::super::MemoryLimiter;
     use super::*;
-    use std::rc::Rc;
+    use crate::base::Shared;
 
     #[test]
     fn current_usage() {
         {
             let limiter = MemoryLimiter::new_shared(10);
-            let mut vec_u8: LimitedVec<u8> = LimitedVec::new(Rc::clone(&limiter));
+            let mut vec_u8: LimitedVec<u8> = LimitedVec::new(Shared::clone(&limiter));
 
             vec_u8.push(1).unwrap();
             vec_u8.push(2).unwrap();
@@ -116,7 +128,7 @@ mod tests {
 
         {
             let limiter = MemoryLimiter::new_shared(10);
-            let mut vec_u32: LimitedVec<u32> = LimitedVec::new(Rc::clone(&limiter));
+            let mut vec_u32: LimitedVec<u32> = LimitedVec::new(Shared::clone(&limiter));
 
             vec_u32.push(1).unwrap();
             vec_u32.push(2).unwrap();
@@ -127,7 +139,7 @@ mod tests {
     #[test]
     fn max_limit() {
         let limiter = MemoryLimiter::new_shared(2);
-        let mut vector: LimitedVec<u8> = LimitedVec::new(Rc::clone(&limiter));
+        let mut vector: LimitedVec<u8> = LimitedVec::new(Shared::clone(&limiter));
 
         vector.push(1).unwrap();
         vector.push(2).unwrap();
@@ -137,12 +149,26 @@ mod tests {
         assert_eq!(err, MemoryLimitExceededError);
     }
 
+    #[test]
+    fn extend_from_slice() {
+        let limiter = MemoryLimiter::new_shared(5);
+        let mut vector: LimitedVec<u8> = LimitedVec::new(Shared::clone(&limiter));
+
+        vector.extend_from_slice(&[1, 2, 3]).unwrap();
+        assert_eq!(limiter.borrow().current_usage(), 3);
+        assert_eq!(&*vector, &[1, 2, 3]);
+
+        let err = vector.extend_from_slice(&[4, 5, 6]).unwrap_err();
+
+        assert_eq!(err, MemoryLimitExceededError);
+    }
+
     #[test]
     fn drop() {
         let limiter = MemoryLimiter::new_shared(1);
 
         {
-            let mut vector: LimitedVec<u8> = LimitedVec::new(Rc::clone(&limiter));
+            let mut vector: LimitedVec<u8> = LimitedVec::new(Shared::clone(&limiter));
 
             vector.push(1).unwrap();
             assert_eq!(limiter.borrow().current_usage(), 1);
@@ -154,7 +180,7 @@ mod tests {
     #[test]
     fn drain() {
         let limiter = MemoryLimiter::new_shared(10);
-        let mut vector: LimitedVec<u8> = LimitedVec::new(Rc::clone(&limiter));
+        let mut vector: LimitedVec<u8> = LimitedVec::new(Shared::clone(&limiter));
 
         vector.push(1).unwrap();
         vector.push(2).unwrap();
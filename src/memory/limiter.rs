@@ -1,8 +1,8 @@
-use std::cell::RefCell;
-use std::rc::Rc;
+use crate::base::Shared;
+use crate::rewriter::RewritingStats;
 use thiserror::Error;
 
-pub type SharedMemoryLimiter = Rc<RefCell<MemoryLimiter>>;
+pub type SharedMemoryLimiter = Shared<MemoryLimiter>;
 
 /// An error that occures when rewriter exceedes the memory limit specified in the
 /// [`MemorySettings`].
@@ -16,14 +16,21 @@ pub struct MemoryLimitExceededError;
 pub struct MemoryLimiter {
     current_usage: usize,
     max: usize,
+    stats: Option<RewritingStats>,
 }
 
 impl MemoryLimiter {
     pub fn new_shared(max: usize) -> SharedMemoryLimiter {
-        Rc::new(RefCell::new(MemoryLimiter {
+        Shared::new(MemoryLimiter {
             max,
             current_usage: 0,
-        }))
+            stats: None,
+        })
+    }
+
+    pub fn set_stats(&mut self, stats: RewritingStats) {
+        stats.record_memory_usage(self.current_usage);
+        self.stats = Some(stats);
     }
 
     #[cfg(test)]
@@ -33,11 +40,19 @@ impl MemoryLimiter {
 
     #[inline]
     pub fn increase_usage(&mut self, byte_count: usize) -> Result<(), MemoryLimitExceededError> {
-        self.current_usage += byte_count;
+        let new_usage = self.current_usage + byte_count;
 
-        if self.current_usage > self.max {
+        // NOTE: the usage is not accounted on failure, as the memory is not
+        // allocated in this case and the limiter can be reused after a reset.
+        if new_usage > self.max {
             Err(MemoryLimitExceededError)
         } else {
+            self.current_usage = new_usage;
+
+            if let Some(ref stats) = self.stats {
+                stats.record_memory_usage(new_usage);
+            }
+
             Ok(())
         }
     }
@@ -78,6 +93,7 @@ mod tests {
         let err = limiter.increase_usage(15).unwrap_err();
 
         assert_eq!(err, MemoryLimitExceededError);
+        assert_eq!(limiter.current_usage(), 4);
     }
 
     #[test]
@@ -93,4 +109,21 @@ mod tests {
 
         limiter.preallocate(10);
     }
+
+    #[test]
+    fn stats() {
+        let limiter = MemoryLimiter::new_shared(10);
+        let stats = RewritingStats::new();
+        let mut limiter = limiter.borrow_mut();
+
+        limiter.increase_usage(3).unwrap();
+        limiter.set_stats(RewritingStats::clone(&stats));
+        assert_eq!(stats.peak_memory_usage(), 3);
+
+        limiter.increase_usage(5).unwrap();
+        limiter.decrease_usage(6);
+        limiter.increase_usage(1).unwrap();
+        limiter.increase_usage(15).unwrap_err();
+        assert_eq!(stats.peak_memory_usage(), 8);
+    }
 }
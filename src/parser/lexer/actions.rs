@@ -87,11 +87,9 @@ impl<S: LexemeSink> StateMachineActions for Lexer<S> {
             .map_err(ActionError::RewritingError)?
         {
             ParserDirective::Lex => Ok(()),
-            ParserDirective::WherePossibleScanForTagsOnly => self.change_parser_directive(
-                self.lexeme_start,
-                ParserDirective::WherePossibleScanForTagsOnly,
-                FeedbackDirective::None,
-            ),
+            directive => {
+                self.change_parser_directive(self.lexeme_start, directive, FeedbackDirective::None)
+            }
         }
     }
 
@@ -128,7 +126,7 @@ impl<S: LexemeSink> StateMachineActions for Lexer<S> {
             name: Range::default(),
             name_hash: LocalNameHash::new(),
             ns: Namespace::default(),
-            attributes: Rc::clone(&self.attr_buffer),
+            attributes: Shared::clone(&self.attr_buffer),
             self_closing: false,
         });
     }
@@ -4,7 +4,7 @@ mod actions;
 mod conditions;
 mod lexeme;
 
-use crate::base::{Align, Range};
+use crate::base::{Align, Range, Shared};
 use crate::html::{LocalNameHash, Namespace, TextType};
 use crate::parser::state_machine::{
     ActionError, ActionResult, FeedbackDirective, StateMachine, StateResult,
@@ -13,8 +13,6 @@ use crate::parser::{
     ParserDirective, ParsingAmbiguityError, TreeBuilderFeedback, TreeBuilderSimulator,
 };
 use crate::rewriter::RewritingError;
-use std::cell::RefCell;
-use std::rc::Rc;
 
 pub use self::lexeme::*;
 
@@ -29,7 +27,7 @@ pub trait LexemeSink {
 }
 
 pub type State<S> = fn(&mut Lexer<S>, &[u8]) -> StateResult;
-pub type SharedAttributeBuffer = Rc<RefCell<Vec<AttributeOutline>>>;
+pub type SharedAttributeBuffer = Shared<Vec<AttributeOutline>>;
 
 pub struct Lexer<S: LexemeSink> {
     next_pos: usize,
@@ -46,13 +44,13 @@ pub struct Lexer<S: LexemeSink> {
     last_start_tag_name_hash: LocalNameHash,
     closing_quote: u8,
     attr_buffer: SharedAttributeBuffer,
-    tree_builder_simulator: Rc<RefCell<TreeBuilderSimulator>>,
+    tree_builder_simulator: Shared<TreeBuilderSimulator>,
     last_text_type: TextType,
     feedback_directive: FeedbackDirective,
 }
 
 impl<S: LexemeSink> Lexer<S> {
-    pub fn new(lexeme_sink: S, tree_builder_simulator: Rc<RefCell<TreeBuilderSimulator>>) -> Self {
+    pub fn new(lexeme_sink: S, tree_builder_simulator: Shared<TreeBuilderSimulator>) -> Self {
         Lexer {
             next_pos: 0,
             is_last_input: false,
@@ -67,15 +65,31 @@ impl<S: LexemeSink> Lexer<S> {
             current_attr: None,
             last_start_tag_name_hash: LocalNameHash::default(),
             closing_quote: b'"',
-            attr_buffer: Rc::new(RefCell::new(Vec::with_capacity(
-                DEFAULT_ATTR_BUFFER_CAPACITY,
-            ))),
+            attr_buffer: Shared::new(Vec::with_capacity(DEFAULT_ATTR_BUFFER_CAPACITY)),
             tree_builder_simulator,
             last_text_type: TextType::Data,
             feedback_directive: FeedbackDirective::None,
         }
     }
 
+    pub fn reset(&mut self) {
+        self.next_pos = 0;
+        self.is_last_input = false;
+        self.lexeme_start = 0;
+        self.token_part_start = 0;
+        self.is_state_enter = true;
+        self.cdata_allowed = false;
+        self.state = Lexer::data_state;
+        self.current_tag_token = None;
+        self.current_non_tag_content_token = None;
+        self.current_attr = None;
+        self.last_start_tag_name_hash = LocalNameHash::default();
+        self.closing_quote = b'"';
+        self.attr_buffer.borrow_mut().clear();
+        self.last_text_type = TextType::Data;
+        self.feedback_directive = FeedbackDirective::None;
+    }
+
     fn try_get_tree_builder_feedback(
         &mut self,
         token: &TagTokenOutline,
@@ -9,11 +9,10 @@ use self::lexer::Lexer;
 use self::state_machine::{ActionError, ParsingTermination, StateMachine};
 use self::tag_scanner::TagScanner;
 use self::tree_builder_simulator::{TreeBuilderFeedback, TreeBuilderSimulator};
+use crate::base::Shared;
 use crate::html::{LocalName, Namespace};
 use crate::rewriter::RewritingError;
 use cfg_if::cfg_if;
-use std::cell::RefCell;
-use std::rc::Rc;
 
 pub use self::lexer::{
     AttributeOutline, Lexeme, LexemeSink, NonTagContentLexeme, NonTagContentTokenOutline,
@@ -26,13 +25,18 @@ pub use self::tree_builder_simulator::ParsingAmbiguityError;
 // the lexer mode if it fails to get tree builder feedback. It's up
 // to consumer to switch the parser back to the tag scan mode in
 // the tag handler.
+//
+// `Stop` terminates parsing right before the tag if it's returned from
+// a tag hint handler and right after the tag if it's returned from a
+// tag lexeme handler. The parser can't be used after that.
 #[derive(Debug)]
 pub enum ParserDirective {
     WherePossibleScanForTagsOnly,
     Lex,
+    Stop,
 }
 
-impl<S: LexemeSink> LexemeSink for Rc<RefCell<S>> {
+impl<S: LexemeSink> LexemeSink for Shared<S> {
     #[inline]
     fn handle_tag(&mut self, lexeme: &TagLexeme) -> Result<ParserDirective, RewritingError> {
         self.borrow_mut().handle_tag(lexeme)
@@ -47,7 +51,7 @@ impl<S: LexemeSink> LexemeSink for Rc<RefCell<S>> {
     }
 }
 
-impl<S: TagHintSink> TagHintSink for Rc<RefCell<S>> {
+impl<S: TagHintSink> TagHintSink for Shared<S> {
     #[inline]
     fn handle_start_tag_hint(
         &mut self,
@@ -66,8 +70,9 @@ impl<S: TagHintSink> TagHintSink for Rc<RefCell<S>> {
 pub trait ParserOutputSink: LexemeSink + TagHintSink {}
 
 pub struct Parser<S: ParserOutputSink> {
-    lexer: Lexer<Rc<RefCell<S>>>,
-    tag_scanner: TagScanner<Rc<RefCell<S>>>,
+    lexer: Lexer<Shared<S>>,
+    tag_scanner: TagScanner<Shared<S>>,
+    tree_builder_simulator: Shared<TreeBuilderSimulator>,
     current_directive: ParserDirective,
 }
 
@@ -79,28 +84,37 @@ macro_rules! with_current_sm {
         match $self.current_directive {
             ParserDirective::WherePossibleScanForTagsOnly => $self.tag_scanner.$fn($($args)*),
             ParserDirective::Lex => $self.lexer.$fn($($args)*),
+            ParserDirective::Stop => unreachable!("Parser should not be used once it's stopped"),
         }
     };
 }
 
 impl<S: ParserOutputSink> Parser<S> {
-    pub fn new(
-        output_sink: &Rc<RefCell<S>>,
-        initial_directive: ParserDirective,
-        strict: bool,
-    ) -> Self {
-        let tree_builder_simulator = Rc::new(RefCell::new(TreeBuilderSimulator::new(strict)));
+    pub fn new(output_sink: &Shared<S>, initial_directive: ParserDirective, strict: bool) -> Self {
+        let tree_builder_simulator = Shared::new(TreeBuilderSimulator::new(strict));
 
         Parser {
-            lexer: Lexer::new(Rc::clone(output_sink), Rc::clone(&tree_builder_simulator)),
+            lexer: Lexer::new(
+                Shared::clone(output_sink),
+                Shared::clone(&tree_builder_simulator),
+            ),
             tag_scanner: TagScanner::new(
-                Rc::clone(output_sink),
-                Rc::clone(&tree_builder_simulator),
+                Shared::clone(output_sink),
+                Shared::clone(&tree_builder_simulator),
             ),
+            tree_builder_simulator,
             current_directive: initial_directive,
         }
     }
 
+    /// Returns the parser to its initial state, so it can be used for another input.
+    pub fn reset(&mut self, initial_directive: ParserDirective) {
+        self.lexer.reset();
+        self.tag_scanner.reset();
+        self.tree_builder_simulator.borrow_mut().reset();
+        self.current_directive = initial_directive;
+    }
+
     pub fn parse(&mut self, input: &[u8], last: bool) -> Result<usize, RewritingError> {
         use ActionError::*;
 
@@ -113,12 +127,23 @@ impl<S: ParserOutputSink> Parser<S> {
                 }) => {
                     return Ok(consumed_byte_count);
                 }
+                Err(ParsingTermination::ActionError(ParserDirectiveChangeRequired(
+                    ParserDirective::Stop,
+                    sm_bookmark,
+                ))) => {
+                    self.current_directive = ParserDirective::Stop;
+
+                    trace!(@directive self.current_directive);
+
+                    return Ok(sm_bookmark.pos);
+                }
                 Err(ParsingTermination::ActionError(ParserDirectiveChangeRequired(
                     new_directive,
                     sm_bookmark,
                 ))) => {
                     self.current_directive = new_directive;
 
+                    trace!(@directive self.current_directive);
                     trace!(@continue_from_bookmark sm_bookmark, self.current_directive, input);
 
                     parse_result =
@@ -227,6 +227,8 @@ pub trait StateMachine: StateMachineActions + StateMachineConditions {
 
     #[inline]
     fn switch_text_type(&mut self, text_type: TextType) {
+        trace!(@text_type text_type);
+
         self.set_last_text_type(text_type);
         self.switch_state(self.next_text_parsing_state());
     }
@@ -63,6 +63,11 @@ impl<S: TagHintSink> StateMachineActions for TagScanner<S> {
 
                 self.change_parser_directive(tag_start, ParserDirective::Lex, feedback_directive)
             }
+            ParserDirective::Stop => self.change_parser_directive(
+                tag_start,
+                ParserDirective::Stop,
+                FeedbackDirective::None,
+            ),
         }
     }
 
@@ -2,16 +2,14 @@
 mod actions;
 mod conditions;
 
-use crate::base::{Align, Bytes, Range};
+use crate::base::{Align, Bytes, Range, Shared};
 use crate::html::{LocalName, LocalNameHash, Namespace, TextType};
 use crate::parser::state_machine::{FeedbackDirective, StateMachine, StateResult};
 use crate::parser::{
     ParserDirective, ParsingAmbiguityError, TreeBuilderFeedback, TreeBuilderSimulator,
 };
 use crate::rewriter::RewritingError;
-use std::cell::RefCell;
 use std::cmp::min;
-use std::rc::Rc;
 
 pub trait TagHintSink {
     fn handle_start_tag_hint(
@@ -49,16 +47,13 @@ pub struct TagScanner<S: TagHintSink> {
     tag_hint_sink: S,
     state: State<S>,
     closing_quote: u8,
-    tree_builder_simulator: Rc<RefCell<TreeBuilderSimulator>>,
+    tree_builder_simulator: Shared<TreeBuilderSimulator>,
     pending_text_type_change: Option<TextType>,
     last_text_type: TextType,
 }
 
 impl<S: TagHintSink> TagScanner<S> {
-    pub fn new(
-        tag_hint_sink: S,
-        tree_builder_simulator: Rc<RefCell<TreeBuilderSimulator>>,
-    ) -> Self {
+    pub fn new(tag_hint_sink: S, tree_builder_simulator: Shared<TreeBuilderSimulator>) -> Self {
         TagScanner {
             next_pos: 0,
             is_last_input: false,
@@ -79,6 +74,23 @@ impl<S: TagHintSink> TagScanner<S> {
         }
     }
 
+    pub fn reset(&mut self) {
+        self.next_pos = 0;
+        self.is_last_input = false;
+        self.tag_start = None;
+        self.ch_sequence_matching_start = None;
+        self.tag_name_start = 0;
+        self.is_in_end_tag = false;
+        self.tag_name_hash = LocalNameHash::default();
+        self.last_start_tag_name_hash = LocalNameHash::default();
+        self.is_state_enter = true;
+        self.cdata_allowed = false;
+        self.state = TagScanner::data_state;
+        self.closing_quote = b'"';
+        self.pending_text_type_change = None;
+        self.last_text_type = TextType::Data;
+    }
+
     fn emit_tag_hint(&mut self, input: &[u8]) -> Result<ParserDirective, RewritingError> {
         let name_range = Range {
             start: self.tag_name_start,
@@ -109,9 +109,11 @@ macro_rules! create_assert_for_tags {
             tag_name: LocalNameHash,
         ) -> Result<(), ParsingAmbiguityError> {
             if tag_is_one_of!(tag_name, [ $($tag),+ ]) {
-                Err(ParsingAmbiguityError {
-                    on_tag_name: tag_hash_to_string(tag_name)
-                })
+                let on_tag_name = tag_hash_to_string(tag_name);
+
+                trace!(@ambiguity_error on_tag_name);
+
+                Err(ParsingAmbiguityError { on_tag_name })
             } else {
                 Ok(())
             }
@@ -123,7 +125,7 @@ create_assert_for_tags!(
     Textarea, Title, Plaintext, Script, Style, Iframe, Xmp, Noembed, Noframes, Noscript
 );
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug)]
 enum State {
     Default,
     InSelect,
@@ -144,6 +146,13 @@ impl Default for AmbiguityGuard {
 }
 
 impl AmbiguityGuard {
+    #[inline]
+    fn switch_state(&mut self, state: State) {
+        trace!(@ambiguity_guard state);
+
+        self.state = state;
+    }
+
     pub fn track_start_tag(
         &mut self,
         tag_name: LocalNameHash,
@@ -151,18 +160,18 @@ impl AmbiguityGuard {
         match self.state {
             State::Default => {
                 if tag_name == Tag::Select {
-                    self.state = State::InSelect;
+                    self.switch_state(State::InSelect);
                 } else if tag_name == Tag::Frameset {
-                    self.state = State::InOrAfterFrameset;
+                    self.switch_state(State::InOrAfterFrameset);
                 }
             }
             State::InSelect => {
                 // NOTE: these start tags cause premature exit
                 // from "in select" insertion mode.
                 if tag_is_one_of!(tag_name, [Select, Textarea, Input, Keygen]) {
-                    self.state = State::Default;
+                    self.switch_state(State::Default);
                 } else if tag_name == Tag::Template {
-                    self.state = State::InTemplateInSelect(1);
+                    self.switch_state(State::InTemplateInSelect(1));
                 }
                 // NOTE: <script> is allowed in "in select" insertion mode.
                 else if tag_name != Tag::Script {
@@ -171,7 +180,7 @@ impl AmbiguityGuard {
             }
             State::InTemplateInSelect(depth) => {
                 if tag_name == Tag::Template {
-                    self.state = State::InTemplateInSelect(depth + 1);
+                    self.switch_state(State::InTemplateInSelect(depth + 1));
                 } else {
                     assert_not_ambigious_text_type_switch(tag_name)?;
                 }
@@ -190,14 +199,14 @@ impl AmbiguityGuard {
     pub fn track_end_tag(&mut self, tag_name: LocalNameHash) {
         match self.state {
             State::InSelect if tag_name == Tag::Select => {
-                self.state = State::Default;
+                self.switch_state(State::Default);
             }
             State::InTemplateInSelect(depth) if tag_name == Tag::Template => {
-                self.state = if depth == 1 {
+                self.switch_state(if depth == 1 {
                     State::InSelect
                 } else {
                     State::InTemplateInSelect(depth - 1)
-                }
+                });
             }
             _ => (),
         }
@@ -14,7 +14,7 @@
 mod ambiguity_guard;
 
 use self::ambiguity_guard::AmbiguityGuard;
-use crate::base::Bytes;
+use crate::base::{Bytes, MaybeSend};
 use crate::html::{LocalNameHash, Namespace, Tag, TextType};
 use crate::parser::{TagLexeme, TagTokenOutline};
 use TagTokenOutline::*;
@@ -27,7 +27,9 @@ const DEFAULT_NS_STACK_CAPACITY: usize = 256;
 pub enum TreeBuilderFeedback {
     SwitchTextType(TextType),
     SetAllowCdata(bool),
-    RequestLexeme(Box<dyn FnMut(&mut TreeBuilderSimulator, &TagLexeme) -> TreeBuilderFeedback>),
+    RequestLexeme(
+        Box<dyn_maybe_send!(FnMut(&mut TreeBuilderSimulator, &TagLexeme) -> TreeBuilderFeedback)>,
+    ),
     None,
 }
 
@@ -40,7 +42,9 @@ impl From<TextType> for TreeBuilderFeedback {
 
 #[inline]
 fn request_lexeme(
-    callback: impl FnMut(&mut TreeBuilderSimulator, &TagLexeme) -> TreeBuilderFeedback + 'static,
+    callback: impl FnMut(&mut TreeBuilderSimulator, &TagLexeme) -> TreeBuilderFeedback
+        + MaybeSend
+        + 'static,
 ) -> TreeBuilderFeedback {
     TreeBuilderFeedback::RequestLexeme(Box::new(callback))
 }
@@ -130,6 +134,13 @@ impl TreeBuilderSimulator {
         simulator
     }
 
+    pub fn reset(&mut self) {
+        self.ns_stack.clear();
+        self.ns_stack.push(Namespace::Html);
+        self.current_ns = Namespace::Html;
+        self.ambiguity_guard = AmbiguityGuard::default();
+    }
+
     pub fn get_feedback_for_start_tag(
         &mut self,
         tag_name: LocalNameHash,
@@ -0,0 +1,238 @@
+use super::mutations::content_to_bytes;
+use super::ContentType;
+
+use encoding_rs::Encoding;
+
+/// A rewritable unit that represents the start of the document.
+///
+/// This exposes the [prepend](#method.prepend) function that can be used to prepend content to
+/// the document. The content is emitted before any output produced from the input. If the input
+/// starts with a byte order mark, the content is inserted after it.
+pub struct DocumentStart {
+    content: Vec<u8>,
+    encoding: &'static Encoding,
+}
+
+impl DocumentStart {
+    pub(crate) fn new(encoding: &'static Encoding) -> Self {
+        DocumentStart {
+            content: Vec::default(),
+            encoding,
+        }
+    }
+
+    /// Prepends `content` to the document.
+    ///
+    /// Subsequent calls to this method prepend `content` to the previously inserted content.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lol_html::{start, rewrite_str, RewriteStrSettings};
+    /// use lol_html::html_content::{ContentType, DocumentStart};
+    ///
+    /// let html = rewrite_str(
+    ///     r#"<div id="foo"><!-- content --></div><img>"#,
+    ///     RewriteStrSettings {
+    ///         document_content_handlers: vec![start!(|start| {
+    ///             start.prepend("<bar>", ContentType::Html);
+    ///             start.prepend("<baz>", ContentType::Text);
+    ///             Ok(())
+    ///         })],
+    ///         ..RewriteStrSettings::default()
+    ///     }
+    /// ).unwrap();
+    ///
+    /// assert_eq!(html, r#"&lt;baz&gt;<bar><div id="foo"><!-- content --></div><img>"#);
+    /// ```
+    #[inline]
+    pub fn prepend(&mut self, content: &str, content_type: ContentType) {
+        let mut pos = 0;
+
+        content_to_bytes(content, content_type, self.encoding, &mut |c| {
+            self.content.splice(pos..pos, c.iter().cloned());
+
+            pos += c.len();
+        });
+    }
+
+    #[inline]
+    pub(crate) fn into_content(self) -> Vec<u8> {
+        self.content
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::html_content::*;
+    use crate::rewritable_units::test_utils::*;
+    use crate::*;
+    use encoding_rs::{Encoding, UTF_16LE, UTF_8, WINDOWS_1251};
+
+    fn rewrite_on_start(
+        html: &[u8],
+        encoding: &'static Encoding,
+        mut handler: impl FnMut(&mut DocumentStart) + MaybeSend,
+    ) -> String {
+        let mut handler_called = false;
+
+        let output = rewrite_html(
+            html,
+            encoding,
+            vec![],
+            vec![start!(|start| {
+                handler_called = true;
+                handler(start);
+
+                Ok(())
+            })],
+        );
+
+        assert!(handler_called, "Handler not called.");
+
+        output
+    }
+
+    fn rewrite_in_chunks(chunks: &[&[u8]], settings: Settings) -> Vec<u8> {
+        let mut output = vec![];
+
+        {
+            let mut rewriter = HtmlRewriter::new(settings, |c: &[u8]| {
+                output.extend_from_slice(c);
+            });
+
+            for chunk in chunks {
+                rewriter.write(chunk).unwrap();
+            }
+
+            rewriter.end().unwrap();
+        }
+
+        output
+    }
+
+    #[test]
+    fn prepend_to_empty_document() {
+        let output = rewrite_on_start(b"", UTF_8, |start| {
+            start.prepend("<div></div>", ContentType::Html);
+        });
+
+        assert_eq!(output, "<div></div>")
+    }
+
+    #[test]
+    fn prepend_content() {
+        for (html, enc) in encoded("<div><h1>Hεllo</h1></div>") {
+            let output = rewrite_on_start(&html, enc, |start| {
+                start.prepend("</span>", ContentType::Html);
+                start.prepend("<foo>", ContentType::Text);
+                start.prepend("Hεllo", ContentType::Text);
+                start.prepend("<span>", ContentType::Html);
+            });
+
+            assert_eq!(
+                output,
+                "<span>Hεllo&lt;foo&gt;</span><div><h1>Hεllo</h1></div>"
+            );
+        }
+    }
+
+    #[test]
+    fn prepend_after_bom() {
+        let chunks: &[&[u8]] = &[b"\xEF", b"\xBB", b"\xBF<div>"];
+
+        let output = rewrite_in_chunks(
+            chunks,
+            Settings {
+                document_content_handlers: vec![start!(|start| {
+                    start.prepend("<!-- banner -->", ContentType::Html);
+
+                    Ok(())
+                })],
+                ..Settings::default()
+            },
+        );
+
+        assert_eq!(output, b"\xEF\xBB\xBF<!-- banner --><div>");
+    }
+
+    #[test]
+    fn source_locations_after_bom() {
+        let mut location = None;
+
+        rewrite_in_chunks(
+            &[b"\xEF\xBB\xBF<div>"],
+            Settings {
+                element_content_handlers: vec![element!("div", |el| {
+                    location = Some(el.source_location());
+
+                    Ok(())
+                })],
+                document_content_handlers: vec![start!(|start| {
+                    start.prepend("<!-- banner -->", ContentType::Html);
+
+                    Ok(())
+                })],
+                ..Settings::default()
+            },
+        );
+
+        let location = location.unwrap();
+
+        assert_eq!((location.offset(), location.column()), (3, 4));
+    }
+
+    #[test]
+    fn prepend_with_detected_encoding() {
+        let (html, _, _) = WINDOWS_1251.encode("<meta charset=windows-1251><p>Привет</p>");
+
+        let output = rewrite_in_chunks(
+            &[&html],
+            Settings {
+                document_content_handlers: vec![start!(|start| {
+                    start.prepend("<!-- Мир -->", ContentType::Html);
+
+                    Ok(())
+                })],
+                detect_encoding: true,
+                ..Settings::default()
+            },
+        );
+
+        assert_eq!(
+            WINDOWS_1251.decode_without_bom_handling(&output).0,
+            "<!-- Мир --><meta charset=windows-1251><p>Привет</p>"
+        );
+    }
+
+    #[test]
+    fn prepend_with_transcoding() {
+        let html = "\u{feff}<p>foo</p>"
+            .encode_utf16()
+            .flat_map(|u| u.to_le_bytes().to_vec())
+            .collect::<Vec<_>>();
+
+        let output = rewrite_in_chunks(
+            &[&html],
+            Settings {
+                document_content_handlers: vec![start!(|start| {
+                    start.prepend("<!-- bar -->", ContentType::Html);
+
+                    Ok(())
+                })],
+                transcoding: Some(Transcoding {
+                    encoding: UTF_16LE,
+                    transcode_output: true,
+                }),
+                ..Settings::default()
+            },
+        );
+
+        let expected = "\u{feff}<!-- bar --><p>foo</p>"
+            .encode_utf16()
+            .flat_map(|u| u.to_le_bytes().to_vec())
+            .collect::<Vec<_>>();
+
+        assert_eq!(output, expected);
+    }
+}
@@ -1,6 +1,9 @@
-use super::{Attribute, AttributeNameError, ContentType, EndTag, Mutations, StartTag};
-use crate::base::Bytes;
-use crate::rewriter::EndTagHandler;
+use super::{
+    Attribute, AttributeNameError, ContentType, EndTag, Mutations, SourceLocation, StartTag,
+    StreamingContent,
+};
+use crate::base::{Bytes, MaybeSend};
+use crate::rewriter::{EndTagHandler, HandlerResult, InnerHtmlHandler};
 use encoding_rs::Encoding;
 use std::any::Any;
 use std::fmt::{self, Debug};
@@ -30,6 +33,26 @@ pub enum TagNameError {
     UnencodableCharacter,
 }
 
+/// An error that occurs when an end tag handler is registered for an element that doesn't have
+/// an end tag, or when the inner content of an element is replaced without being captured.
+#[derive(Error, Debug, PartialEq, Copy, Clone)]
+pub enum EndTagError {
+    /// The element is an [empty element] or a self-closing element in a foreign content and,
+    /// thus, can't have an end tag.
+    ///
+    /// [empty element]: https://developer.mozilla.org/en-US/docs/Glossary/Empty_element
+    #[error("The element doesn't have an end tag.")]
+    NoEndTag,
+
+    /// [`EndTag::set_inner_content`] is called outside of the handler passed to
+    /// [`Element::capture_inner_html`].
+    ///
+    /// [`EndTag::set_inner_content`]: ../html_content/struct.EndTag.html#method.set_inner_content
+    /// [`Element::capture_inner_html`]: ../html_content/struct.Element.html#method.capture_inner_html
+    #[error("The inner content of the element is not captured.")]
+    NoCapturedInnerContent,
+}
+
 /// An HTML element rewritable unit.
 ///
 /// Exposes API for examination and modification of a parsed HTML element.
@@ -37,6 +60,8 @@ pub struct Element<'r, 't> {
     start_tag: &'r mut StartTag<'t>,
     end_tag_mutations: Option<Mutations>,
     modified_end_tag_name: Option<Bytes<'static>>,
+    end_tag_handlers: Vec<EndTagHandler<'static>>,
+    inner_html_handlers: Vec<(usize, InnerHtmlHandler<'static>)>,
     can_have_content: bool,
     should_remove_content: bool,
     encoding: &'static Encoding,
@@ -51,6 +76,8 @@ impl<'r, 't> Element<'r, 't> {
             start_tag,
             end_tag_mutations: None,
             modified_end_tag_name: None,
+            end_tag_handlers: Vec::default(),
+            inner_html_handlers: Vec::default(),
             can_have_content,
             should_remove_content: false,
             encoding,
@@ -97,6 +124,16 @@ impl<'r, 't> Element<'r, 't> {
             .get_or_insert_with(|| Mutations::new(encoding))
     }
 
+    /// Returns the location of the element's start tag in the input.
+    ///
+    /// Refer to [`SourceLocation`] documentation for more information.
+    ///
+    /// [`SourceLocation`]: struct.SourceLocation.html
+    #[inline]
+    pub fn source_location(&self) -> SourceLocation {
+        self.start_tag.source_location()
+    }
+
     /// Returns the tag name of the element.
     #[inline]
     pub fn tag_name(&self) -> String {
@@ -131,20 +168,61 @@ impl<'r, 't> Element<'r, 't> {
         self.start_tag.attributes()
     }
 
+    #[inline]
+    fn find_attribute(&self, name: &str) -> Option<&Attribute<'t>> {
+        let name = name.to_ascii_lowercase();
+
+        self.attributes().iter().find(|attr| attr.name() == name)
+    }
+
     /// Returns the value of an attribute with the `name`.
     ///
     /// Returns `None` if the element doesn't have an attribute with the `name`.
+    ///
+    /// Character references in the value are not decoded. Use [`get_decoded_attribute`] to get
+    /// the value with decoded character references.
+    ///
+    /// [`get_decoded_attribute`]: #method.get_decoded_attribute
     #[inline]
     pub fn get_attribute(&self, name: &str) -> Option<String> {
-        let name = name.to_ascii_lowercase();
+        self.find_attribute(name).map(Attribute::value)
+    }
 
-        self.attributes().iter().find_map(|attr| {
-            if attr.name() == name {
-                Some(attr.value())
-            } else {
-                None
-            }
-        })
+    /// Returns the value of an attribute with the `name` with decoded [character references].
+    ///
+    /// Returns `None` if the element doesn't have an attribute with the `name`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lol_html::{element, rewrite_str, RewriteStrSettings};
+    ///
+    /// rewrite_str(
+    ///     r#"<a href="/search?q=foo&amp;lang=en&copy=1"></a>"#,
+    ///     RewriteStrSettings {
+    ///         element_content_handlers: vec![element!("a", |el| {
+    ///             assert_eq!(
+    ///                 el.get_attribute("href").unwrap(),
+    ///                 "/search?q=foo&amp;lang=en&copy=1"
+    ///             );
+    ///
+    ///             assert_eq!(
+    ///                 el.get_decoded_attribute("href").unwrap(),
+    ///                 "/search?q=foo&lang=en&copy=1"
+    ///             );
+    ///
+    ///             Ok(())
+    ///         })],
+    ///         ..RewriteStrSettings::default()
+    ///     },
+    /// )
+    /// .unwrap();
+    /// ```
+    ///
+    /// [character references]: https://html.spec.whatwg.org/multipage/syntax.html#character-references
+    #[inline]
+    pub fn get_decoded_attribute(&self, name: &str) -> Option<String> {
+        self.find_attribute(name).map(Attribute::decoded_value)
     }
 
     /// Returns `true` if the element has an attribute with `name`.
@@ -159,6 +237,11 @@ impl<'r, 't> Element<'r, 't> {
     ///
     /// If element doesn't have an attribute with the `name`, method adds new attribute
     /// to the element with `name` and `value`.
+    ///
+    /// The `value` is treated as a decoded value: `&` and `"` characters are escaped in the output,
+    /// so the [decoded value] of the attribute is equal to the `value`.
+    ///
+    /// [decoded value]: #method.get_decoded_attribute
     #[inline]
     pub fn set_attribute(&mut self, name: &str, value: &str) -> Result<(), AttributeNameError> {
         self.start_tag.set_attribute(name, value)
@@ -403,6 +486,99 @@ impl<'r, 't> Element<'r, 't> {
         }
     }
 
+    /// Inserts streaming `content` before the element.
+    ///
+    /// The streaming version of [`before`]. The content is pulled from the producer and written
+    /// directly to the output once the element's start tag is emitted.
+    ///
+    /// [`before`]: #method.before
+    #[inline]
+    pub fn streaming_before(&mut self, content: StreamingContent, content_type: ContentType) {
+        self.start_tag
+            .mutations
+            .streaming_before(content, content_type);
+    }
+
+    /// Inserts streaming `content` after the element.
+    ///
+    /// The streaming version of [`after`].
+    ///
+    /// [`after`]: #method.after
+    #[inline]
+    pub fn streaming_after(&mut self, content: StreamingContent, content_type: ContentType) {
+        if self.can_have_content {
+            self.end_tag_mutations_mut()
+                .streaming_after(content, content_type);
+        } else {
+            self.start_tag
+                .mutations
+                .streaming_after(content, content_type);
+        }
+    }
+
+    /// Prepends streaming `content` to the element's inner content.
+    ///
+    /// The streaming version of [`prepend`].
+    ///
+    /// [`prepend`]: #method.prepend
+    #[inline]
+    pub fn streaming_prepend(&mut self, content: StreamingContent, content_type: ContentType) {
+        if self.can_have_content {
+            self.start_tag
+                .mutations
+                .streaming_after(content, content_type);
+        }
+    }
+
+    /// Appends streaming `content` to the element's inner content.
+    ///
+    /// The streaming version of [`append`].
+    ///
+    /// [`append`]: #method.append
+    #[inline]
+    pub fn streaming_append(&mut self, content: StreamingContent, content_type: ContentType) {
+        if self.can_have_content {
+            self.end_tag_mutations_mut()
+                .streaming_before(content, content_type);
+        }
+    }
+
+    /// Replaces inner content of the element with streaming `content`.
+    ///
+    /// The streaming version of [`set_inner_content`].
+    ///
+    /// [`set_inner_content`]: #method.set_inner_content
+    #[inline]
+    pub fn streaming_set_inner_content(
+        &mut self,
+        content: StreamingContent,
+        content_type: ContentType,
+    ) {
+        if self.can_have_content {
+            self.remove_content();
+            self.start_tag
+                .mutations
+                .streaming_after(content, content_type);
+        }
+    }
+
+    /// Replaces the element and its inner content with streaming `content`.
+    ///
+    /// The streaming version of [`replace`].
+    ///
+    /// [`replace`]: #method.replace
+    #[inline]
+    pub fn streaming_replace(&mut self, content: StreamingContent, content_type: ContentType) {
+        self.start_tag
+            .mutations
+            .streaming_replace(content, content_type);
+
+        if self.can_have_content {
+            self.remove_content();
+            self.end_tag_mutations_mut().remove();
+        }
+    }
+
     /// Removes the element and its inner content.
     #[inline]
     pub fn remove(&mut self) {
@@ -452,23 +628,202 @@ impl<'r, 't> Element<'r, 't> {
         self.start_tag.mutations.removed()
     }
 
+    /// Registers a `handler` that will be invoked once the end tag of the element is reached.
+    ///
+    /// The handler is also invoked if the element is closed implicitly (e.g. by the end tag
+    /// of its parent). In this case the handler receives the end tag that closes the element.
+    ///
+    /// Returns an error if the element can't have an end tag, e.g. if it is an [empty element].
+    ///
+    /// [empty element]: https://developer.mozilla.org/en-US/docs/Glossary/Empty_element
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lol_html::{rewrite_str, element, RewriteStrSettings};
+    /// use lol_html::html_content::ContentType;
+    ///
+    /// let html = rewrite_str(
+    ///     r#"<div><span>foo</span></div>"#,
+    ///     RewriteStrSettings {
+    ///         element_content_handlers: vec![
+    ///             element!("span", |el| {
+    ///                 el.on_end_tag(|end| {
+    ///                     assert_eq!(end.name(), "span");
+    ///
+    ///                     end.after("bar", ContentType::Text);
+    ///
+    ///                     Ok(())
+    ///                 })?;
+    ///
+    ///                 Ok(())
+    ///             })
+    ///         ],
+    ///         ..RewriteStrSettings::default()
+    ///     }
+    /// ).unwrap();
+    ///
+    /// assert_eq!(html, r#"<div><span>foo</span>bar</div>"#);
+    /// ```
+    #[inline]
+    pub fn on_end_tag(
+        &mut self,
+        handler: impl FnOnce(&mut EndTag) -> HandlerResult + MaybeSend + 'static,
+    ) -> Result<(), EndTagError> {
+        if self.can_have_content {
+            self.end_tag_handlers.push(Box::new(handler));
+
+            Ok(())
+        } else {
+            Err(EndTagError::NoEndTag)
+        }
+    }
+
+    /// Captures the inner content of the element and invokes the `handler` with it once the end
+    /// tag of the element is reached.
+    ///
+    /// The inner content is buffered instead of being written to the output until the end tag
+    /// is reached. The buffered content is charged to the memory limit specified in
+    /// [`MemorySettings`]. The inner content is captured as it would be written to the output,
+    /// i.e. with the modifications made by other content handlers, rather than as it appears
+    /// in the input. Unless replaced with [`EndTag::set_inner_content`], the captured content
+    /// is written to the output after the handler is invoked. If the end of the input is reached
+    /// before the end tag, the captured content is written to the output without invoking
+    /// the handler.
+    ///
+    /// The handler is not invoked if the inner content is longer than `max_len` bytes. Once
+    /// the inner content exceeds the limits of all the handlers of the element, the buffered
+    /// content is written to the output (or captured for an enclosing element) and the rest of
+    /// the inner content is passed through without being buffered.
+    ///
+    /// Returns an error if the element can't have an end tag, e.g. if it is an [empty element].
+    ///
+    /// [`MemorySettings`]: ../struct.MemorySettings.html
+    /// [`EndTag::set_inner_content`]: struct.EndTag.html#method.set_inner_content
+    /// [empty element]: https://developer.mozilla.org/en-US/docs/Glossary/Empty_element
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lol_html::{rewrite_str, element, RewriteStrSettings};
+    /// use lol_html::html_content::ContentType;
+    ///
+    /// let html = rewrite_str(
+    ///     r#"<script type="application/ld+json">{"name": "foo"}</script>"#,
+    ///     RewriteStrSettings {
+    ///         element_content_handlers: vec![
+    ///             element!(r#"script[type="application/ld+json"]"#, |el| {
+    ///                 el.capture_inner_html(1024, |html, end| {
+    ///                     assert_eq!(html, r#"{"name": "foo"}"#);
+    ///
+    ///                     end.set_inner_content(r#"{"name": "bar"}"#, ContentType::Html)?;
+    ///
+    ///                     Ok(())
+    ///                 })?;
+    ///
+    ///                 Ok(())
+    ///             })
+    ///         ],
+    ///         ..RewriteStrSettings::default()
+    ///     }
+    /// ).unwrap();
+    ///
+    /// assert_eq!(html, r#"<script type="application/ld+json">{"name": "bar"}</script>"#);
+    /// ```
+    #[inline]
+    pub fn capture_inner_html(
+        &mut self,
+        max_len: usize,
+        handler: impl FnOnce(&str, &mut EndTag) -> HandlerResult + MaybeSend + 'static,
+    ) -> Result<(), EndTagError> {
+        if self.can_have_content {
+            self.inner_html_handlers.push((max_len, Box::new(handler)));
+
+            Ok(())
+        } else {
+            Err(EndTagError::NoEndTag)
+        }
+    }
+
     #[inline]
     pub(crate) fn should_remove_content(&self) -> bool {
         self.should_remove_content
     }
 
+    // NOTE: the content is captured for the handler with the greatest limit.
+    #[inline]
+    pub(crate) fn content_capture_max_len(&self) -> Option<usize> {
+        self.inner_html_handlers
+            .iter()
+            .map(|(max_len, _)| *max_len)
+            .max()
+    }
+
     pub(crate) fn into_end_tag_handler(self) -> Option<EndTagHandler<'static>> {
         let end_tag_mutations = self.end_tag_mutations;
         let modified_end_tag_name = self.modified_end_tag_name;
+        let end_tag_handlers = self.end_tag_handlers;
+        let inner_html_handlers = self.inner_html_handlers;
 
-        if end_tag_mutations.is_some() || modified_end_tag_name.is_some() {
+        if end_tag_mutations.is_some()
+            || modified_end_tag_name.is_some()
+            || !end_tag_handlers.is_empty()
+            || !inner_html_handlers.is_empty()
+        {
             Some(Box::new(move |end_tag: &mut EndTag| {
                 if let Some(name) = modified_end_tag_name {
                     end_tag.set_name(name);
                 }
 
+                // NOTE: the captured content is missing if it has exceeded the limits
+                // of the handlers and has been passed through.
+                let mut inner_content = if inner_html_handlers.is_empty() {
+                    None
+                } else {
+                    end_tag.take_captured_inner_content()
+                };
+
+                if let Some(ref mut content) = inner_content {
+                    let encoding = end_tag.encoding();
+
+                    // NOTE: the end tag can implicitly close the elements nested in this one,
+                    // and the content that their handlers inserted before the end tag
+                    // belongs to the captured content.
+                    end_tag
+                        .mutations
+                        .content_before
+                        .serialize(encoding, &mut |c| content.extend_from_slice(c))?;
+                }
+
+                // NOTE: the end tag can close several elements, so we don't overwrite
+                // the mutations made for the other elements.
                 if let Some(mutations) = end_tag_mutations {
-                    end_tag.mutations = mutations;
+                    end_tag.mutations.merge(mutations);
+                }
+
+                if let Some(content) = inner_content {
+                    {
+                        let encoding = end_tag.encoding();
+                        let (html, _) = encoding.decode_without_bom_handling(&content);
+
+                        end_tag.set_inner_content_captured(true);
+
+                        for (max_len, handler) in inner_html_handlers {
+                            if content.len() <= max_len {
+                                handler(&html, end_tag)?;
+                            }
+                        }
+
+                        end_tag.set_inner_content_captured(false);
+                    }
+
+                    let content = end_tag.take_inner_content_replacement().unwrap_or(content);
+
+                    end_tag.mutations.content_before.prepend_bytes(content);
+                }
+
+                for handler in end_tag_handlers {
+                    handler(end_tag)?;
                 }
 
                 Ok(())
@@ -496,13 +851,14 @@ mod tests {
     use crate::html_content::*;
     use crate::rewritable_units::test_utils::*;
     use crate::*;
-    use encoding_rs::{Encoding, EUC_JP, UTF_8};
+    use encoding_rs::{Encoding, EUC_JP, UTF_8, WINDOWS_1251};
+    use std::io;
 
     fn rewrite_element(
         html: &[u8],
         encoding: &'static Encoding,
         selector: &str,
-        mut handler: impl FnMut(&mut Element),
+        mut handler: impl FnMut(&mut Element) + MaybeSend,
     ) -> String {
         let mut handler_called = false;
 
@@ -709,6 +1065,90 @@ mod tests {
         }
     }
 
+    #[test]
+    fn get_decoded_attrs() {
+        for (html, enc) in
+            encoded(r#"<a href="/?a=1&amp;b=2&copy=3&#x3B1;" title="&lt;Barβ&gt" id=foo>"#)
+        {
+            rewrite_element(&html, enc, "a", |el| {
+                assert_eq!(
+                    el.get_attribute("href").unwrap(),
+                    "/?a=1&amp;b=2&copy=3&#x3B1;",
+                    "Encoding: {}",
+                    enc.name()
+                );
+
+                assert_eq!(
+                    el.get_decoded_attribute("href").unwrap(),
+                    "/?a=1&b=2&copy=3α",
+                    "Encoding: {}",
+                    enc.name()
+                );
+
+                assert_eq!(
+                    el.get_decoded_attribute("TITLE").unwrap(),
+                    "<Barβ>",
+                    "Encoding: {}",
+                    enc.name()
+                );
+
+                assert_eq!(
+                    el.get_decoded_attribute("id").unwrap(),
+                    "foo",
+                    "Encoding: {}",
+                    enc.name()
+                );
+
+                assert_eq!(
+                    el.get_decoded_attribute("foo"),
+                    None,
+                    "Encoding: {}",
+                    enc.name()
+                );
+            });
+        }
+    }
+
+    #[test]
+    fn set_attr_round_trip_with_decoded_value() {
+        for (html, enc) in encoded("<a title=β>") {
+            let output = rewrite_element(&html, enc, "a", |el| {
+                el.set_attribute("href", "/?a=1&amp;b=\"β\"").unwrap();
+
+                assert_eq!(
+                    el.get_attribute("href").unwrap(),
+                    "/?a=1&amp;b=\"β\"",
+                    "Encoding: {}",
+                    enc.name()
+                );
+
+                assert_eq!(
+                    el.get_decoded_attribute("href").unwrap(),
+                    "/?a=1&amp;b=\"β\"",
+                    "Encoding: {}",
+                    enc.name()
+                );
+            });
+
+            assert_eq!(
+                output,
+                r#"<a title=β href="/?a=1&amp;amp;b=&quot;β&quot;">"#
+            );
+        }
+    }
+
+    #[test]
+    fn set_attr_with_unencodable_characters() {
+        let output = rewrite_element(b"<a>", WINDOWS_1251, "a", |el| {
+            el.set_attribute("title", "β&").unwrap();
+
+            assert_eq!(el.get_attribute("title").unwrap(), "β&");
+            assert_eq!(el.get_decoded_attribute("title").unwrap(), "β&");
+        });
+
+        assert_eq!(output, r#"<a title="&#946;&amp;">"#);
+    }
+
     #[test]
     fn has_attr() {
         for (html, enc) in encoded("<Foo FooѦ1=Bar1 FooѤ2=Bar2>") {
@@ -870,32 +1310,203 @@ mod tests {
     }
 
     #[test]
-    fn remove() {
-        for (html, enc) in
-            encoded("<div><span㗵>Hi<inner-remove-me>Remove</inner-remove-me></span㗵></div>")
-        {
-            let output = rewrite_element(&html, enc, "span㗵", |el| {
-                el.prepend("<prepended>", ContentType::Html);
-                el.append("<appended>", ContentType::Html);
-                el.remove();
+    fn streaming_insertion() {
+        for (html, enc) in encoded("<div><span>ĥi</span></div>") {
+            let output = rewrite_element(&html, enc, "span", |el| {
+                el.before("<b>", ContentType::Html);
+                el.streaming_before(
+                    StreamingContent::from_chunks(vec!["<img", "Ĥ>"]),
+                    ContentType::Text,
+                );
+                el.before("<i>", ContentType::Html);
 
-                assert!(el.removed());
+                el.streaming_prepend(
+                    StreamingContent::from_reader("<p>Ĥ".as_bytes()),
+                    ContentType::Html,
+                );
+
+                el.streaming_append(
+                    StreamingContent::from_chunks(vec!["<s>".to_string()]),
+                    ContentType::Text,
+                );
+
+                el.after("<a>", ContentType::Html);
+                el.streaming_after(
+                    StreamingContent::from_chunks(vec!["<u>"]),
+                    ContentType::Html,
+                );
             });
 
-            assert_eq!(output, "<div></div>");
+            assert_eq!(
+                output,
+                "<div><b>&lt;imgĤ&gt;<i><span><p>Ĥĥi&lt;s&gt;</span><u><a></div>"
+            );
         }
     }
 
     #[test]
-    fn remove_with_unfinished_end_tag() {
-        for (html, enc) in encoded("<div><span㚴>Heello</span㚴  ") {
-            let output = rewrite_element(&html, enc, "span㚴", |el| {
-                el.remove();
+    fn streaming_set_inner_content_and_replace() {
+        for (html, enc) in
+            encoded("<div><span>Hi<inner-remove-me>Remove㘗</inner-remove-me></span></div>")
+        {
+            let output = rewrite_element(&html, enc, "span", |el| {
+                el.append("<appended>", ContentType::Html);
+                el.streaming_set_inner_content(
+                    StreamingContent::from_chunks(vec!["<img㘘>"]),
+                    ContentType::Text,
+                );
+            });
+
+            assert_eq!(output, "<div><span>&lt;img㘘&gt;</span></div>");
+
+            let output = rewrite_element(&html, enc, "span", |el| {
+                el.prepend("<prepended>", ContentType::Html);
+                el.streaming_replace(
+                    StreamingContent::from_chunks(vec!["<img㘘>"]),
+                    ContentType::Html,
+                );
 
                 assert!(el.removed());
             });
 
-            assert_eq!(output, "<div>");
+            assert_eq!(output, "<div><img㘘></div>");
+        }
+    }
+
+    #[test]
+    fn streaming_content_split_between_reads() {
+        struct ByteByByteReader(&'static [u8]);
+
+        impl io::Read for ByteByByteReader {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                match self.0.split_first() {
+                    Some((&b, rest)) if !buf.is_empty() => {
+                        buf[0] = b;
+                        self.0 = rest;
+
+                        Ok(1)
+                    }
+                    _ => Ok(0),
+                }
+            }
+        }
+
+        for (html, enc) in encoded("<div>Привет</div>") {
+            let output = rewrite_element(&html, enc, "div", |el| {
+                el.streaming_set_inner_content(
+                    StreamingContent::from_reader(ByteByByteReader("Привет, <мир>".as_bytes())),
+                    ContentType::Text,
+                );
+            });
+
+            assert_eq!(output, "<div>Привет, &lt;мир&gt;</div>");
+        }
+    }
+
+    #[test]
+    fn streaming_content_of_removed_element() {
+        let output = rewrite_html(
+            b"<div><span>Hi</span></div>",
+            UTF_8,
+            vec![
+                element!("span", |el| {
+                    el.streaming_append(
+                        StreamingContent::from_chunks(std::iter::from_fn(|| -> Option<&str> {
+                            panic!("Removed content should not be pulled.")
+                        })),
+                        ContentType::Html,
+                    );
+
+                    Ok(())
+                }),
+                element!("div", |el| {
+                    el.remove();
+
+                    Ok(())
+                }),
+            ],
+            vec![],
+        );
+
+        assert_eq!(output, "");
+    }
+
+    #[test]
+    fn streaming_content_errors() {
+        struct FailingReader;
+
+        impl io::Read for FailingReader {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::new(io::ErrorKind::Other, "Reading failed."))
+            }
+        }
+
+        let rewrite = |content: fn() -> StreamingContent| {
+            let mut rewriter = HtmlRewriter::new(
+                Settings {
+                    element_content_handlers: vec![element!("div", move |el| {
+                        el.streaming_before(content(), ContentType::Html);
+
+                        Ok(())
+                    })],
+                    ..Settings::default()
+                },
+                |_: &[u8]| {},
+            );
+
+            rewriter.write(b"<div></div>").unwrap_err()
+        };
+
+        let err = rewrite(|| StreamingContent::from_reader(FailingReader));
+
+        assert_eq!(err.to_string(), "Reading failed.");
+
+        let err = rewrite(|| StreamingContent::from_reader(&[b'a', 0xFF, b'b'][..]));
+
+        match err {
+            RewritingError::ContentHandlerError(e) => {
+                let e = e.downcast::<io::Error>().unwrap();
+
+                assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+            }
+            _ => panic!("{}", err),
+        }
+
+        let err = rewrite(|| StreamingContent::from_reader(&[b'a', 0xD0][..]));
+
+        assert_eq!(
+            err.to_string(),
+            "Streaming content ends with an incomplete UTF-8 sequence."
+        );
+    }
+
+    #[test]
+    fn remove() {
+        for (html, enc) in
+            encoded("<div><span㗵>Hi<inner-remove-me>Remove</inner-remove-me></span㗵></div>")
+        {
+            let output = rewrite_element(&html, enc, "span㗵", |el| {
+                el.prepend("<prepended>", ContentType::Html);
+                el.append("<appended>", ContentType::Html);
+                el.remove();
+
+                assert!(el.removed());
+            });
+
+            assert_eq!(output, "<div></div>");
+        }
+    }
+
+    #[test]
+    fn remove_with_unfinished_end_tag() {
+        for (html, enc) in encoded("<div><span㚴>Heello</span㚴  ") {
+            let output = rewrite_element(&html, enc, "span㚴", |el| {
+                el.remove();
+
+                assert!(el.removed());
+            });
+
+            assert_eq!(output, "<div>");
         }
     }
 
@@ -962,6 +1573,445 @@ mod tests {
         assert_eq!(output, "<svg><bar/><!--after-->Hi</foo></svg>");
     }
 
+    #[test]
+    fn end_tag_handler() {
+        for (html, enc) in encoded("<div><span>Hĩ</span></div>") {
+            let output = rewrite_element(&html, enc, "span", |el| {
+                el.on_end_tag(|end| {
+                    assert_eq!(end.name(), "span");
+
+                    end.before("<before>", ContentType::Html);
+                    end.after("<after>", ContentType::Text);
+
+                    Ok(())
+                })
+                .unwrap();
+            });
+
+            assert_eq!(output, "<div><span>Hĩ<before></span>&lt;after&gt;</div>");
+        }
+    }
+
+    #[test]
+    fn end_tag_handler_with_other_end_tag_mutations() {
+        let output = rewrite_element(b"<div><span>Hi</span></div>", UTF_8, "span", |el| {
+            el.append("<append>", ContentType::Html);
+            el.after("<after1>", ContentType::Html);
+            el.set_tag_name("foo").unwrap();
+
+            el.on_end_tag(|end| {
+                assert_eq!(end.name(), "foo");
+
+                end.before("<before>", ContentType::Html);
+                end.after("<after2>", ContentType::Html);
+
+                Ok(())
+            })
+            .unwrap();
+        });
+
+        assert_eq!(
+            output,
+            "<div><foo>Hi<append><before></foo><after2><after1></div>"
+        );
+    }
+
+    #[test]
+    fn multiple_end_tag_handlers() {
+        let output = rewrite_element(b"<div><span>Hi</span></div>", UTF_8, "span", |el| {
+            el.on_end_tag(|end| {
+                end.after("<after1>", ContentType::Html);
+                Ok(())
+            })
+            .unwrap();
+
+            el.on_end_tag(|end| {
+                end.after("<after2>", ContentType::Html);
+                Ok(())
+            })
+            .unwrap();
+        });
+
+        assert_eq!(output, "<div><span>Hi</span><after2><after1></div>");
+    }
+
+    #[test]
+    fn end_tag_handler_for_implicitly_closed_element() {
+        let output = rewrite_element(b"<div><span>Hi</div>", UTF_8, "span", |el| {
+            el.on_end_tag(|end| {
+                assert_eq!(end.name(), "div");
+
+                end.before("<before>", ContentType::Html);
+
+                Ok(())
+            })
+            .unwrap();
+        });
+
+        assert_eq!(output, "<div><span>Hi<before></div>");
+    }
+
+    #[test]
+    fn end_tag_removal() {
+        let output = rewrite_element(b"<div><span>Hi</span></div>", UTF_8, "span", |el| {
+            el.on_end_tag(|end| {
+                assert!(!end.removed());
+
+                end.remove();
+
+                assert!(end.removed());
+
+                Ok(())
+            })
+            .unwrap();
+        });
+
+        assert_eq!(output, "<div><span>Hi</div>");
+    }
+
+    #[test]
+    fn end_tag_handler_for_element_without_end_tag() {
+        rewrite_element(b"<img>", UTF_8, "img", |el| {
+            let err = el.on_end_tag(|_| Ok(())).unwrap_err();
+
+            assert_eq!(err, EndTagError::NoEndTag);
+        });
+
+        rewrite_element(b"<svg><foo/></svg>", UTF_8, "foo", |el| {
+            let err = el.on_end_tag(|_| Ok(())).unwrap_err();
+
+            assert_eq!(err, EndTagError::NoEndTag);
+        });
+    }
+
+    #[test]
+    fn end_tag_handler_error_propagation() {
+        let mut rewriter = HtmlRewriter::new(
+            Settings {
+                element_content_handlers: vec![element!("span", |el| {
+                    el.on_end_tag(|_| Err("Error in end tag handler".into()))?;
+
+                    Ok(())
+                })],
+                ..Settings::default()
+            },
+            |_: &[u8]| {},
+        );
+
+        let err = rewriter.write(b"<div><span>Hi</span></div>").unwrap_err();
+
+        assert_eq!(format!("{}", err), "Error in end tag handler");
+    }
+
+    #[test]
+    fn capture_inner_html() {
+        for (html, enc) in encoded("<div><span>Hĩ <b>there</b><!-- c --></span></div>") {
+            let output = rewrite_element(&html, enc, "span", |el| {
+                el.capture_inner_html(1024, |html, end| {
+                    assert_eq!(html, "Hĩ <b>there</b><!-- c -->");
+                    assert_eq!(end.name(), "span");
+
+                    Ok(())
+                })
+                .unwrap();
+            });
+
+            assert_eq!(output, "<div><span>Hĩ <b>there</b><!-- c --></span></div>");
+        }
+    }
+
+    #[test]
+    fn capture_inner_html_replacement() {
+        for (html, enc) in encoded("<div><span>Hi<b>!</b></span></div>") {
+            let output = rewrite_element(&html, enc, "span", |el| {
+                el.append("<append>", ContentType::Html);
+
+                el.capture_inner_html(1024, |_, end| {
+                    end.set_inner_content("<b>Hello</b>", ContentType::Html)?;
+                    end.set_inner_content("<i>Hello</i>", ContentType::Text)?;
+                    end.before("<before>", ContentType::Html);
+
+                    Ok(())
+                })
+                .unwrap();
+            });
+
+            assert_eq!(
+                output,
+                "<div><span>&lt;i&gt;Hello&lt;/i&gt;<append><before></span></div>"
+            );
+        }
+    }
+
+    #[test]
+    fn capture_inner_html_with_other_handlers() {
+        let output = rewrite_html(
+            b"<div>a<span>b</span><i>c</i>d</div>",
+            UTF_8,
+            vec![
+                element!("div", |el| {
+                    el.capture_inner_html(1024, |html, _| {
+                        assert_eq!(html, "a<span>x</span><b>c</b>d");
+
+                        Ok(())
+                    })?;
+
+                    Ok(())
+                }),
+                element!("span", |el| {
+                    el.set_inner_content("x", ContentType::Text);
+
+                    Ok(())
+                }),
+                element!("i", |el| {
+                    el.set_tag_name("b")?;
+
+                    Ok(())
+                }),
+            ],
+            vec![],
+        );
+
+        assert_eq!(output, "<div>a<span>x</span><b>c</b>d</div>");
+    }
+
+    #[test]
+    fn nested_inner_html_captures() {
+        const HTML: &str = "<div>a<span>b<i>c</i></span>d</div><span>e</span>";
+
+        let mut output = vec![];
+
+        {
+            let mut rewriter = HtmlRewriter::new(
+                Settings {
+                    element_content_handlers: vec![
+                        element!("div", |el| {
+                            el.capture_inner_html(1024, |html, end| {
+                                assert_eq!(html, "a<span>[b<i>c</i>]</span>d");
+
+                                end.set_inner_content("x", ContentType::Text)?;
+
+                                Ok(())
+                            })?;
+
+                            Ok(())
+                        }),
+                        element!("span", |el| {
+                            el.capture_inner_html(1024, |html, end| {
+                                assert!(html == "b<i>c</i>" || html == "e");
+
+                                end.set_inner_content(&format!("[{}]", html), ContentType::Html)?;
+
+                                Ok(())
+                            })?;
+
+                            Ok(())
+                        }),
+                    ],
+                    ..Settings::default()
+                },
+                |c: &[u8]| output.extend_from_slice(c),
+            );
+
+            // NOTE: write the input byte by byte to test the capturing across the chunks.
+            for b in HTML.bytes() {
+                rewriter.write(&[b]).unwrap();
+            }
+
+            rewriter.end().unwrap();
+        }
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "<div>x</div><span>[e]</span>"
+        );
+    }
+
+    #[test]
+    fn capture_inner_html_max_len() {
+        let output = rewrite_element(b"<div><span>abcde</span></div>", UTF_8, "span", |el| {
+            el.capture_inner_html(5, |html, end| {
+                assert_eq!(html, "abcde");
+
+                end.set_inner_content("x", ContentType::Text)?;
+
+                Ok(())
+            })
+            .unwrap();
+
+            el.capture_inner_html(4, |_, _| panic!("Handler should not be called."))
+                .unwrap();
+        });
+
+        assert_eq!(output, "<div><span>x</span></div>");
+    }
+
+    #[test]
+    fn capture_inner_html_exceeding_max_len() {
+        const HTML: &str = "<div>a<span>bcdef</span>g</div><p>hijk</p>";
+
+        let mut output = vec![];
+
+        {
+            let mut rewriter = HtmlRewriter::new(
+                Settings {
+                    element_content_handlers: vec![
+                        element!("div", |el| {
+                            el.capture_inner_html(100, |html, end| {
+                                assert_eq!(html, "a<span>bcdef</span>g");
+
+                                end.set_inner_content("x", ContentType::Text)?;
+
+                                Ok(())
+                            })?;
+
+                            Ok(())
+                        }),
+                        element!("span, p", |el| {
+                            el.capture_inner_html(3, |_, _| {
+                                panic!("Handler should not be called.")
+                            })?;
+
+                            Ok(())
+                        }),
+                    ],
+                    ..Settings::default()
+                },
+                |c: &[u8]| output.extend_from_slice(c),
+            );
+
+            // NOTE: write the input byte by byte, so the content overflows the capture
+            // in the middle of the element.
+            for b in HTML.bytes() {
+                rewriter.write(&[b]).unwrap();
+            }
+
+            rewriter.end().unwrap();
+        }
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "<div>x</div><p>hijk</p>"
+        );
+    }
+
+    #[test]
+    fn capture_inner_html_of_implicitly_closed_elements() {
+        let output = rewrite_html(
+            b"<div>a<p>b</div>",
+            UTF_8,
+            vec![
+                element!("div", |el| {
+                    el.capture_inner_html(1024, |html, _| {
+                        assert_eq!(html, "a<p>c<append>");
+
+                        Ok(())
+                    })?;
+
+                    Ok(())
+                }),
+                element!("p", |el| {
+                    el.append("<append>", ContentType::Html);
+
+                    el.capture_inner_html(1024, |html, end| {
+                        assert_eq!(html, "b");
+
+                        end.set_inner_content("c", ContentType::Text)?;
+
+                        Ok(())
+                    })?;
+
+                    Ok(())
+                }),
+            ],
+            vec![],
+        );
+
+        assert_eq!(output, "<div>a<p>c<append></div>");
+    }
+
+    #[test]
+    fn capture_inner_html_of_unclosed_element() {
+        let output = rewrite_element(b"<div>a<span>b", UTF_8, "div", |el| {
+            el.capture_inner_html(1024, |_, _| panic!("Handler should not be called."))
+                .unwrap();
+        });
+
+        assert_eq!(output, "<div>a<span>b");
+    }
+
+    #[test]
+    fn capture_inner_html_of_removed_element() {
+        let output = rewrite_element(b"<div><span>a</span></div>", UTF_8, "span", |el| {
+            el.capture_inner_html(1024, |html, _| {
+                assert_eq!(html, "");
+
+                Ok(())
+            })
+            .unwrap();
+
+            el.remove();
+        });
+
+        assert_eq!(output, "<div></div>");
+    }
+
+    #[test]
+    fn capture_inner_html_for_element_without_end_tag() {
+        rewrite_element(b"<img>", UTF_8, "img", |el| {
+            let err = el.capture_inner_html(1024, |_, _| Ok(())).unwrap_err();
+
+            assert_eq!(err, EndTagError::NoEndTag);
+        });
+    }
+
+    #[test]
+    fn set_inner_content_without_capture() {
+        let output = rewrite_element(b"<div><span>a</span></div>", UTF_8, "span", |el| {
+            el.on_end_tag(|end| {
+                let err = end.set_inner_content("b", ContentType::Text).unwrap_err();
+
+                assert_eq!(err, EndTagError::NoCapturedInnerContent);
+
+                Ok(())
+            })
+            .unwrap();
+        });
+
+        assert_eq!(output, "<div><span>a</span></div>");
+    }
+
+    #[test]
+    fn capture_inner_html_memory_limit() {
+        const MAX: usize = 1024;
+
+        let mut rewriter = HtmlRewriter::new(
+            Settings {
+                element_content_handlers: vec![element!("div", |el| {
+                    el.capture_inner_html(usize::MAX, |_, _| Ok(()))?;
+
+                    Ok(())
+                })],
+                memory_settings: MemorySettings {
+                    max_allowed_memory_usage: MAX,
+                    preallocated_parsing_buffer_size: 0,
+                },
+                ..Settings::default()
+            },
+            |_: &[u8]| {},
+        );
+
+        rewriter.write(b"<div>").unwrap();
+        rewriter.write("x".repeat(MAX / 2).as_bytes()).unwrap();
+
+        let err = rewriter.write("x".repeat(MAX).as_bytes()).unwrap_err();
+
+        match err {
+            RewritingError::MemoryLimitExceeded(e) => assert_eq!(e, MemoryLimitExceededError),
+            _ => panic!("{}", err),
+        }
+    }
+
     #[test]
     fn user_data() {
         rewrite_element(b"<div><span>Hi</span></div>", UTF_8, "span", |el| {
@@ -1,8 +1,13 @@
 use std::any::Any;
 
 pub use self::document_end::*;
+pub use self::document_start::*;
 pub use self::element::*;
-pub use self::mutations::{ContentType, Mutations};
+pub(crate) use self::mutations::content_to_bytes;
+pub use self::mutations::{ContentType, Mutations, StreamingContent};
+pub use self::source_location::SourceLocation;
+pub(crate) use self::source_location::SourceLocationTracker;
+pub use self::text_node::TextNode;
 pub use self::tokens::*;
 
 /// Data that can be attached to a rewritable unit by a user and shared between content handler
@@ -82,7 +87,10 @@ macro_rules! impl_user_data {
 mod mutations;
 
 mod document_end;
+mod document_start;
 mod element;
+mod source_location;
+mod text_node;
 mod tokens;
 
 #[cfg(test)]
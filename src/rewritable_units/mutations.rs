@@ -1,7 +1,13 @@
-use crate::base::Bytes;
+use crate::base::{Bytes, MaybeSend};
+use crate::rewriter::RewritingError;
 use encoding_rs::Encoding;
+use std::io::{self, Read};
+use std::str;
+
+const STREAMING_CONTENT_READ_BUFFER_SIZE: usize = 8192;
 
 /// The type of inserted content.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum ContentType {
     /// HTML content type. The rewriter will insert the content as is.
     Html,
@@ -13,7 +19,7 @@ pub enum ContentType {
 }
 
 #[inline]
-pub(super) fn content_to_bytes(
+pub(crate) fn content_to_bytes(
     content: &str,
     content_type: ContentType,
     encoding: &'static Encoding,
@@ -32,10 +38,220 @@ pub(super) fn content_to_bytes(
     }
 }
 
+type StreamingContentWriter = Box<dyn_maybe_send!(FnOnce(&mut dyn FnMut(&str)) -> io::Result<()>)>;
+
+/// Content that is produced in chunks and written to the output as it is produced.
+///
+/// The content is never materialized as a whole: the rewriter pulls the chunks from the producer
+/// and writes them directly to the output sink at the moment when the rewritable unit is
+/// serialized. This allows insertion of large fragments without buffering them in memory.
+///
+/// The content is discarded without being pulled if the insertion point gets removed.
+///
+/// # Example
+///
+/// ```
+/// use lol_html::{rewrite_str, element, RewriteStrSettings};
+/// use lol_html::html_content::{ContentType, StreamingContent};
+///
+/// let html = rewrite_str(
+///     r#"<div id="foo"></div>"#,
+///     RewriteStrSettings {
+///         element_content_handlers: vec![
+///             element!("#foo", |el| {
+///                 el.streaming_append(
+///                     StreamingContent::from_chunks(vec!["<bar>", "<qux>"]),
+///                     ContentType::Text,
+///                 );
+///
+///                 el.streaming_after(
+///                     StreamingContent::from_reader(&b"<!-- partial -->"[..]),
+///                     ContentType::Html,
+///                 );
+///
+///                 Ok(())
+///             })
+///         ],
+///         ..RewriteStrSettings::default()
+///     }
+/// ).unwrap();
+///
+/// assert_eq!(html, r#"<div id="foo">&lt;bar&gt;&lt;qux&gt;</div><!-- partial -->"#);
+/// ```
+pub struct StreamingContent {
+    writer: StreamingContentWriter,
+}
+
+impl StreamingContent {
+    /// Creates streaming content from an iterator over the content chunks.
+    pub fn from_chunks<I>(chunks: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+        I::IntoIter: MaybeSend + 'static,
+    {
+        let chunks = chunks.into_iter();
+
+        StreamingContent {
+            writer: Box::new(move |output_handler: &mut dyn FnMut(&str)| {
+                for chunk in chunks {
+                    output_handler(chunk.as_ref());
+                }
+
+                Ok(())
+            }),
+        }
+    }
+
+    /// Creates streaming content from a reader of UTF-8 encoded content.
+    ///
+    /// I/O errors of the reader, as well as invalid UTF-8 sequences in its content, are propagated
+    /// as [`RewritingError::ContentHandlerError`].
+    ///
+    /// [`RewritingError::ContentHandlerError`]: ../errors/enum.RewritingError.html#variant.ContentHandlerError
+    pub fn from_reader<R: Read + MaybeSend + 'static>(reader: R) -> Self {
+        StreamingContent {
+            writer: Box::new(move |output_handler: &mut dyn FnMut(&str)| {
+                write_utf8_from_reader(reader, output_handler)
+            }),
+        }
+    }
+}
+
+fn write_utf8_from_reader(
+    mut reader: impl Read,
+    output_handler: &mut dyn FnMut(&str),
+) -> io::Result<()> {
+    let mut buffer = vec![0; STREAMING_CONTENT_READ_BUFFER_SIZE];
+    let mut pending_len = 0;
+
+    loop {
+        let read_len = match reader.read(&mut buffer[pending_len..]) {
+            Ok(len) => len,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+
+        let end = pending_len + read_len;
+
+        if read_len == 0 {
+            return if pending_len == 0 {
+                Ok(())
+            } else {
+                Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Streaming content ends with an incomplete UTF-8 sequence.",
+                ))
+            };
+        }
+
+        // NOTE: a character can be split between reads, so we hold back its
+        // leading bytes until we read the rest of them.
+        let valid_len = match str::from_utf8(&buffer[..end]) {
+            Ok(_) => end,
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+        };
+
+        if let Ok(chunk) = str::from_utf8(&buffer[..valid_len]) {
+            if !chunk.is_empty() {
+                output_handler(chunk);
+            }
+        }
+
+        buffer.copy_within(valid_len..end, 0);
+        pending_len = end - valid_len;
+    }
+}
+
+enum ContentChunk {
+    Bytes(Vec<u8>),
+    Streaming(StreamingContent, ContentType),
+}
+
+/// Content inserted by the mutations.
+///
+/// NOTE: streaming content can't be converted to bytes in advance, so the content
+/// is stored as a sequence of chunks that are serialized only when the rewritable
+/// unit is written to the output.
+#[derive(Default)]
+pub struct MutationsContent {
+    chunks: Vec<ContentChunk>,
+}
+
+impl MutationsContent {
+    #[inline]
+    pub fn clear(&mut self) {
+        self.chunks.clear();
+    }
+
+    #[inline]
+    pub fn push_bytes(&mut self, bytes: &[u8]) {
+        if bytes.is_empty() {
+            return;
+        }
+
+        match self.chunks.last_mut() {
+            Some(ContentChunk::Bytes(last)) => last.extend_from_slice(bytes),
+            _ => self.chunks.push(ContentChunk::Bytes(bytes.to_vec())),
+        }
+    }
+
+    #[inline]
+    pub fn prepend_bytes(&mut self, bytes: Vec<u8>) {
+        if !bytes.is_empty() {
+            self.chunks.insert(0, ContentChunk::Bytes(bytes));
+        }
+    }
+
+    #[inline]
+    pub fn push_streaming(&mut self, content: StreamingContent, content_type: ContentType) {
+        self.chunks
+            .push(ContentChunk::Streaming(content, content_type));
+    }
+
+    #[inline]
+    pub fn prepend_streaming(&mut self, content: StreamingContent, content_type: ContentType) {
+        self.chunks
+            .insert(0, ContentChunk::Streaming(content, content_type));
+    }
+
+    #[inline]
+    pub fn append(&mut self, mut content: MutationsContent) {
+        self.chunks.append(&mut content.chunks);
+    }
+
+    #[inline]
+    pub fn prepend(&mut self, content: MutationsContent) {
+        self.chunks.splice(0..0, content.chunks);
+    }
+
+    /// Writes the content to the output pulling all the streaming content chunks.
+    pub fn serialize(
+        &mut self,
+        encoding: &'static Encoding,
+        output_handler: &mut dyn FnMut(&[u8]),
+    ) -> Result<(), RewritingError> {
+        for chunk in self.chunks.drain(..) {
+            match chunk {
+                ContentChunk::Bytes(bytes) => output_handler(&bytes),
+                ContentChunk::Streaming(content, content_type) => {
+                    (content.writer)(&mut |c| {
+                        content_to_bytes(c, content_type, encoding, output_handler)
+                    })
+                    .map_err(|e| RewritingError::ContentHandlerError(Box::new(e)))?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
 pub struct Mutations {
-    pub content_before: Vec<u8>,
-    pub replacement: Vec<u8>,
-    pub content_after: Vec<u8>,
+    pub content_before: MutationsContent,
+    pub replacement: MutationsContent,
+    pub content_after: MutationsContent,
     pub removed: bool,
     encoding: &'static Encoding,
 }
@@ -44,44 +260,69 @@ impl Mutations {
     #[inline]
     pub fn new(encoding: &'static Encoding) -> Self {
         Mutations {
-            content_before: Vec::default(),
-            replacement: Vec::default(),
-            content_after: Vec::default(),
+            content_before: MutationsContent::default(),
+            replacement: MutationsContent::default(),
+            content_after: MutationsContent::default(),
             removed: false,
             encoding,
         }
     }
 
+    #[inline]
+    pub(crate) fn encoding(&self) -> &'static Encoding {
+        self.encoding
+    }
+
     #[inline]
     pub fn before(&mut self, content: &str, content_type: ContentType) {
         content_to_bytes(content, content_type, self.encoding, &mut |c| {
-            self.content_before.extend_from_slice(c);
+            self.content_before.push_bytes(c);
         });
     }
 
     #[inline]
     pub fn after(&mut self, content: &str, content_type: ContentType) {
-        let mut pos = 0;
+        let mut bytes = Vec::default();
 
         content_to_bytes(content, content_type, self.encoding, &mut |c| {
-            self.content_after.splice(pos..pos, c.iter().cloned());
-
-            pos += c.len();
+            bytes.extend_from_slice(c);
         });
+
+        self.content_after.prepend_bytes(bytes);
     }
 
     #[inline]
     pub fn replace(&mut self, content: &str, content_type: ContentType) {
-        let mut replacement = Vec::default();
+        let mut replacement = MutationsContent::default();
 
         content_to_bytes(content, content_type, self.encoding, &mut |c| {
-            replacement.extend_from_slice(c);
+            replacement.push_bytes(c);
         });
 
         self.replacement = replacement;
         self.remove();
     }
 
+    #[inline]
+    pub fn streaming_before(&mut self, content: StreamingContent, content_type: ContentType) {
+        self.content_before.push_streaming(content, content_type);
+    }
+
+    #[inline]
+    pub fn streaming_after(&mut self, content: StreamingContent, content_type: ContentType) {
+        self.content_after.prepend_streaming(content, content_type);
+    }
+
+    #[inline]
+    pub fn streaming_replace(&mut self, content: StreamingContent, content_type: ContentType) {
+        let mut replacement = MutationsContent::default();
+
+        replacement.push_streaming(content, content_type);
+
+        self.replacement = replacement;
+        self.remove();
+    }
+
     #[inline]
     pub fn remove(&mut self) {
         self.removed = true;
@@ -91,4 +332,16 @@ impl Mutations {
     pub fn removed(&self) -> bool {
         self.removed
     }
+
+    /// Applies `mutations` as if they were made after the existing ones.
+    #[inline]
+    pub fn merge(&mut self, mutations: Mutations) {
+        self.content_before.append(mutations.content_before);
+        self.content_after.prepend(mutations.content_after);
+
+        if mutations.removed {
+            self.replacement = mutations.replacement;
+            self.removed = true;
+        }
+    }
 }
@@ -0,0 +1,323 @@
+/// A location of a rewritable unit in the input.
+///
+/// The byte offset is absolute: it's counted from the beginning of the whole input stream rather
+/// than from the beginning of the chunk the unit was found in. Lines and columns are 1-based.
+/// Line breaks are `\n`, `\r\n` and a sole `\r`. Columns are counted in bytes.
+///
+/// # Note
+/// If the input is [transcoded], the location refers to the transcoded `UTF-8` input.
+///
+/// [transcoded]: ../struct.Settings.html#structfield.transcoding
+///
+/// # Example
+/// ```
+/// use lol_html::{element, rewrite_str, RewriteStrSettings};
+///
+/// let mut locations = vec![];
+///
+/// rewrite_str(
+///     "<div>\n  <span></span>\n</div>",
+///     RewriteStrSettings {
+///         element_content_handlers: vec![element!("span", |el| {
+///             let location = el.source_location();
+///
+///             locations.push((location.offset(), location.line(), location.column()));
+///
+///             Ok(())
+///         })],
+///         ..RewriteStrSettings::default()
+///     },
+/// )
+/// .unwrap();
+///
+/// assert_eq!(locations, vec![(8, 2, 3)]);
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SourceLocation {
+    offset: usize,
+    line: usize,
+    column: usize,
+}
+
+impl SourceLocation {
+    /// Returns the byte offset of the unit in the input.
+    #[inline]
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Returns the 1-based line number of the unit in the input.
+    #[inline]
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Returns the 1-based column number of the unit in its line.
+    #[inline]
+    pub fn column(&self) -> usize {
+        self.column
+    }
+}
+
+impl Default for SourceLocation {
+    #[inline]
+    fn default() -> Self {
+        SourceLocation {
+            offset: 0,
+            line: 1,
+            column: 1,
+        }
+    }
+}
+
+/// Tracks locations in the input that is fed to the parser chunk by chunk.
+///
+/// Locations must be requested in non-decreasing order, so each byte
+/// of the input is scanned for line breaks only once.
+#[derive(Default)]
+pub(crate) struct SourceLocationTracker {
+    chunk_offset: usize,
+    location: SourceLocation,
+    after_cr: bool,
+}
+
+impl SourceLocationTracker {
+    /// Returns the location of the byte at `pos` in the current `chunk`.
+    ///
+    /// # Panics
+    /// Panics if the location precedes the last requested one or if `pos`
+    /// is out of the `chunk` bounds.
+    pub fn location_at(&mut self, chunk: &[u8], pos: usize) -> SourceLocation {
+        let start = self.location.offset - self.chunk_offset;
+
+        assert!(
+            start <= pos && pos <= chunk.len(),
+            "Source locations should be requested in order within the current chunk"
+        );
+
+        for &b in &chunk[start..pos] {
+            match b {
+                b'\n' if self.after_cr => self.location.column = 1,
+                b'\n' | b'\r' => {
+                    self.location.line += 1;
+                    self.location.column = 1;
+                }
+                _ => self.location.column += 1,
+            }
+
+            self.after_cr = b == b'\r';
+        }
+
+        self.location.offset = self.chunk_offset + pos;
+
+        self.location
+    }
+
+    /// Moves to the next chunk which starts after the `consumed_byte_count`
+    /// bytes of the current `chunk`.
+    #[inline]
+    pub fn finish_chunk(&mut self, chunk: &[u8], consumed_byte_count: usize) {
+        self.location_at(chunk, consumed_byte_count);
+        self.chunk_offset += consumed_byte_count;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::html_content::EndTag;
+    use crate::{AsciiCompatibleEncoding, HtmlRewriter, Settings};
+    use encoding_rs::{Encoding, SHIFT_JIS, UTF_8};
+    use std::sync::{Arc, Mutex};
+
+    fn location(offset: usize, line: usize, column: usize) -> SourceLocation {
+        SourceLocation {
+            offset,
+            line,
+            column,
+        }
+    }
+
+    fn assert_text_chunk_locations(html: &[u8], encoding: &'static Encoding) {
+        let text_end = html.len() - b"</div>".len();
+
+        for chunk_size in &[1, 2, 3, 7, html.len()] {
+            let mut text_chunks = vec![];
+
+            {
+                let mut rewriter = HtmlRewriter::new(
+                    Settings {
+                        element_content_handlers: vec![text!("div", |t| {
+                            text_chunks.push((t.as_str().to_string(), t.source_location()));
+
+                            Ok(())
+                        })],
+                        encoding: AsciiCompatibleEncoding::new(encoding).unwrap(),
+                        ..Settings::default()
+                    },
+                    |_: &[u8]| {},
+                );
+
+                for chunk in html.chunks(*chunk_size) {
+                    rewriter.write(chunk).unwrap();
+                }
+
+                rewriter.end().unwrap();
+            }
+
+            let mut expected_offset = b"<div>".len();
+
+            for (text, location) in &text_chunks {
+                let raw = encoding.encode(text).0;
+                let preceding = &html[..expected_offset];
+                let line_start = preceding
+                    .iter()
+                    .rposition(|&b| b == b'\n')
+                    .map_or(0, |p| p + 1);
+
+                assert_eq!(
+                    *location,
+                    SourceLocation {
+                        offset: expected_offset,
+                        line: preceding.iter().filter(|&&b| b == b'\n').count() + 1,
+                        column: expected_offset - line_start + 1,
+                    },
+                    "Chunk size: {}",
+                    chunk_size
+                );
+
+                assert_eq!(&html[expected_offset..expected_offset + raw.len()], &*raw);
+
+                expected_offset += raw.len();
+            }
+
+            assert_eq!(expected_offset, text_end);
+            assert_eq!(text_chunks.last().unwrap().0, "");
+        }
+    }
+
+    #[test]
+    fn line_breaks() {
+        let input = b"a\nb\r\nc\rd";
+        let mut tracker = SourceLocationTracker::default();
+
+        assert_eq!(tracker.location_at(input, 0), location(0, 1, 1));
+        assert_eq!(tracker.location_at(input, 2), location(2, 2, 1));
+        assert_eq!(tracker.location_at(input, 3), location(3, 2, 2));
+        assert_eq!(tracker.location_at(input, 5), location(5, 3, 1));
+        assert_eq!(tracker.location_at(input, 7), location(7, 4, 1));
+        assert_eq!(tracker.location_at(input, 8), location(8, 4, 2));
+    }
+
+    #[test]
+    fn split_between_chunks() {
+        let mut tracker = SourceLocationTracker::default();
+
+        tracker.finish_chunk(b"ab\r", 3);
+
+        // NOTE: the next chunk starts with the unconsumed bytes of the previous one.
+        assert_eq!(tracker.location_at(b"\ncd<e", 3), location(6, 2, 3));
+
+        tracker.finish_chunk(b"\ncd<e", 3);
+
+        assert_eq!(tracker.location_at(b"<ef", 1), location(7, 2, 4));
+    }
+
+    #[test]
+    fn rewritable_units_locations() {
+        let html = "<!doctype html>\r\n<div>\n  <!-- c -->foo</div>";
+
+        for chunk_size in &[1, 3, html.len()] {
+            let units = Mutex::new(vec![]);
+            let mut text_chunks = vec![];
+            let end_tags = Arc::new(Mutex::new(vec![]));
+
+            {
+                let units = &units;
+                let end_tags = Arc::clone(&end_tags);
+
+                let mut rewriter = HtmlRewriter::new(
+                    Settings {
+                        element_content_handlers: vec![
+                            element!("div", move |el| {
+                                units.lock().unwrap().push(("div", el.source_location()));
+
+                                let end_tags = Arc::clone(&end_tags);
+
+                                el.on_end_tag(move |end: &mut EndTag| {
+                                    end_tags.lock().unwrap().push(end.source_location());
+
+                                    Ok(())
+                                })?;
+
+                                Ok(())
+                            }),
+                            text!("div", |t| {
+                                text_chunks.push((t.as_str().to_string(), t.source_location()));
+
+                                Ok(())
+                            }),
+                        ],
+                        document_content_handlers: vec![
+                            doctype!(|d| {
+                                units.lock().unwrap().push(("doctype", d.source_location()));
+
+                                Ok(())
+                            }),
+                            doc_comments!(|c| {
+                                units.lock().unwrap().push(("comment", c.source_location()));
+
+                                Ok(())
+                            }),
+                        ],
+                        ..Settings::default()
+                    },
+                    |_: &[u8]| {},
+                );
+
+                for chunk in html.as_bytes().chunks(*chunk_size) {
+                    rewriter.write(chunk).unwrap();
+                }
+
+                rewriter.end().unwrap();
+            }
+
+            assert_eq!(
+                *units.lock().unwrap(),
+                vec![
+                    ("doctype", location(0, 1, 1)),
+                    ("div", location(17, 2, 1)),
+                    ("comment", location(25, 3, 3)),
+                ]
+            );
+
+            assert_eq!(*end_tags.lock().unwrap(), vec![location(38, 3, 16)]);
+
+            for (text, location) in &text_chunks {
+                let offset = location.offset();
+
+                assert_eq!(&html[offset..offset + text.len()], text);
+            }
+
+            let (last_text, last_location) = text_chunks.last().unwrap();
+
+            assert_eq!(last_text, "");
+            assert_eq!(*last_location, location(38, 3, 16));
+        }
+    }
+
+    #[test]
+    fn long_text_locations() {
+        let html = format!("<div>{}</div>", "lorem ipsum\n".repeat(300));
+
+        assert_text_chunk_locations(html.as_bytes(), UTF_8);
+    }
+
+    #[test]
+    fn multibyte_text_locations() {
+        let text = "日本語のテキスト\n".repeat(100);
+        let html = SHIFT_JIS.encode(&format!("<div>{}</div>", text)).0;
+
+        assert_text_chunk_locations(&html, SHIFT_JIS);
+    }
+}
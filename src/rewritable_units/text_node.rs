@@ -0,0 +1,365 @@
+use super::{ContentType, Mutations, SourceLocation};
+use crate::html::{decode_text, TextType};
+use encoding_rs::Encoding;
+use std::borrow::Cow;
+use std::fmt::{self, Debug};
+
+/// A whole HTML text node.
+///
+/// Unlike [`TextChunk`]s, the text node is passed to a handler as one unit once the parser reaches
+/// its end. The content of the text node is buffered until then, so no output is produced for it
+/// before the handler is invoked. The buffered content is charged to the memory limit specified
+/// in [`MemorySettings`].
+///
+/// # Example
+/// ```
+/// use lol_html::{HtmlRewriter, Settings, text_node};
+/// use lol_html::html_content::ContentType;
+///
+/// let mut output = vec![];
+///
+/// {
+///     let mut rewriter = HtmlRewriter::new(
+///         Settings {
+///             element_content_handlers: vec![text_node!("div", |t| {
+///                 if t.as_str() == "Hello world" {
+///                     t.replace("Hello rewriter", ContentType::Text);
+///                 }
+///
+///                 Ok(())
+///             })],
+///             ..Settings::default()
+///         },
+///         |c: &[u8]| output.extend_from_slice(c),
+///     );
+///
+///     rewriter.write(b"<div>He").unwrap();
+///     rewriter.write(b"llo w").unwrap();
+///     rewriter.write(b"orld</div>").unwrap();
+///     rewriter.end().unwrap();
+/// }
+///
+/// assert_eq!(String::from_utf8(output).unwrap(), "<div>Hello rewriter</div>");
+/// ```
+///
+/// [`TextChunk`]: struct.TextChunk.html
+/// [`MemorySettings`]: ../struct.MemorySettings.html
+pub struct TextNode {
+    text: String,
+    text_type: TextType,
+    source_location: SourceLocation,
+    mutations: Mutations,
+}
+
+impl TextNode {
+    pub(crate) fn new(
+        text: String,
+        text_type: TextType,
+        source_location: SourceLocation,
+        encoding: &'static Encoding,
+    ) -> Self {
+        TextNode {
+            text,
+            text_type,
+            source_location,
+            mutations: Mutations::new(encoding),
+        }
+    }
+
+    /// Returns the textual content of the text node.
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// Returns the textual content of the text node with decoded [character references].
+    ///
+    /// Refer to [`TextChunk::decoded_text`] documentation for more information.
+    ///
+    /// [character references]: https://html.spec.whatwg.org/multipage/syntax.html#character-references
+    /// [`TextChunk::decoded_text`]: struct.TextChunk.html#method.decoded_text
+    #[inline]
+    pub fn decoded_text(&self) -> Cow<str> {
+        if self.text_type.allows_html_entities() {
+            decode_text(&self.text)
+        } else {
+            Cow::Borrowed(&self.text)
+        }
+    }
+
+    /// Returns the type of the text in the text node.
+    ///
+    /// Refer to [`TextType`] documentation for more information.
+    ///
+    /// [`TextType`]: enum.TextType.html
+    #[inline]
+    pub fn text_type(&self) -> TextType {
+        self.text_type
+    }
+
+    /// Returns the location of the text node in the input.
+    ///
+    /// Refer to [`SourceLocation`] documentation for more information.
+    ///
+    /// [`SourceLocation`]: struct.SourceLocation.html
+    #[inline]
+    pub fn source_location(&self) -> SourceLocation {
+        self.source_location
+    }
+
+    /// Inserts `content` before the text node.
+    ///
+    /// Consequent calls to the method append `content` to the previously inserted content.
+    #[inline]
+    pub fn before(&mut self, content: &str, content_type: ContentType) {
+        self.mutations.before(content, content_type);
+    }
+
+    /// Inserts `content` after the text node.
+    ///
+    /// Consequent calls to the method prepend `content` to the previously inserted content.
+    #[inline]
+    pub fn after(&mut self, content: &str, content_type: ContentType) {
+        self.mutations.after(content, content_type);
+    }
+
+    /// Replaces the text node with the `content`.
+    ///
+    /// Consequent calls to the method overwrite previous replacement content.
+    #[inline]
+    pub fn replace(&mut self, content: &str, content_type: ContentType) {
+        self.mutations.replace(content, content_type);
+    }
+
+    /// Removes the text node.
+    #[inline]
+    pub fn remove(&mut self) {
+        self.mutations.remove();
+    }
+
+    /// Returns `true` if the text node has been replaced or removed.
+    #[inline]
+    pub fn removed(&self) -> bool {
+        self.mutations.removed()
+    }
+
+    #[inline]
+    pub(crate) fn into_parts(self) -> (String, Mutations) {
+        (self.text, self.mutations)
+    }
+}
+
+impl Debug for TextNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TextNode")
+            .field("text", &self.as_str())
+            .field("text_type", &self.text_type())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::errors::*;
+    use crate::html_content::*;
+    use crate::rewritable_units::test_utils::*;
+    use crate::*;
+    use encoding_rs::{Encoding, UTF_8};
+
+    fn rewrite_text_node(
+        html: &[u8],
+        encoding: &'static Encoding,
+        mut handler: impl FnMut(&mut TextNode) + MaybeSend,
+    ) -> String {
+        let mut handler_called = false;
+
+        let output = rewrite_html(
+            html,
+            encoding,
+            vec![text_node!("div", |t| {
+                handler_called = true;
+                handler(t);
+                Ok(())
+            })],
+            vec![],
+        );
+
+        assert!(handler_called);
+
+        output
+    }
+
+    fn rewrite_in_chunks(chunks: &[&str], settings: Settings) -> Result<String, RewritingError> {
+        let mut output = vec![];
+
+        {
+            let mut rewriter = HtmlRewriter::new(settings, |c: &[u8]| output.extend_from_slice(c));
+
+            for chunk in chunks {
+                rewriter.write(chunk.as_bytes())?;
+            }
+
+            rewriter.end()?;
+        }
+
+        Ok(String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn whole_text_node_across_chunks() {
+        let mut texts = vec![];
+
+        let output = rewrite_in_chunks(
+            &["<div>Hel", "lo", " &amp; wor", "ld</div><div>", "!</div>"],
+            Settings {
+                element_content_handlers: vec![text_node!("div", |t| {
+                    texts.push(t.as_str().to_string());
+                    Ok(())
+                })],
+                ..Settings::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(output, "<div>Hello &amp; world</div><div>!</div>");
+        assert_eq!(texts, vec!["Hello &amp; world", "!"]);
+    }
+
+    #[test]
+    fn decoded_text() {
+        rewrite_text_node(b"<div>a&lt;b&amp;c&notin;</div>", UTF_8, |t| {
+            assert_eq!(t.as_str(), "a&lt;b&amp;c&notin;");
+            assert_eq!(t.decoded_text(), "a<b&c\u{2209}");
+        });
+    }
+
+    #[test]
+    fn source_location() {
+        rewrite_text_node(b"<div>foo</div>", UTF_8, |t| {
+            assert_eq!(t.source_location().offset(), 5);
+        });
+    }
+
+    #[test]
+    fn in_place_text_modifications() {
+        for (html, enc) in encoded("<div>Привет</div>") {
+            let output = rewrite_text_node(&html, enc, |t| {
+                t.before("<span>", ContentType::Html);
+                t.before("<", ContentType::Text);
+                t.after("</span>", ContentType::Html);
+                t.after(">", ContentType::Text);
+
+                assert!(!t.removed());
+            });
+
+            assert_eq!(output, "<div><span>&lt;Привет&gt;</span></div>");
+        }
+    }
+
+    #[test]
+    fn replace() {
+        for (html, enc) in encoded("<div>Привет</div>") {
+            let output = rewrite_text_node(&html, enc, |t| {
+                t.replace("<b>", ContentType::Html);
+                t.replace("<i>Мир</i>", ContentType::Html);
+
+                assert!(t.removed());
+            });
+
+            assert_eq!(output, "<div><i>Мир</i></div>");
+        }
+    }
+
+    #[test]
+    fn remove() {
+        let output = rewrite_text_node(b"<div>foo</div>", UTF_8, |t| {
+            t.before("[", ContentType::Text);
+            t.after("]", ContentType::Text);
+            t.remove();
+
+            assert!(t.removed());
+        });
+
+        assert_eq!(output, "<div>[]</div>");
+    }
+
+    #[test]
+    fn with_text_handler() {
+        let mut chunks = vec![];
+        let mut nodes = vec![];
+
+        let output = rewrite_in_chunks(
+            &["<div>fo", "o</div>"],
+            Settings {
+                element_content_handlers: vec![
+                    text!("div", |c| {
+                        chunks.push(c.as_str().to_string());
+                        Ok(())
+                    }),
+                    text_node!("div", |t| {
+                        nodes.push(t.as_str().to_string());
+                        t.replace("bar", ContentType::Text);
+                        Ok(())
+                    }),
+                ],
+                ..Settings::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(output, "<div>bar</div>");
+        assert_eq!(chunks, vec!["fo", "o", ""]);
+        assert_eq!(nodes, vec!["foo"]);
+    }
+
+    #[test]
+    fn multiple_handlers() {
+        let output = rewrite_in_chunks(
+            &["<div><span>fo", "o</span></div>"],
+            Settings {
+                element_content_handlers: vec![
+                    text_node!("div", |t| {
+                        t.before("[", ContentType::Text);
+                        t.after("]", ContentType::Text);
+                        Ok(())
+                    }),
+                    text_node!("span", |t| {
+                        assert_eq!(t.as_str(), "foo");
+                        t.before("(", ContentType::Text);
+                        t.after(")", ContentType::Text);
+                        Ok(())
+                    }),
+                ],
+                ..Settings::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(output, "<div><span>[(foo)]</span></div>");
+    }
+
+    #[test]
+    fn memory_limit_exceeded() {
+        const MAX: usize = 100;
+
+        let text = "x".repeat(MAX);
+
+        let err = rewrite_in_chunks(
+            &["<div>", &text, &text, "</div>"],
+            Settings {
+                element_content_handlers: vec![text_node!("div", |_| Ok(()))],
+                memory_settings: MemorySettings {
+                    max_allowed_memory_usage: MAX,
+                    preallocated_parsing_buffer_size: 0,
+                },
+                ..Settings::default()
+            },
+        )
+        .unwrap_err();
+
+        match err {
+            RewritingError::MemoryLimitExceeded(e) => assert_eq!(e, MemoryLimitExceededError),
+            _ => panic!("{}", err),
+        }
+    }
+}
@@ -1,8 +1,10 @@
 use crate::base::Bytes;
+use crate::html::decode_attribute_value;
 use crate::parser::SharedAttributeBuffer;
 use crate::rewritable_units::Serialize;
 use encoding_rs::Encoding;
 use lazycell::LazyCell;
+use std::borrow::Cow;
 use std::fmt::{self, Debug};
 use std::ops::Deref;
 use thiserror::Error;
@@ -82,12 +84,25 @@ impl<'i> Attribute<'i> {
     ) -> Result<Self, AttributeNameError> {
         Ok(Attribute {
             name: Attribute::name_from_str(name, encoding)?,
-            value: Bytes::from_str(value, encoding).into_owned(),
+            value: Attribute::value_from_str(value, encoding),
             raw: None,
             encoding,
         })
     }
 
+    // NOTE: the value is escaped before it's encoded, so numeric character references
+    // produced by the encoder for unmappable characters are not escaped.
+    #[inline]
+    fn value_from_str(value: &str, encoding: &'static Encoding) -> Bytes<'static> {
+        let value = if value.contains(|ch| ch == '&' || ch == '"') {
+            Cow::Owned(value.replace('&', "&amp;").replace('"', "&quot;"))
+        } else {
+            Cow::Borrowed(value)
+        };
+
+        Bytes::from_str(&value, encoding).into_owned()
+    }
+
     /// Returns the name of the attribute.
     #[inline]
     pub fn name(&self) -> String {
@@ -95,14 +110,41 @@ impl<'i> Attribute<'i> {
     }
 
     /// Returns the value of the attribute.
+    ///
+    /// Character references in the value are not decoded, e.g. the value of `href="a&amp;b"`
+    /// is `a&amp;b`. Use [`decoded_value`] to get the value with decoded character references.
+    /// The value of an attribute that was set with [`Element::set_attribute`] is returned as it
+    /// was set.
+    ///
+    /// [`decoded_value`]: #method.decoded_value
+    /// [`Element::set_attribute`]: struct.Element.html#method.set_attribute
     #[inline]
     pub fn value(&self) -> String {
-        self.value.as_string(self.encoding)
+        let value = self.value.as_string(self.encoding);
+
+        if self.raw.is_some() {
+            value
+        } else {
+            decode_attribute_value(&value).into_owned()
+        }
+    }
+
+    /// Returns the value of the attribute with decoded [character references].
+    ///
+    /// Character references are decoded the same way as browsers decode them in attribute values,
+    /// e.g. the decoded value of `href="a&amp;b"` is `a&b`. The value of an attribute that was set
+    /// with [`Element::set_attribute`] is returned as it was set.
+    ///
+    /// [character references]: https://html.spec.whatwg.org/multipage/syntax.html#character-references
+    /// [`Element::set_attribute`]: struct.Element.html#method.set_attribute
+    #[inline]
+    pub fn decoded_value(&self) -> String {
+        decode_attribute_value(&self.value.as_string(self.encoding)).into_owned()
     }
 
     #[inline]
     fn set_value(&mut self, value: &str) {
-        self.value = Bytes::from_str(value, self.encoding).into_owned();
+        self.value = Attribute::value_from_str(value, self.encoding);
         self.raw = None;
     }
 }
@@ -115,7 +157,7 @@ impl Serialize for Attribute<'_> {
             None => {
                 output_handler(&self.name);
                 output_handler(b"=\"");
-                self.value.replace_byte((b'"', b"&quot;"), output_handler);
+                output_handler(&self.value);
                 output_handler(b"\"");
             }
         }
@@ -222,7 +264,10 @@ impl<'i> Attributes<'i> {
 
     #[cfg(test)]
     pub fn raw_attributes(&self) -> (&'i Bytes<'i>, SharedAttributeBuffer) {
-        (self.input, std::rc::Rc::clone(&self.attribute_buffer))
+        (
+            self.input,
+            crate::base::Shared::clone(&self.attribute_buffer),
+        )
     }
 }
 
@@ -20,10 +20,20 @@ bitflags! {
     }
 }
 
+/// The position in the input where the raw content of a produced token starts.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TokenStart {
+    /// The token starts at the given offset in the raw content of the current lexeme.
+    InLexeme(usize),
+    /// The token starts with the raw text carried over from the previous lexemes, i.e.
+    /// where the previously produced text chunk ends.
+    AfterPreviousText,
+}
+
 #[derive(Debug)]
 pub enum TokenCapturerEvent<'i> {
     LexemeConsumed,
-    TokenProduced(Box<Token<'i>>),
+    TokenProduced(Box<Token<'i>>, TokenStart),
 }
 
 type CapturerEventHandler<'h> = &'h mut dyn FnMut(TokenCapturerEvent) -> Result<(), RewritingError>;
@@ -43,6 +53,18 @@ impl TokenCapturer {
         }
     }
 
+    #[inline]
+    pub fn set_encoding(&mut self, encoding: &'static Encoding) {
+        self.encoding = encoding;
+        self.text_decoder = TextDecoder::new(encoding);
+    }
+
+    #[inline]
+    pub fn reset(&mut self, capture_flags: TokenCaptureFlags) {
+        self.text_decoder.reset();
+        self.capture_flags = capture_flags;
+    }
+
     #[inline]
     pub fn has_captures(&self) -> bool {
         !self.capture_flags.is_empty()
@@ -61,6 +83,14 @@ impl TokenCapturer {
         self.text_decoder.flush_pending(event_handler)
     }
 
+    /// Returns the offset in the raw content of the last fed text lexeme where the text that
+    /// hasn't been produced yet starts. Returns `None` if there is no such text lexeme since
+    /// the last call or if the text starts in one of the previous lexemes.
+    #[inline]
+    pub fn take_pending_text_offset(&mut self) -> Option<usize> {
+        self.text_decoder.take_pending_text_offset()
+    }
+
     pub fn feed<'i, T>(
         &mut self,
         lexeme: &Lexeme<'i, T>,
@@ -73,7 +103,10 @@ impl TokenCapturer {
             ToTokenResult::Token(token) => {
                 self.flush_pending_text(&mut event_handler)?;
                 event_handler(TokenCapturerEvent::LexemeConsumed)?;
-                event_handler(TokenCapturerEvent::TokenProduced(token))
+                event_handler(TokenCapturerEvent::TokenProduced(
+                    token,
+                    TokenStart::InLexeme(0),
+                ))
             }
             ToTokenResult::Text(text_type) => {
                 if self.capture_flags.contains(TokenCaptureFlags::TEXT) {
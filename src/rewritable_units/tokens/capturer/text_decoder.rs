@@ -1,23 +1,76 @@
 use super::*;
-use crate::html::TextType;
+use crate::html::{incomplete_character_reference_len, TextType};
 use crate::rewriter::RewritingError;
 use encoding_rs::{CoderResult, Decoder, Encoding};
+use std::mem;
 
 // NOTE: this can't be refactored into method, because we hold a mutable reference for `self`
 // during the decoding loop in `feed_text`.
 macro_rules! emit {
-    ($self:tt, $text:expr, $last:ident, $event_handler:ident) => {{
-        let token = TextChunk::new_token($text, $self.last_text_type, $last, $self.encoding);
+    ($self:tt, $text:expr, $last:expr, $start:expr, $event_handler:ident) => {{
+        let text = $text;
 
-        $event_handler(TokenCapturerEvent::TokenProduced(Box::new(token)))
+        let (reference_prefix, incomplete_reference_len) =
+            if $self.last_text_type.allows_html_entities() {
+                take_incomplete_reference(&mut $self.pending_reference, text, $last)
+            } else {
+                (String::new(), 0)
+            };
+
+        let token = TextChunk::new_token(
+            text,
+            $self.last_text_type,
+            $last,
+            reference_prefix,
+            incomplete_reference_len,
+            $self.encoding,
+        );
+
+        $event_handler(TokenCapturerEvent::TokenProduced(Box::new(token), $start))
     }};
 }
 
+// NOTE: characters are at most 4 bytes long in all the supported encodings, so at most 3 bytes
+// of an incomplete character can be carried over to the next lexeme in the streaming decoder.
+const MAX_INCOMPLETE_CHAR_LEN: usize = 3;
+
+// NOTE: a character reference can be split between text chunks, so the trailing part of
+// the chunk's text that can be the beginning of a reference is carried over to the next chunk
+// for decoding. Returns the part carried over from the previous chunk and the length of the
+// part that is carried over to the next one.
+fn take_incomplete_reference(pending: &mut String, text: &str, last: bool) -> (String, usize) {
+    let prefix = mem::take(pending);
+
+    if last {
+        return (prefix, 0);
+    }
+
+    let incomplete_len = if prefix.is_empty() {
+        let len = incomplete_character_reference_len(text);
+
+        pending.push_str(&text[text.len() - len..]);
+
+        len
+    } else {
+        let combined = prefix.clone() + text;
+        let len = incomplete_character_reference_len(&combined);
+
+        pending.push_str(&combined[combined.len() - len..]);
+
+        len
+    };
+
+    (prefix, incomplete_len)
+}
+
 pub struct TextDecoder {
     encoding: &'static Encoding,
     pending_text_streaming_decoder: Option<Decoder>,
     text_buffer: String,
     last_text_type: TextType,
+    pending_reference: String,
+    pending_text_start: TokenStart,
+    pending_text_offset: Option<usize>,
 }
 
 impl TextDecoder {
@@ -28,21 +81,40 @@ impl TextDecoder {
             // TODO make adjustable
             text_buffer: String::from_utf8(vec![0u8; 1024]).unwrap(),
             last_text_type: TextType::Data,
+            pending_reference: String::new(),
+            pending_text_start: TokenStart::InLexeme(0),
+            pending_text_offset: None,
         }
     }
 
+    #[inline]
+    pub fn reset(&mut self) {
+        self.pending_text_streaming_decoder = None;
+        self.last_text_type = TextType::Data;
+        self.pending_reference.clear();
+        self.pending_text_start = TokenStart::InLexeme(0);
+        self.pending_text_offset = None;
+    }
+
     #[inline]
     pub fn flush_pending(
         &mut self,
         event_handler: CapturerEventHandler,
     ) -> Result<(), RewritingError> {
         if self.pending_text_streaming_decoder.is_some() {
+            self.pending_text_start = TokenStart::AfterPreviousText;
             self.decode_with_streaming_decoder(&[], true, event_handler)?;
             self.pending_text_streaming_decoder = None;
+            self.pending_text_start = TokenStart::InLexeme(0);
         }
         Ok(())
     }
 
+    #[inline]
+    pub fn take_pending_text_offset(&mut self) -> Option<usize> {
+        self.pending_text_offset.take()
+    }
+
     fn decode_with_streaming_decoder(
         &mut self,
         raw: &[u8],
@@ -56,22 +128,88 @@ impl TextDecoder {
             .pending_text_streaming_decoder
             .get_or_insert_with(|| encoding.new_decoder_without_bom_handling());
 
+        // NOTE: the leading bytes of a character that is split between lexemes are carried
+        // over in the decoder. To find out where the carried over bytes start, the bytes that
+        // can belong to an incomplete character are decoded one by one: the last of them
+        // that produces output ends a complete character. If none of them does, all of them
+        // are carried over.
+        let bulk_len = if last {
+            raw.len()
+        } else {
+            raw.len().saturating_sub(MAX_INCOMPLETE_CHAR_LEN)
+        };
+
+        let mut decoded_end = if !last && raw.len() >= MAX_INCOMPLETE_CHAR_LEN {
+            TokenStart::InLexeme(bulk_len)
+        } else {
+            self.pending_text_start
+        };
+
         let mut consumed = 0;
+        let mut written = 0;
 
         loop {
-            let (status, read, written, ..) = decoder.decode_to_str(&raw[consumed..], buffer, last);
+            let src_end = if consumed < bulk_len {
+                bulk_len
+            } else {
+                raw.len().min(consumed + 1)
+            };
 
-            if written > 0 || last {
-                emit!(self, &buffer[..written], last, event_handler)?;
-            }
+            let (status, read, chunk_written, ..) =
+                decoder.decode_to_str(&raw[consumed..src_end], &mut buffer[written..], last);
 
-            if let CoderResult::InputEmpty = status {
-                break;
-            }
+            let decoded_byte_by_byte = src_end > bulk_len;
 
             consumed += read;
+            written += chunk_written;
+
+            if let CoderResult::OutputFull = status {
+                // NOTE: the decoder doesn't consume incomplete characters if the output is full.
+                emit!(
+                    self,
+                    &buffer[..written],
+                    false,
+                    self.pending_text_start,
+                    event_handler
+                )?;
+
+                written = 0;
+                self.pending_text_start = TokenStart::InLexeme(consumed);
+
+                if decoded_byte_by_byte {
+                    decoded_end = self.pending_text_start;
+                }
+            } else {
+                if decoded_byte_by_byte && chunk_written > 0 {
+                    decoded_end = TokenStart::InLexeme(consumed);
+                }
+
+                if consumed == raw.len() {
+                    break;
+                }
+            }
         }
 
+        if written > 0 || last {
+            emit!(
+                self,
+                &buffer[..written],
+                last,
+                self.pending_text_start,
+                event_handler
+            )?;
+        }
+
+        self.pending_text_offset = match decoded_end {
+            TokenStart::InLexeme(offset) => Some(offset),
+            TokenStart::AfterPreviousText => None,
+        };
+
+        self.pending_text_start = match decoded_end {
+            TokenStart::InLexeme(offset) if offset == raw.len() => TokenStart::InLexeme(0),
+            _ => TokenStart::AfterPreviousText,
+        };
+
         Ok(())
     }
 
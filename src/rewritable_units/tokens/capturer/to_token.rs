@@ -1,8 +1,8 @@
 use super::*;
+use crate::base::Shared;
 use crate::html::TextType;
 use crate::parser::{NonTagContentLexeme, NonTagContentTokenOutline, TagLexeme, TagTokenOutline};
 use encoding_rs::Encoding;
-use std::rc::Rc;
 
 pub enum ToTokenResult<'i> {
     Token(Box<Token<'i>>),
@@ -44,7 +44,7 @@ impl ToToken for TagLexeme<'_> {
 
                 StartTag::new_token(
                     self.part(name),
-                    Attributes::new(self.input(), Rc::clone(attributes), encoding),
+                    Attributes::new(self.input(), Shared::clone(attributes), encoding),
                     ns,
                     self_closing,
                     self.raw(),
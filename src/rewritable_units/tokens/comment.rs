@@ -1,5 +1,6 @@
 use super::{Mutations, Token};
 use crate::base::Bytes;
+use crate::rewritable_units::SourceLocation;
 use encoding_rs::Encoding;
 use std::any::Any;
 use std::fmt::{self, Debug};
@@ -26,6 +27,7 @@ pub struct Comment<'i> {
     text: Bytes<'i>,
     raw: Option<Bytes<'i>>,
     encoding: &'static Encoding,
+    source_location: SourceLocation,
     mutations: Mutations,
     user_data: Box<dyn Any>,
 }
@@ -40,11 +42,27 @@ impl<'i> Comment<'i> {
             text,
             raw: Some(raw),
             encoding,
+            source_location: SourceLocation::default(),
             mutations: Mutations::new(encoding),
             user_data: Box::new(()),
         })
     }
 
+    /// Returns the location of the comment in the input.
+    ///
+    /// Refer to [`SourceLocation`] documentation for more information.
+    ///
+    /// [`SourceLocation`]: struct.SourceLocation.html
+    #[inline]
+    pub fn source_location(&self) -> SourceLocation {
+        self.source_location
+    }
+
+    #[inline]
+    pub(crate) fn set_source_location(&mut self, source_location: SourceLocation) {
+        self.source_location = source_location;
+    }
+
     /// Returns the text of the comment.
     #[inline]
     pub fn text(&self) -> String {
@@ -216,7 +234,7 @@ mod tests {
     fn rewrite_comment(
         html: &[u8],
         encoding: &'static Encoding,
-        mut handler: impl FnMut(&mut Comment),
+        mut handler: impl FnMut(&mut Comment) + MaybeSend,
     ) -> String {
         let mut handler_called = false;
 
@@ -1,13 +1,38 @@
+use super::{Mutations, Token};
 use crate::base::Bytes;
-use crate::rewritable_units::{Serialize, Token};
+use crate::rewritable_units::{ContentType, SourceLocation};
 use encoding_rs::Encoding;
 use std::any::Any;
 use std::fmt::{self, Debug};
+use thiserror::Error;
+
+/// An error that occurs when invalid value is provided for the doctype name or identifiers.
+#[derive(Error, Debug, PartialEq, Copy, Clone)]
+pub enum DoctypeError {
+    /// The provided name is empty.
+    #[error("Doctype name can't be empty.")]
+    EmptyName,
+
+    /// The provided value contains a character that is forbidden by the HTML grammar in
+    /// the doctype name or identifiers (e.g. `'>'`).
+    #[error("`{0}` character is forbidden in the doctype.")]
+    ForbiddenCharacter(char),
+
+    /// The provided identifier contains both single and double quotes, so it can't be quoted.
+    #[error("Doctype identifier can't contain both single and double quotes.")]
+    UnquotableIdentifier,
+
+    /// The provided value contains a character that can't be represented in the document's
+    /// [`encoding`].
+    ///
+    /// [`encoding`]: ../struct.Settings.html#structfield.encoding
+    #[error("The doctype contains a character that can't be represented in the document's character encoding.")]
+    UnencodableCharacter,
+}
 
 /// A [document type declaration] preamble.
 ///
-/// Note that unlike other HTML content, `Doctype` can't be modified and should be used only for
-/// the examination purposes.
+/// Exposes API for examination and modification of a parsed document type declaration.
 ///
 /// # Example
 /// ```
@@ -36,8 +61,10 @@ pub struct Doctype<'i> {
     public_id: Option<Bytes<'i>>,
     system_id: Option<Bytes<'i>>,
     force_quirks: bool,
-    raw: Bytes<'i>,
+    raw: Option<Bytes<'i>>,
     encoding: &'static Encoding,
+    source_location: SourceLocation,
+    mutations: Mutations,
     user_data: Box<dyn Any>,
 }
 
@@ -55,12 +82,29 @@ impl<'i> Doctype<'i> {
             public_id,
             system_id,
             force_quirks,
-            raw,
+            raw: Some(raw),
             encoding,
+            source_location: SourceLocation::default(),
+            mutations: Mutations::new(encoding),
             user_data: Box::new(()),
         })
     }
 
+    /// Returns the location of the document type declaration in the input.
+    ///
+    /// Refer to [`SourceLocation`] documentation for more information.
+    ///
+    /// [`SourceLocation`]: struct.SourceLocation.html
+    #[inline]
+    pub fn source_location(&self) -> SourceLocation {
+        self.source_location
+    }
+
+    #[inline]
+    pub(crate) fn set_source_location(&mut self, source_location: SourceLocation) {
+        self.source_location = source_location;
+    }
+
     /// The name of the doctype.
     #[inline]
     pub fn name(&self) -> Option<String> {
@@ -69,34 +113,253 @@ impl<'i> Doctype<'i> {
             .map(|n| n.as_lowercase_string(self.encoding))
     }
 
+    /// Sets the name of the doctype.
+    ///
+    /// The doctype is serialized without the name if `None` is provided.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lol_html::{rewrite_str, doctype, RewriteStrSettings};
+    ///
+    /// let html = rewrite_str(
+    ///     r#"<!DOCTYPE html PUBLIC "-//W3C//DTD HTML 4.01//EN">"#,
+    ///     RewriteStrSettings {
+    ///         document_content_handlers: vec![
+    ///             doctype!(|d| {
+    ///                 d.set_name(Some("html")).unwrap();
+    ///                 d.set_public_id(None).unwrap();
+    ///
+    ///                 Ok(())
+    ///             })
+    ///         ],
+    ///         ..RewriteStrSettings::default()
+    ///     }
+    /// ).unwrap();
+    ///
+    /// assert_eq!(html, r#"<!DOCTYPE html>"#);
+    /// ```
+    #[inline]
+    pub fn set_name(&mut self, name: Option<&str>) -> Result<(), DoctypeError> {
+        if let Some(name) = name {
+            if name.is_empty() {
+                return Err(DoctypeError::EmptyName);
+            }
+
+            if let Some(ch) = name
+                .chars()
+                .find(|&ch| matches!(ch, ' ' | '\n' | '\r' | '\t' | '\x0C' | '>'))
+            {
+                return Err(DoctypeError::ForbiddenCharacter(ch));
+            }
+        }
+
+        self.name = self.bytes_from_opt_str(name)?;
+        self.raw = None;
+
+        Ok(())
+    }
+
     /// The public identifier of the doctype.
     #[inline]
     pub fn public_id(&self) -> Option<String> {
         self.public_id.as_ref().map(|i| i.as_string(self.encoding))
     }
 
+    /// Sets the public identifier of the doctype.
+    ///
+    /// The doctype is serialized without the public identifier if `None` is provided.
+    #[inline]
+    pub fn set_public_id(&mut self, public_id: Option<&str>) -> Result<(), DoctypeError> {
+        self.public_id = self.identifier_bytes_from_opt_str(public_id)?;
+        self.raw = None;
+
+        Ok(())
+    }
+
     /// The system identifier of the doctype.
     #[inline]
     pub fn system_id(&self) -> Option<String> {
         self.system_id.as_ref().map(|i| i.as_string(self.encoding))
     }
 
+    /// Sets the system identifier of the doctype.
+    ///
+    /// The doctype is serialized without the system identifier if `None` is provided.
+    #[inline]
+    pub fn set_system_id(&mut self, system_id: Option<&str>) -> Result<(), DoctypeError> {
+        self.system_id = self.identifier_bytes_from_opt_str(system_id)?;
+        self.raw = None;
+
+        Ok(())
+    }
+
     #[inline]
     #[cfg(feature = "integration_test")]
     pub fn force_quirks(&self) -> bool {
         self.force_quirks
     }
-}
 
-impl_user_data!(Doctype<'_>);
+    fn bytes_from_opt_str(&self, value: Option<&str>) -> Result<Option<Bytes<'i>>, DoctypeError> {
+        value
+            .map(|value| {
+                // NOTE: if character can't be represented in the given
+                // encoding then encoding_rs replaces it with a numeric
+                // character reference. Character references are not
+                // supported in doctypes, so we need to bail.
+                Bytes::from_str_without_replacements(value, self.encoding)
+                    .map(Bytes::into_owned)
+                    .map_err(|_| DoctypeError::UnencodableCharacter)
+            })
+            .transpose()
+    }
+
+    fn identifier_bytes_from_opt_str(
+        &self,
+        value: Option<&str>,
+    ) -> Result<Option<Bytes<'i>>, DoctypeError> {
+        if let Some(value) = value {
+            if value.contains('>') {
+                return Err(DoctypeError::ForbiddenCharacter('>'));
+            }
+
+            if value.contains('"') && value.contains('\'') {
+                return Err(DoctypeError::UnquotableIdentifier);
+            }
+        }
+
+        self.bytes_from_opt_str(value)
+    }
+
+    /// Inserts `content` before the doctype.
+    ///
+    /// Consequent calls to the method append `content` to the previously inserted content.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lol_html::{rewrite_str, doctype, RewriteStrSettings};
+    /// use lol_html::html_content::ContentType;
+    ///
+    /// let html = rewrite_str(
+    ///     r#"<!DOCTYPE html><html></html>"#,
+    ///     RewriteStrSettings {
+    ///         document_content_handlers: vec![
+    ///             doctype!(|d| {
+    ///                 d.before("<!-- foo -->", ContentType::Html);
+    ///                 d.after("<!-- bar -->", ContentType::Html);
+    ///
+    ///                 Ok(())
+    ///             })
+    ///         ],
+    ///         ..RewriteStrSettings::default()
+    ///     }
+    /// ).unwrap();
+    ///
+    /// assert_eq!(html, r#"<!-- foo --><!DOCTYPE html><!-- bar --><html></html>"#);
+    /// ```
+    #[inline]
+    pub fn before(&mut self, content: &str, content_type: ContentType) {
+        self.mutations.before(content, content_type);
+    }
+
+    /// Inserts `content` after the doctype.
+    ///
+    /// Consequent calls to the method prepend `content` to the previously inserted content.
+    #[inline]
+    pub fn after(&mut self, content: &str, content_type: ContentType) {
+        self.mutations.after(content, content_type);
+    }
+
+    /// Replaces the doctype with the `content`.
+    ///
+    /// Consequent calls to the method overwrite previous replacement content.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lol_html::{rewrite_str, doctype, RewriteStrSettings};
+    /// use lol_html::html_content::ContentType;
+    ///
+    /// let html = rewrite_str(
+    ///     r#"<!DOCTYPE html PUBLIC "-//W3C//DTD HTML 4.01//EN"><html></html>"#,
+    ///     RewriteStrSettings {
+    ///         document_content_handlers: vec![
+    ///             doctype!(|d| {
+    ///                 d.replace("<!DOCTYPE html>", ContentType::Html);
+    ///
+    ///                 Ok(())
+    ///             })
+    ///         ],
+    ///         ..RewriteStrSettings::default()
+    ///     }
+    /// ).unwrap();
+    ///
+    /// assert_eq!(html, r#"<!DOCTYPE html><html></html>"#);
+    /// ```
+    #[inline]
+    pub fn replace(&mut self, content: &str, content_type: ContentType) {
+        self.mutations.replace(content, content_type);
+    }
+
+    /// Removes the doctype.
+    #[inline]
+    pub fn remove(&mut self) {
+        self.mutations.remove();
+    }
+
+    /// Returns `true` if the doctype has been replaced or removed.
+    #[inline]
+    pub fn removed(&self) -> bool {
+        self.mutations.removed()
+    }
+
+    #[inline]
+    fn raw(&self) -> Option<&Bytes> {
+        self.raw.as_ref()
+    }
 
-impl Serialize for Doctype<'_> {
     #[inline]
-    fn to_bytes(&self, output_handler: &mut dyn FnMut(&[u8])) {
-        output_handler(&self.raw);
+    fn serialize_from_parts(&self, output_handler: &mut dyn FnMut(&[u8])) {
+        let serialize_id = |id: &Bytes, output_handler: &mut dyn FnMut(&[u8])| {
+            let quote: &[u8] = if id.contains(&b'"') { b"'" } else { b"\"" };
+
+            output_handler(b" ");
+            output_handler(quote);
+            output_handler(id);
+            output_handler(quote);
+        };
+
+        output_handler(b"<!DOCTYPE");
+
+        if let Some(ref name) = self.name {
+            output_handler(b" ");
+            output_handler(name);
+        }
+
+        match (&self.public_id, &self.system_id) {
+            (Some(public_id), system_id) => {
+                output_handler(b" PUBLIC");
+                serialize_id(public_id, output_handler);
+
+                if let Some(system_id) = system_id {
+                    serialize_id(system_id, output_handler);
+                }
+            }
+            (None, Some(system_id)) => {
+                output_handler(b" SYSTEM");
+                serialize_id(system_id, output_handler);
+            }
+            (None, None) => (),
+        }
+
+        output_handler(b">");
     }
 }
 
+impl_serialize!(Doctype);
+impl_user_data!(Doctype<'_>);
+
 impl Debug for Doctype<'_> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("Doctype")
@@ -110,15 +373,16 @@ impl Debug for Doctype<'_> {
 
 #[cfg(test)]
 mod tests {
+    use crate::errors::*;
     use crate::html_content::*;
     use crate::rewritable_units::test_utils::*;
     use crate::*;
-    use encoding_rs::{Encoding, UTF_8};
+    use encoding_rs::{Encoding, EUC_JP, UTF_8};
 
     fn rewrite_doctype(
         html: &[u8],
         encoding: &'static Encoding,
-        mut handler: impl FnMut(&mut Doctype),
+        mut handler: impl FnMut(&mut Doctype) + MaybeSend,
     ) -> String {
         let mut handler_called = false;
 
@@ -152,11 +416,136 @@ mod tests {
     }
 
     #[test]
-    fn serialization() {
-        for (html, enc) in encoded(r#"<!DOCTYPE html SYSTEM "Ĥey">"#) {
-            let output = rewrite_doctype(&html, enc, |_| {});
+    fn invalid_name() {
+        rewrite_doctype(b"<!doctype html>", UTF_8, |d| {
+            assert_eq!(d.set_name(Some("")).unwrap_err(), DoctypeError::EmptyName);
+
+            assert_eq!(
+                d.set_name(Some("foo bar")).unwrap_err(),
+                DoctypeError::ForbiddenCharacter(' ')
+            );
+
+            assert_eq!(
+                d.set_name(Some("foo>")).unwrap_err(),
+                DoctypeError::ForbiddenCharacter('>')
+            );
+        });
+    }
+
+    #[test]
+    fn invalid_identifiers() {
+        rewrite_doctype(b"<!doctype html>", UTF_8, |d| {
+            assert_eq!(
+                d.set_public_id(Some("foo>")).unwrap_err(),
+                DoctypeError::ForbiddenCharacter('>')
+            );
+
+            assert_eq!(
+                d.set_system_id(Some(r#"'foo""#)).unwrap_err(),
+                DoctypeError::UnquotableIdentifier
+            );
+        });
+    }
+
+    #[test]
+    fn encoding_unmappable_chars() {
+        rewrite_doctype(b"<!doctype html>", EUC_JP, |d| {
+            assert_eq!(
+                d.set_name(Some("foo\u{00F8}bar")).unwrap_err(),
+                DoctypeError::UnencodableCharacter
+            );
+
+            assert_eq!(
+                d.set_system_id(Some("foo\u{00F8}bar")).unwrap_err(),
+                DoctypeError::UnencodableCharacter
+            );
+        });
+    }
+
+    mod serialization {
+        use super::*;
+
+        const HTML: &str = r#"<!DOCTYPE html SYSTEM "Ĥey">"#;
+
+        macro_rules! test {
+            ($handler:expr, $expected:expr) => {
+                for (html, enc) in encoded(HTML) {
+                    assert_eq!(rewrite_doctype(&html, enc, $handler), $expected);
+                }
+            };
+        }
+
+        #[test]
+        fn parsed() {
+            test!(|_| {}, r#"<!DOCTYPE html SYSTEM "Ĥey">"#);
+        }
+
+        #[test]
+        fn modified_name_and_identifiers() {
+            test!(
+                |d| {
+                    d.set_name(Some("HTML")).unwrap();
+                    d.set_public_id(Some("-//W3C//DTD HTML 4.01//EN")).unwrap();
+                    d.set_system_id(Some(r#"Ĥey "you""#)).unwrap();
+
+                    assert_eq!(d.name(), Some("html".into()));
+                },
+                r#"<!DOCTYPE HTML PUBLIC "-//W3C//DTD HTML 4.01//EN" 'Ĥey "you"'>"#
+            );
+
+            test!(
+                |d| {
+                    d.set_name(None).unwrap();
+                    d.set_system_id(None).unwrap();
+                },
+                "<!DOCTYPE>"
+            );
+        }
+
+        #[test]
+        fn with_prepends_and_appends() {
+            test!(
+                |d| {
+                    d.before("<span>", ContentType::Text);
+                    d.before("<!-- foo -->", ContentType::Html);
+                    d.after("</foo>", ContentType::Html);
+                    d.after("<foo & bar>", ContentType::Text);
+                },
+                concat!(
+                    r#"&lt;span&gt;<!-- foo --><!DOCTYPE html SYSTEM "Ĥey">"#,
+                    "&lt;foo &amp; bar&gt;</foo>",
+                )
+            );
+        }
+
+        #[test]
+        fn removed() {
+            test!(
+                |d| {
+                    assert!(!d.removed());
+
+                    d.remove();
+
+                    assert!(d.removed());
+
+                    d.before("<before>", ContentType::Html);
+                    d.after("<after>", ContentType::Html);
+                },
+                "<before><after>"
+            );
+        }
+
+        #[test]
+        fn replaced() {
+            test!(
+                |d| {
+                    d.replace("<!DOCTYPE foo>", ContentType::Html);
+                    d.replace("<!DOCTYPE html>", ContentType::Html);
 
-            assert_eq!(output, r#"<!DOCTYPE html SYSTEM "Ĥey">"#);
+                    assert!(d.removed());
+                },
+                "<!DOCTYPE html>"
+            );
         }
     }
 }
@@ -1,13 +1,25 @@
 use super::{Mutations, Token};
 use crate::base::Bytes;
+use crate::rewritable_units::mutations::content_to_bytes;
+use crate::rewritable_units::{ContentType, EndTagError, SourceLocation};
 use encoding_rs::Encoding;
 use std::fmt::{self, Debug};
 
+/// An HTML end tag rewritable unit.
+///
+/// Exposes API for examination and modification of a parsed HTML end tag. End tags can be
+/// captured with [`Element::on_end_tag`].
+///
+/// [`Element::on_end_tag`]: struct.Element.html#method.on_end_tag
 pub struct EndTag<'i> {
     name: Bytes<'i>,
     raw: Option<Bytes<'i>>,
     encoding: &'static Encoding,
-    pub mutations: Mutations,
+    source_location: SourceLocation,
+    captured_inner_contents: Vec<Option<Vec<u8>>>,
+    inner_content_captured: bool,
+    inner_content_replacement: Option<Vec<u8>>,
+    pub(crate) mutations: Mutations,
 }
 
 impl<'i> EndTag<'i> {
@@ -20,21 +32,185 @@ impl<'i> EndTag<'i> {
             name,
             raw: Some(raw),
             encoding,
+            source_location: SourceLocation::default(),
+            captured_inner_contents: Vec::default(),
+            inner_content_captured: false,
+            inner_content_replacement: None,
             mutations: Mutations::new(encoding),
         })
     }
 
+    /// Returns the location of the end tag in the input.
+    ///
+    /// Refer to [`SourceLocation`] documentation for more information.
+    ///
+    /// [`SourceLocation`]: struct.SourceLocation.html
+    #[inline]
+    pub fn source_location(&self) -> SourceLocation {
+        self.source_location
+    }
+
+    #[inline]
+    pub(crate) fn set_source_location(&mut self, source_location: SourceLocation) {
+        self.source_location = source_location;
+    }
+
+    /// Returns the name of the tag.
     #[inline]
     pub fn name(&self) -> String {
         self.name.as_lowercase_string(self.encoding)
     }
 
     #[inline]
-    pub fn set_name(&mut self, name: Bytes<'static>) {
+    pub(crate) fn set_name(&mut self, name: Bytes<'static>) {
         self.name = name;
         self.raw = None;
     }
 
+    #[inline]
+    pub(crate) fn encoding(&self) -> &'static Encoding {
+        self.encoding
+    }
+
+    // NOTE: the contents are captured in the order of the elements in the document, while
+    // the handlers are invoked from the innermost element. The contents that have exceeded
+    // the capture limits are passed through and, thus, are missing.
+    #[inline]
+    pub(crate) fn set_captured_inner_contents(&mut self, contents: Vec<Option<Vec<u8>>>) {
+        self.captured_inner_contents = contents;
+    }
+
+    #[inline]
+    pub(crate) fn take_captured_inner_content(&mut self) -> Option<Vec<u8>> {
+        self.captured_inner_contents.pop().flatten()
+    }
+
+    // NOTE: the inner content can be replaced only while the handlers of the element
+    // that captured it are invoked.
+    #[inline]
+    pub(crate) fn set_inner_content_captured(&mut self, captured: bool) {
+        self.inner_content_captured = captured;
+    }
+
+    #[inline]
+    pub(crate) fn take_inner_content_replacement(&mut self) -> Option<Vec<u8>> {
+        self.inner_content_replacement.take()
+    }
+
+    /// Replaces the inner content of the element, that was captured with
+    /// [`Element::capture_inner_html`], with `content`.
+    ///
+    /// The method can be called only in the handler passed to [`Element::capture_inner_html`],
+    /// otherwise [`EndTagError::NoCapturedInnerContent`] is returned. Consequent calls to
+    /// the method overwrite previous replacement content.
+    ///
+    /// [`Element::capture_inner_html`]: struct.Element.html#method.capture_inner_html
+    /// [`EndTagError::NoCapturedInnerContent`]: ../errors/enum.EndTagError.html#variant.NoCapturedInnerContent
+    #[inline]
+    pub fn set_inner_content(
+        &mut self,
+        content: &str,
+        content_type: ContentType,
+    ) -> Result<(), EndTagError> {
+        if !self.inner_content_captured {
+            return Err(EndTagError::NoCapturedInnerContent);
+        }
+
+        let mut replacement = Vec::default();
+
+        content_to_bytes(content, content_type, self.encoding, &mut |c| {
+            replacement.extend_from_slice(c);
+        });
+
+        self.inner_content_replacement = Some(replacement);
+
+        Ok(())
+    }
+
+    /// Inserts `content` before the end tag.
+    ///
+    /// Consequent calls to the method append `content` to the previously inserted content.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lol_html::{rewrite_str, element, RewriteStrSettings};
+    /// use lol_html::html_content::ContentType;
+    ///
+    /// let html = rewrite_str(
+    ///     r#"<div id="foo">bar</div>"#,
+    ///     RewriteStrSettings {
+    ///         element_content_handlers: vec![
+    ///             element!("#foo", |el| {
+    ///                 el.on_end_tag(|end| {
+    ///                     end.before("<baz>", ContentType::Html);
+    ///                     end.before("<qux>", ContentType::Text);
+    ///
+    ///                     Ok(())
+    ///                 })?;
+    ///
+    ///                 Ok(())
+    ///             })
+    ///         ],
+    ///         ..RewriteStrSettings::default()
+    ///     }
+    /// ).unwrap();
+    ///
+    /// assert_eq!(html, r#"<div id="foo">bar<baz>&lt;qux&gt;</div>"#);
+    /// ```
+    #[inline]
+    pub fn before(&mut self, content: &str, content_type: ContentType) {
+        self.mutations.before(content, content_type);
+    }
+
+    /// Inserts `content` after the end tag.
+    ///
+    /// Consequent calls to the method prepend `content` to the previously inserted content.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lol_html::{rewrite_str, element, RewriteStrSettings};
+    /// use lol_html::html_content::ContentType;
+    ///
+    /// let html = rewrite_str(
+    ///     r#"<div id="foo">bar</div>"#,
+    ///     RewriteStrSettings {
+    ///         element_content_handlers: vec![
+    ///             element!("#foo", |el| {
+    ///                 el.on_end_tag(|end| {
+    ///                     end.after("<baz>", ContentType::Html);
+    ///                     end.after("<qux>", ContentType::Text);
+    ///
+    ///                     Ok(())
+    ///                 })?;
+    ///
+    ///                 Ok(())
+    ///             })
+    ///         ],
+    ///         ..RewriteStrSettings::default()
+    ///     }
+    /// ).unwrap();
+    ///
+    /// assert_eq!(html, r#"<div id="foo">bar</div>&lt;qux&gt;<baz>"#);
+    /// ```
+    #[inline]
+    pub fn after(&mut self, content: &str, content_type: ContentType) {
+        self.mutations.after(content, content_type);
+    }
+
+    /// Removes the end tag.
+    #[inline]
+    pub fn remove(&mut self) {
+        self.mutations.remove();
+    }
+
+    /// Returns `true` if the end tag has been removed.
+    #[inline]
+    pub fn removed(&self) -> bool {
+        self.mutations.removed()
+    }
+
     #[inline]
     fn raw(&self) -> Option<&Bytes> {
         self.raw.as_ref()
@@ -1,7 +1,8 @@
 mod attributes;
 mod capturer;
 
-use super::Mutations;
+use super::{Mutations, SourceLocation};
+use crate::rewriter::RewritingError;
 
 pub(super) use self::attributes::Attributes;
 pub use self::attributes::{Attribute, AttributeNameError};
@@ -11,35 +12,40 @@ pub trait Serialize {
     fn to_bytes(&self, output_handler: &mut dyn FnMut(&[u8]));
 }
 
+/// Serialization of the rewritable units that pulls the streaming content inserted by
+/// the mutations, so it requires mutable access and can fail.
+pub trait SerializeWithMutations {
+    fn to_bytes(&mut self, output_handler: &mut dyn FnMut(&[u8])) -> Result<(), RewritingError>;
+}
+
 macro_rules! impl_serialize {
     ($Token:ident) => {
-        impl crate::rewritable_units::Serialize for $Token<'_> {
+        impl crate::rewritable_units::SerializeWithMutations for $Token<'_> {
             #[inline]
-            fn to_bytes(&self, output_handler: &mut dyn FnMut(&[u8])) {
-                let Mutations {
-                    content_before,
-                    replacement,
-                    content_after,
-                    removed,
-                    ..
-                } = &self.mutations;
+            fn to_bytes(
+                &mut self,
+                output_handler: &mut dyn FnMut(&[u8]),
+            ) -> Result<(), crate::rewriter::RewritingError> {
+                let encoding = self.mutations.encoding();
 
-                if !content_before.is_empty() {
-                    output_handler(content_before);
-                }
+                self.mutations
+                    .content_before
+                    .serialize(encoding, output_handler)?;
 
-                if !removed {
+                if !self.mutations.removed {
                     match self.raw() {
                         Some(raw) => output_handler(raw),
                         None => self.serialize_from_parts(output_handler),
                     }
-                } else if !replacement.is_empty() {
-                    output_handler(replacement);
+                } else {
+                    self.mutations
+                        .replacement
+                        .serialize(encoding, output_handler)?;
                 }
 
-                if !content_after.is_empty() {
-                    output_handler(content_after);
-                }
+                self.mutations
+                    .content_after
+                    .serialize(encoding, output_handler)
             }
         }
     };
@@ -52,7 +58,7 @@ mod start_tag;
 mod text_chunk;
 
 pub use self::comment::{Comment, CommentTextError};
-pub use self::doctype::Doctype;
+pub use self::doctype::{Doctype, DoctypeError};
 pub use self::end_tag::EndTag;
 pub use self::start_tag::StartTag;
 pub use self::text_chunk::TextChunk;
@@ -66,9 +72,22 @@ pub enum Token<'i> {
     Doctype(Doctype<'i>),
 }
 
-impl Serialize for Token<'_> {
+impl Token<'_> {
+    #[inline]
+    pub(crate) fn set_source_location(&mut self, source_location: SourceLocation) {
+        match self {
+            Token::TextChunk(t) => t.set_source_location(source_location),
+            Token::Comment(t) => t.set_source_location(source_location),
+            Token::StartTag(t) => t.set_source_location(source_location),
+            Token::EndTag(t) => t.set_source_location(source_location),
+            Token::Doctype(t) => t.set_source_location(source_location),
+        }
+    }
+}
+
+impl SerializeWithMutations for Token<'_> {
     #[inline]
-    fn to_bytes(&self, output_handler: &mut dyn FnMut(&[u8])) {
+    fn to_bytes(&mut self, output_handler: &mut dyn FnMut(&[u8])) -> Result<(), RewritingError> {
         match self {
             Token::TextChunk(t) => t.to_bytes(output_handler),
             Token::Comment(t) => t.to_bytes(output_handler),
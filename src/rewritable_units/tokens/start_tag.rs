@@ -2,6 +2,7 @@ use super::{Attribute, AttributeNameError, Attributes};
 use super::{Mutations, Serialize, Token};
 use crate::base::Bytes;
 use crate::html::Namespace;
+use crate::rewritable_units::SourceLocation;
 use encoding_rs::Encoding;
 use std::fmt::{self, Debug};
 
@@ -12,6 +13,7 @@ pub struct StartTag<'i> {
     self_closing: bool,
     raw: Option<Bytes<'i>>,
     encoding: &'static Encoding,
+    source_location: SourceLocation,
     pub mutations: Mutations,
 }
 
@@ -31,10 +33,21 @@ impl<'i> StartTag<'i> {
             self_closing,
             raw: Some(raw),
             encoding,
+            source_location: SourceLocation::default(),
             mutations: Mutations::new(encoding),
         })
     }
 
+    #[inline]
+    pub fn source_location(&self) -> SourceLocation {
+        self.source_location
+    }
+
+    #[inline]
+    pub(crate) fn set_source_location(&mut self, source_location: SourceLocation) {
+        self.source_location = source_location;
+    }
+
     #[inline]
     pub fn encoding(&self) -> &'static Encoding {
         self.encoding
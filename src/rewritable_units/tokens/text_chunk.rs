@@ -1,10 +1,12 @@
 use super::{Mutations, Token};
 use crate::base::Bytes;
-use crate::html::TextType;
+use crate::html::{decode_text, TextType};
+use crate::rewritable_units::SourceLocation;
 use encoding_rs::Encoding;
 use std::any::Any;
 use std::borrow::Cow;
 use std::fmt::{self, Debug};
+use std::mem;
 
 /// An HTML text node chunk.
 ///
@@ -57,7 +59,17 @@ pub struct TextChunk<'i> {
     text: Cow<'i, str>,
     text_type: TextType,
     last_in_text_node: bool,
+    // NOTE: the beginning of a character reference that is split between
+    // the previous chunk and this one.
+    reference_prefix: String,
+    // NOTE: the length of the trailing part of the reference prefix and the text that
+    // can be the beginning of a character reference split between this chunk and the next one.
+    incomplete_reference_len: usize,
+    // NOTE: the content of the whole text node that is emitted instead of the text
+    // of the last chunk if the text node is buffered for the text node handlers.
+    text_node_content: Option<String>,
     encoding: &'static Encoding,
+    source_location: SourceLocation,
     mutations: Mutations,
     user_data: Box<dyn Any>,
 }
@@ -67,24 +79,105 @@ impl<'i> TextChunk<'i> {
         text: &'i str,
         text_type: TextType,
         last_in_text_node: bool,
+        reference_prefix: String,
+        incomplete_reference_len: usize,
         encoding: &'static Encoding,
     ) -> Token<'i> {
         Token::TextChunk(TextChunk {
             text: text.into(),
             text_type,
             last_in_text_node,
+            reference_prefix,
+            incomplete_reference_len,
+            text_node_content: None,
             encoding,
+            source_location: SourceLocation::default(),
             mutations: Mutations::new(encoding),
             user_data: Box::new(()),
         })
     }
 
+    /// Returns the location of the text chunk in the input.
+    ///
+    /// The location points to the first byte of the chunk's raw text, so the last chunk
+    /// of a text node, which is empty, is located where the text node ends.
+    ///
+    /// Refer to [`SourceLocation`] documentation for more information.
+    ///
+    /// [`SourceLocation`]: struct.SourceLocation.html
+    #[inline]
+    pub fn source_location(&self) -> SourceLocation {
+        self.source_location
+    }
+
+    #[inline]
+    pub(crate) fn set_source_location(&mut self, source_location: SourceLocation) {
+        self.source_location = source_location;
+    }
+
     /// Returns the textual content of the chunk.
     #[inline]
     pub fn as_str(&self) -> &str {
         &*self.text
     }
 
+    /// Returns the textual content of the chunk with decoded [character references].
+    ///
+    /// Character references are decoded only in the text types that allow them (see
+    /// [`TextType::allows_html_entities`]), for other text types the content is returned as is.
+    ///
+    /// A character reference can be split between chunks. In this case, it's decoded as a part of
+    /// the chunk where it ends, so the decoded content of all the chunks in a text node is equal
+    /// to the decoded content of the whole text node.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lol_html::{HtmlRewriter, Settings, text};
+    ///
+    /// let mut decoded = String::new();
+    ///
+    /// {
+    ///     let mut rewriter = HtmlRewriter::new(
+    ///         Settings {
+    ///             element_content_handlers: vec![text!("div", |t| {
+    ///                 decoded += &t.decoded_text();
+    ///
+    ///                 Ok(())
+    ///             })],
+    ///             ..Settings::default()
+    ///         },
+    ///         |_: &[u8]| {},
+    ///     );
+    ///
+    ///     rewriter.write(b"<div>Tom &am").unwrap();
+    ///     rewriter.write(b"p; Jerry&#x21;</div>").unwrap();
+    ///     rewriter.end().unwrap();
+    /// }
+    ///
+    /// assert_eq!(decoded, "Tom & Jerry!");
+    /// ```
+    ///
+    /// [character references]: https://html.spec.whatwg.org/multipage/syntax.html#character-references
+    /// [`TextType::allows_html_entities`]: enum.TextType.html#method.allows_html_entities
+    pub fn decoded_text(&self) -> Cow<str> {
+        if !self.text_type.allows_html_entities() {
+            return Cow::Borrowed(&self.text);
+        }
+
+        if self.reference_prefix.is_empty() {
+            let end = self.text.len() - self.incomplete_reference_len;
+
+            decode_text(&self.text[..end])
+        } else {
+            let mut text = self.reference_prefix.clone() + &self.text;
+
+            text.truncate(text.len() - self.incomplete_reference_len);
+
+            Cow::Owned(decode_text(&text).into_owned())
+        }
+    }
+
     /// Returns the type of the text in the chunk.
     ///
     /// The type of the text depends on the surrounding context of the text. E.g. regular visible
@@ -245,6 +338,43 @@ impl<'i> TextChunk<'i> {
         self.mutations.removed()
     }
 
+    // NOTE: unlike `before`, inserts the content before all the previously inserted content.
+    #[inline]
+    pub(crate) fn prepend_before(
+        &mut self,
+        content: &str,
+        content_type: crate::rewritable_units::ContentType,
+    ) {
+        let mut mutations = Mutations::new(self.encoding);
+
+        mutations.before(content, content_type);
+        mutations
+            .content_before
+            .append(mem::take(&mut self.mutations.content_before));
+
+        self.mutations.content_before = mutations.content_before;
+    }
+
+    #[inline]
+    pub(crate) fn encoding(&self) -> &'static Encoding {
+        self.encoding
+    }
+
+    #[inline]
+    pub(crate) fn has_text_node_content(&self) -> bool {
+        self.text_node_content.is_some()
+    }
+
+    #[inline]
+    pub(crate) fn set_text_node_content(&mut self, text_node_content: String) {
+        self.text_node_content = Some(text_node_content);
+    }
+
+    #[inline]
+    pub(crate) fn apply_text_node_mutations(&mut self, mutations: Mutations) {
+        self.mutations.merge(mutations);
+    }
+
     #[inline]
     fn raw(&self) -> Option<&Bytes> {
         None
@@ -252,8 +382,10 @@ impl<'i> TextChunk<'i> {
 
     #[inline]
     fn serialize_from_parts(&self, output_handler: &mut dyn FnMut(&[u8])) {
-        if !self.text.is_empty() {
-            output_handler(&Bytes::from_str(&self.text, self.encoding));
+        let text = self.text_node_content.as_deref().unwrap_or(&self.text);
+
+        if !text.is_empty() {
+            output_handler(&Bytes::from_str(text, self.encoding));
         }
     }
 }
@@ -280,7 +412,7 @@ mod tests {
     fn rewrite_text_chunk(
         html: &[u8],
         encoding: &'static Encoding,
-        mut handler: impl FnMut(&mut TextChunk),
+        mut handler: impl FnMut(&mut TextChunk) + MaybeSend,
     ) -> String {
         let mut handler_called = false;
 
@@ -313,6 +445,72 @@ mod tests {
         });
     }
 
+    fn decode_text_in_chunks(chunks: &[&[u8]]) -> Vec<(TextType, String)> {
+        let mut text_nodes = vec![];
+        let mut current_text = String::new();
+
+        {
+            let mut rewriter = HtmlRewriter::new(
+                Settings {
+                    document_content_handlers: vec![doc_text!(|c| {
+                        current_text += &c.decoded_text();
+
+                        if c.last_in_text_node() {
+                            text_nodes.push((c.text_type(), current_text.split_off(0)));
+                        }
+
+                        Ok(())
+                    })],
+                    ..Settings::default()
+                },
+                |_: &[u8]| {},
+            );
+
+            for chunk in chunks {
+                rewriter.write(chunk).unwrap();
+            }
+
+            rewriter.end().unwrap();
+        }
+
+        text_nodes
+    }
+
+    #[test]
+    fn decoded_text() {
+        const HTML: &str = concat!(
+            "<div>a&amp;b&#x27;cé&notin;d&nbsp&copy=&#128512;&foo;&</div>",
+            "<textarea>&lt;&gt</textarea><script>&amp;</script><title>&am</title>"
+        );
+
+        let expected = vec![
+            (
+                TextType::Data,
+                "a&b'cé\u{2209}d\u{A0}\u{A9}=\u{1F600}&foo;&".to_string(),
+            ),
+            (TextType::RCData, "<>".to_string()),
+            (TextType::ScriptData, "&amp;".to_string()),
+            (TextType::RCData, "&am".to_string()),
+        ];
+
+        assert_eq!(decode_text_in_chunks(&[HTML.as_bytes()]), expected);
+
+        for split_pos in 1..HTML.len() {
+            let (chunk1, chunk2) = HTML.as_bytes().split_at(split_pos);
+
+            assert_eq!(
+                decode_text_in_chunks(&[chunk1, chunk2]),
+                expected,
+                "Split position: {}",
+                split_pos
+            );
+        }
+
+        let byte_chunks = HTML.as_bytes().chunks(1).collect::<Vec<_>>();
+
+        assert_eq!(decode_text_in_chunks(&byte_chunks), expected);
+    }
+
     mod serialization {
         use super::*;
 
@@ -0,0 +1,479 @@
+use super::settings::HandlerResult;
+use super::{
+    AsciiCompatibleEncoding, DocumentContentHandlers, ElementContentHandlers, HtmlRewriter,
+    MemorySettings, RewritingError, RewritingStats, Settings, StopSignal,
+};
+use crate::base::MaybeSend;
+use crate::rewritable_units::{Comment, Doctype, Element, TextChunk, TextNode};
+use crate::selectors_vm::Selector;
+use crate::transform_stream::OutputSink;
+use encoding_rs::Encoding;
+use std::borrow::Cow;
+use std::fmt::{self, Debug};
+
+struct NoopOutputSink;
+
+impl OutputSink for NoopOutputSink {
+    #[inline]
+    fn handle_chunk(&mut self, _: &[u8]) {}
+}
+
+/// Specifies element content analysis handlers associated with a selector.
+///
+/// The handlers receive the rewritable units by shared reference, so the units can be
+/// inspected, but not modified.
+#[derive(Default)]
+pub struct ElementContentAnalyzers<'h>(ElementContentHandlers<'h>);
+
+impl<'h> ElementContentAnalyzers<'h> {
+    /// Sets a handler for elements matched by a selector.
+    #[inline]
+    pub fn element(
+        self,
+        mut handler: impl FnMut(&Element) -> HandlerResult + MaybeSend + 'h,
+    ) -> Self {
+        ElementContentAnalyzers(self.0.element(move |el| handler(el)))
+    }
+
+    /// Sets a handler for HTML comments in the inner content of elements matched by a selector.
+    #[inline]
+    pub fn comments(
+        self,
+        mut handler: impl FnMut(&Comment) -> HandlerResult + MaybeSend + 'h,
+    ) -> Self {
+        ElementContentAnalyzers(self.0.comments(move |c| handler(c)))
+    }
+
+    /// Sets a handler for text chunks in the inner content of elements matched by a selector.
+    #[inline]
+    pub fn text(
+        self,
+        mut handler: impl FnMut(&TextChunk) -> HandlerResult + MaybeSend + 'h,
+    ) -> Self {
+        ElementContentAnalyzers(self.0.text(move |t| handler(t)))
+    }
+
+    /// Sets a handler for whole text nodes in the inner content of elements matched by a selector.
+    ///
+    /// Refer to [`ElementContentHandlers::text_node`] documentation for more information.
+    ///
+    /// [`ElementContentHandlers::text_node`]: struct.ElementContentHandlers.html#method.text_node
+    #[inline]
+    pub fn text_node(
+        self,
+        mut handler: impl FnMut(&TextNode) -> HandlerResult + MaybeSend + 'h,
+    ) -> Self {
+        ElementContentAnalyzers(self.0.text_node(move |t| handler(t)))
+    }
+}
+
+/// Specifies document-level content analysis handlers.
+///
+/// Refer to [`DocumentContentHandlers`] documentation for more information.
+///
+/// [`DocumentContentHandlers`]: struct.DocumentContentHandlers.html
+#[derive(Default)]
+pub struct DocumentContentAnalyzers<'h>(DocumentContentHandlers<'h>);
+
+impl<'h> DocumentContentAnalyzers<'h> {
+    /// Sets a handler for the [document type declaration].
+    ///
+    /// [document type declaration]: https://developer.mozilla.org/en-US/docs/Glossary/Doctype
+    #[inline]
+    pub fn doctype(
+        self,
+        mut handler: impl FnMut(&Doctype) -> HandlerResult + MaybeSend + 'h,
+    ) -> Self {
+        DocumentContentAnalyzers(self.0.doctype(move |d| handler(d)))
+    }
+
+    /// Sets a handler for all HTML comments present in the input HTML markup.
+    #[inline]
+    pub fn comments(
+        self,
+        mut handler: impl FnMut(&Comment) -> HandlerResult + MaybeSend + 'h,
+    ) -> Self {
+        DocumentContentAnalyzers(self.0.comments(move |c| handler(c)))
+    }
+
+    /// Sets a handler for all text chunks present in the input HTML markup.
+    #[inline]
+    pub fn text(
+        self,
+        mut handler: impl FnMut(&TextChunk) -> HandlerResult + MaybeSend + 'h,
+    ) -> Self {
+        DocumentContentAnalyzers(self.0.text(move |t| handler(t)))
+    }
+
+    /// Sets a handler for the document end, which is called after the last chunk is processed.
+    #[inline]
+    pub fn end(self, mut handler: impl FnMut() -> HandlerResult + MaybeSend + 'h) -> Self {
+        DocumentContentAnalyzers(self.0.end(move |_| handler()))
+    }
+}
+
+/// Specifies settings for [`HtmlAnalyzer`].
+///
+/// The settings have the same meaning as the corresponding [`Settings`] of the rewriter.
+///
+/// [`HtmlAnalyzer`]: struct.HtmlAnalyzer.html
+/// [`Settings`]: struct.Settings.html
+pub struct AnalyzerSettings<'h, 's> {
+    /// Specifies CSS selectors and analysis handlers for elements and their inner content.
+    ///
+    /// ### Default
+    ///
+    /// `vec![]` when constructed with `AnalyzerSettings::default()`.
+    pub element_content_handlers: Vec<(Cow<'s, Selector>, ElementContentAnalyzers<'h>)>,
+
+    /// Specifies document-level content analysis handlers.
+    ///
+    /// ### Default
+    ///
+    /// `vec![]` when constructed with `AnalyzerSettings::default()`.
+    pub document_content_handlers: Vec<DocumentContentAnalyzers<'h>>,
+
+    /// Specifies the [character encoding] for the input.
+    ///
+    /// [character encoding]: https://developer.mozilla.org/en-US/docs/Glossary/character_encoding
+    ///
+    /// ### Default
+    ///
+    /// `"utf-8"` when constructed with `AnalyzerSettings::default()`.
+    pub encoding: AsciiCompatibleEncoding,
+
+    /// Refer to [`Settings::detect_encoding`] documentation.
+    ///
+    /// [`Settings::detect_encoding`]: struct.Settings.html#structfield.detect_encoding
+    ///
+    /// ### Default
+    ///
+    /// `false` when constructed with `AnalyzerSettings::default()`.
+    pub detect_encoding: bool,
+
+    /// Refer to [`Settings::match_decoded_attribute_values`] documentation.
+    ///
+    /// [`Settings::match_decoded_attribute_values`]: struct.Settings.html#structfield.match_decoded_attribute_values
+    ///
+    /// ### Default
+    ///
+    /// `false` when constructed with `AnalyzerSettings::default()`.
+    pub match_decoded_attribute_values: bool,
+
+    /// Specifies the memory settings.
+    pub memory_settings: MemorySettings,
+
+    /// Refer to [`Settings::strict`] documentation.
+    ///
+    /// [`Settings::strict`]: struct.Settings.html#structfield.strict
+    ///
+    /// ### Default
+    ///
+    /// `true` when constructed with `AnalyzerSettings::default()`.
+    pub strict: bool,
+
+    /// Specifies the signal that stops the analysis of the rest of the input.
+    ///
+    /// Refer to [`StopSignal`] documentation for more information.
+    ///
+    /// [`StopSignal`]: struct.StopSignal.html
+    ///
+    /// ### Default
+    ///
+    /// `None` when constructed with `AnalyzerSettings::default()`.
+    pub stop_signal: Option<StopSignal>,
+
+    /// Specifies the statistics collected during the analysis.
+    ///
+    /// Refer to [`RewritingStats`] documentation for more information.
+    ///
+    /// [`RewritingStats`]: struct.RewritingStats.html
+    ///
+    /// ### Default
+    ///
+    /// `None` when constructed with `AnalyzerSettings::default()`.
+    pub stats: Option<RewritingStats>,
+}
+
+impl Default for AnalyzerSettings<'_, '_> {
+    #[inline]
+    fn default() -> Self {
+        AnalyzerSettings {
+            element_content_handlers: vec![],
+            document_content_handlers: vec![],
+            encoding: AsciiCompatibleEncoding(encoding_rs::UTF_8),
+            detect_encoding: false,
+            match_decoded_attribute_values: false,
+            memory_settings: MemorySettings::default(),
+            strict: true,
+            stop_signal: None,
+            stats: None,
+        }
+    }
+}
+
+impl<'h, 's> From<AnalyzerSettings<'h, 's>> for Settings<'h, 's> {
+    #[inline]
+    fn from(settings: AnalyzerSettings<'h, 's>) -> Self {
+        Settings {
+            element_content_handlers: settings
+                .element_content_handlers
+                .into_iter()
+                .map(|(selector, handlers)| (selector, handlers.0))
+                .collect(),
+            document_content_handlers: settings
+                .document_content_handlers
+                .into_iter()
+                .map(|handlers| handlers.0)
+                .collect(),
+            encoding: settings.encoding,
+            detect_encoding: settings.detect_encoding,
+            match_decoded_attribute_values: settings.match_decoded_attribute_values,
+            memory_settings: settings.memory_settings,
+            strict: settings.strict,
+            stop_signal: settings.stop_signal,
+            stats: settings.stats,
+            ..Settings::default()
+        }
+    }
+}
+
+/// A streaming HTML analyzer.
+///
+/// The analyzer invokes the handlers for the input in the same way as [`HtmlRewriter`] does,
+/// but it doesn't produce any output: the handlers can't modify the content and the content
+/// is not serialized, which makes the analyzer faster than the rewriter for the extraction
+/// tasks.
+///
+/// [`HtmlRewriter`]: struct.HtmlRewriter.html
+///
+/// # Example
+/// ```
+/// use lol_html::{AnalyzerSettings, ElementContentAnalyzers, HtmlAnalyzer};
+/// use std::borrow::Cow;
+///
+/// let mut links = vec![];
+///
+/// {
+///     let mut analyzer = HtmlAnalyzer::new(AnalyzerSettings {
+///         element_content_handlers: vec![(
+///             Cow::Owned("a[href]".parse().unwrap()),
+///             ElementContentAnalyzers::default().element(|el| {
+///                 links.push(el.get_attribute("href").unwrap());
+///
+///                 Ok(())
+///             }),
+///         )],
+///         ..AnalyzerSettings::default()
+///     });
+///
+///     analyzer.write(b"<a href='/'>Home</a><a hr").unwrap();
+///     analyzer.write(b"ef='/about'>About</a>").unwrap();
+///     analyzer.end().unwrap();
+/// }
+///
+/// assert_eq!(links, ["/", "/about"]);
+/// ```
+pub struct HtmlAnalyzer<'h> {
+    rewriter: HtmlRewriter<'h, NoopOutputSink>,
+}
+
+impl<'h> HtmlAnalyzer<'h> {
+    /// Constructs a new analyzer with the provided `settings`.
+    pub fn new<'s>(settings: AnalyzerSettings<'h, 's>) -> Self {
+        let mut rewriter = HtmlRewriter::new(settings.into(), NoopOutputSink);
+
+        rewriter.stream.disable_output();
+
+        HtmlAnalyzer { rewriter }
+    }
+
+    /// Returns the character encoding of the input.
+    ///
+    /// Refer to [`HtmlRewriter::encoding`] documentation for more information.
+    ///
+    /// [`HtmlRewriter::encoding`]: struct.HtmlRewriter.html#method.encoding
+    #[inline]
+    pub fn encoding(&self) -> Option<&'static Encoding> {
+        self.rewriter.encoding()
+    }
+
+    /// Returns the statistics collected by the analyzer.
+    ///
+    /// Returns `None` if the [`stats`] setting is not specified.
+    ///
+    /// [`stats`]: struct.AnalyzerSettings.html#structfield.stats
+    #[inline]
+    pub fn stats(&self) -> Option<&RewritingStats> {
+        self.rewriter.stats()
+    }
+
+    /// Writes a chunk of input data to the analyzer.
+    ///
+    /// # Panics
+    ///  * If previous invocation of the method returned a [`RewritingError`]
+    ///    (these errors are unrecovarable).
+    ///
+    /// [`RewritingError`]: errors/enum.RewritingError.html
+    #[inline]
+    pub fn write(&mut self, data: &[u8]) -> Result<(), RewritingError> {
+        self.rewriter.write(data)
+    }
+
+    /// Stops the analysis of the rest of the document.
+    ///
+    /// The handlers are not invoked for the rest of the input.
+    #[inline]
+    pub fn stop_analysis(&self) {
+        self.rewriter.stop_rewriting();
+    }
+
+    /// Finalizes the analysis process.
+    ///
+    /// Should be called once the last chunk of the input is written.
+    ///
+    /// # Panics
+    ///  * If previous invocation of [`write`] returned a [`RewritingError`] (these errors
+    ///    are unrecovarable).
+    ///
+    /// [`RewritingError`]: errors/enum.RewritingError.html
+    /// [`write`]: struct.HtmlAnalyzer.html#method.write
+    #[inline]
+    pub fn end(self) -> Result<(), RewritingError> {
+        self.rewriter.end()
+    }
+
+    /// Finalizes the analysis process like [`end`] and [resets] the analyzer, so it can be
+    /// reused for another document.
+    ///
+    /// [`end`]: struct.HtmlAnalyzer.html#method.end
+    /// [resets]: struct.HtmlAnalyzer.html#method.reset
+    #[inline]
+    pub fn end_and_reset(&mut self) -> Result<(), RewritingError> {
+        self.rewriter.end_and_reset(NoopOutputSink).map(|_| ())
+    }
+
+    /// Returns the analyzer to its initial state, so it can be reused for another document.
+    ///
+    /// Refer to [`HtmlRewriter::reset`] documentation for more information.
+    ///
+    /// [`HtmlRewriter::reset`]: struct.HtmlRewriter.html#method.reset
+    #[inline]
+    pub fn reset(&mut self) {
+        self.rewriter.reset(NoopOutputSink);
+    }
+}
+
+// NOTE: this opaque Debug implementation is required to make
+// `.unwrap()` and `.expect()` methods available on Result
+// returned by the analyzer.
+impl Debug for HtmlAnalyzer<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "HtmlAnalyzer")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    fn analyze(html: &str, settings: AnalyzerSettings) {
+        let mut analyzer = HtmlAnalyzer::new(settings);
+
+        for chunk in html.as_bytes().chunks(3) {
+            analyzer.write(chunk).unwrap();
+        }
+
+        analyzer.end().unwrap();
+    }
+
+    #[test]
+    fn handlers_invocation() {
+        let events = Mutex::new(vec![]);
+
+        analyze(
+            "<!doctype html><div class='a'>Hey<!-- 1 --><b>you</b></div><!-- 2 -->",
+            AnalyzerSettings {
+                element_content_handlers: vec![(
+                    Cow::Owned(".a".parse().unwrap()),
+                    ElementContentAnalyzers::default()
+                        .element(|el| {
+                            events
+                                .lock()
+                                .unwrap()
+                                .push(format!("element: {}", el.tag_name()));
+
+                            Ok(())
+                        })
+                        .comments(|c| {
+                            events
+                                .lock()
+                                .unwrap()
+                                .push(format!("comment: {}", c.text()));
+
+                            Ok(())
+                        })
+                        .text_node(|t| {
+                            events.lock().unwrap().push(format!("text: {}", t.as_str()));
+
+                            Ok(())
+                        }),
+                )],
+                document_content_handlers: vec![DocumentContentAnalyzers::default()
+                    .doctype(|d| {
+                        events
+                            .lock()
+                            .unwrap()
+                            .push(format!("doctype: {}", d.name().unwrap()));
+
+                        Ok(())
+                    })
+                    .end(|| {
+                        events.lock().unwrap().push("end".into());
+
+                        Ok(())
+                    })],
+                ..AnalyzerSettings::default()
+            },
+        );
+
+        assert_eq!(
+            events.into_inner().unwrap(),
+            [
+                "doctype: html",
+                "element: div",
+                "text: Hey",
+                "comment:  1 ",
+                "text: you",
+                "end"
+            ]
+        );
+    }
+
+    #[test]
+    fn no_output() {
+        let stats = RewritingStats::new();
+        let mut element_count = 0;
+
+        analyze(
+            "<div><span>Hello</span><span>world</span></div>",
+            AnalyzerSettings {
+                element_content_handlers: vec![(
+                    Cow::Owned("span".parse().unwrap()),
+                    ElementContentAnalyzers::default().element(|_| {
+                        element_count += 1;
+
+                        Ok(())
+                    }),
+                )],
+                stats: Some(stats.clone()),
+                ..AnalyzerSettings::default()
+            },
+        );
+
+        assert_eq!(element_count, 2);
+        assert_eq!(stats.bytes_in(), 47);
+        assert_eq!(stats.bytes_out(), 0);
+    }
+}
@@ -0,0 +1,644 @@
+use super::sync_io::{closed_error, to_io_error, SharedOutputBuffer};
+use super::{
+    DocumentContentHandlers, ElementContentHandlers, HandlerResult, HtmlRewriter, RewritingError,
+    Settings,
+};
+use crate::base::Shared;
+use crate::rewritable_units::{Comment, Doctype, DocumentEnd, DocumentStart, Element, TextChunk};
+use futures_channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use futures_core::{ready, Stream};
+use std::any::Any;
+use std::cell::RefCell;
+use std::error::Error;
+use std::future::Future;
+use std::io;
+use std::mem;
+use std::panic;
+use std::pin::Pin;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::task::{Context, Poll};
+use std::thread::{self, JoinHandle};
+
+type HandlerError = Box<dyn Error + Send + Sync>;
+type AwaitedFuture = Pin<Box<dyn Future<Output = Box<dyn Any + Send>> + Send>>;
+
+enum WorkerCommand {
+    Write(Vec<u8>),
+    End,
+}
+
+enum WorkerEvent {
+    Await(AwaitedFuture),
+    Done(Result<Vec<u8>, RewritingError>),
+}
+
+struct HandlerBridge {
+    events: UnboundedSender<WorkerEvent>,
+    resolved: Receiver<Box<dyn Any + Send>>,
+}
+
+thread_local! {
+    static HANDLER_BRIDGE: RefCell<Option<HandlerBridge>> = RefCell::new(None);
+}
+
+// NOTE: async handlers are invoked synchronously on the rewriter's worker thread. The future
+// returned by a handler is sent to the driver that polls it in the async context, while the
+// worker thread (and, thus, parsing) is suspended until the future resolves.
+fn await_in_driver<O: Send + 'static>(
+    future: impl Future<Output = O> + Send + 'static,
+) -> Result<O, HandlerError> {
+    HANDLER_BRIDGE.with(|bridge| {
+        let bridge = bridge.borrow();
+
+        let bridge = bridge.as_ref().ok_or(
+            "Async content handlers can be used only with rewriters \
+             constructed with `with_async_handlers`.",
+        )?;
+
+        let future = Box::pin(async move { Box::new(future.await) as Box<dyn Any + Send> });
+
+        bridge
+            .events
+            .unbounded_send(WorkerEvent::Await(future))
+            .map_err(|_| "The rewriter has been dropped.")?;
+
+        let output = bridge
+            .resolved
+            .recv()
+            .map_err(|_| "The rewriter has been dropped.")?;
+
+        Ok(*output
+            .downcast()
+            .expect("Future output should have the type of the awaited future"))
+    })
+}
+
+macro_rules! impl_async_handler {
+    ($self:ident.$handler_name:ident($handler:ident: $Content:ty)) => {{
+        let mut $handler = $handler;
+
+        $self.$handler_name(move |content: &mut $Content| {
+            let continuation = await_in_driver($handler(content))??;
+
+            continuation(content)
+        })
+    }};
+}
+
+impl<'h> ElementContentHandlers<'h> {
+    /// Sets an async handler for elements matched by a selector.
+    ///
+    /// The future returned by the handler is awaited before the rewriting proceeds. It resolves
+    /// to a continuation that is called with the element once the future is complete.
+    ///
+    /// Requires the `async_handlers` feature.
+    #[inline]
+    pub fn element_async<F, C>(self, handler: impl FnMut(&mut Element) -> F + Send + 'h) -> Self
+    where
+        F: Future<Output = Result<C, HandlerError>> + Send + 'static,
+        C: FnOnce(&mut Element) -> HandlerResult + Send + 'static,
+    {
+        impl_async_handler!(self.element(handler: Element))
+    }
+
+    /// Sets an async handler for HTML comments in the inner content of elements matched by
+    /// a selector.
+    ///
+    /// Requires the `async_handlers` feature.
+    #[inline]
+    pub fn comments_async<F, C>(self, handler: impl FnMut(&mut Comment) -> F + Send + 'h) -> Self
+    where
+        F: Future<Output = Result<C, HandlerError>> + Send + 'static,
+        C: FnOnce(&mut Comment) -> HandlerResult + Send + 'static,
+    {
+        impl_async_handler!(self.comments(handler: Comment))
+    }
+
+    /// Sets an async handler for text chunks in the inner content of elements matched by
+    /// a selector.
+    ///
+    /// Requires the `async_handlers` feature.
+    #[inline]
+    pub fn text_async<F, C>(self, handler: impl FnMut(&mut TextChunk) -> F + Send + 'h) -> Self
+    where
+        F: Future<Output = Result<C, HandlerError>> + Send + 'static,
+        C: FnOnce(&mut TextChunk) -> HandlerResult + Send + 'static,
+    {
+        impl_async_handler!(self.text(handler: TextChunk))
+    }
+}
+
+impl<'h> DocumentContentHandlers<'h> {
+    /// Sets an async handler for the document type declaration.
+    ///
+    /// Requires the `async_handlers` feature.
+    #[inline]
+    pub fn doctype_async<F, C>(self, handler: impl FnMut(&mut Doctype) -> F + Send + 'h) -> Self
+    where
+        F: Future<Output = Result<C, HandlerError>> + Send + 'static,
+        C: FnOnce(&mut Doctype) -> HandlerResult + Send + 'static,
+    {
+        impl_async_handler!(self.doctype(handler: Doctype))
+    }
+
+    /// Sets an async handler for all HTML comments present in the input HTML markup.
+    ///
+    /// Requires the `async_handlers` feature.
+    #[inline]
+    pub fn comments_async<F, C>(self, handler: impl FnMut(&mut Comment) -> F + Send + 'h) -> Self
+    where
+        F: Future<Output = Result<C, HandlerError>> + Send + 'static,
+        C: FnOnce(&mut Comment) -> HandlerResult + Send + 'static,
+    {
+        impl_async_handler!(self.comments(handler: Comment))
+    }
+
+    /// Sets an async handler for all text chunks present in the input HTML markup.
+    ///
+    /// Requires the `async_handlers` feature.
+    #[inline]
+    pub fn text_async<F, C>(self, handler: impl FnMut(&mut TextChunk) -> F + Send + 'h) -> Self
+    where
+        F: Future<Output = Result<C, HandlerError>> + Send + 'static,
+        C: FnOnce(&mut TextChunk) -> HandlerResult + Send + 'static,
+    {
+        impl_async_handler!(self.text(handler: TextChunk))
+    }
+
+    /// Sets an async handler for the document start, which is called before any output
+    /// is produced.
+    ///
+    /// Requires the `async_handlers` feature.
+    #[inline]
+    pub fn start_async<F, C>(self, handler: impl FnMut(&mut DocumentStart) -> F + Send + 'h) -> Self
+    where
+        F: Future<Output = Result<C, HandlerError>> + Send + 'static,
+        C: FnOnce(&mut DocumentStart) -> HandlerResult + Send + 'static,
+    {
+        impl_async_handler!(self.start(handler: DocumentStart))
+    }
+
+    /// Sets an async handler for the document end, which is called after the last chunk
+    /// is processed.
+    ///
+    /// Requires the `async_handlers` feature.
+    #[inline]
+    pub fn end_async<F, C>(self, handler: impl FnMut(&mut DocumentEnd) -> F + Send + 'h) -> Self
+    where
+        F: Future<Output = Result<C, HandlerError>> + Send + 'static,
+        C: FnOnce(&mut DocumentEnd) -> HandlerResult + Send + 'static,
+    {
+        impl_async_handler!(self.end(handler: DocumentEnd))
+    }
+}
+
+/// Drives a rewriter that runs on a dedicated worker thread, so it can be suspended
+/// while async content handlers are awaited.
+pub(super) struct WorkerDriver {
+    commands: Sender<WorkerCommand>,
+    events: UnboundedReceiver<WorkerEvent>,
+    resolved: Sender<Box<dyn Any + Send>>,
+    awaited: Option<AwaitedFuture>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl WorkerDriver {
+    pub fn new(settings: Settings<'static, 'static>) -> Self {
+        let (commands, commands_rx) = channel();
+        let (events_tx, events) = unbounded();
+        let (resolved, resolved_rx) = channel();
+
+        let worker = thread::spawn(move || {
+            HANDLER_BRIDGE.with(|bridge| {
+                *bridge.borrow_mut() = Some(HandlerBridge {
+                    events: events_tx.clone(),
+                    resolved: resolved_rx,
+                });
+            });
+
+            let output = Shared::new(Vec::default());
+            let sink = SharedOutputBuffer(Shared::clone(&output));
+            let mut rewriter = Some(HtmlRewriter::new(settings, sink));
+
+            for command in commands_rx {
+                let res = match (command, rewriter.as_mut()) {
+                    (WorkerCommand::Write(chunk), Some(rewriter)) => rewriter.write(&chunk),
+                    (WorkerCommand::End, Some(_)) => rewriter.take().unwrap().end(),
+                    _ => break,
+                };
+
+                let done = res.is_err() || rewriter.is_none();
+                let res = res.map(|_| mem::take(&mut *output.borrow_mut()));
+
+                if events_tx.unbounded_send(WorkerEvent::Done(res)).is_err() || done {
+                    break;
+                }
+            }
+        });
+
+        WorkerDriver {
+            commands,
+            events,
+            resolved,
+            awaited: None,
+            worker: Some(worker),
+        }
+    }
+
+    #[inline]
+    pub fn start_write(&mut self, chunk: &[u8]) {
+        // NOTE: if the worker has already exited the following poll of the
+        // events receiver will report it, so we can ignore the error here.
+        let _ = self.commands.send(WorkerCommand::Write(chunk.to_vec()));
+    }
+
+    #[inline]
+    pub fn start_end(&mut self) {
+        let _ = self.commands.send(WorkerCommand::End);
+    }
+
+    pub fn poll_result(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<Vec<u8>>> {
+        loop {
+            if let Some(future) = self.awaited.as_mut() {
+                let output = ready!(future.as_mut().poll(cx));
+
+                self.awaited = None;
+
+                // NOTE: the worker can't exit while it waits for the future's output.
+                let _ = self.resolved.send(output);
+            }
+
+            match ready!(Pin::new(&mut self.events).poll_next(cx)) {
+                Some(WorkerEvent::Await(future)) => self.awaited = Some(future),
+                Some(WorkerEvent::Done(res)) => return Poll::Ready(res.map_err(to_io_error)),
+                None => return Poll::Ready(Err(self.worker_exit_error())),
+            }
+        }
+    }
+
+    // NOTE: the worker closes the events channel only on exit, so joining it doesn't block
+    // for long. If the worker has panicked (e.g. in a content handler), the panic is resumed
+    // in the caller instead of being reported as a closed rewriter.
+    fn worker_exit_error(&mut self) -> io::Error {
+        if let Some(Err(panic)) = self.worker.take().map(JoinHandle::join) {
+            panic::resume_unwind(panic);
+        }
+
+        closed_error()
+    }
+}
+
+/// A convenience macro to construct an async rewriting handler for elements that can be matched
+/// by the specified CSS selector.
+///
+/// The handler returns a future that resolves to a continuation which is called with the element
+/// once the future is complete. The rewriting is suspended until then.
+///
+/// Requires the `async_handlers` feature.
+///
+/// # Example
+/// ```
+/// use futures::executor::block_on;
+/// use futures::stream::{self, TryStreamExt};
+/// use lol_html::html_content::Element;
+/// use lol_html::{async_element, RewritingStream, Settings};
+///
+/// async fn is_allowed(href: &str) -> bool {
+///     href.starts_with("https:")
+/// }
+///
+/// let input = stream::iter(vec![r#"<a href="http://foo.com"></a><a href="https://bar.com"></a>"#]);
+///
+/// let output = RewritingStream::with_async_handlers(
+///     Settings {
+///         element_content_handlers: vec![async_element!("a[href]", |el| {
+///             let href = el.get_attribute("href").unwrap();
+///
+///             async move {
+///                 let allowed = is_allowed(&href).await;
+///
+///                 Ok(move |el: &mut Element| {
+///                     if !allowed {
+///                         el.remove_attribute("href");
+///                     }
+///
+///                     Ok(())
+///                 })
+///             }
+///         })],
+///         ..Settings::default()
+///     },
+///     input,
+/// );
+///
+/// let chunks: Vec<Vec<u8>> = block_on(output.try_collect()).unwrap();
+///
+/// assert_eq!(
+///     String::from_utf8(chunks.concat()).unwrap(),
+///     r#"<a></a><a href="https://bar.com"></a>"#
+/// );
+/// ```
+#[macro_export(local_inner_macros)]
+macro_rules! async_element {
+    ($selector:expr, $handler:expr) => {
+        __element_content_handler!($selector, element_async, $handler);
+    };
+}
+
+/// A convenience macro to construct an async rewriting handler for text chunks in the inner
+/// content of an element that can be matched by the specified CSS selector.
+///
+/// Requires the `async_handlers` feature.
+#[macro_export(local_inner_macros)]
+macro_rules! async_text {
+    ($selector:expr, $handler:expr) => {
+        __element_content_handler!($selector, text_async, $handler);
+    };
+}
+
+/// A convenience macro to construct an async rewriting handler for HTML comments in the inner
+/// content of an element that can be matched by the specified CSS selector.
+///
+/// Requires the `async_handlers` feature.
+#[macro_export(local_inner_macros)]
+macro_rules! async_comments {
+    ($selector:expr, $handler:expr) => {
+        __element_content_handler!($selector, comments_async, $handler);
+    };
+}
+
+/// A convenience macro to construct an async handler for document type declarations
+/// in the HTML document.
+///
+/// Requires the `async_handlers` feature.
+#[macro_export(local_inner_macros)]
+macro_rules! async_doctype {
+    ($handler:expr) => {
+        __document_content_handler!(doctype_async, $handler);
+    };
+}
+
+/// A convenience macro to construct an async rewriting handler for all text chunks in
+/// the HTML document.
+///
+/// Requires the `async_handlers` feature.
+#[macro_export(local_inner_macros)]
+macro_rules! async_doc_text {
+    ($handler:expr) => {
+        __document_content_handler!(text_async, $handler);
+    };
+}
+
+/// A convenience macro to construct an async rewriting handler for all HTML comments in
+/// the HTML document.
+///
+/// Requires the `async_handlers` feature.
+#[macro_export(local_inner_macros)]
+macro_rules! async_doc_comments {
+    ($handler:expr) => {
+        __document_content_handler!(comments_async, $handler);
+    };
+}
+
+/// A convenience macro to construct an async rewriting handler for the start of the document.
+///
+/// Requires the `async_handlers` feature.
+#[macro_export(local_inner_macros)]
+macro_rules! async_start {
+    ($handler:expr) => {
+        __document_content_handler!(start_async, $handler);
+    };
+}
+
+/// A convenience macro to construct an async rewriting handler for the end of the document.
+///
+/// Requires the `async_handlers` feature.
+#[macro_export(local_inner_macros)]
+macro_rules! async_end {
+    ($handler:expr) => {
+        __document_content_handler!(end_async, $handler);
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::html_content::ContentType;
+    use crate::{rewrite_str, AsyncHtmlRewriter, RewriteStrSettings, RewritingStream};
+    use futures::channel::oneshot;
+    use futures::executor::block_on;
+    use futures::io::AsyncWriteExt;
+    use futures::poll;
+    use futures::stream::{self, TryStreamExt};
+    use std::time::Duration;
+
+    fn delayed<T: Send + 'static>(value: T) -> oneshot::Receiver<T> {
+        let (tx, rx) = oneshot::channel();
+
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            let _ = tx.send(value);
+        });
+
+        rx
+    }
+
+    fn create_settings() -> Settings<'static, 'static> {
+        Settings {
+            element_content_handlers: vec![
+                async_element!("span", |el| {
+                    let id = el.get_attribute("id").unwrap();
+
+                    async move {
+                        let id = delayed(id.to_uppercase()).await?;
+
+                        Ok(move |el: &mut Element| {
+                            el.set_attribute("id", &id)?;
+
+                            Ok(())
+                        })
+                    }
+                }),
+                async_text!("span", |t| {
+                    let last = t.last_in_text_node();
+
+                    async move {
+                        delayed(()).await?;
+
+                        Ok(move |t: &mut TextChunk| {
+                            if last {
+                                t.after("!", ContentType::Text);
+                            }
+
+                            Ok(())
+                        })
+                    }
+                }),
+            ],
+            document_content_handlers: vec![
+                async_start!(|_| async move {
+                    let header = delayed("<header></header>").await?;
+
+                    Ok(move |start: &mut DocumentStart| {
+                        start.prepend(header, ContentType::Html);
+
+                        Ok(())
+                    })
+                }),
+                async_end!(|_| async move {
+                    let footer = delayed("<footer></footer>").await?;
+
+                    Ok(move |end: &mut DocumentEnd| {
+                        end.append(footer, ContentType::Html);
+
+                        Ok(())
+                    })
+                }),
+            ],
+            ..Settings::default()
+        }
+    }
+
+    fn create_erroring_settings() -> Settings<'static, 'static> {
+        Settings {
+            element_content_handlers: vec![async_element!("span", |_| async move {
+                delayed(()).await?;
+
+                Err::<fn(&mut Element) -> HandlerResult, _>("Error".into())
+            })],
+            ..Settings::default()
+        }
+    }
+
+    #[test]
+    fn async_handlers_with_rewriting_stream() {
+        let input = stream::iter(vec![r#"<div><span id="f"#, r#"oo">Hi</span"#, "></div>"]);
+        let output = RewritingStream::with_async_handlers(create_settings(), input);
+        let chunks: Vec<_> = block_on(output.try_collect()).unwrap();
+
+        assert_eq!(
+            String::from_utf8(chunks.concat()).unwrap(),
+            r#"<header></header><div><span id="FOO">Hi!</span></div><footer></footer>"#
+        );
+    }
+
+    #[test]
+    fn async_handlers_with_async_rewriter() {
+        let mut output = vec![];
+
+        block_on(async {
+            let mut rewriter =
+                AsyncHtmlRewriter::with_async_handlers(create_settings(), &mut output);
+
+            for chunk in &[r#"<div><span id="f"#, r#"oo">Hi</span"#, "></div>"] {
+                rewriter.write_all(chunk.as_bytes()).await?;
+            }
+
+            rewriter.close().await
+        })
+        .unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            r#"<header></header><div><span id="FOO">Hi!</span></div><footer></footer>"#
+        );
+    }
+
+    #[test]
+    fn async_rewriter_dropped_pending_write() {
+        let (resolve, resolved) = oneshot::channel();
+        let mut resolved = Some(resolved);
+        let mut output = vec![];
+
+        block_on(async {
+            let mut rewriter = AsyncHtmlRewriter::with_async_handlers(
+                Settings {
+                    element_content_handlers: vec![async_element!("span", move |_| {
+                        let resolved = resolved.take().unwrap();
+
+                        async move {
+                            resolved.await?;
+
+                            Ok(|el: &mut Element| {
+                                el.set_attribute("id", "foo")?;
+
+                                Ok(())
+                            })
+                        }
+                    })],
+                    ..Settings::default()
+                },
+                &mut output,
+            );
+
+            rewriter.write_all(b"<div><span>").await?;
+
+            // NOTE: the write is suspended until the previous chunk is rewritten, so the
+            // dropped chunk should never reach the rewriter.
+            assert!(poll!(rewriter.write(b"dropped")).is_pending());
+
+            resolve.send(()).unwrap();
+
+            rewriter.write_all(b"Hi</span></div>").await?;
+            rewriter.close().await
+        })
+        .unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            r#"<div><span id="foo">Hi</span></div>"#
+        );
+    }
+
+    #[test]
+    fn async_handler_error_propagation() {
+        let input = stream::iter(vec!["<div><span></span></div>"]);
+        let output = RewritingStream::with_async_handlers(create_erroring_settings(), input);
+        let err = block_on(output.try_collect::<Vec<_>>()).unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::Other);
+        assert_eq!(err.to_string(), "Error");
+    }
+
+    #[test]
+    fn async_handler_panic_propagation() {
+        let input = stream::iter(vec!["<div><span></span></div>"]);
+
+        let output = RewritingStream::with_async_handlers(
+            Settings {
+                element_content_handlers: vec![async_element!("span", |_| async move {
+                    delayed(()).await?;
+
+                    Ok(|_: &mut Element| -> HandlerResult { panic!("Handler panic") })
+                })],
+                ..Settings::default()
+            },
+            input,
+        );
+
+        let panic = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            block_on(output.try_collect::<Vec<_>>())
+        }))
+        .unwrap_err();
+
+        assert_eq!(panic.downcast_ref::<&str>(), Some(&"Handler panic"));
+    }
+
+    #[test]
+    fn async_handlers_without_worker() {
+        let err = rewrite_str(
+            "<div><span></span></div>",
+            RewriteStrSettings {
+                element_content_handlers: create_erroring_settings().element_content_handlers,
+                ..RewriteStrSettings::default()
+            },
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "Async content handlers can be used only with rewriters constructed with \
+             `with_async_handlers`."
+        );
+    }
+}
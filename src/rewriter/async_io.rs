@@ -0,0 +1,542 @@
+#[cfg(feature = "async_handlers")]
+use super::async_handlers::WorkerDriver;
+use super::sync_io::{closed_error, to_io_error, SharedOutputBuffer};
+use super::{HtmlRewriter, RewritingError, Settings};
+use crate::base::Shared;
+use futures_core::{ready, Stream};
+use futures_io::AsyncWrite;
+use std::io;
+use std::mem;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+struct InlineDriver<'h> {
+    rewriter: Option<HtmlRewriter<'h, SharedOutputBuffer>>,
+    output: Shared<Vec<u8>>,
+    result: Option<io::Result<Vec<u8>>>,
+}
+
+impl<'h> InlineDriver<'h> {
+    fn new<'s>(settings: Settings<'h, 's>) -> Self {
+        let output = Shared::new(Vec::default());
+        let rewriter = HtmlRewriter::new(settings, SharedOutputBuffer(Shared::clone(&output)));
+
+        InlineDriver {
+            rewriter: Some(rewriter),
+            output,
+            result: None,
+        }
+    }
+
+    fn complete(&mut self, res: Result<(), RewritingError>) {
+        self.result = Some(match res {
+            Ok(()) => Ok(mem::take(&mut *self.output.borrow_mut())),
+            Err(err) => {
+                self.rewriter = None;
+
+                Err(to_io_error(err))
+            }
+        });
+    }
+}
+
+// NOTE: the rewriter either runs inline, or, if async content handlers are used, on a
+// worker thread. In both cases a chunk is submitted first and then the driver is polled
+// for the rewritten output of the chunk.
+enum Driver<'h> {
+    Inline(InlineDriver<'h>),
+    #[cfg(feature = "async_handlers")]
+    Worker(WorkerDriver),
+}
+
+impl Driver<'_> {
+    fn start_write(&mut self, chunk: &[u8]) {
+        match self {
+            Driver::Inline(driver) => match driver.rewriter.as_mut() {
+                Some(rewriter) => {
+                    let res = rewriter.write(chunk);
+
+                    driver.complete(res);
+                }
+                None => driver.result = Some(Err(closed_error())),
+            },
+            #[cfg(feature = "async_handlers")]
+            Driver::Worker(driver) => driver.start_write(chunk),
+        }
+    }
+
+    fn start_end(&mut self) {
+        match self {
+            Driver::Inline(driver) => match driver.rewriter.take() {
+                Some(rewriter) => driver.complete(rewriter.end()),
+                None => driver.result = Some(Err(closed_error())),
+            },
+            #[cfg(feature = "async_handlers")]
+            Driver::Worker(driver) => driver.start_end(),
+        }
+    }
+
+    fn poll_result(&mut self, _cx: &mut Context<'_>) -> Poll<io::Result<Vec<u8>>> {
+        match self {
+            Driver::Inline(driver) => Poll::Ready(
+                driver
+                    .result
+                    .take()
+                    .expect("Result should be available for the submitted chunk"),
+            ),
+            #[cfg(feature = "async_handlers")]
+            Driver::Worker(driver) => driver.poll_result(_cx),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum State {
+    Idle,
+    Writing,
+    Ending,
+    Finished,
+}
+
+/// A [`Stream`] of rewritten chunks produced from the wrapped input [`Stream`].
+///
+/// Input chunks are pulled only when the rewritten stream is polled, so the rewriter never runs
+/// ahead of the consumer. [`RewritingError`]s are yielded as [`io::Error`]s with
+/// [`io::ErrorKind::Other`], after which the stream terminates.
+///
+/// Requires the `async` feature.
+///
+/// # Example
+/// ```
+/// use futures::executor::block_on;
+/// use futures::stream::{self, TryStreamExt};
+/// use lol_html::{element, RewritingStream, Settings};
+///
+/// let input = stream::iter(vec!["<div><a href=", "http://example.com>", "</a></div>"]);
+///
+/// let output = RewritingStream::new(
+///     Settings {
+///         element_content_handlers: vec![element!("a[href]", |el| {
+///             let href = el.get_attribute("href").unwrap().replace("http:", "https:");
+///
+///             el.set_attribute("href", &href)?;
+///
+///             Ok(())
+///         })],
+///         ..Settings::default()
+///     },
+///     input,
+/// );
+///
+/// let chunks: Vec<Vec<u8>> = block_on(output.try_collect()).unwrap();
+///
+/// assert_eq!(
+///     String::from_utf8(chunks.concat()).unwrap(),
+///     r#"<div><a href="https://example.com"></a></div>"#
+/// );
+/// ```
+///
+/// [`Stream`]: https://docs.rs/futures-core/0.3/futures_core/stream/trait.Stream.html
+/// [`RewritingError`]: errors/enum.RewritingError.html
+/// [`io::Error`]: https://doc.rust-lang.org/std/io/struct.Error.html
+/// [`io::ErrorKind::Other`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.Other
+pub struct RewritingStream<'h, S> {
+    input: S,
+    driver: Driver<'h>,
+    state: State,
+}
+
+impl<'h, S, B> RewritingStream<'h, S>
+where
+    S: Stream<Item = B> + Unpin,
+    B: AsRef<[u8]>,
+{
+    /// Constructs a new rewriting stream with the provided `settings` that rewrites
+    /// chunks of the `input` stream.
+    ///
+    /// # Note
+    ///
+    /// The `input` stream should be [`Unpin`], [`Box::pin`] can be used for streams
+    /// that are not.
+    ///
+    /// [`Unpin`]: https://doc.rust-lang.org/std/marker/trait.Unpin.html
+    /// [`Box::pin`]: https://doc.rust-lang.org/std/boxed/struct.Box.html#method.pin
+    pub fn new<'s>(settings: Settings<'h, 's>, input: S) -> Self {
+        RewritingStream {
+            input,
+            driver: Driver::Inline(InlineDriver::new(settings)),
+            state: State::Idle,
+        }
+    }
+}
+
+#[cfg(feature = "async_handlers")]
+impl<S, B> RewritingStream<'static, S>
+where
+    S: Stream<Item = B> + Unpin,
+    B: AsRef<[u8]>,
+{
+    /// Constructs a new rewriting stream that supports async content handlers.
+    ///
+    /// The rewriter runs on a dedicated OS thread that is spawned for each rewriter and blocks
+    /// while the futures returned by async content handlers are polled by the stream. Hence,
+    /// the settings should be `'static` and a thread per concurrently processed document
+    /// should be accounted for. A panic in a content handler is resumed in the task that
+    /// polls the stream.
+    ///
+    /// Requires the `async_handlers` feature.
+    pub fn with_async_handlers(settings: Settings<'static, 'static>, input: S) -> Self {
+        RewritingStream {
+            input,
+            driver: Driver::Worker(WorkerDriver::new(settings)),
+            state: State::Idle,
+        }
+    }
+}
+
+impl<'h, S, B> Stream for RewritingStream<'h, S>
+where
+    S: Stream<Item = B> + Unpin,
+    B: AsRef<[u8]>,
+{
+    type Item = io::Result<Vec<u8>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        loop {
+            match this.state {
+                State::Idle => match ready!(Pin::new(&mut this.input).poll_next(cx)) {
+                    Some(chunk) => {
+                        this.driver.start_write(chunk.as_ref());
+                        this.state = State::Writing;
+                    }
+                    None => {
+                        this.driver.start_end();
+                        this.state = State::Ending;
+                    }
+                },
+                State::Writing | State::Ending => {
+                    let res = ready!(this.driver.poll_result(cx));
+
+                    this.state = match (this.state, &res) {
+                        (State::Writing, Ok(_)) => State::Idle,
+                        _ => State::Finished,
+                    };
+
+                    match res {
+                        Ok(output) if output.is_empty() => (),
+                        res => return Poll::Ready(Some(res)),
+                    }
+                }
+                State::Finished => return Poll::Ready(None),
+            }
+        }
+    }
+}
+
+/// An [`AsyncWrite`] rewriter that forwards the rewritten output to the wrapped [`AsyncWrite`].
+///
+/// The rewritten output of each written chunk is forwarded to the inner writer before the next
+/// chunk is accepted, so writes are suspended while the inner writer is not ready. A write that
+/// is suspended doesn't consume any data, and an accepted chunk is copied into the rewriter.
+/// The rewriting is finalized on [`poll_close`]. [`RewritingError`]s are returned as [`io::Error`]s with
+/// [`io::ErrorKind::Other`], after which the rewriter can't be used anymore.
+///
+/// Requires the `async` feature.
+///
+/// # Example
+/// ```
+/// use futures::executor::block_on;
+/// use futures::io::AsyncWriteExt;
+/// use lol_html::html_content::ContentType;
+/// use lol_html::{element, AsyncHtmlRewriter, Settings};
+///
+/// let mut output = vec![];
+///
+/// block_on(async {
+///     let mut rewriter = AsyncHtmlRewriter::new(
+///         Settings {
+///             element_content_handlers: vec![element!("div", |el| {
+///                 el.append("world", ContentType::Text);
+///
+///                 Ok(())
+///             })],
+///             ..Settings::default()
+///         },
+///         &mut output,
+///     );
+///
+///     rewriter.write_all(b"<div>Hello ").await?;
+///     rewriter.write_all(b"</div>").await?;
+///     rewriter.close().await
+/// })
+/// .unwrap();
+///
+/// assert_eq!(String::from_utf8(output).unwrap(), "<div>Hello world</div>");
+/// ```
+///
+/// [`AsyncWrite`]: https://docs.rs/futures-io/0.3/futures_io/trait.AsyncWrite.html
+/// [`poll_close`]: https://docs.rs/futures-io/0.3/futures_io/trait.AsyncWrite.html#tymethod.poll_close
+/// [`RewritingError`]: errors/enum.RewritingError.html
+/// [`io::Error`]: https://doc.rust-lang.org/std/io/struct.Error.html
+/// [`io::ErrorKind::Other`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.Other
+pub struct AsyncHtmlRewriter<'h, W> {
+    inner: W,
+    driver: Driver<'h>,
+    state: State,
+    pending: Vec<u8>,
+    pending_pos: usize,
+}
+
+impl<'h, W: AsyncWrite + Unpin> AsyncHtmlRewriter<'h, W> {
+    /// Constructs a new rewriter with the provided `settings` that writes
+    /// the output to the `inner` writer.
+    pub fn new<'s>(settings: Settings<'h, 's>, inner: W) -> Self {
+        Self::with_driver(Driver::Inline(InlineDriver::new(settings)), inner)
+    }
+
+    fn with_driver(driver: Driver<'h>, inner: W) -> Self {
+        AsyncHtmlRewriter {
+            inner,
+            driver,
+            state: State::Idle,
+            pending: Vec::default(),
+            pending_pos: 0,
+        }
+    }
+
+    /// Consumes the rewriter, returning the inner writer.
+    #[inline]
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    fn poll_write_pending(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while self.pending_pos < self.pending.len() {
+            let chunk = &self.pending[self.pending_pos..];
+
+            match ready!(Pin::new(&mut self.inner).poll_write(cx, chunk))? {
+                0 => return Poll::Ready(Err(io::ErrorKind::WriteZero.into())),
+                len => self.pending_pos += len,
+            }
+        }
+
+        self.pending.clear();
+        self.pending_pos = 0;
+
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_rewriting_result(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let res = ready!(self.driver.poll_result(cx));
+
+        self.state = match (self.state, &res) {
+            (State::Writing, Ok(_)) => State::Idle,
+            _ => State::Finished,
+        };
+
+        Poll::Ready(res.map(|output| self.pending = output))
+    }
+
+    fn poll_complete_write(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        ready!(self.poll_write_pending(cx))?;
+
+        if self.state == State::Writing {
+            ready!(self.poll_rewriting_result(cx))?;
+            ready!(self.poll_write_pending(cx))?;
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(feature = "async_handlers")]
+impl<W: AsyncWrite + Unpin> AsyncHtmlRewriter<'static, W> {
+    /// Constructs a new rewriter that supports async content handlers.
+    ///
+    /// The rewriter runs on a dedicated OS thread that is spawned for each rewriter and blocks
+    /// while the futures returned by async content handlers are polled by the writer. Hence,
+    /// the settings should be `'static` and a thread per concurrently processed document
+    /// should be accounted for. A panic in a content handler is resumed in the task that
+    /// polls the writer.
+    ///
+    /// Requires the `async_handlers` feature.
+    pub fn with_async_handlers(settings: Settings<'static, 'static>, inner: W) -> Self {
+        Self::with_driver(Driver::Worker(WorkerDriver::new(settings)), inner)
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for AsyncHtmlRewriter<'_, W> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        ready!(self.poll_complete_write(cx))?;
+
+        if self.state != State::Idle {
+            return Poll::Ready(Err(closed_error()));
+        }
+
+        self.driver.start_write(buf);
+        self.state = State::Writing;
+
+        // NOTE: the chunk is accepted at this point, as the caller is not required to retry
+        // the write with the same data. The rewriting and the output forwarding are completed
+        // right away if possible, otherwise by the next calls.
+        if let Poll::Ready(res) = self.poll_complete_write(cx) {
+            res?;
+        }
+
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        ready!(self.poll_complete_write(cx))?;
+
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        ready!(self.poll_complete_write(cx))?;
+
+        if self.state == State::Idle {
+            self.driver.start_end();
+            self.state = State::Ending;
+        }
+
+        if self.state == State::Ending {
+            ready!(self.poll_rewriting_result(cx))?;
+            ready!(self.poll_write_pending(cx))?;
+        }
+
+        Pin::new(&mut self.inner).poll_close(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::html_content::ContentType;
+    use crate::{element, text};
+    use futures::executor::block_on;
+    use futures::io::AsyncWriteExt;
+    use futures::stream::{self, StreamExt, TryStreamExt};
+
+    fn create_settings<'h>() -> Settings<'h, 'static> {
+        Settings {
+            element_content_handlers: vec![
+                element!("div", |el| {
+                    el.set_attribute("foo", "bar")?;
+
+                    Ok(())
+                }),
+                text!("span", |t| {
+                    if t.last_in_text_node() {
+                        t.after("!", ContentType::Text);
+                    }
+
+                    Ok(())
+                }),
+            ],
+            ..Settings::default()
+        }
+    }
+
+    fn create_erroring_settings<'h>() -> Settings<'h, 'static> {
+        Settings {
+            element_content_handlers: vec![element!("span", |_| Err("Error".into()))],
+            ..Settings::default()
+        }
+    }
+
+    #[test]
+    fn rewriting_stream() {
+        let input = stream::iter(vec!["<div><sp", "an>Hey", "</span></", "div>"]);
+        let chunks: Vec<_> =
+            block_on(RewritingStream::new(create_settings(), input).try_collect()).unwrap();
+
+        assert_eq!(
+            String::from_utf8(chunks.concat()).unwrap(),
+            r#"<div foo="bar"><span>Hey!</span></div>"#
+        );
+    }
+
+    #[test]
+    fn rewriting_stream_pulls_input_lazily() {
+        let mut pulled = 0;
+
+        let input = stream::iter(vec!["<div>", "<span>", "</span>", "</div>"]).inspect(|_| {
+            pulled += 1;
+        });
+
+        let mut output = RewritingStream::new(create_settings(), input);
+
+        assert_eq!(
+            block_on(output.next()).unwrap().unwrap(),
+            br#"<div foo="bar">"#.to_vec()
+        );
+
+        drop(output);
+
+        assert_eq!(pulled, 1);
+    }
+
+    #[test]
+    fn rewriting_stream_error() {
+        let input = stream::iter(vec!["<div>", "<span>", "</span></div>"]);
+        let mut output = RewritingStream::new(create_erroring_settings(), input);
+
+        assert_eq!(block_on(output.next()).unwrap().unwrap(), b"<div>".to_vec());
+
+        let err = block_on(output.next()).unwrap().unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::Other);
+        assert_eq!(err.to_string(), "Error");
+        assert!(block_on(output.next()).is_none());
+    }
+
+    #[test]
+    fn async_rewriter() {
+        let mut output = vec![];
+
+        block_on(async {
+            let mut rewriter = AsyncHtmlRewriter::new(create_settings(), &mut output);
+
+            for chunk in &["<div><sp", "an>Hey", "</span></", "div>"] {
+                rewriter.write_all(chunk.as_bytes()).await?;
+            }
+
+            rewriter.close().await
+        })
+        .unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            r#"<div foo="bar"><span>Hey!</span></div>"#
+        );
+    }
+
+    #[test]
+    fn async_rewriter_error() {
+        let mut output = vec![];
+
+        block_on(async {
+            let mut rewriter = AsyncHtmlRewriter::new(create_erroring_settings(), &mut output);
+
+            rewriter.write_all(b"<div>").await.unwrap();
+
+            let err = rewriter.write_all(b"<span>").await.unwrap_err();
+
+            assert_eq!(err.kind(), io::ErrorKind::Other);
+            assert_eq!(err.to_string(), "Error");
+
+            let err = rewriter.write_all(b"</span>").await.unwrap_err();
+
+            assert_eq!(err.to_string(), "The rewriter has been closed.");
+        });
+
+        assert_eq!(String::from_utf8(output).unwrap(), "<div>");
+    }
+}
@@ -0,0 +1,292 @@
+use super::AsciiCompatibleEncoding;
+use crate::selectors_vm::{Ast, Compiler, Program, Selector};
+use std::fmt::{self, Debug};
+use std::sync::Arc;
+
+/// A set of CSS selectors compiled into a program for the rewriter's selector matching engine.
+///
+/// Parsing and compilation of selectors can be costly for large selector sets. `CompiledSelectors`
+/// allows to do it once and then cheaply instantiate any number of rewriters from the result
+/// with [`HtmlRewriter::with_compiled_selectors`], binding content handlers to selectors by their
+/// index in the list the `CompiledSelectors` were built from.
+///
+/// `CompiledSelectors` are immutable, [`Send`] and [`Sync`]. Cloning them is cheap, as the
+/// compiled program is reference counted, so they can be shared between threads either by
+/// cloning or by wrapping them into an [`Arc`].
+///
+/// [`HtmlRewriter::with_compiled_selectors`]: struct.HtmlRewriter.html#method.with_compiled_selectors
+/// [`Send`]: https://doc.rust-lang.org/std/marker/trait.Send.html
+/// [`Sync`]: https://doc.rust-lang.org/std/marker/trait.Sync.html
+/// [`Arc`]: https://doc.rust-lang.org/std/sync/struct.Arc.html
+///
+/// # Example
+/// ```
+/// use lol_html::{CompiledSelectors, ElementContentHandlers, HtmlRewriter, Selector, Settings};
+///
+/// let selectors: Vec<Selector> = vec!["a[href]".parse().unwrap(), "img".parse().unwrap()];
+/// let compiled_selectors = CompiledSelectors::new(&selectors, Settings::default().encoding);
+///
+/// let mut output = vec![];
+///
+/// {
+///     let mut rewriter = HtmlRewriter::with_compiled_selectors(
+///         &compiled_selectors,
+///         vec![(
+///             1,
+///             ElementContentHandlers::default().element(|el| {
+///                 el.remove();
+///
+///                 Ok(())
+///             }),
+///         )],
+///         Settings::default(),
+///         |c: &[u8]| output.extend_from_slice(c),
+///     );
+///
+///     rewriter.write(b"<a href='/'><img src='/foo.png'></a>").unwrap();
+///     rewriter.end().unwrap();
+/// }
+///
+/// assert_eq!(String::from_utf8(output).unwrap(), "<a href='/'></a>");
+/// ```
+#[derive(Clone)]
+pub struct CompiledSelectors {
+    program: Arc<Program<usize>>,
+    encoding: AsciiCompatibleEncoding,
+    // NOTE: selectors are retained, as they need to be recompiled if they contain
+    // non-ASCII characters and the rewriter detects another encoding of the input.
+    selectors: Arc<[Selector]>,
+    is_ascii: bool,
+}
+
+impl CompiledSelectors {
+    /// Compiles the `selectors` for the rewriters operating on the input in the given `encoding`.
+    ///
+    /// Each selector is identified by its index in the `selectors` sequence.
+    pub fn new<'s>(
+        selectors: impl IntoIterator<Item = &'s Selector>,
+        encoding: AsciiCompatibleEncoding,
+    ) -> Self {
+        let selectors = selectors.into_iter().cloned().collect::<Arc<[_]>>();
+
+        CompiledSelectors {
+            program: Self::compile(&selectors, encoding),
+            encoding,
+            is_ascii: selectors.iter().all(Selector::is_ascii),
+            selectors,
+        }
+    }
+
+    fn compile(selectors: &[Selector], encoding: AsciiCompatibleEncoding) -> Arc<Program<usize>> {
+        let mut ast = Ast::default();
+
+        for (idx, selector) in selectors.iter().enumerate() {
+            ast.add_selector(selector, idx);
+        }
+
+        Arc::new(Compiler::new(encoding.into()).compile(ast))
+    }
+
+    /// Returns the number of compiled selectors.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.selectors.len()
+    }
+
+    /// Returns `true` if no selectors were compiled.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.selectors.is_empty()
+    }
+
+    /// Returns the encoding the selectors were compiled for.
+    #[inline]
+    pub fn encoding(&self) -> AsciiCompatibleEncoding {
+        self.encoding
+    }
+
+    /// Returns the program for the input in the given `encoding`, recompiling
+    /// the selectors if the compiled program can't be used for it.
+    pub(super) fn program_for(&self, encoding: AsciiCompatibleEncoding) -> Arc<Program<usize>> {
+        if self.is_ascii || encoding == self.encoding {
+            Arc::clone(&self.program)
+        } else {
+            Self::compile(&self.selectors, encoding)
+        }
+    }
+
+    /// Returns new `CompiledSelectors` with the `selectors` appended to the existing ones.
+    pub(super) fn extend(&self, selectors: &[Selector]) -> Self {
+        Self::new(self.selectors.iter().chain(selectors), self.encoding)
+    }
+}
+
+impl Debug for CompiledSelectors {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CompiledSelectors")
+            .field("len", &self.len())
+            .field("encoding", &self.encoding)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rewriter::{ElementContentHandlers, HtmlRewriter, Settings};
+    use std::thread;
+
+    fn compile(selectors: &[&str]) -> CompiledSelectors {
+        let selectors = selectors
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect::<Vec<Selector>>();
+
+        CompiledSelectors::new(&selectors, Settings::default().encoding)
+    }
+
+    fn rewrite(
+        compiled_selectors: &CompiledSelectors,
+        element_content_handlers: Vec<(usize, ElementContentHandlers)>,
+        html: &str,
+    ) -> String {
+        let mut output = vec![];
+
+        {
+            let mut rewriter = HtmlRewriter::with_compiled_selectors(
+                compiled_selectors,
+                element_content_handlers,
+                Settings::default(),
+                |c: &[u8]| output.extend_from_slice(c),
+            );
+
+            rewriter.write(html.as_bytes()).unwrap();
+            rewriter.end().unwrap();
+        }
+
+        String::from_utf8(output).unwrap()
+    }
+
+    fn set_tag_name(name: &'static str) -> ElementContentHandlers<'static> {
+        ElementContentHandlers::default().element(move |el| {
+            el.set_tag_name(name).unwrap();
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn handlers_bound_by_selector_index() {
+        let compiled_selectors = compile(&["div", "span", "p.foo"]);
+
+        assert_eq!(compiled_selectors.len(), 3);
+
+        let output = rewrite(
+            &compiled_selectors,
+            vec![(2, set_tag_name("h1")), (0, set_tag_name("section"))],
+            "<div><span></span><p class=foo></p></div>",
+        );
+
+        assert_eq!(
+            output,
+            "<section><span></span><h1 class=foo></h1></section>"
+        );
+
+        let output = rewrite(
+            &compiled_selectors,
+            vec![(1, set_tag_name("b"))],
+            "<div><span></span><p class=foo></p></div>",
+        );
+
+        assert_eq!(output, "<div><b></b><p class=foo></p></div>");
+    }
+
+    #[test]
+    fn no_bound_handlers() {
+        let compiled_selectors = compile(&["div"]);
+        let output = rewrite(&compiled_selectors, vec![], "<div>Hi</div>");
+
+        assert_eq!(output, "<div>Hi</div>");
+    }
+
+    #[test]
+    fn shared_between_threads() {
+        let compiled_selectors = Arc::new(compile(&["div", "span"]));
+
+        let threads = (0..4)
+            .map(|_| {
+                let compiled_selectors = Arc::clone(&compiled_selectors);
+
+                thread::spawn(move || {
+                    rewrite(
+                        &compiled_selectors,
+                        vec![(1, set_tag_name("b"))],
+                        "<div><span>Hi</span></div>",
+                    )
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for t in threads {
+            assert_eq!(t.join().unwrap(), "<div><b>Hi</b></div>");
+        }
+    }
+
+    #[test]
+    fn meta_charset_rewriting() {
+        let compiled_selectors = compile(&["meta", "div"]);
+        let mut output = vec![];
+
+        {
+            let mut rewriter = HtmlRewriter::with_compiled_selectors(
+                &compiled_selectors,
+                vec![(1, set_tag_name("p"))],
+                Settings {
+                    output_encoding: Some(encoding_rs::UTF_16LE),
+                    rewrite_meta_charset: true,
+                    ..Settings::default()
+                },
+                |c: &[u8]| output.extend_from_slice(c),
+            );
+
+            rewriter.write(b"<meta charset=utf-8><div></div>").unwrap();
+            rewriter.end().unwrap();
+        }
+
+        assert_eq!(
+            encoding_rs::UTF_16LE.decode_without_bom_handling(&output).0,
+            r#"<meta charset="UTF-16LE"><p></p>"#
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Selector index 1 is out of bounds")]
+    fn out_of_bounds_selector_index() {
+        rewrite(&compile(&["div"]), vec![(1, set_tag_name("b"))], "");
+    }
+
+    #[test]
+    #[should_panic(expected = "Handlers are already bound to selector 0")]
+    fn handlers_bound_twice() {
+        rewrite(
+            &compile(&["div"]),
+            vec![(0, set_tag_name("b")), (0, set_tag_name("i"))],
+            "",
+        );
+    }
+
+    #[test]
+    fn encoding_mismatch() {
+        let selectors = vec![r#"[title="привет"]"#.parse().unwrap()];
+        let encoding = AsciiCompatibleEncoding::new(encoding_rs::WINDOWS_1251).unwrap();
+        let compiled_selectors = CompiledSelectors::new(&selectors, encoding);
+
+        let output = rewrite(
+            &compiled_selectors,
+            vec![(0, set_tag_name("b"))],
+            r#"<div title="привет"></div>"#,
+        );
+
+        assert_eq!(output, r#"<b title="привет"></b>"#);
+    }
+}
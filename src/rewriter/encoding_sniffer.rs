@@ -0,0 +1,440 @@
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252, X_USER_DEFINED};
+use std::collections::HashSet;
+
+// NOTE: the spec allows implementations to scan more, but recommends the prescan
+// to look at the first 1024 bytes of the input.
+const PRESCAN_LIMIT: usize = 1024;
+
+// NOTE: the longest pattern the prescan checks is `<meta` followed by a separator.
+const MAX_PATTERN_LEN: usize = 6;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SniffingResult {
+    /// The encoding is determined by the BOM or by a `<meta>` tag.
+    Detected(&'static Encoding),
+    /// The input doesn't declare its encoding.
+    NotDetected,
+    /// More input is required to determine the encoding.
+    Incomplete,
+}
+
+// NOTE: signals that the prescan ran out of the available input.
+struct EndOfInput;
+
+type PrescanResult<T> = Result<T, EndOfInput>;
+
+static BOMS: [(&[u8], &Encoding); 3] = [
+    (b"\xEF\xBB\xBF", UTF_8),
+    (b"\xFE\xFF", UTF_16BE),
+    (b"\xFF\xFE", UTF_16LE),
+];
+
+#[inline]
+fn is_whitespace(b: u8) -> bool {
+    matches!(b, b'\t' | b'\n' | b'\x0C' | b'\r' | b' ')
+}
+
+// See: https://html.spec.whatwg.org/multipage/urls-and-fetching.html#algorithm-for-extracting-a-character-encoding-from-a-meta-element
+fn extract_encoding_from_meta_content(content: &[u8]) -> Option<&'static Encoding> {
+    let mut pos = 0;
+
+    loop {
+        // NOTE: the content is already lowercased at this point.
+        pos += content[pos..].windows(7).position(|w| w == b"charset")? + 7;
+
+        while content.get(pos).copied().map_or(false, is_whitespace) {
+            pos += 1;
+        }
+
+        if content.get(pos) == Some(&b'=') {
+            pos += 1;
+            break;
+        }
+    }
+
+    while content.get(pos).copied().map_or(false, is_whitespace) {
+        pos += 1;
+    }
+
+    match *content.get(pos)? {
+        quote @ b'"' | quote @ b'\'' => {
+            let value = &content[pos + 1..];
+            let end = value.iter().position(|&b| b == quote)?;
+
+            Encoding::for_label(&value[..end])
+        }
+        _ => {
+            let value = &content[pos..];
+            let end = value
+                .iter()
+                .position(|&b| is_whitespace(b) || b == b';')
+                .unwrap_or_else(|| value.len());
+
+            Encoding::for_label(&value[..end])
+        }
+    }
+}
+
+// See: https://html.spec.whatwg.org/multipage/parsing.html#prescan-a-byte-stream-to-determine-its-encoding
+struct Prescanner<'i> {
+    input: &'i [u8],
+    pos: usize,
+}
+
+impl<'i> Prescanner<'i> {
+    #[inline]
+    fn byte(&self) -> PrescanResult<u8> {
+        self.input.get(self.pos).copied().ok_or(EndOfInput)
+    }
+
+    #[inline]
+    fn next_byte(&mut self) -> PrescanResult<u8> {
+        self.pos += 1;
+        self.byte()
+    }
+
+    #[inline]
+    fn rest(&self) -> &'i [u8] {
+        &self.input[self.pos.min(self.input.len())..]
+    }
+
+    fn skip_to(&mut self, predicate: impl Fn(u8) -> bool) -> PrescanResult<()> {
+        match self.rest().iter().position(|&b| predicate(b)) {
+            Some(offset) => {
+                self.pos += offset;
+                Ok(())
+            }
+            None => Err(EndOfInput),
+        }
+    }
+
+    fn is_meta_start(&self) -> bool {
+        let rest = self.rest();
+
+        rest.len() >= MAX_PATTERN_LEN
+            && rest[..5].eq_ignore_ascii_case(b"<meta")
+            && (is_whitespace(rest[5]) || rest[5] == b'/')
+    }
+
+    fn is_tag_start(&self) -> bool {
+        let rest = self.rest();
+        let name_start = if rest.starts_with(b"</") { 2 } else { 1 };
+
+        rest.starts_with(b"<")
+            && rest
+                .get(name_start)
+                .map_or(false, |b| b.is_ascii_alphabetic())
+    }
+
+    // See: https://html.spec.whatwg.org/multipage/parsing.html#concept-get-attributes-when-sniffing
+    fn get_attribute(&mut self) -> PrescanResult<Option<(Vec<u8>, Vec<u8>)>> {
+        let mut b = self.byte()?;
+
+        while is_whitespace(b) || b == b'/' {
+            b = self.next_byte()?;
+        }
+
+        if b == b'>' {
+            return Ok(None);
+        }
+
+        let mut name = Vec::default();
+        let mut value = Vec::default();
+
+        loop {
+            match b {
+                b'=' if !name.is_empty() => {
+                    b = self.next_byte()?;
+                    break;
+                }
+                _ if is_whitespace(b) => {
+                    while is_whitespace(b) {
+                        b = self.next_byte()?;
+                    }
+
+                    if b != b'=' {
+                        return Ok(Some((name, value)));
+                    }
+
+                    b = self.next_byte()?;
+                    break;
+                }
+                b'/' | b'>' => return Ok(Some((name, value))),
+                _ => name.push(b.to_ascii_lowercase()),
+            }
+
+            b = self.next_byte()?;
+        }
+
+        while is_whitespace(b) {
+            b = self.next_byte()?;
+        }
+
+        match b {
+            quote @ b'"' | quote @ b'\'' => loop {
+                b = self.next_byte()?;
+
+                if b == quote {
+                    self.pos += 1;
+
+                    return Ok(Some((name, value)));
+                }
+
+                value.push(b.to_ascii_lowercase());
+            },
+            b'>' => return Ok(Some((name, value))),
+            _ => value.push(b.to_ascii_lowercase()),
+        }
+
+        loop {
+            b = self.next_byte()?;
+
+            if is_whitespace(b) || b == b'>' {
+                return Ok(Some((name, value)));
+            }
+
+            value.push(b.to_ascii_lowercase());
+        }
+    }
+
+    fn process_meta(&mut self) -> PrescanResult<Option<&'static Encoding>> {
+        let mut attribute_list = HashSet::new();
+        let mut got_pragma = false;
+        let mut need_pragma = None;
+        let mut charset = None;
+
+        // NOTE: skip `<meta`, so we point at the separator byte.
+        self.pos += 5;
+
+        while let Some((name, value)) = self.get_attribute()? {
+            if !attribute_list.insert(name.clone()) {
+                continue;
+            }
+
+            match &*name {
+                b"http-equiv" => got_pragma = got_pragma || value == b"content-type",
+                b"content" if charset.is_none() => {
+                    if let Some(encoding) = extract_encoding_from_meta_content(&value) {
+                        charset = Some(encoding);
+                        need_pragma = Some(true);
+                    }
+                }
+                b"charset" => {
+                    charset = Encoding::for_label(&value);
+                    need_pragma = Some(false);
+                }
+                _ => (),
+            }
+        }
+
+        let charset = match need_pragma {
+            None => None,
+            Some(true) if !got_pragma => None,
+            _ => charset,
+        };
+
+        Ok(charset.map(|encoding| {
+            if encoding == UTF_16BE || encoding == UTF_16LE {
+                UTF_8
+            } else if encoding == X_USER_DEFINED {
+                WINDOWS_1252
+            } else {
+                encoding
+            }
+        }))
+    }
+
+    fn prescan(&mut self, is_complete: bool) -> PrescanResult<Option<&'static Encoding>> {
+        loop {
+            let rest = self.rest();
+
+            // NOTE: we can't match the patterns reliably until we have enough input.
+            if rest.is_empty() || (!is_complete && rest.len() < MAX_PATTERN_LEN) {
+                return Err(EndOfInput);
+            }
+
+            if rest.starts_with(b"<!--") {
+                // NOTE: the dashes of the comment start can be a part of its end.
+                self.pos += 2;
+
+                match rest[2..].windows(3).position(|w| w == b"-->") {
+                    Some(offset) => self.pos += offset + 2,
+                    None => return Err(EndOfInput),
+                }
+            } else if self.is_meta_start() {
+                if let Some(encoding) = self.process_meta()? {
+                    return Ok(Some(encoding));
+                }
+            } else if self.is_tag_start() {
+                self.skip_to(|b| is_whitespace(b) || b == b'>')?;
+
+                while self.get_attribute()?.is_some() {}
+            } else if rest.starts_with(b"<!") || rest.starts_with(b"</") || rest.starts_with(b"<?")
+            {
+                self.skip_to(|b| b == b'>')?;
+            }
+
+            self.pos += 1;
+        }
+    }
+}
+
+/// Determines the encoding of the input by its BOM or, following the HTML prescan algorithm,
+/// by a `<meta>` tag in the first 1024 bytes of the input.
+pub fn sniff_encoding(input: &[u8], is_last: bool) -> SniffingResult {
+    let is_complete = is_last || input.len() >= PRESCAN_LIMIT;
+
+    for &(bom, encoding) in BOMS.iter() {
+        if input.starts_with(bom) {
+            return SniffingResult::Detected(encoding);
+        }
+
+        // NOTE: wait for the input that's sufficient to rule out the BOM.
+        if !is_complete && bom.starts_with(input) {
+            return SniffingResult::Incomplete;
+        }
+    }
+
+    let mut prescanner = Prescanner {
+        input: &input[..input.len().min(PRESCAN_LIMIT)],
+        pos: 0,
+    };
+
+    match prescanner.prescan(is_complete) {
+        Ok(Some(encoding)) => SniffingResult::Detected(encoding),
+        Ok(None) => SniffingResult::NotDetected,
+        Err(EndOfInput) if is_complete => SniffingResult::NotDetected,
+        Err(EndOfInput) => SniffingResult::Incomplete,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding_rs::{SHIFT_JIS, WINDOWS_1251};
+
+    fn assert_detected(input: &str, expected: &'static Encoding) {
+        assert_eq!(
+            sniff_encoding(input.as_bytes(), true),
+            SniffingResult::Detected(expected),
+            "Input: {}",
+            input
+        );
+    }
+
+    fn assert_not_detected(input: &str) {
+        assert_eq!(
+            sniff_encoding(input.as_bytes(), true),
+            SniffingResult::NotDetected,
+            "Input: {}",
+            input
+        );
+    }
+
+    #[test]
+    fn bom() {
+        assert_eq!(
+            sniff_encoding(b"\xEF\xBB\xBF<meta charset=windows-1251>", true),
+            SniffingResult::Detected(UTF_8)
+        );
+
+        assert_eq!(
+            sniff_encoding(b"\xFE\xFF\x00<", true),
+            SniffingResult::Detected(UTF_16BE)
+        );
+
+        assert_eq!(
+            sniff_encoding(b"\xFF\xFE<\x00", true),
+            SniffingResult::Detected(UTF_16LE)
+        );
+
+        assert_eq!(
+            sniff_encoding(b"\xEF\xBB", false),
+            SniffingResult::Incomplete
+        );
+    }
+
+    #[test]
+    fn meta_charset() {
+        assert_detected("<meta charset=windows-1251>", WINDOWS_1251);
+        assert_detected("<META CHARSET='Shift_JIS'>", SHIFT_JIS);
+        assert_detected(
+            r#"<html><head><meta foo charset = "cp1251"/>"#,
+            WINDOWS_1251,
+        );
+        assert_detected("<meta/charset=windows-1251>", WINDOWS_1251);
+        assert_detected("<meta charset=utf-16le>", UTF_8);
+        assert_detected("<meta charset=x-user-defined>", WINDOWS_1252);
+        assert_not_detected("<meta charset=foobar>");
+        assert_not_detected("<metacharset=windows-1251>");
+    }
+
+    #[test]
+    fn meta_http_equiv() {
+        assert_detected(
+            r#"<meta http-equiv="Content-Type" content="text/html; charset=windows-1251">"#,
+            WINDOWS_1251,
+        );
+
+        assert_detected(
+            r#"<meta content='text/html;charset="shift_jis"' http-equiv=content-type>"#,
+            SHIFT_JIS,
+        );
+
+        assert_not_detected(r#"<meta content="text/html; charset=windows-1251">"#);
+        assert_not_detected(r#"<meta http-equiv="Content-Type" content="text/html">"#);
+    }
+
+    #[test]
+    fn skipped_markup() {
+        assert_detected(
+            "<!-- <meta charset=shift_jis> --><meta charset=windows-1251>",
+            WINDOWS_1251,
+        );
+
+        assert_detected("<!--><meta charset=windows-1251>", WINDOWS_1251);
+
+        assert_detected(
+            "<div title='<meta charset=shift_jis>'><meta charset=windows-1251>",
+            WINDOWS_1251,
+        );
+
+        assert_detected(
+            "<!DOCTYPE html><?pi <meta charset=shift_jis>?></x><meta charset=windows-1251>",
+            WINDOWS_1251,
+        );
+    }
+
+    #[test]
+    fn prescan_limit() {
+        let input = format!("{}<meta charset=windows-1251>", " ".repeat(PRESCAN_LIMIT));
+
+        assert_eq!(
+            sniff_encoding(input.as_bytes(), false),
+            SniffingResult::NotDetected
+        );
+    }
+
+    #[test]
+    fn incomplete_input() {
+        assert_eq!(
+            sniff_encoding(b"<meta charset=windows", false),
+            SniffingResult::Incomplete
+        );
+
+        assert_eq!(
+            sniff_encoding(b"<meta charset=windows-1251>", false),
+            SniffingResult::Detected(WINDOWS_1251)
+        );
+
+        assert_eq!(
+            sniff_encoding(b"<!-- <meta charset=windows-1251>", false),
+            SniffingResult::Incomplete
+        );
+
+        assert_eq!(
+            sniff_encoding(b"<!-- <meta charset=windows-1251>", true),
+            SniffingResult::NotDetected
+        );
+    }
+}
@@ -1,7 +1,15 @@
 use super::settings::*;
+use super::stats::RewritingStats;
+use super::text_replacer::{text_replacing_handler, text_replacing_text_node_handler};
 use super::ElementDescriptor;
-use crate::rewritable_units::{DocumentEnd, Element, StartTag, Token, TokenCaptureFlags};
+use crate::base::Shared;
+use crate::memory::{LimitedVec, MemoryLimitExceededError, SharedMemoryLimiter};
+use crate::rewritable_units::{
+    DocumentEnd, DocumentStart, Element, SourceLocation, StartTag, TextChunk, TextNode, Token,
+    TokenCaptureFlags,
+};
 use crate::selectors_vm::MatchInfo;
+use std::error::Error;
 
 #[derive(Copy, Clone, Default, Debug, PartialEq, Eq, Hash)]
 pub struct SelectorHandlersLocator {
@@ -10,9 +18,12 @@ pub struct SelectorHandlersLocator {
     pub text_handler_idx: Option<usize>,
 }
 
+type InvocationResult = Result<usize, Box<dyn Error + Send + Sync>>;
+
 struct HandlerVecItem<H> {
     handler: H,
     user_count: usize,
+    always_active: bool,
 }
 
 struct HandlerVec<H> {
@@ -35,6 +46,7 @@ impl<H> HandlerVec<H> {
         let item = HandlerVecItem {
             handler,
             user_count: if always_active { 1 } else { 0 },
+            always_active,
         };
 
         self.user_count += item.user_count;
@@ -46,6 +58,23 @@ impl<H> HandlerVec<H> {
         self.items.len()
     }
 
+    #[inline]
+    pub fn clear(&mut self) {
+        self.items.clear();
+        self.user_count = 0;
+    }
+
+    /// Deactivates all the handlers, except the ones that are always active.
+    #[inline]
+    pub fn reset(&mut self) {
+        self.user_count = 0;
+
+        for item in self.items.iter_mut() {
+            item.user_count = if item.always_active { 1 } else { 0 };
+            self.user_count += item.user_count;
+        }
+    }
+
     #[inline]
     pub fn inc_user_count(&mut self, idx: usize) {
         self.items[idx].user_count += 1;
@@ -63,68 +92,223 @@ impl<H> HandlerVec<H> {
         self.user_count > 0
     }
 
+    // NOTE: the methods that invoke the handlers return the number of invocations.
     #[inline]
     pub fn for_each_active(
         &mut self,
         mut cb: impl FnMut(&mut H) -> HandlerResult,
-    ) -> HandlerResult {
+    ) -> InvocationResult {
+        let mut invocation_count = 0;
+
         for item in self.items.iter_mut() {
             if item.user_count > 0 {
+                invocation_count += 1;
                 cb(&mut item.handler)?;
             }
         }
 
-        Ok(())
+        Ok(invocation_count)
     }
 
     #[inline]
     pub fn do_for_each_active_and_deactivate(
         &mut self,
         mut cb: impl FnMut(&mut H) -> HandlerResult,
-    ) -> HandlerResult {
+    ) -> InvocationResult {
+        let mut invocation_count = 0;
+
         for item in self.items.iter_mut() {
             if item.user_count > 0 {
+                invocation_count += 1;
                 cb(&mut item.handler)?;
                 self.user_count -= item.user_count;
                 item.user_count = 0;
             }
         }
 
-        Ok(())
+        Ok(invocation_count)
     }
 
     #[inline]
     pub fn do_for_each_active_and_remove(
         &mut self,
         mut cb: impl FnMut(H) -> HandlerResult,
-    ) -> HandlerResult {
+    ) -> InvocationResult {
+        let mut invocation_count = 0;
+
         for i in (0..self.items.len()).rev() {
             if self.items[i].user_count > 0 {
                 let item = self.items.remove(i);
 
                 self.user_count -= item.user_count;
 
+                invocation_count += 1;
                 cb(item.handler)?;
             }
         }
 
+        Ok(invocation_count)
+    }
+}
+
+/// The text buffered by a text handler wrapper until the end of the text node.
+///
+/// NOTE: the buffer is shared with the dispatcher, so the text can be discarded if the rewriter
+/// is reset in the middle of a text node.
+pub(super) struct TextBuffer {
+    pub bytes: LimitedVec<u8>,
+    pub source_location: Option<SourceLocation>,
+}
+
+impl TextBuffer {
+    #[inline]
+    fn clear(&mut self) {
+        self.bytes.drain(..);
+        self.source_location = None;
+    }
+}
+
+// NOTE: text node handlers are implemented on top of text handlers: text of the chunks is
+// buffered and the chunks are removed, the whole text node is emitted with the last chunk.
+fn buffered_text_handler<'h>(
+    mut text_handler: Option<TextHandler<'h>>,
+    mut text_node_handler: TextNodeHandler<'h>,
+    buffer: Shared<TextBuffer>,
+) -> TextHandler<'h> {
+    Box::new(move |chunk: &mut TextChunk| {
+        if let Some(ref mut handler) = text_handler {
+            handler(chunk)?;
+        }
+
+        let mut buffer = buffer.borrow_mut();
+
+        buffer.bytes.extend_from_slice(chunk.as_str().as_bytes())?;
+        buffer
+            .source_location
+            .get_or_insert_with(|| chunk.source_location());
+
+        if !chunk.last_in_text_node() {
+            chunk.remove();
+
+            return Ok(());
+        }
+
+        let text = String::from_utf8(buffer.bytes.drain(..).collect())
+            .expect("Buffered text should be a valid UTF-8 string");
+
+        let mut text_node = TextNode::new(
+            text,
+            chunk.text_type(),
+            buffer.source_location.take().unwrap_or_default(),
+            chunk.encoding(),
+        );
+
+        text_node_handler(&mut text_node)?;
+
+        let (text, mutations) = text_node.into_parts();
+
+        // NOTE: the content can be already set by another text node handler.
+        if !chunk.has_text_node_content() {
+            chunk.set_text_node_content(text);
+        }
+
+        chunk.apply_text_node_mutations(mutations);
+
         Ok(())
+    })
+}
+
+/// The inner content of an element captured for the handlers registered
+/// with `Element::capture_inner_html`.
+struct ContentCapture {
+    content: LimitedVec<u8>,
+    max_len: usize,
+    overflowed: bool,
+}
+
+impl ContentCapture {
+    #[inline]
+    fn into_content(self) -> Option<Vec<u8>> {
+        if self.overflowed {
+            None
+        } else {
+            Some(self.content.to_vec())
+        }
     }
 }
 
-#[derive(Default)]
 pub struct ContentHandlersDispatcher<'h> {
     doctype_handlers: HandlerVec<DoctypeHandler<'h>>,
     comment_handlers: HandlerVec<CommentHandler<'h>>,
     text_handlers: HandlerVec<TextHandler<'h>>,
     end_tag_handlers: HandlerVec<EndTagHandler<'h>>,
     element_handlers: HandlerVec<ElementHandler<'h>>,
+    start_handlers: HandlerVec<StartHandler<'h>>,
     end_handlers: HandlerVec<EndHandler<'h>>,
+    selector_handlers: Vec<Option<SelectorHandlersLocator>>,
     next_element_can_have_content: bool,
     matched_elements_with_removed_content: usize,
+    // NOTE: the captures of the elements that are closed by the current end tag are set
+    // to `None` until all of these elements are processed.
+    content_captures: Vec<Option<ContentCapture>>,
+    finished_content_captures: Vec<ContentCapture>,
+    text_buffers: Vec<Shared<TextBuffer>>,
+    memory_limiter: SharedMemoryLimiter,
+    stats: Option<RewritingStats>,
 }
 
 impl<'h> ContentHandlersDispatcher<'h> {
+    pub fn new(memory_limiter: SharedMemoryLimiter, stats: Option<RewritingStats>) -> Self {
+        ContentHandlersDispatcher {
+            doctype_handlers: HandlerVec::default(),
+            comment_handlers: HandlerVec::default(),
+            text_handlers: HandlerVec::default(),
+            end_tag_handlers: HandlerVec::default(),
+            element_handlers: HandlerVec::default(),
+            start_handlers: HandlerVec::default(),
+            end_handlers: HandlerVec::default(),
+            selector_handlers: Vec::default(),
+            next_element_can_have_content: false,
+            matched_elements_with_removed_content: 0,
+            content_captures: Vec::default(),
+            finished_content_captures: Vec::default(),
+            text_buffers: Vec::default(),
+            memory_limiter,
+            stats,
+        }
+    }
+
+    /// Returns the dispatcher to its initial state, so the handlers can be used for
+    /// another document.
+    pub fn reset(&mut self) {
+        self.doctype_handlers.reset();
+        self.comment_handlers.reset();
+        self.text_handlers.reset();
+        self.end_tag_handlers.clear();
+        self.element_handlers.reset();
+        self.start_handlers.reset();
+        self.end_handlers.reset();
+        self.next_element_can_have_content = false;
+        self.matched_elements_with_removed_content = 0;
+        self.content_captures.clear();
+        self.finished_content_captures.clear();
+
+        for buffer in &self.text_buffers {
+            buffer.borrow_mut().clear();
+        }
+    }
+
+    fn create_text_buffer(&mut self) -> Shared<TextBuffer> {
+        let buffer = Shared::new(TextBuffer {
+            bytes: LimitedVec::new(Shared::clone(&self.memory_limiter)),
+            source_location: None,
+        });
+
+        self.text_buffers.push(Shared::clone(&buffer));
+
+        buffer
+    }
+
     #[inline]
     pub fn add_document_content_handlers(&mut self, handlers: DocumentContentHandlers<'h>) {
         if let Some(handler) = handlers.doctype {
@@ -139,6 +323,10 @@ impl<'h> ContentHandlersDispatcher<'h> {
             self.text_handlers.push(handler, true);
         }
 
+        if let Some(handler) = handlers.start {
+            self.start_handlers.push(handler, true);
+        }
+
         if let Some(handler) = handlers.end {
             self.end_handlers.push(handler, true);
         }
@@ -147,9 +335,41 @@ impl<'h> ContentHandlersDispatcher<'h> {
     #[inline]
     pub fn add_selector_associated_handlers(
         &mut self,
+        selector_idx: usize,
         handlers: ElementContentHandlers<'h>,
-    ) -> SelectorHandlersLocator {
-        SelectorHandlersLocator {
+    ) {
+        if self.selector_handlers.len() <= selector_idx {
+            self.selector_handlers.resize(selector_idx + 1, None);
+        }
+
+        assert!(
+            self.selector_handlers[selector_idx].is_none(),
+            "Handlers are already bound to selector {}.",
+            selector_idx
+        );
+
+        let text_handler = match (handlers.text_node, handlers.text_replacer) {
+            (Some(text_node_handler), text_replacer) => {
+                let text_node_handler = match text_replacer {
+                    Some(replacer) => text_replacing_text_node_handler(text_node_handler, replacer),
+                    None => text_node_handler,
+                };
+
+                Some(buffered_text_handler(
+                    handlers.text,
+                    text_node_handler,
+                    self.create_text_buffer(),
+                ))
+            }
+            (None, Some(replacer)) => Some(text_replacing_handler(
+                handlers.text,
+                replacer,
+                self.create_text_buffer(),
+            )),
+            (None, None) => handlers.text,
+        };
+
+        self.selector_handlers[selector_idx] = Some(SelectorHandlersLocator {
             element_handler_idx: handlers.element.map(|h| {
                 self.element_handlers.push(h, false);
                 self.element_handlers.len() - 1
@@ -158,11 +378,39 @@ impl<'h> ContentHandlersDispatcher<'h> {
                 self.comment_handlers.push(h, false);
                 self.comment_handlers.len() - 1
             }),
-            text_handler_idx: handlers.text.map(|h| {
+            text_handler_idx: text_handler.map(|h| {
                 self.text_handlers.push(h, false);
                 self.text_handlers.len() - 1
             }),
-        }
+        });
+    }
+
+    #[inline]
+    fn selector_handlers_locator(&self, selector_idx: usize) -> SelectorHandlersLocator {
+        // NOTE: selectors without bound handlers still can be matched if
+        // the rewriter is instantiated from shared compiled selectors.
+        self.selector_handlers
+            .get(selector_idx)
+            .copied()
+            .flatten()
+            .unwrap_or_default()
+    }
+
+    // NOTE: end tag handlers are registered for the elements with end tag mutations,
+    // removed or captured content. They are removed once they are invoked.
+    #[inline]
+    pub fn has_pending_end_tag_handlers(&self) -> bool {
+        self.end_tag_handlers.len() > 0
+    }
+
+    // NOTE: document end handlers are not taken into account, since they are
+    // invoked even if the rest of the input is passed through without rewriting.
+    #[inline]
+    pub fn has_handlers_for_remaining_content(&self) -> bool {
+        !self.get_token_capture_flags().is_empty()
+            || self.has_pending_end_tag_handlers()
+            || self.has_matched_elements_with_removed_content()
+            || !self.content_captures.is_empty()
     }
 
     #[inline]
@@ -171,8 +419,14 @@ impl<'h> ContentHandlersDispatcher<'h> {
     }
 
     #[inline]
-    pub fn start_matching(&mut self, match_info: MatchInfo<SelectorHandlersLocator>) {
-        let locator = match_info.payload;
+    pub fn start_matching(&mut self, match_info: MatchInfo<usize>) {
+        trace!(@selector_match match_info.payload, match_info.with_content);
+
+        if let Some(ref stats) = self.stats {
+            stats.record_selector_match(match_info.payload);
+        }
+
+        let locator = self.selector_handlers_locator(match_info.payload);
 
         if match_info.with_content {
             if let Some(idx) = locator.comment_handler_idx {
@@ -193,7 +447,9 @@ impl<'h> ContentHandlersDispatcher<'h> {
 
     #[inline]
     pub fn stop_matching(&mut self, elem_desc: ElementDescriptor) {
-        for locator in elem_desc.matched_content_handlers {
+        for selector_idx in elem_desc.matched_content_handlers {
+            let locator = self.selector_handlers_locator(selector_idx);
+
             if let Some(idx) = locator.comment_handler_idx {
                 self.comment_handlers.dec_user_count(idx);
             }
@@ -210,6 +466,90 @@ impl<'h> ContentHandlersDispatcher<'h> {
         if elem_desc.remove_content {
             self.matched_elements_with_removed_content -= 1;
         }
+
+        if let Some(idx) = elem_desc.content_capture_idx {
+            if let Some(capture) = self.content_captures[idx].take() {
+                self.finished_content_captures.push(capture);
+            }
+
+            while let Some(None) = self.content_captures.last() {
+                self.content_captures.pop();
+            }
+        }
+    }
+
+    #[inline]
+    pub fn content_capture(&self) -> Option<usize> {
+        if self.matched_elements_with_removed_content == 0 {
+            self.content_captures
+                .iter()
+                .rposition(|c| matches!(c, Some(c) if !c.overflowed))
+        } else {
+            None
+        }
+    }
+
+    /// Captures the `chunk` of the content with the given capture. The content that exceeds
+    /// the limit of the capture is passed through along with the content captured so far:
+    /// it's captured with the enclosing capture or, if there is none, passed to
+    /// the `output_handler`.
+    pub fn capture_content(
+        &mut self,
+        capture_idx: usize,
+        chunk: &[u8],
+        output_handler: &mut dyn FnMut(&[u8]),
+    ) -> Result<(), MemoryLimitExceededError> {
+        // NOTE: the capture can be already finished by the end tag hint when
+        // we receive the last chunk of the content that precedes the end tag.
+        // The finished captures are pushed starting from the outermost element,
+        // so the chunk belongs to the last one.
+        let (finished, open_count) = match self.content_captures.get(capture_idx) {
+            Some(Some(_)) => (&mut self.finished_content_captures[..0], capture_idx + 1),
+            _ => (
+                &mut self.finished_content_captures[..],
+                self.content_captures.len(),
+            ),
+        };
+
+        let open = self.content_captures[..open_count]
+            .iter_mut()
+            .rev()
+            .flatten();
+        let captures = finished
+            .iter_mut()
+            .rev()
+            .chain(open)
+            .filter(|c| !c.overflowed);
+
+        let mut passed_through_content: Option<Vec<u8>> = None;
+
+        for capture in captures {
+            let content = passed_through_content.as_deref().unwrap_or(chunk);
+
+            if capture.content.len() + content.len() <= capture.max_len {
+                return capture.content.extend_from_slice(content);
+            }
+
+            let mut overflowed_content = capture.content.drain(..).collect::<Vec<_>>();
+
+            overflowed_content.extend_from_slice(content);
+            capture.overflowed = true;
+            passed_through_content = Some(overflowed_content);
+        }
+
+        output_handler(passed_through_content.as_deref().unwrap_or(chunk));
+
+        Ok(())
+    }
+
+    pub fn take_captured_content(&mut self) -> Vec<u8> {
+        let mut content = Vec::default();
+
+        for capture in self.content_captures.drain(..).flatten() {
+            content.extend_from_slice(&capture.content);
+        }
+
+        content
     }
 
     pub fn handle_start_tag(
@@ -223,9 +563,12 @@ impl<'h> ContentHandlersDispatcher<'h> {
 
         let mut element = Element::new(start_tag, self.next_element_can_have_content);
 
-        self.element_handlers
+        let invocation_count = self
+            .element_handlers
             .do_for_each_active_and_deactivate(|h| h(&mut element))?;
 
+        self.record_handler_invocations(invocation_count);
+
         if self.next_element_can_have_content {
             if let Some(elem_desc) = current_element_data {
                 if element.should_remove_content() {
@@ -233,6 +576,16 @@ impl<'h> ContentHandlersDispatcher<'h> {
                     self.matched_elements_with_removed_content += 1;
                 }
 
+                if let Some(max_len) = element.content_capture_max_len() {
+                    elem_desc.content_capture_idx = Some(self.content_captures.len());
+
+                    self.content_captures.push(Some(ContentCapture {
+                        content: LimitedVec::new(Shared::clone(&self.memory_limiter)),
+                        max_len,
+                        overflowed: false,
+                    }));
+                }
+
                 if let Some(handler) = element.into_end_tag_handler() {
                     elem_desc.end_tag_handler_idx = Some(self.end_tag_handlers.len());
 
@@ -249,20 +602,54 @@ impl<'h> ContentHandlersDispatcher<'h> {
         token: &mut Token,
         current_element_data: Option<&mut ElementDescriptor>,
     ) -> HandlerResult {
-        match token {
-            Token::Doctype(doctype) => self.doctype_handlers.for_each_active(|h| h(doctype)),
-            Token::StartTag(start_tag) => self.handle_start_tag(start_tag, current_element_data),
-            Token::EndTag(end_tag) => self
-                .end_tag_handlers
-                .do_for_each_active_and_remove(|h| h(end_tag)),
-            Token::TextChunk(text) => self.text_handlers.for_each_active(|h| h(text)),
-            Token::Comment(comment) => self.comment_handlers.for_each_active(|h| h(comment)),
-        }
+        let invocation_count = match token {
+            Token::Doctype(doctype) => self.doctype_handlers.for_each_active(|h| h(doctype))?,
+            Token::StartTag(start_tag) => {
+                return self.handle_start_tag(start_tag, current_element_data)
+            }
+            Token::EndTag(end_tag) => {
+                if !self.finished_content_captures.is_empty() {
+                    end_tag.set_captured_inner_contents(
+                        self.finished_content_captures
+                            .drain(..)
+                            .map(ContentCapture::into_content)
+                            .collect(),
+                    );
+                }
+
+                self.end_tag_handlers
+                    .do_for_each_active_and_remove(|h| h(end_tag))?
+            }
+            Token::TextChunk(text) => self.text_handlers.for_each_active(|h| h(text))?,
+            Token::Comment(comment) => self.comment_handlers.for_each_active(|h| h(comment))?,
+        };
+
+        self.record_handler_invocations(invocation_count);
+
+        Ok(())
+    }
+
+    pub fn handle_start(&mut self, document_start: &mut DocumentStart) -> HandlerResult {
+        let invocation_count = self.start_handlers.for_each_active(|h| h(document_start))?;
+
+        self.record_handler_invocations(invocation_count);
+
+        Ok(())
     }
 
     pub fn handle_end(&mut self, document_end: &mut DocumentEnd) -> HandlerResult {
-        self.end_handlers
-            .do_for_each_active_and_remove(|h| h(document_end))
+        let invocation_count = self.end_handlers.for_each_active(|h| h(document_end))?;
+
+        self.record_handler_invocations(invocation_count);
+
+        Ok(())
+    }
+
+    #[inline]
+    fn record_handler_invocations(&self, invocation_count: usize) {
+        if let Some(ref stats) = self.stats {
+            stats.record_handler_invocations(invocation_count);
+        }
     }
 
     #[inline]
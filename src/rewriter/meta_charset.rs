@@ -0,0 +1,95 @@
+use super::ElementContentHandlers;
+use crate::rewritable_units::Element;
+use crate::selectors_vm::Selector;
+use encoding_rs::Encoding;
+
+fn rewrite_meta_charset(el: &mut Element, encoding: &'static Encoding) {
+    if el.has_attribute("charset") {
+        el.set_attribute("charset", encoding.name()).unwrap();
+    } else if let Some(content) = el.get_attribute("content") {
+        let is_content_type = el
+            .get_attribute("http-equiv")
+            .map_or(false, |v| v.trim().eq_ignore_ascii_case("content-type"));
+
+        if is_content_type && content.to_ascii_lowercase().contains("charset") {
+            let mime_type = content.split(';').next().unwrap_or_default().trim();
+
+            el.set_attribute(
+                "content",
+                &format!("{}; charset={}", mime_type, encoding.name()),
+            )
+            .unwrap();
+        }
+    }
+}
+
+/// Returns the selector and the content handlers that rewrite character encoding declarations
+/// of the document to the given `encoding`.
+pub(super) fn meta_charset_handlers(
+    encoding: &'static Encoding,
+) -> (Selector, ElementContentHandlers<'static>) {
+    let selector = "meta".parse().unwrap();
+
+    let handlers = ElementContentHandlers::default().element(move |el| {
+        rewrite_meta_charset(el, encoding);
+
+        Ok(())
+    });
+
+    (selector, handlers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rewriter::{HtmlRewriter, Settings};
+    use encoding_rs::WINDOWS_1251;
+    use std::borrow::Cow;
+
+    fn rewrite(html: &str) -> String {
+        let mut output = vec![];
+
+        {
+            let (selector, handlers) = meta_charset_handlers(WINDOWS_1251);
+
+            let mut rewriter = HtmlRewriter::new(
+                Settings {
+                    element_content_handlers: vec![(Cow::Owned(selector), handlers)],
+                    ..Settings::default()
+                },
+                |c: &[u8]| output.extend_from_slice(c),
+            );
+
+            rewriter.write(html.as_bytes()).unwrap();
+            rewriter.end().unwrap();
+        }
+
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn charset_attribute() {
+        assert_eq!(
+            rewrite(r#"<meta charset="utf-8"><meta name="foo">"#),
+            r#"<meta charset="windows-1251"><meta name="foo">"#
+        );
+    }
+
+    #[test]
+    fn content_type() {
+        assert_eq!(
+            rewrite(r#"<meta http-equiv="Content-Type" content="text/html; charset=utf-8">"#),
+            r#"<meta http-equiv="Content-Type" content="text/html; charset=windows-1251">"#
+        );
+
+        assert_eq!(
+            rewrite(r#"<meta http-equiv="refresh" content="5; charset=utf-8">"#),
+            r#"<meta http-equiv="refresh" content="5; charset=utf-8">"#
+        );
+
+        assert_eq!(
+            rewrite(r#"<meta http-equiv="content-type" content="text/html">"#),
+            r#"<meta http-equiv="content-type" content="text/html">"#
+        );
+    }
+}
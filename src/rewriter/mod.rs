@@ -1,28 +1,58 @@
+mod analyzer;
+#[cfg(feature = "async_handlers")]
+mod async_handlers;
+#[cfg(feature = "async")]
+mod async_io;
+mod compiled_selectors;
+mod encoding_sniffer;
 mod handlers_dispatcher;
+mod meta_charset;
 mod rewrite_controller;
+mod stats;
+mod sync_io;
+mod text_replacer;
+mod transcoding;
 
 #[macro_use]
 mod settings;
 
+use self::encoding_sniffer::{sniff_encoding, SniffingResult};
 use self::handlers_dispatcher::ContentHandlersDispatcher;
+use self::meta_charset::meta_charset_handlers;
 use self::rewrite_controller::*;
+use self::transcoding::{InputTranscoder, OutputTranscoder, TranscodingOutputSink};
+use crate::base::Shared;
 use crate::memory::MemoryLimitExceededError;
-use crate::memory::MemoryLimiter;
+use crate::memory::{MemoryLimiter, SharedMemoryLimiter};
 use crate::parser::ParsingAmbiguityError;
-use crate::selectors_vm::{self, SelectorMatchingVm};
+use crate::selectors_vm::SelectorMatchingVm;
 use crate::transform_stream::*;
-use encoding_rs::Encoding;
+use encoding_rs::{Encoding, UTF_8};
+use std::borrow::Cow;
 use std::error::Error as StdError;
 use std::fmt::{self, Debug};
-use std::rc::Rc;
+use std::mem;
 use thiserror::Error;
 
+pub use self::analyzer::{
+    AnalyzerSettings, DocumentContentAnalyzers, ElementContentAnalyzers, HtmlAnalyzer,
+};
+pub use self::compiled_selectors::CompiledSelectors;
 pub use self::settings::*;
+pub use self::stats::RewritingStats;
+pub use self::sync_io::{IoOutputSink, RewritingReader};
+pub use self::text_replacer::{TextPattern, TextPatternError};
+
+#[cfg(feature = "async")]
+pub use self::async_io::{AsyncHtmlRewriter, RewritingStream};
 
 /// This is an encoding known to be ASCII-compatible.
 ///
 /// Non-ASCII-compatible encodings (`UTF-16LE`, `UTF-16BE`, `ISO-2022-JP` and
-/// `replacement`) are not supported by lol_html.
+/// `replacement`) can't be parsed by lol_html directly. The input in these encodings
+/// should be transcoded with the [`transcoding`] setting.
+///
+/// [`transcoding`]: struct.Settings.html#structfield.transcoding
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct AsciiCompatibleEncoding(&'static Encoding);
 
@@ -80,6 +110,15 @@ pub enum RewritingError {
 
 /// A streaming HTML rewriter.
 ///
+/// # Thread safety
+///
+/// By default the rewriter is neither [`Send`] nor [`Sync`]. If the `send` feature is enabled,
+/// the rewriter uses thread-safe primitives internally and requires all content handlers to be
+/// [`Send`], so it can be moved to another thread as long as its output sink is [`Send`] as well.
+///
+/// [`Send`]: https://doc.rust-lang.org/std/marker/trait.Send.html
+/// [`Sync`]: https://doc.rust-lang.org/std/marker/trait.Sync.html
+///
 /// # Example
 /// ```
 /// use lol_html::{element, HtmlRewriter, Settings};
@@ -119,10 +158,51 @@ pub enum RewritingError {
 /// );
 /// ```
 pub struct HtmlRewriter<'h, O: OutputSink> {
-    stream: TransformStream<HtmlRewriteController<'h>, O>,
+    stream: TransformStream<HtmlRewriteController<'h>, TranscodingOutputSink<O>>,
+    encoding: AsciiCompatibleEncoding,
+    input_transcoder: Option<InputTranscoder>,
+    encoding_detection: Option<EncodingDetection>,
+    stop_signal: StopSignal,
+    initial_state: InitialState,
+    stats: Option<RewritingStats>,
     poisoned: bool,
 }
 
+// NOTE: the input is buffered until its encoding is determined.
+#[derive(Clone)]
+struct EncodingDetection {
+    buffered_input: Vec<u8>,
+    compiled_selectors: CompiledSelectors,
+    memory_limiter: SharedMemoryLimiter,
+    output_encoding: Option<&'static Encoding>,
+    transcode_output: bool,
+    match_decoded_attribute_values: bool,
+}
+
+// NOTE: the encoding of the input and the output transcoding can be changed once the encoding
+// is detected, so their initial state is retained to restore it when the rewriter is reset.
+struct InitialState {
+    encoding: AsciiCompatibleEncoding,
+    input_encoding: Option<&'static Encoding>,
+    output_encoding: Option<&'static Encoding>,
+    encoding_detection: Option<EncodingDetection>,
+}
+
+// NOTE: the output is produced in the encoding the input is parsed in, unless specified otherwise.
+fn create_output_transcoder(
+    parsing_encoding: AsciiCompatibleEncoding,
+    output_encoding: Option<&'static Encoding>,
+) -> Option<OutputTranscoder> {
+    let parsing_encoding = parsing_encoding.into();
+
+    match output_encoding.map(transcoding::output_encoding) {
+        Some(output_encoding) if output_encoding != parsing_encoding => {
+            Some(OutputTranscoder::new(parsing_encoding, output_encoding))
+        }
+        _ => None,
+    }
+}
+
 macro_rules! guarded {
     ($self:ident, $expr:expr) => {{
         assert!(
@@ -149,36 +229,156 @@ impl<'h, O: OutputSink> HtmlRewriter<'h, O> {
     /// For the convenience the [`OutputSink`] trait is implemented for closures.
     ///
     /// [`OutputSink`]: trait.OutputSink.html
-    pub fn new<'s>(settings: Settings<'h, 's>, output_sink: O) -> Self {
-        let encoding = settings.encoding;
-        let mut selectors_ast = selectors_vm::Ast::default();
-        let mut dispatcher = ContentHandlersDispatcher::default();
-        let has_selectors = !settings.element_content_handlers.is_empty();
+    pub fn new<'s>(mut settings: Settings<'h, 's>, output_sink: O) -> Self {
+        let (selectors, element_content_handlers): (Vec<_>, Vec<_>) =
+            mem::take(&mut settings.element_content_handlers)
+                .into_iter()
+                .unzip();
+
+        let compiled_selectors =
+            CompiledSelectors::new(selectors.iter().map(|s| &**s), settings.encoding);
+
+        Self::with_compiled_selectors(
+            &compiled_selectors,
+            element_content_handlers.into_iter().enumerate().collect(),
+            settings,
+            output_sink,
+        )
+    }
+
+    /// Constructs a new rewriter from the selectors that were compiled in advance.
+    ///
+    /// Content handlers are bound to the selectors by their index in the list `compiled_selectors`
+    /// were built from. Selectors without bound handlers are ignored.
+    ///
+    /// Refer to [`CompiledSelectors`] documentation for more information.
+    ///
+    /// Selectors that contain non-ASCII characters are recompiled if `settings` specify an encoding
+    /// different from the one `compiled_selectors` were compiled for.
+    ///
+    /// # Panics
+    ///  * If `settings` contain [`element_content_handlers`].
+    ///  * If a selector index is out of bounds of `compiled_selectors`.
+    ///  * If handlers are bound to the same selector more than once.
+    ///
+    /// [`CompiledSelectors`]: struct.CompiledSelectors.html
+    /// [`element_content_handlers`]: struct.Settings.html#structfield.element_content_handlers
+    pub fn with_compiled_selectors<'s>(
+        compiled_selectors: &CompiledSelectors,
+        element_content_handlers: Vec<(usize, ElementContentHandlers<'h>)>,
+        settings: Settings<'h, 's>,
+        output_sink: O,
+    ) -> Self {
+        assert!(
+            settings.element_content_handlers.is_empty(),
+            "Element content handlers should be bound to the compiled selectors by index."
+        );
+
+        // NOTE: transcoded input is parsed as UTF-8.
+        let encoding = match settings.transcoding {
+            Some(_) => AsciiCompatibleEncoding(UTF_8),
+            None => settings.encoding,
+        };
+
+        let memory_limiter =
+            MemoryLimiter::new_shared(settings.memory_settings.max_allowed_memory_usage);
+
+        if let Some(ref stats) = settings.stats {
+            stats.init_selector_match_counts(compiled_selectors.len());
+            memory_limiter
+                .borrow_mut()
+                .set_stats(RewritingStats::clone(stats));
+        }
 
-        for (selector, handlers) in settings.element_content_handlers {
-            let locator = dispatcher.add_selector_associated_handlers(handlers);
+        let mut dispatcher =
+            ContentHandlersDispatcher::new(Shared::clone(&memory_limiter), settings.stats.clone());
+        let mut has_selectors = !element_content_handlers.is_empty();
 
-            selectors_ast.add_selector(&selector, locator);
+        for (selector_idx, handlers) in element_content_handlers {
+            assert!(
+                selector_idx < compiled_selectors.len(),
+                "Selector index {} is out of bounds.",
+                selector_idx
+            );
+
+            dispatcher.add_selector_associated_handlers(selector_idx, handlers);
         }
 
+        let compiled_selectors = match settings.output_encoding {
+            Some(output_encoding) if settings.rewrite_meta_charset => {
+                let (selector, handlers) =
+                    meta_charset_handlers(transcoding::output_encoding(output_encoding));
+
+                dispatcher.add_selector_associated_handlers(compiled_selectors.len(), handlers);
+                has_selectors = true;
+
+                Cow::Owned(compiled_selectors.extend(&[selector]))
+            }
+            _ => Cow::Borrowed(compiled_selectors),
+        };
+
         for handlers in settings.document_content_handlers {
             dispatcher.add_document_content_handlers(handlers);
         }
 
-        let memory_limiter =
-            MemoryLimiter::new_shared(settings.memory_settings.max_allowed_memory_usage);
-
+        // NOTE: fast path - we don't need the selector matching VM if
+        // there are no handlers bound to the selectors.
         let selector_matching_vm = if has_selectors {
             Some(SelectorMatchingVm::new(
-                selectors_ast,
-                encoding.into(),
-                Rc::clone(&memory_limiter),
+                compiled_selectors.program_for(encoding),
+                Shared::clone(&memory_limiter),
+                if settings.match_decoded_attribute_values {
+                    Some(encoding.into())
+                } else {
+                    None
+                },
             ))
         } else {
             None
         };
 
-        let controller = HtmlRewriteController::new(dispatcher, selector_matching_vm);
+        let stop_signal = settings.stop_signal.unwrap_or_default();
+
+        let controller = HtmlRewriteController::new(
+            dispatcher,
+            selector_matching_vm,
+            StopSignal::clone(&stop_signal),
+        );
+
+        let encoding_detection = if settings.detect_encoding {
+            Some(EncodingDetection {
+                buffered_input: Vec::default(),
+                compiled_selectors: compiled_selectors.into_owned(),
+                memory_limiter: Shared::clone(&memory_limiter),
+                output_encoding: settings.output_encoding,
+                transcode_output: settings.transcoding.map_or(false, |t| t.transcode_output),
+                match_decoded_attribute_values: settings.match_decoded_attribute_values,
+            })
+        } else {
+            None
+        };
+
+        let input_transcoder = settings
+            .transcoding
+            .map(|transcoding| InputTranscoder::new(transcoding.encoding));
+
+        let transcoding = settings.transcoding;
+
+        let output_encoding = settings.output_encoding.or_else(|| match transcoding {
+            Some(transcoding) if transcoding.transcode_output => Some(transcoding.encoding),
+            _ => None,
+        });
+
+        let initial_state = InitialState {
+            encoding,
+            input_encoding: transcoding.map(|transcoding| transcoding.encoding),
+            output_encoding,
+            encoding_detection: encoding_detection.clone(),
+        };
+
+        let mut output_sink = TranscodingOutputSink::new(output_sink, settings.stats.clone());
+
+        output_sink.set_transcoder(create_output_transcoder(encoding, output_encoding));
 
         let stream = TransformStream::new(TransformStreamSettings {
             transform_controller: controller,
@@ -189,14 +389,148 @@ impl<'h, O: OutputSink> HtmlRewriter<'h, O> {
             memory_limiter,
             encoding: encoding.into(),
             strict: settings.strict,
+            stats: settings.stats.clone(),
         });
 
         HtmlRewriter {
             stream,
+            encoding,
+            input_transcoder,
+            encoding_detection,
+            stop_signal,
+            initial_state,
+            stats: settings.stats,
             poisoned: false,
         }
     }
 
+    /// Returns the character encoding of the input.
+    ///
+    /// Returns `None` if [`detect_encoding`] is enabled and the rewriter hasn't received enough
+    /// input to determine the encoding yet.
+    ///
+    /// [`detect_encoding`]: struct.Settings.html#structfield.detect_encoding
+    #[inline]
+    pub fn encoding(&self) -> Option<&'static Encoding> {
+        if self.encoding_detection.is_some() {
+            None
+        } else {
+            Some(match self.input_transcoder {
+                Some(ref transcoder) => transcoder.encoding(),
+                None => self.encoding.into(),
+            })
+        }
+    }
+
+    fn finish_encoding_detection(&mut self, result: SniffingResult) -> Result<(), RewritingError> {
+        let detection = self
+            .encoding_detection
+            .take()
+            .expect("Encoding detection should be in progress");
+
+        if let SniffingResult::Detected(detected_encoding) = result {
+            // NOTE: non-ASCII-compatible encodings are transcoded into UTF-8 for parsing.
+            let (encoding, input_transcoder) = match AsciiCompatibleEncoding::new(detected_encoding)
+            {
+                Some(encoding) => (encoding, None),
+                None => (
+                    AsciiCompatibleEncoding(UTF_8),
+                    Some(InputTranscoder::new(detected_encoding)),
+                ),
+            };
+
+            let output_encoding = detection.output_encoding.or(if detection.transcode_output {
+                Some(detected_encoding)
+            } else {
+                None
+            });
+
+            let output_transcoder = create_output_transcoder(encoding, output_encoding);
+
+            if encoding != self.encoding {
+                self.set_parsing_encoding(encoding, &detection);
+            }
+
+            self.input_transcoder = input_transcoder;
+
+            self.stream
+                .with_output_sink(move |sink| sink.set_transcoder(output_transcoder));
+        }
+
+        self.write_to_stream(&detection.buffered_input)
+    }
+
+    fn set_parsing_encoding(
+        &mut self,
+        encoding: AsciiCompatibleEncoding,
+        detection: &EncodingDetection,
+    ) {
+        let program = detection.compiled_selectors.program_for(encoding);
+        let memory_limiter = Shared::clone(&detection.memory_limiter);
+
+        let attr_decoding_encoding = if detection.match_decoded_attribute_values {
+            Some(encoding.into())
+        } else {
+            None
+        };
+
+        self.stream.set_encoding(encoding.into());
+
+        self.stream.with_transform_controller(move |controller| {
+            controller.set_selector_matching_program(
+                program,
+                memory_limiter,
+                attr_decoding_encoding,
+            )
+        });
+
+        self.encoding = encoding;
+    }
+
+    fn write_to_stream(&mut self, data: &[u8]) -> Result<(), RewritingError> {
+        match self.input_transcoder {
+            Some(ref mut transcoder) => self.stream.write(transcoder.transcode(data, false)),
+            None => self.stream.write(data),
+        }
+    }
+
+    #[inline]
+    fn with_output_sink<T>(&self, f: impl FnOnce(&mut O) -> T) -> T {
+        self.stream.with_output_sink(|sink| f(sink.inner_mut()))
+    }
+
+    fn write_input(&mut self, data: &[u8]) -> Result<(), RewritingError> {
+        if let Some(ref stats) = self.stats {
+            stats.record_bytes_in(data.len());
+        }
+
+        match self.encoding_detection {
+            Some(ref mut detection) => {
+                detection.buffered_input.extend_from_slice(data);
+
+                match sniff_encoding(&detection.buffered_input, false) {
+                    SniffingResult::Incomplete => Ok(()),
+                    result => self.finish_encoding_detection(result),
+                }
+            }
+            None => self.write_to_stream(data),
+        }
+    }
+
+    fn end_input(&mut self) -> Result<(), RewritingError> {
+        if let Some(ref detection) = self.encoding_detection {
+            let result = sniff_encoding(&detection.buffered_input, true);
+
+            self.finish_encoding_detection(result)?;
+        }
+
+        if let Some(ref mut transcoder) = self.input_transcoder {
+            self.stream.write(transcoder.transcode(&[], true))?;
+        }
+
+        self.stream.end()
+    }
+
     /// Writes a chunk of input data to the rewriter.
     ///
     /// # Panics
@@ -207,7 +541,30 @@ impl<'h, O: OutputSink> HtmlRewriter<'h, O> {
     /// [`end`]: struct.HtmlRewriter.html#method.end
     #[inline]
     pub fn write(&mut self, data: &[u8]) -> Result<(), RewritingError> {
-        guarded!(self, self.stream.write(data))
+        guarded!(self, self.write_input(data))
+    }
+
+    /// Returns the statistics collected by the rewriter.
+    ///
+    /// Returns `None` if the [`stats`] setting is not specified.
+    ///
+    /// [`stats`]: struct.Settings.html#structfield.stats
+    #[inline]
+    pub fn stats(&self) -> Option<&RewritingStats> {
+        self.stats.as_ref()
+    }
+
+    /// Stops the rewriting of the rest of the document.
+    ///
+    /// The rest of the input is passed to the output as is. This is the same as raising
+    /// the [`stop_signal`] specified in the settings, refer to [`StopSignal`] documentation for
+    /// more information.
+    ///
+    /// [`stop_signal`]: struct.Settings.html#structfield.stop_signal
+    /// [`StopSignal`]: struct.StopSignal.html
+    #[inline]
+    pub fn stop_rewriting(&self) {
+        self.stop_signal.raise();
     }
 
     /// Finalizes the rewriting process.
@@ -222,7 +579,112 @@ impl<'h, O: OutputSink> HtmlRewriter<'h, O> {
     /// [`write`]: struct.HtmlRewriter.html#method.write
     #[inline]
     pub fn end(mut self) -> Result<(), RewritingError> {
-        guarded!(self, self.stream.end())
+        guarded!(self, self.end_input())
+    }
+
+    /// Finalizes the rewriting process like [`end`] and [resets] the rewriter, so it can be
+    /// reused for another document which output is written to `output_sink`.
+    ///
+    /// Returns the output sink of the finished document.
+    ///
+    /// # Panics
+    ///  * If previous invocation of [`write`] returned a [`RewritingError`] (these errors
+    ///    are unrecovarable). [`reset`] can be used to recover the rewriter instead.
+    ///
+    /// [`end`]: struct.HtmlRewriter.html#method.end
+    /// [resets]: struct.HtmlRewriter.html#method.reset
+    /// [`reset`]: struct.HtmlRewriter.html#method.reset
+    /// [`RewritingError`]: errors/enum.RewritingError.html
+    /// [`write`]: struct.HtmlRewriter.html#method.write
+    pub fn end_and_reset(&mut self, output_sink: O) -> Result<O, RewritingError> {
+        guarded!(self, self.end_input())?;
+
+        Ok(self.reset(output_sink))
+    }
+
+    /// Returns the rewriter to its initial state, so it can be reused for another document
+    /// which output is written to `output_sink`.
+    ///
+    /// Returns the output sink of the previous document. The rest of the previous document is
+    /// discarded if it wasn't finalized with [`end_and_reset`]. The rewriter can be reset after
+    /// a [`RewritingError`] as well.
+    ///
+    /// The content handlers and compiled selectors are retained, so reusing the rewriter is
+    /// cheaper than constructing a new one for each document. Note that the handlers retain
+    /// their own state. The parsing buffer is shrunk back to the
+    /// [`preallocated_parsing_buffer_size`].
+    ///
+    /// # Example
+    /// ```
+    /// use lol_html::{element, HtmlRewriter, Settings};
+    /// use std::cell::RefCell;
+    ///
+    /// let output = RefCell::new(vec![]);
+    /// let output_sink = |c: &[u8]| output.borrow_mut().extend_from_slice(c);
+    ///
+    /// let mut rewriter = HtmlRewriter::new(
+    ///     Settings {
+    ///         element_content_handlers: vec![element!("a[href]", |el| {
+    ///             el.set_attribute("rel", "nofollow")?;
+    ///
+    ///             Ok(())
+    ///         })],
+    ///         ..Settings::default()
+    ///     },
+    ///     output_sink,
+    /// );
+    ///
+    /// rewriter.write(b"<a href='/'>Home</a>").unwrap();
+    /// rewriter.end_and_reset(output_sink).unwrap();
+    ///
+    /// assert_eq!(
+    ///     String::from_utf8(output.take()).unwrap(),
+    ///     r#"<a href='/' rel="nofollow">Home</a>"#
+    /// );
+    ///
+    /// rewriter.write(b"<a href='/about'>About</a>").unwrap();
+    /// rewriter.end_and_reset(output_sink).unwrap();
+    ///
+    /// assert_eq!(
+    ///     String::from_utf8(output.take()).unwrap(),
+    ///     r#"<a href='/about' rel="nofollow">About</a>"#
+    /// );
+    /// ```
+    ///
+    /// [`end_and_reset`]: struct.HtmlRewriter.html#method.end_and_reset
+    /// [`RewritingError`]: errors/enum.RewritingError.html
+    /// [`preallocated_parsing_buffer_size`]: struct.MemorySettings.html#structfield.preallocated_parsing_buffer_size
+    pub fn reset(&mut self, output_sink: O) -> O {
+        let encoding = self.initial_state.encoding;
+
+        if encoding != self.encoding {
+            let detection = self
+                .initial_state
+                .encoding_detection
+                .take()
+                .expect("Parsing encoding can be changed only by encoding detection");
+
+            self.set_parsing_encoding(encoding, &detection);
+            self.initial_state.encoding_detection = Some(detection);
+        }
+
+        match self.encoding_detection {
+            Some(ref mut detection) => detection.buffered_input.clear(),
+            None => self.encoding_detection = self.initial_state.encoding_detection.clone(),
+        }
+
+        self.input_transcoder = self.initial_state.input_encoding.map(InputTranscoder::new);
+
+        let output_transcoder =
+            create_output_transcoder(encoding, self.initial_state.output_encoding);
+
+        self.stream.reset();
+        self.poisoned = false;
+
+        self.stream.with_output_sink(move |sink| {
+            sink.set_transcoder(output_transcoder);
+            sink.replace_inner(output_sink)
+        })
     }
 }
 
@@ -287,10 +749,8 @@ mod tests {
     use super::*;
     use crate::html_content::ContentType;
     use crate::test_utils::{Output, ASCII_COMPATIBLE_ENCODINGS, NON_ASCII_COMPATIBLE_ENCODINGS};
-    use encoding_rs::Encoding;
-    use std::cell::RefCell;
+    use encoding_rs::{Encoding, ISO_2022_JP, REPLACEMENT, UTF_16BE, UTF_16LE, WINDOWS_1251};
     use std::convert::TryInto;
-    use std::rc::Rc;
 
     fn write_chunks<O: OutputSink>(
         mut rewriter: HtmlRewriter<O>,
@@ -506,12 +966,12 @@ mod tests {
 
     #[test]
     fn handler_invocation_order() {
-        let handlers_executed = Rc::new(RefCell::new(Vec::default()));
+        let handlers_executed = Shared::new(Vec::default());
 
         macro_rules! create_handlers {
             ($sel:expr, $idx:expr) => {
                 element!($sel, {
-                    let handlers_executed = Rc::clone(&handlers_executed);
+                    let handlers_executed = Shared::clone(&handlers_executed);
 
                     move |_| {
                         handlers_executed.borrow_mut().push($idx);
@@ -539,6 +999,654 @@ mod tests {
         assert_eq!(*handlers_executed.borrow(), vec![0, 1, 2, 3, 4]);
     }
 
+    #[test]
+    fn encoding_detection() {
+        let html = concat!(
+            r#"<html><head><meta charset="windows-1251"></head>"#,
+            r#"<body><div title="привет">Привет</div></body></html>"#
+        );
+
+        let (input, _, _) = WINDOWS_1251.encode(html);
+        let mut output = Output::new(WINDOWS_1251);
+        let mut text = String::new();
+
+        {
+            let mut rewriter = HtmlRewriter::new(
+                Settings {
+                    element_content_handlers: vec![
+                        element!(r#"div[title="привет"]"#, |el| {
+                            el.set_attribute("lang", "ru")?;
+
+                            Ok(())
+                        }),
+                        text!("div", |t| {
+                            text.push_str(t.as_str());
+
+                            Ok(())
+                        }),
+                    ],
+                    detect_encoding: true,
+                    ..Settings::default()
+                },
+                |c: &[u8]| output.push(c),
+            );
+
+            assert_eq!(rewriter.encoding(), None);
+
+            for byte in input.chunks(1) {
+                rewriter.write(byte).unwrap();
+            }
+
+            assert_eq!(rewriter.encoding(), Some(WINDOWS_1251));
+
+            rewriter.end().unwrap();
+        }
+
+        let output: String = output.into();
+
+        assert_eq!(
+            output,
+            html.replace(r#"title="привет""#, r#"title="привет" lang="ru""#)
+        );
+
+        assert_eq!(text, "Привет");
+    }
+
+    #[test]
+    fn encoding_detection_fallback() {
+        let mut output = vec![];
+
+        {
+            let mut rewriter = HtmlRewriter::new(
+                Settings {
+                    detect_encoding: true,
+                    ..Settings::default()
+                },
+                |c: &[u8]| output.extend_from_slice(c),
+            );
+
+            rewriter.write(b"<div>Hi</div>").unwrap();
+
+            assert_eq!(rewriter.encoding(), None);
+
+            rewriter.end().unwrap();
+        }
+
+        assert_eq!(String::from_utf8(output).unwrap(), "<div>Hi</div>");
+
+        let rewriter = HtmlRewriter::new(Settings::default(), |_: &[u8]| {});
+
+        assert_eq!(rewriter.encoding(), Some(encoding_rs::UTF_8));
+    }
+
+    fn encode_non_ascii_compatible(text: &str, encoding: &'static Encoding) -> Vec<u8> {
+        // NOTE: encoding_rs can't encode UTF-16, so we do it manually.
+        if encoding == UTF_16LE {
+            text.encode_utf16()
+                .flat_map(|c| c.to_le_bytes().to_vec())
+                .collect()
+        } else if encoding == UTF_16BE {
+            text.encode_utf16()
+                .flat_map(|c| c.to_be_bytes().to_vec())
+                .collect()
+        } else {
+            encoding.encode(text).0.into_owned()
+        }
+    }
+
+    fn rewrite_transcoded(input: &[u8], settings: Settings) -> Vec<u8> {
+        let mut output = vec![];
+
+        {
+            let mut rewriter = HtmlRewriter::new(settings, |c: &[u8]| {
+                output.extend_from_slice(c);
+            });
+
+            for byte in input.chunks(1) {
+                rewriter.write(byte).unwrap();
+            }
+
+            rewriter.end().unwrap();
+        }
+
+        output
+    }
+
+    #[test]
+    fn transcoding() {
+        let html = r#"<div title="привет"><span>こんにちは</span></div>"#;
+        let expected = r#"<div title="привет" lang="ru"><b>こんにちは</b></div>"#;
+
+        for &encoding in NON_ASCII_COMPATIBLE_ENCODINGS.iter() {
+            if encoding == REPLACEMENT {
+                continue;
+            }
+
+            let input = encode_non_ascii_compatible(html, encoding);
+
+            for &transcode_output in &[false, true] {
+                let output = rewrite_transcoded(
+                    &input,
+                    Settings {
+                        element_content_handlers: vec![
+                            element!(r#"div[title="привет"]"#, |el| {
+                                el.set_attribute("lang", "ru")?;
+
+                                Ok(())
+                            }),
+                            element!("span", |el| {
+                                el.set_tag_name("b")?;
+
+                                Ok(())
+                            }),
+                        ],
+                        transcoding: Some(Transcoding {
+                            encoding,
+                            transcode_output,
+                        }),
+                        ..Settings::default()
+                    },
+                );
+
+                if transcode_output {
+                    assert_eq!(output, encode_non_ascii_compatible(expected, encoding));
+                } else {
+                    assert_eq!(String::from_utf8(output).unwrap(), expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn transcoding_replacement_encoding() {
+        let output = rewrite_transcoded(
+            b"<div>Hi</div>",
+            Settings {
+                transcoding: Some(Transcoding {
+                    encoding: REPLACEMENT,
+                    transcode_output: true,
+                }),
+                ..Settings::default()
+            },
+        );
+
+        assert_eq!(String::from_utf8(output).unwrap(), "\u{FFFD}");
+    }
+
+    #[test]
+    fn transcoding_with_encoding_detection() {
+        let html = "\u{FEFF}<div>Привет</div>";
+
+        for &(encoding, transcode_output) in &[(UTF_16LE, false), (UTF_16BE, true)] {
+            let mut output = vec![];
+
+            {
+                let mut rewriter = HtmlRewriter::new(
+                    Settings {
+                        element_content_handlers: vec![element!("div", |el| {
+                            el.set_tag_name("p")?;
+
+                            Ok(())
+                        })],
+                        detect_encoding: true,
+                        transcoding: Some(Transcoding {
+                            encoding: UTF_16LE,
+                            transcode_output,
+                        }),
+                        ..Settings::default()
+                    },
+                    |c: &[u8]| output.extend_from_slice(c),
+                );
+
+                rewriter
+                    .write(&encode_non_ascii_compatible(html, encoding))
+                    .unwrap();
+
+                assert_eq!(rewriter.encoding(), Some(encoding));
+
+                rewriter.end().unwrap();
+            }
+
+            let expected = "\u{FEFF}<p>Привет</p>";
+
+            if transcode_output {
+                assert_eq!(output, encode_non_ascii_compatible(expected, encoding));
+            } else {
+                assert_eq!(String::from_utf8(output).unwrap(), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn output_encoding() {
+        let html = concat!(
+            r#"<head><meta charset="windows-1251"></head>"#,
+            r#"<div title="привет">Привет</div>"#
+        );
+
+        let (input, _, _) = WINDOWS_1251.encode(html);
+
+        for &rewrite_meta_charset in &[false, true] {
+            let output = rewrite_transcoded(
+                &input,
+                Settings {
+                    element_content_handlers: vec![element!(r#"div[title="привет"]"#, |el| {
+                        el.append(", мир!", ContentType::Text);
+
+                        Ok(())
+                    })],
+                    encoding: WINDOWS_1251.try_into().unwrap(),
+                    output_encoding: Some(encoding_rs::UTF_8),
+                    rewrite_meta_charset,
+                    ..Settings::default()
+                },
+            );
+
+            let expected = html.replace("Привет<", "Привет, мир!<");
+
+            if rewrite_meta_charset {
+                assert_eq!(
+                    String::from_utf8(output).unwrap(),
+                    expected.replace("windows-1251", "UTF-8")
+                );
+            } else {
+                assert_eq!(String::from_utf8(output).unwrap(), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn output_encoding_with_transcoding() {
+        let html = r#"<meta charset="utf-16"><div>こんにちは</div>"#;
+        let expected = r#"<meta charset="ISO-2022-JP"><div>こんにちは</div>"#;
+
+        for &transcode_output in &[false, true] {
+            let output = rewrite_transcoded(
+                &encode_non_ascii_compatible(html, UTF_16BE),
+                Settings {
+                    transcoding: Some(Transcoding {
+                        encoding: UTF_16BE,
+                        transcode_output,
+                    }),
+                    output_encoding: Some(ISO_2022_JP),
+                    rewrite_meta_charset: true,
+                    ..Settings::default()
+                },
+            );
+
+            assert_eq!(output, encode_non_ascii_compatible(expected, ISO_2022_JP));
+        }
+    }
+
+    #[test]
+    fn output_encoding_with_encoding_detection() {
+        let html = r#"<meta charset="windows-1251"><div>Привет</div>"#;
+        let (input, _, _) = WINDOWS_1251.encode(html);
+
+        let output = rewrite_transcoded(
+            &input,
+            Settings {
+                detect_encoding: true,
+                output_encoding: Some(UTF_16LE),
+                rewrite_meta_charset: true,
+                ..Settings::default()
+            },
+        );
+
+        assert_eq!(
+            output,
+            encode_non_ascii_compatible(r#"<meta charset="UTF-16LE"><div>Привет</div>"#, UTF_16LE)
+        );
+    }
+
+    #[test]
+    fn match_decoded_attribute_values() {
+        let html = r#"<meta charset="windows-1251"><a href="/?a=1&amp;b=Привет&#33;"></a>"#;
+        let (input, _, _) = WINDOWS_1251.encode(html);
+
+        for &match_decoded_attribute_values in &[false, true] {
+            let mut matched = false;
+
+            rewrite_transcoded(
+                &input,
+                Settings {
+                    element_content_handlers: vec![element!(
+                        r#"a[href="/?a=1&b=Привет!"]"#,
+                        |_| {
+                            matched = true;
+
+                            Ok(())
+                        }
+                    )],
+                    detect_encoding: true,
+                    match_decoded_attribute_values,
+                    ..Settings::default()
+                },
+            );
+
+            assert_eq!(matched, match_decoded_attribute_values);
+        }
+    }
+
+    fn rewrite_chunks(chunks: &[&str], settings: Settings) -> String {
+        let mut output = vec![];
+
+        {
+            let mut rewriter = HtmlRewriter::new(settings, |c: &[u8]| output.extend_from_slice(c));
+
+            for chunk in chunks {
+                rewriter.write(chunk.as_bytes()).unwrap();
+            }
+
+            rewriter.end().unwrap();
+        }
+
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn stop_signal() {
+        let chunks = ["<div><span>", "</span><di", "v>Hi</div><", "div></div>"];
+
+        for split_at in 0..chunks.len() {
+            let stop_signal = StopSignal::new();
+            let handler_stop_signal = stop_signal.clone();
+            let mut handler_calls = 0;
+
+            let output = rewrite_chunks(
+                &[&chunks[..split_at].concat(), &chunks[split_at..].concat()],
+                Settings {
+                    element_content_handlers: vec![element!("div", |el| {
+                        handler_calls += 1;
+
+                        el.set_attribute("foo", "bar")?;
+                        handler_stop_signal.raise();
+
+                        Ok(())
+                    })],
+                    stop_signal: Some(stop_signal),
+                    ..Settings::default()
+                },
+            );
+
+            assert_eq!(
+                output,
+                r#"<div foo="bar"><span></span><div>Hi</div><div></div>"#
+            );
+
+            assert_eq!(handler_calls, 1);
+        }
+    }
+
+    #[test]
+    fn stop_signal_with_pending_end_tag_mutations() {
+        let stop_signal = StopSignal::new();
+        let handler_stop_signal = stop_signal.clone();
+
+        let output = rewrite_chunks(
+            &[
+                "<head><meta><title>Hi</title><meta></head>",
+                "<body><meta><div><b>Bye</b></div></body>",
+            ],
+            Settings {
+                element_content_handlers: vec![
+                    element!("head", |el| {
+                        el.append("<script></script>", ContentType::Html);
+                        handler_stop_signal.raise();
+
+                        Ok(())
+                    }),
+                    element!("meta", |el| {
+                        el.set_attribute("foo", "bar")?;
+
+                        Ok(())
+                    }),
+                    element!("div", |el| {
+                        el.set_inner_content("Removed", ContentType::Text);
+
+                        Ok(())
+                    }),
+                ],
+                stop_signal: Some(stop_signal),
+                ..Settings::default()
+            },
+        );
+
+        assert_eq!(
+            output,
+            concat!(
+                r#"<head><meta foo="bar"><title>Hi</title><meta foo="bar"><script></script></head>"#,
+                "<body><meta><div><b>Bye</b></div></body>"
+            )
+        );
+    }
+
+    #[test]
+    fn stop_signal_with_unclosed_elements() {
+        let stop_signal = StopSignal::new();
+        let handler_stop_signal = stop_signal.clone();
+
+        let output = rewrite_chunks(
+            &["<div><span>Hi</span>", "<span>Bye</span>"],
+            Settings {
+                element_content_handlers: vec![
+                    element!("div", |el| {
+                        el.on_end_tag(|_| Ok(()))?;
+                        handler_stop_signal.raise();
+
+                        Ok(())
+                    }),
+                    element!("span", |el| {
+                        el.set_inner_content("Hey", ContentType::Text);
+
+                        Ok(())
+                    }),
+                ],
+                stop_signal: Some(stop_signal),
+                ..Settings::default()
+            },
+        );
+
+        assert_eq!(output, "<div><span>Hey</span><span>Hey</span>");
+    }
+
+    #[test]
+    fn stop_signal_from_text_handler() {
+        let stop_signal = StopSignal::new();
+        let handler_stop_signal = stop_signal.clone();
+
+        let output = rewrite_chunks(
+            &["<div>Hello</div><div>Bye</div>"],
+            Settings {
+                element_content_handlers: vec![text!("div", |t| {
+                    if t.last_in_text_node() {
+                        t.after("!", ContentType::Text);
+                        handler_stop_signal.raise();
+                    }
+
+                    Ok(())
+                })],
+                stop_signal: Some(stop_signal),
+                ..Settings::default()
+            },
+        );
+
+        assert_eq!(output, "<div>Hello!</div><div>Bye</div>");
+    }
+
+    #[test]
+    fn stop_rewriting() {
+        let mut output = vec![];
+
+        {
+            let mut rewriter = HtmlRewriter::new(
+                Settings {
+                    element_content_handlers: vec![element!("div", |el| {
+                        el.set_attribute("foo", "bar")?;
+
+                        Ok(())
+                    })],
+                    document_content_handlers: vec![end!(|end| {
+                        end.append("<!-- end -->", ContentType::Html);
+
+                        Ok(())
+                    })],
+                    ..Settings::default()
+                },
+                |c: &[u8]| output.extend_from_slice(c),
+            );
+
+            rewriter.write(b"<div></div><di").unwrap();
+            rewriter.stop_rewriting();
+            rewriter.write(b"v></div>").unwrap();
+            rewriter.write(b"<div></div>").unwrap();
+            rewriter.end().unwrap();
+        }
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            r#"<div foo="bar"></div><div></div><div></div><!-- end -->"#
+        );
+    }
+
+    #[test]
+    fn automatic_passthrough() {
+        let rewrite = |selector: &str| {
+            let mut output = vec![];
+            let mut rewriter = HtmlRewriter::new(
+                Settings {
+                    element_content_handlers: vec![element!(selector, |el| {
+                        el.set_attribute("foo", "bar")?;
+
+                        Ok(())
+                    })],
+                    strict: true,
+                    ..Settings::default()
+                },
+                |c: &[u8]| output.extend_from_slice(c),
+            );
+
+            // NOTE: the rewriter bails out on the `<xmp>` in `<select>` in the strict mode,
+            // unless the input is passed through without parsing.
+            rewriter
+                .write(b"<html><head><meta></head><body><select><xmp></xmp>")
+                .and_then(|_| rewriter.end())
+                .map(|_| String::from_utf8(output).unwrap())
+        };
+
+        for &selector in &["head > meta", "html > head > meta", "html head meta"] {
+            assert_eq!(
+                rewrite(selector).unwrap(),
+                r#"<html><head><meta foo="bar"></head><body><select><xmp></xmp>"#
+            );
+        }
+
+        assert!(matches!(
+            rewrite("meta"),
+            Err(RewritingError::ParsingAmbiguity(_))
+        ));
+    }
+
+    #[test]
+    fn automatic_passthrough_with_active_handlers() {
+        let html = "<head><title>Title</title></head><body><p>Text</p></body>";
+
+        let mut text = String::new();
+
+        let output = rewrite_chunks(
+            &[html],
+            Settings {
+                element_content_handlers: vec![element!("head > title", |el| {
+                    el.set_attribute("foo", "bar")?;
+
+                    Ok(())
+                })],
+                document_content_handlers: vec![doc_text!(|t| {
+                    text += t.as_str();
+
+                    Ok(())
+                })],
+                ..Settings::default()
+            },
+        );
+
+        assert_eq!(
+            output,
+            r#"<head><title foo="bar">Title</title></head><body><p>Text</p></body>"#
+        );
+        assert_eq!(text, "TitleText");
+    }
+
+    #[test]
+    fn head_start_tags_after_closed_head() {
+        let mut handler_calls = 0;
+
+        // NOTE: the document text handler keeps the rewriting going, so the `head` start tag
+        // that is ignored by HTML parser is matched as any other start tag.
+        let output = rewrite_chunks(
+            &["<head></head><body><head><meta></head></body>"],
+            Settings {
+                element_content_handlers: vec![
+                    element!("head", |_| {
+                        handler_calls += 1;
+
+                        Ok(())
+                    }),
+                    element!("body", |el| {
+                        el.set_attribute("foo", "bar")?;
+
+                        Ok(())
+                    }),
+                ],
+                document_content_handlers: vec![doc_text!(|_| Ok(()))],
+                ..Settings::default()
+            },
+        );
+
+        assert_eq!(
+            output,
+            r#"<head></head><body foo="bar"><head><meta></head></body>"#
+        );
+        assert_eq!(handler_calls, 2);
+    }
+
+    #[cfg(feature = "send")]
+    #[test]
+    fn send_rewriter() {
+        use std::sync::mpsc::channel;
+        use std::thread;
+
+        let (tx, rx) = channel();
+
+        let mut rewriter = HtmlRewriter::new(
+            Settings {
+                element_content_handlers: vec![element!("span", |el| {
+                    el.set_attribute("foo", "bar")?;
+
+                    Ok(())
+                })],
+                ..Settings::default()
+            },
+            move |c: &[u8]| tx.send(c.to_vec()).unwrap(),
+        );
+
+        rewriter.write(b"<div><span>Hi").unwrap();
+
+        thread::spawn(move || {
+            rewriter.write(b"</span></div>").unwrap();
+            rewriter.end().unwrap();
+        })
+        .join()
+        .unwrap();
+
+        let output = rx.iter().flatten().collect::<Vec<_>>();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            r#"<div><span foo="bar">Hi</span></div>"#
+        );
+    }
+
     mod fatal_errors {
         use super::*;
         use crate::errors::MemoryLimitExceededError;
@@ -593,6 +1701,29 @@ mod tests {
             rewriter.end().unwrap_err();
         }
 
+        #[test]
+        fn reset_after_fatal_error() {
+            use std::cell::RefCell;
+
+            const MAX: usize = 200;
+
+            let output = RefCell::new(vec![]);
+            let output_sink = |c: &[u8]| output.borrow_mut().extend_from_slice(c);
+            let mut rewriter = create_rewriter(MAX, output_sink);
+            let chunk_1 = format!("<img alt=\"{}", "l".repeat(MAX / 2));
+            let chunk_2 = format!("{}\" />", "r".repeat(MAX / 2));
+
+            rewriter.write(chunk_1.as_bytes()).unwrap();
+            rewriter.write(chunk_2.as_bytes()).unwrap_err();
+            let _ = rewriter.reset(output_sink);
+            output.take();
+
+            rewriter.write(b"<div>Hi</div>").unwrap();
+            let _ = rewriter.end_and_reset(output_sink).unwrap();
+
+            assert_eq!(String::from_utf8(output.take()).unwrap(), "<div>Hi</div>");
+        }
+
         #[test]
         fn content_handler_error_propagation() {
             fn assert_err(
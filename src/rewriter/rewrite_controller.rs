@@ -1,32 +1,40 @@
-use super::handlers_dispatcher::{ContentHandlersDispatcher, SelectorHandlersLocator};
-use super::RewritingError;
+use super::handlers_dispatcher::ContentHandlersDispatcher;
+use super::{RewritingError, StopSignal};
+use crate::base::Shared;
 use crate::html::{LocalName, Namespace};
-use crate::rewritable_units::{DocumentEnd, Token, TokenCaptureFlags};
-use crate::selectors_vm::{AuxStartTagInfoRequest, ElementData, SelectorMatchingVm, VmError};
+use crate::memory::{MemoryLimitExceededError, SharedMemoryLimiter};
+use crate::rewritable_units::{DocumentEnd, DocumentStart, Token, TokenCaptureFlags};
+use crate::selectors_vm::{
+    AuxStartTagInfoRequest, ElementData, Program, SelectorMatchingVm, VmError,
+};
 use crate::transform_stream::*;
+use encoding_rs::Encoding;
 use hashbrown::HashSet;
-use std::cell::RefCell;
-use std::rc::Rc;
+use std::error::Error as StdError;
+use std::sync::Arc;
 
 #[derive(Default)]
 pub struct ElementDescriptor {
-    pub matched_content_handlers: HashSet<SelectorHandlersLocator>,
+    /// Indices of the matched selectors.
+    pub matched_content_handlers: HashSet<usize>,
     pub end_tag_handler_idx: Option<usize>,
     pub remove_content: bool,
+    pub content_capture_idx: Option<usize>,
 }
 
 impl ElementData for ElementDescriptor {
-    type MatchPayload = SelectorHandlersLocator;
+    type MatchPayload = usize;
 
     #[inline]
-    fn matched_payload_mut(&mut self) -> &mut HashSet<SelectorHandlersLocator> {
+    fn matched_payload_mut(&mut self) -> &mut HashSet<usize> {
         &mut self.matched_content_handlers
     }
 }
 
 pub struct HtmlRewriteController<'h> {
-    handlers_dispatcher: Rc<RefCell<ContentHandlersDispatcher<'h>>>,
+    handlers_dispatcher: Shared<ContentHandlersDispatcher<'h>>,
     selector_matching_vm: Option<SelectorMatchingVm<ElementDescriptor>>,
+    stop_signal: StopSignal,
 }
 
 impl<'h> HtmlRewriteController<'h> {
@@ -34,10 +42,25 @@ impl<'h> HtmlRewriteController<'h> {
     pub fn new(
         handlers_dispatcher: ContentHandlersDispatcher<'h>,
         selector_matching_vm: Option<SelectorMatchingVm<ElementDescriptor>>,
+        stop_signal: StopSignal,
     ) -> Self {
         HtmlRewriteController {
-            handlers_dispatcher: Rc::new(RefCell::new(handlers_dispatcher)),
+            handlers_dispatcher: Shared::new(handlers_dispatcher),
             selector_matching_vm,
+            stop_signal,
+        }
+    }
+
+    // NOTE: the program can be replaced only before any input is processed.
+    #[inline]
+    pub fn set_selector_matching_program(
+        &mut self,
+        program: Arc<Program<usize>>,
+        memory_limiter: SharedMemoryLimiter,
+        attr_decoding_encoding: Option<&'static Encoding>,
+    ) {
+        if let Some(ref mut vm) = self.selector_matching_vm {
+            *vm = SelectorMatchingVm::new(program, memory_limiter, attr_decoding_encoding);
         }
     }
 }
@@ -46,7 +69,7 @@ impl<'h> HtmlRewriteController<'h> {
 // when we hold a mutable reference for the selector matching VM.
 macro_rules! create_match_handler {
     ($self:tt) => {{
-        let handlers_dispatcher = Rc::clone(&$self.handlers_dispatcher);
+        let handlers_dispatcher = Shared::clone(&$self.handlers_dispatcher);
 
         move |m| handlers_dispatcher.borrow_mut().start_matching(m)
     }};
@@ -55,7 +78,7 @@ macro_rules! create_match_handler {
 impl<'h> HtmlRewriteController<'h> {
     #[inline]
     fn respond_to_aux_info_request(
-        aux_info_req: AuxStartTagInfoRequest<ElementDescriptor, SelectorHandlersLocator>,
+        aux_info_req: AuxStartTagInfoRequest<ElementDescriptor, usize>,
     ) -> StartTagHandlingResult<Self> {
         Err(DispatcherError::InfoRequest(Box::new(
             move |this, aux_info| {
@@ -77,6 +100,16 @@ impl<'h> HtmlRewriteController<'h> {
     }
 }
 
+// NOTE: buffering of text nodes for the text node handlers is charged to the memory limiter,
+// so exceeding the limit there is reported the same way as for the rest of the rewriter.
+#[inline]
+fn to_rewriting_error(err: Box<dyn StdError + Send + Sync>) -> RewritingError {
+    match err.downcast::<MemoryLimitExceededError>() {
+        Ok(err) => RewritingError::MemoryLimitExceeded(*err),
+        Err(err) => RewritingError::ContentHandlerError(err),
+    }
+}
+
 impl TransformController for HtmlRewriteController<'_> {
     #[inline]
     fn initial_capture_flags(&self) -> TokenCaptureFlags {
@@ -108,7 +141,7 @@ impl TransformController for HtmlRewriteController<'_> {
 
     fn handle_end_tag(&mut self, local_name: LocalName) -> TokenCaptureFlags {
         if let Some(ref mut vm) = self.selector_matching_vm {
-            let handlers_dispatcher = Rc::clone(&self.handlers_dispatcher);
+            let handlers_dispatcher = Shared::clone(&self.handlers_dispatcher);
 
             vm.exec_for_end_tag(local_name, move |elem_desc| {
                 handlers_dispatcher.borrow_mut().stop_matching(elem_desc);
@@ -128,6 +161,13 @@ impl TransformController for HtmlRewriteController<'_> {
         self.handlers_dispatcher
             .borrow_mut()
             .handle_token(token, current_element_data)
+            .map_err(to_rewriting_error)
+    }
+
+    fn handle_start(&mut self, document_start: &mut DocumentStart) -> Result<(), RewritingError> {
+        self.handlers_dispatcher
+            .borrow_mut()
+            .handle_start(document_start)
             .map_err(RewritingError::ContentHandlerError)
     }
 
@@ -140,9 +180,62 @@ impl TransformController for HtmlRewriteController<'_> {
 
     #[inline]
     fn should_emit_content(&self) -> bool {
-        !self
-            .handlers_dispatcher
-            .borrow()
-            .has_matched_elements_with_removed_content()
+        let handlers_dispatcher = self.handlers_dispatcher.borrow();
+
+        !handlers_dispatcher.has_matched_elements_with_removed_content()
+            && handlers_dispatcher.content_capture().is_none()
+    }
+
+    #[inline]
+    fn content_capture(&self) -> Option<usize> {
+        self.handlers_dispatcher.borrow().content_capture()
+    }
+
+    #[inline]
+    fn capture_content(
+        &mut self,
+        capture_idx: usize,
+        chunk: &[u8],
+        output_handler: &mut dyn FnMut(&[u8]),
+    ) -> Result<(), RewritingError> {
+        self.handlers_dispatcher
+            .borrow_mut()
+            .capture_content(capture_idx, chunk, output_handler)
+            .map_err(RewritingError::MemoryLimitExceeded)
+    }
+
+    #[inline]
+    fn take_captured_content(&mut self) -> Vec<u8> {
+        self.handlers_dispatcher
+            .borrow_mut()
+            .take_captured_content()
+    }
+
+    fn reset(&mut self) {
+        self.handlers_dispatcher.borrow_mut().reset();
+
+        if let Some(ref mut vm) = self.selector_matching_vm {
+            vm.reset();
+        }
+
+        self.stop_signal.lower();
+    }
+
+    // NOTE: apart from the explicit stop signal, the rewriting is stopped automatically once
+    // none of the handlers can be invoked for the rest of the input: there are no active
+    // handlers and none of the selectors can match anymore.
+    #[inline]
+    fn should_stop_rewriting(&self) -> bool {
+        let handlers_dispatcher = self.handlers_dispatcher.borrow();
+
+        if self.stop_signal.is_raised() {
+            !handlers_dispatcher.has_pending_end_tag_handlers()
+        } else {
+            !handlers_dispatcher.has_handlers_for_remaining_content()
+                && !self
+                    .selector_matching_vm
+                    .as_ref()
+                    .map_or(false, |vm| vm.can_match_further())
+        }
     }
 }
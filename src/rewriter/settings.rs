@@ -1,17 +1,29 @@
-use crate::rewritable_units::{Comment, Doctype, DocumentEnd, Element, EndTag, TextChunk};
+use super::stats::RewritingStats;
+use super::text_replacer::{TextPattern, TextReplacer};
+use crate::base::MaybeSend;
+use crate::rewritable_units::{
+    Comment, ContentType, Doctype, DocumentEnd, DocumentStart, Element, EndTag, TextChunk, TextNode,
+};
 use crate::selectors_vm::Selector;
 // N.B. `use crate::` will break this because the constructor is not public, only the struct itself
 use super::AsciiCompatibleEncoding;
+use encoding_rs::Encoding;
 use std::borrow::Cow;
 use std::error::Error;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
-pub(super) type HandlerResult = Result<(), Box<dyn Error + Send + Sync>>;
-pub type DoctypeHandler<'h> = Box<dyn FnMut(&mut Doctype) -> HandlerResult + 'h>;
-pub type CommentHandler<'h> = Box<dyn FnMut(&mut Comment) -> HandlerResult + 'h>;
-pub type TextHandler<'h> = Box<dyn FnMut(&mut TextChunk) -> HandlerResult + 'h>;
-pub type ElementHandler<'h> = Box<dyn FnMut(&mut Element) -> HandlerResult + 'h>;
-pub type EndTagHandler<'h> = Box<dyn FnOnce(&mut EndTag) -> HandlerResult + 'h>;
-pub type EndHandler<'h> = Box<dyn FnOnce(&mut DocumentEnd) -> HandlerResult + 'h>;
+pub(crate) type HandlerResult = Result<(), Box<dyn Error + Send + Sync>>;
+pub type DoctypeHandler<'h> = Box<dyn_maybe_send!(FnMut(&mut Doctype) -> HandlerResult + 'h)>;
+pub type CommentHandler<'h> = Box<dyn_maybe_send!(FnMut(&mut Comment) -> HandlerResult + 'h)>;
+pub type TextHandler<'h> = Box<dyn_maybe_send!(FnMut(&mut TextChunk) -> HandlerResult + 'h)>;
+pub type TextNodeHandler<'h> = Box<dyn_maybe_send!(FnMut(&mut TextNode) -> HandlerResult + 'h)>;
+pub type ElementHandler<'h> = Box<dyn_maybe_send!(FnMut(&mut Element) -> HandlerResult + 'h)>;
+pub type EndTagHandler<'h> = Box<dyn_maybe_send!(FnOnce(&mut EndTag) -> HandlerResult + 'h)>;
+pub type InnerHtmlHandler<'h> =
+    Box<dyn_maybe_send!(FnOnce(&str, &mut EndTag) -> HandlerResult + 'h)>;
+pub type StartHandler<'h> = Box<dyn_maybe_send!(FnMut(&mut DocumentStart) -> HandlerResult + 'h)>;
+pub type EndHandler<'h> = Box<dyn_maybe_send!(FnMut(&mut DocumentEnd) -> HandlerResult + 'h)>;
 
 /// Specifies element content handlers associated with a selector.
 #[derive(Default)]
@@ -19,12 +31,17 @@ pub struct ElementContentHandlers<'h> {
     pub(super) element: Option<ElementHandler<'h>>,
     pub(super) comments: Option<CommentHandler<'h>>,
     pub(super) text: Option<TextHandler<'h>>,
+    pub(super) text_node: Option<TextNodeHandler<'h>>,
+    pub(super) text_replacer: Option<TextReplacer<'h>>,
 }
 
 impl<'h> ElementContentHandlers<'h> {
     /// Sets a handler for elements matched by a selector.
     #[inline]
-    pub fn element(mut self, handler: impl FnMut(&mut Element) -> HandlerResult + 'h) -> Self {
+    pub fn element(
+        mut self,
+        handler: impl FnMut(&mut Element) -> HandlerResult + MaybeSend + 'h,
+    ) -> Self {
         self.element = Some(Box::new(handler));
 
         self
@@ -32,7 +49,10 @@ impl<'h> ElementContentHandlers<'h> {
 
     /// Sets a handler for HTML comments in the inner content of elements matched by a selector.
     #[inline]
-    pub fn comments(mut self, handler: impl FnMut(&mut Comment) -> HandlerResult + 'h) -> Self {
+    pub fn comments(
+        mut self,
+        handler: impl FnMut(&mut Comment) -> HandlerResult + MaybeSend + 'h,
+    ) -> Self {
         self.comments = Some(Box::new(handler));
 
         self
@@ -40,11 +60,99 @@ impl<'h> ElementContentHandlers<'h> {
 
     /// Sets a handler for text chunks in the inner content of elements matched by a selector.
     #[inline]
-    pub fn text(mut self, handler: impl FnMut(&mut TextChunk) -> HandlerResult + 'h) -> Self {
+    pub fn text(
+        mut self,
+        handler: impl FnMut(&mut TextChunk) -> HandlerResult + MaybeSend + 'h,
+    ) -> Self {
         self.text = Some(Box::new(handler));
 
         self
     }
+
+    /// Sets a handler for whole text nodes in the inner content of elements matched by a selector.
+    ///
+    /// Text nodes are buffered and charged to [`MemorySettings::max_allowed_memory_usage`], the
+    /// rewriting fails with [`RewritingError::MemoryLimitExceeded`] if a text node exceeds it.
+    /// If a [`text`] handler is set as well, it's invoked for each chunk of a text node before
+    /// the chunk is buffered.
+    ///
+    /// Refer to [`TextNode`] documentation for more information.
+    ///
+    /// [`MemorySettings::max_allowed_memory_usage`]: struct.MemorySettings.html#structfield.max_allowed_memory_usage
+    /// [`RewritingError::MemoryLimitExceeded`]: errors/enum.RewritingError.html#variant.MemoryLimitExceeded
+    /// [`text`]: #method.text
+    /// [`TextNode`]: html_content/struct.TextNode.html
+    #[inline]
+    pub fn text_node(
+        mut self,
+        handler: impl FnMut(&mut TextNode) -> HandlerResult + MaybeSend + 'h,
+    ) -> Self {
+        self.text_node = Some(Box::new(handler));
+
+        self
+    }
+
+    /// Replaces matches of the `pattern` in the text in the inner content of elements matched by
+    /// a selector with the content produced by `replacement` from the matched text.
+    ///
+    /// The matches are searched for across the chunks of a text node: the end of a chunk that can
+    /// be a beginning of a match is held back until the subsequent chunks complete or rule out
+    /// the match. The held back text is charged to
+    /// [`MemorySettings::max_allowed_memory_usage`].
+    ///
+    /// The replacements are made after the [`text`] handler is invoked for a chunk, so
+    /// the handler gets the original text of the chunk. If the handler replaces or removes
+    /// the chunk, the chunk's text is not searched for matches, and the text held back
+    /// from the previous chunks is inserted before the chunk.
+    ///
+    /// If a [`text_node`] handler is set as well, the whole text node is replaced with
+    /// the text in which the matches are substituted before the handler is invoked. The handler
+    /// still gets the original text, and [`TextNode::removed`] returns `true` if there are any
+    /// matches in it. Replacing or removing the text node in the handler discards the replaced
+    /// text.
+    ///
+    /// # Example
+    /// ```
+    /// use std::borrow::Cow;
+    /// use lol_html::{rewrite_str, ElementContentHandlers, RewriteStrSettings, TextPattern};
+    /// use lol_html::html_content::ContentType;
+    ///
+    /// let element_content_handlers = vec![(
+    ///     Cow::Owned("p".parse().unwrap()),
+    ///     ElementContentHandlers::default().replace_text(
+    ///         TextPattern::regex(r"\d{3}-\d{4}").unwrap(),
+    ///         |_| "<redacted>".into(),
+    ///         ContentType::Text,
+    ///     ),
+    /// )];
+    ///
+    /// let html = rewrite_str(
+    ///     "<p>Call 555-0100</p>",
+    ///     RewriteStrSettings {
+    ///         element_content_handlers,
+    ///         ..RewriteStrSettings::default()
+    ///     },
+    /// )
+    /// .unwrap();
+    ///
+    /// assert_eq!(html, "<p>Call &lt;redacted&gt;</p>");
+    /// ```
+    ///
+    /// [`MemorySettings::max_allowed_memory_usage`]: struct.MemorySettings.html#structfield.max_allowed_memory_usage
+    /// [`text`]: #method.text
+    /// [`text_node`]: #method.text_node
+    /// [`TextNode::removed`]: html_content/struct.TextNode.html#method.removed
+    #[inline]
+    pub fn replace_text(
+        mut self,
+        pattern: TextPattern,
+        replacement: impl FnMut(&str) -> String + MaybeSend + 'h,
+        content_type: ContentType,
+    ) -> Self {
+        self.text_replacer = Some(TextReplacer::new(pattern, replacement, content_type));
+
+        self
+    }
 }
 
 /// Specifies document-level content handlers.
@@ -67,6 +175,7 @@ pub struct DocumentContentHandlers<'h> {
     pub(super) doctype: Option<DoctypeHandler<'h>>,
     pub(super) comments: Option<CommentHandler<'h>>,
     pub(super) text: Option<TextHandler<'h>>,
+    pub(super) start: Option<StartHandler<'h>>,
     pub(super) end: Option<EndHandler<'h>>,
 }
 
@@ -75,7 +184,10 @@ impl<'h> DocumentContentHandlers<'h> {
     ///
     /// [document type declaration]: https://developer.mozilla.org/en-US/docs/Glossary/Doctype
     #[inline]
-    pub fn doctype(mut self, handler: impl FnMut(&mut Doctype) -> HandlerResult + 'h) -> Self {
+    pub fn doctype(
+        mut self,
+        handler: impl FnMut(&mut Doctype) -> HandlerResult + MaybeSend + 'h,
+    ) -> Self {
         self.doctype = Some(Box::new(handler));
 
         self
@@ -83,7 +195,10 @@ impl<'h> DocumentContentHandlers<'h> {
 
     /// Sets a handler for all HTML comments present in the input HTML markup.
     #[inline]
-    pub fn comments(mut self, handler: impl FnMut(&mut Comment) -> HandlerResult + 'h) -> Self {
+    pub fn comments(
+        mut self,
+        handler: impl FnMut(&mut Comment) -> HandlerResult + MaybeSend + 'h,
+    ) -> Self {
         self.comments = Some(Box::new(handler));
 
         self
@@ -91,15 +206,32 @@ impl<'h> DocumentContentHandlers<'h> {
 
     /// Sets a handler for all text chunks present in the input HTML markup.
     #[inline]
-    pub fn text(mut self, handler: impl FnMut(&mut TextChunk) -> HandlerResult + 'h) -> Self {
+    pub fn text(
+        mut self,
+        handler: impl FnMut(&mut TextChunk) -> HandlerResult + MaybeSend + 'h,
+    ) -> Self {
         self.text = Some(Box::new(handler));
 
         self
     }
 
+    /// Sets a handler for the document start, which is called before any output is produced.
+    #[inline]
+    pub fn start(
+        mut self,
+        handler: impl FnMut(&mut DocumentStart) -> HandlerResult + MaybeSend + 'h,
+    ) -> Self {
+        self.start = Some(Box::new(handler));
+
+        self
+    }
+
     /// Sets a handler for the document end, which is called after the last chunk is processed.
     #[inline]
-    pub fn end(mut self, handler: impl FnMut(&mut DocumentEnd) -> HandlerResult + 'h) -> Self {
+    pub fn end(
+        mut self,
+        handler: impl FnMut(&mut DocumentEnd) -> HandlerResult + MaybeSend + 'h,
+    ) -> Self {
         self.end = Some(Box::new(handler));
 
         self
@@ -181,6 +313,39 @@ macro_rules! text {
     };
 }
 
+/// A convenience macro to construct a rewriting handler for whole text nodes in the inner content
+/// of an element that can be matched by the specified CSS selector.
+///
+/// # Example
+/// ```
+/// use lol_html::{rewrite_str, text_node, RewriteStrSettings};
+/// use lol_html::html_content::ContentType;
+///
+/// let html = rewrite_str(
+///     r#"<span>Hello</span>"#,
+///     RewriteStrSettings {
+///         element_content_handlers: vec![
+///             text_node!("span", |t| {
+///                 let greeting = format!("{} world", t.as_str());
+///
+///                 t.replace(&greeting, ContentType::Text);
+///
+///                 Ok(())
+///             })
+///         ],
+///         ..RewriteStrSettings::default()
+///     }
+/// ).unwrap();
+///
+/// assert_eq!(html, r#"<span>Hello world</span>"#);
+/// ```
+#[macro_export(local_inner_macros)]
+macro_rules! text_node {
+    ($selector:expr, $handler:expr) => {
+        __element_content_handler!($selector, text_node, $handler);
+    };
+}
+
 /// A convenience macro to construct a rewriting handler for HTML comments in the inner content of
 /// an element that can be matched by the specified CSS selector.
 ///
@@ -312,6 +477,38 @@ macro_rules! doc_comments {
     };
 }
 
+/// A convenience macro to construct a rewriting handler for the start of the document.
+///
+/// This handler will be called before any output is produced by the rewriter.
+///
+/// # Example
+/// ```
+/// use lol_html::{rewrite_str, start, RewriteStrSettings};
+/// use lol_html::html_content::ContentType;
+///
+/// let html = rewrite_str(
+///     r#"<span>foo</span>"#,
+///     RewriteStrSettings {
+///         document_content_handlers: vec![
+///             start!(|start| {
+///                 start.prepend("<!-- banner -->", ContentType::Html);
+///
+///                 Ok(())
+///             })
+///         ],
+///         ..RewriteStrSettings::default()
+///     }
+/// ).unwrap();
+///
+/// assert_eq!(html, r#"<!-- banner --><span>foo</span>"#);
+/// ```
+#[macro_export(local_inner_macros)]
+macro_rules! start {
+    ($handler:expr) => {
+        __document_content_handler!(start, $handler);
+    };
+}
+
 /// A convenience macro to construct a rewriting handler for the end of the document.
 ///
 /// This handler will only be called after the rewriter has finished processing the final chunk.
@@ -407,6 +604,154 @@ impl Default for MemorySettings {
     }
 }
 
+/// Specifies transcoding of the input for [`HtmlRewriter`].
+///
+/// The rewriter can parse only the input in ASCII-compatible encodings. The input in other
+/// encodings (e.g. `UTF-16LE`) is transcoded into `UTF-8` before the parser, so the rewriter
+/// operates on `UTF-8` content. Code units that are split between the input chunks are handled
+/// transparently.
+///
+/// [`HtmlRewriter`]: struct.HtmlRewriter.html
+///
+/// # Example
+/// ```
+/// use lol_html::{element, HtmlRewriter, Settings, Transcoding};
+///
+/// let input: Vec<u8> = "<b>Hello</b>"
+///     .encode_utf16()
+///     .flat_map(|c| c.to_le_bytes().to_vec())
+///     .collect();
+///
+/// let mut output = vec![];
+///
+/// {
+///     let mut rewriter = HtmlRewriter::new(
+///         Settings {
+///             element_content_handlers: vec![element!("b", |el| {
+///                 el.set_tag_name("strong").unwrap();
+///
+///                 Ok(())
+///             })],
+///             transcoding: Some(Transcoding {
+///                 encoding: encoding_rs::UTF_16LE,
+///                 transcode_output: false,
+///             }),
+///             ..Settings::default()
+///         },
+///         |c: &[u8]| output.extend_from_slice(c),
+///     );
+///
+///     rewriter.write(&input).unwrap();
+///     rewriter.end().unwrap();
+/// }
+///
+/// assert_eq!(String::from_utf8(output).unwrap(), "<strong>Hello</strong>");
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Transcoding {
+    /// Specifies the [character encoding] of the input.
+    ///
+    /// Can be any of the web-compatible encodings, including `UTF-16LE`, `UTF-16BE`,
+    /// `ISO-2022-JP` and `replacement`.
+    ///
+    /// [character encoding]: https://developer.mozilla.org/en-US/docs/Glossary/character_encoding
+    pub encoding: &'static Encoding,
+
+    /// If set to `true` the output is transcoded back to the [`encoding`]. Otherwise, the output
+    /// is produced in `UTF-8`.
+    ///
+    /// Note that the `replacement` encoding can't be used for the output, so the output is
+    /// always produced in `UTF-8` for it.
+    ///
+    /// [`encoding`]: #structfield.encoding
+    pub transcode_output: bool,
+}
+
+/// A signal that stops the rewriting of the rest of the document.
+///
+/// Once the signal is raised, the rewriter stops parsing and passes the rest of the input to
+/// the output as is. The signal can be cloned and raised from content handlers, e.g. once all
+/// the content the handlers were looking for is rewritten.
+///
+/// The rewriting is stopped on the next tag boundary after the signal is raised. Elements with
+/// pending end tag mutations or handlers (including the ones with removed or captured inner
+/// content) require their end tags to be processed, so the rewriting continues until all of
+/// them are closed. Document end handlers are still invoked once the input ends.
+///
+/// The rewriting can be also stopped from the outside with [`HtmlRewriter::stop_rewriting`].
+///
+/// Note that there is no need to raise the signal if none of the handlers can be invoked for the
+/// rest of the document: the rewriter stops the rewriting automatically once there are no
+/// active handlers and none of the selectors can match anymore. Currently, the only selectors
+/// that are detected as such are the ones that require `html`, `head` or `body` elements that
+/// have already been created (e.g. `head > meta` or `html > head > meta` can't match once the
+/// document's `head` element is closed), since any other start tags of these elements are
+/// ignored by the HTML parser.
+///
+/// [`HtmlRewriter::stop_rewriting`]: struct.HtmlRewriter.html#method.stop_rewriting
+///
+/// # Example
+/// ```
+/// use lol_html::{element, HtmlRewriter, Settings, StopSignal};
+/// use lol_html::html_content::ContentType;
+///
+/// let stop_signal = StopSignal::new();
+/// let handler_stop_signal = stop_signal.clone();
+///
+/// let mut output = vec![];
+///
+/// {
+///     let mut rewriter = HtmlRewriter::new(
+///         Settings {
+///             element_content_handlers: vec![element!("head, link", move |el| {
+///                 el.append("<script></script>", ContentType::Html);
+///                 handler_stop_signal.raise();
+///
+///                 Ok(())
+///             })],
+///             stop_signal: Some(stop_signal),
+///             ..Settings::default()
+///         },
+///         |c: &[u8]| output.extend_from_slice(c),
+///     );
+///
+///     rewriter.write(b"<head><title></title></head><body><link></body>").unwrap();
+///     rewriter.end().unwrap();
+/// }
+///
+/// assert_eq!(
+///     String::from_utf8(output).unwrap(),
+///     "<head><title></title><script></script></head><body><link></body>"
+/// );
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct StopSignal(Arc<AtomicBool>);
+
+impl StopSignal {
+    /// Creates a new signal that is not raised.
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Raises the signal.
+    #[inline]
+    pub fn raise(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns `true` if the signal is raised.
+    #[inline]
+    pub fn is_raised(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    #[inline]
+    pub(crate) fn lower(&self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+}
+
 /// Specifies settings for [`HtmlRewriter`].
 ///
 /// [`HtmlRewriter`]: struct.HtmlRewriter.html
@@ -469,17 +814,103 @@ pub struct Settings<'h, 's> {
     /// Specifies the [character encoding] for the input and the output of the rewriter.
     ///
     /// Can be a [label] for any of the web-compatible encodings with an exception for `UTF-16LE`,
-    /// `UTF-16BE`, `ISO-2022-JP` and `replacement` (these non-ASCII-compatible encodings
-    /// are not supported).
+    /// `UTF-16BE`, `ISO-2022-JP` and `replacement` (the input in these non-ASCII-compatible
+    /// encodings should be transcoded with the [`transcoding`] setting).
     ///
     /// [character encoding]: https://developer.mozilla.org/en-US/docs/Glossary/character_encoding
     /// [label]: https://encoding.spec.whatwg.org/#names-and-labels
+    /// [`transcoding`]: #structfield.transcoding
     ///
     /// ### Default
     ///
     /// `"utf-8"` when constructed with `Settings::default()`.
     pub encoding: AsciiCompatibleEncoding,
 
+    /// If set to `true` the rewriter determines the character encoding of the input from its
+    /// [byte order mark] or from a `<meta charset>` or `<meta http-equiv="Content-Type">` tag
+    /// in the first 1024 bytes of the input, following the HTML [prescan] algorithm.
+    ///
+    /// The input is buffered until the encoding is determined, so no content handlers are
+    /// invoked and no output is produced before that. [`encoding`] is used if the input doesn't
+    /// declare its encoding. The encoding the rewriter ends up with can be obtained with
+    /// [`HtmlRewriter::encoding`].
+    ///
+    /// Note that the encoding can't be changed after the input is processed, so a `<meta>` tag
+    /// that's located after the first 1024 bytes of the input is ignored.
+    ///
+    /// The input with a `UTF-16` byte order mark is transcoded as if it was specified in
+    /// [`transcoding`]. If [`transcoding`] is specified as well, its encoding is used if the
+    /// input doesn't declare its encoding.
+    ///
+    /// [byte order mark]: https://en.wikipedia.org/wiki/Byte_order_mark
+    /// [prescan]: https://html.spec.whatwg.org/multipage/parsing.html#prescan-a-byte-stream-to-determine-its-encoding
+    /// [`encoding`]: #structfield.encoding
+    /// [`HtmlRewriter::encoding`]: struct.HtmlRewriter.html#method.encoding
+    /// [`transcoding`]: #structfield.transcoding
+    ///
+    /// ### Default
+    ///
+    /// `false` when constructed with `Settings::default()`.
+    pub detect_encoding: bool,
+
+    /// Specifies transcoding of the input in an encoding that is not ASCII-compatible.
+    ///
+    /// If specified, [`encoding`] is ignored. Refer to [`Transcoding`] documentation for
+    /// more information.
+    ///
+    /// [`encoding`]: #structfield.encoding
+    /// [`Transcoding`]: struct.Transcoding.html
+    ///
+    /// ### Default
+    ///
+    /// `None` when constructed with `Settings::default()`.
+    pub transcoding: Option<Transcoding>,
+
+    /// Specifies the [character encoding] of the output, if it should differ from the encoding
+    /// of the input.
+    ///
+    /// Both the passthrough content and the content inserted by the content handlers are
+    /// transcoded into this encoding. Note that characters of the inserted content that can't be
+    /// represented in the input encoding are inserted as numeric character references.
+    ///
+    /// Takes precedence over [`Transcoding::transcode_output`].
+    ///
+    /// [character encoding]: https://developer.mozilla.org/en-US/docs/Glossary/character_encoding
+    /// [`Transcoding::transcode_output`]: struct.Transcoding.html#structfield.transcode_output
+    ///
+    /// ### Default
+    ///
+    /// `None` when constructed with `Settings::default()`.
+    pub output_encoding: Option<&'static Encoding>,
+
+    /// If set to `true`, character encoding declarations of the document (`<meta charset>` and
+    /// `<meta http-equiv="Content-Type">` tags) are rewritten to match [`output_encoding`].
+    ///
+    /// Has no effect if [`output_encoding`] is not specified.
+    ///
+    /// Note that `<meta>` tags can appear anywhere in the document, so the rewriting is never
+    /// stopped automatically if this setting is enabled (see [`StopSignal`]).
+    ///
+    /// [`output_encoding`]: #structfield.output_encoding
+    /// [`StopSignal`]: struct.StopSignal.html
+    ///
+    /// ### Default
+    ///
+    /// `false` when constructed with `Settings::default()`.
+    pub rewrite_meta_charset: bool,
+
+    /// If set to `true`, attribute selectors match against attribute values with decoded
+    /// [character references], e.g. `a[href="/?a=1&b=2"]` matches `<a href="/?a=1&amp;b=2">`.
+    ///
+    /// Otherwise, attribute selectors match against the values as they appear in the input.
+    ///
+    /// [character references]: https://html.spec.whatwg.org/multipage/syntax.html#character-references
+    ///
+    /// ### Default
+    ///
+    /// `false` when constructed with `Settings::default()`.
+    pub match_decoded_attribute_values: bool,
+
     /// Specifies the memory settings.
     pub memory_settings: MemorySettings,
 
@@ -516,6 +947,28 @@ pub struct Settings<'h, 's> {
     ///
     /// `true` when constructed with `Settings::default()`.
     pub strict: bool,
+
+    /// Specifies a signal that stops the rewriting of the rest of the document once raised.
+    ///
+    /// Refer to [`StopSignal`] documentation for more information.
+    ///
+    /// [`StopSignal`]: struct.StopSignal.html
+    ///
+    /// ### Default
+    ///
+    /// `None` when constructed with `Settings::default()`.
+    pub stop_signal: Option<StopSignal>,
+
+    /// Specifies the statistics collected during the rewriting.
+    ///
+    /// Refer to [`RewritingStats`] documentation for more information.
+    ///
+    /// [`RewritingStats`]: struct.RewritingStats.html
+    ///
+    /// ### Default
+    ///
+    /// `None` when constructed with `Settings::default()`.
+    pub stats: Option<RewritingStats>,
 }
 
 impl Default for Settings<'_, '_> {
@@ -525,8 +978,15 @@ impl Default for Settings<'_, '_> {
             element_content_handlers: vec![],
             document_content_handlers: vec![],
             encoding: AsciiCompatibleEncoding(encoding_rs::UTF_8),
+            detect_encoding: false,
+            transcoding: None,
+            output_encoding: None,
+            rewrite_meta_charset: false,
+            match_decoded_attribute_values: false,
             memory_settings: MemorySettings::default(),
             strict: true,
+            stop_signal: None,
+            stats: None,
         }
     }
 }
@@ -0,0 +1,226 @@
+use crate::base::Shared;
+
+#[derive(Debug, Default)]
+struct Counters {
+    bytes_in: usize,
+    bytes_out: usize,
+    lexed_token_count: usize,
+    scanned_tag_count: usize,
+    selector_match_counts: Vec<usize>,
+    handler_invocation_count: usize,
+    peak_memory_usage: usize,
+    buffering_count: usize,
+    largest_buffered_tag_size: usize,
+}
+
+/// Statistics of the rewriting process.
+///
+/// The statistics are collected by the rewriter once they are specified in the
+/// [`stats`] setting. The clones of the statistics refer to the same counters, so they can be
+/// queried at any point, including from content handlers and after the rewriting is finished.
+///
+/// The counters are accumulated across all the documents processed by the rewriter if it's
+/// [reused], or across all the rewriters the statistics are specified for, given that the
+/// rewriters use the same selectors. The counters can be cleared with the [`clear`] method.
+///
+/// [`stats`]: struct.Settings.html#structfield.stats
+/// [reused]: struct.HtmlRewriter.html#method.reset
+/// [`clear`]: #method.clear
+///
+/// # Example
+/// ```
+/// use lol_html::{element, HtmlRewriter, RewritingStats, Settings};
+///
+/// let stats = RewritingStats::new();
+/// let mut output = vec![];
+///
+/// {
+///     let mut rewriter = HtmlRewriter::new(
+///         Settings {
+///             element_content_handlers: vec![
+///                 element!("a[href]", |el| {
+///                     el.set_attribute("rel", "nofollow")?;
+///
+///                     Ok(())
+///                 }),
+///                 element!("img", |el| {
+///                     el.set_attribute("loading", "lazy")?;
+///
+///                     Ok(())
+///                 }),
+///             ],
+///             stats: Some(stats.clone()),
+///             ..Settings::default()
+///         },
+///         |c: &[u8]| output.extend_from_slice(c),
+///     );
+///
+///     rewriter.write(b"<a href='/'>Home</a><a href='/about'>About</a>").unwrap();
+///     rewriter.end().unwrap();
+/// }
+///
+/// assert_eq!(stats.bytes_in(), 46);
+/// assert_eq!(stats.bytes_out(), output.len());
+/// assert_eq!(stats.selector_match_counts(), vec![2, 0]);
+/// assert_eq!(stats.handler_invocation_count(), 2);
+/// ```
+#[derive(Clone, Debug)]
+pub struct RewritingStats(Shared<Counters>);
+
+impl Default for RewritingStats {
+    #[inline]
+    fn default() -> Self {
+        RewritingStats(Shared::new(Counters::default()))
+    }
+}
+
+impl RewritingStats {
+    /// Creates new statistics with all the counters set to zero.
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of bytes written to the rewriter.
+    #[inline]
+    pub fn bytes_in(&self) -> usize {
+        self.0.borrow().bytes_in
+    }
+
+    /// Returns the number of bytes produced by the rewriter to the output sink.
+    #[inline]
+    pub fn bytes_out(&self) -> usize {
+        self.0.borrow().bytes_out
+    }
+
+    /// Returns the number of tokens produced by the full-featured lexer.
+    ///
+    /// The lexer is used only when content handlers need the tokens, e.g. for the matched
+    /// elements and their content. Otherwise, the rewriter uses the lightweight tag scanner
+    /// that only looks for the tag names.
+    #[inline]
+    pub fn lexed_token_count(&self) -> usize {
+        self.0.borrow().lexed_token_count
+    }
+
+    /// Returns the number of tags found by the tag scanner.
+    ///
+    /// Note that if a handler matches a scanned tag, the tag is lexed as well.
+    #[inline]
+    pub fn scanned_tag_count(&self) -> usize {
+        self.0.borrow().scanned_tag_count
+    }
+
+    /// Returns the number of matched elements for each of the selectors, in the order the
+    /// selectors are specified in the [`element_content_handlers`] setting.
+    ///
+    /// [`element_content_handlers`]: struct.Settings.html#structfield.element_content_handlers
+    #[inline]
+    pub fn selector_match_counts(&self) -> Vec<usize> {
+        self.0.borrow().selector_match_counts.clone()
+    }
+
+    /// Returns the number of content handler invocations.
+    #[inline]
+    pub fn handler_invocation_count(&self) -> usize {
+        self.0.borrow().handler_invocation_count
+    }
+
+    /// Returns the peak memory usage as accounted for the [`max_allowed_memory_usage`] limit.
+    ///
+    /// [`max_allowed_memory_usage`]: struct.MemorySettings.html#structfield.max_allowed_memory_usage
+    #[inline]
+    pub fn peak_memory_usage(&self) -> usize {
+        self.0.borrow().peak_memory_usage
+    }
+
+    /// Returns the number of times an unfinished tag (or other markup, like a comment) at the
+    /// end of an input chunk was buffered until the next chunk.
+    #[inline]
+    pub fn buffering_count(&self) -> usize {
+        self.0.borrow().buffering_count
+    }
+
+    /// Returns the size of the largest buffered unfinished tag in bytes.
+    #[inline]
+    pub fn largest_buffered_tag_size(&self) -> usize {
+        self.0.borrow().largest_buffered_tag_size
+    }
+
+    /// Sets all the counters to zero.
+    pub fn clear(&self) {
+        let mut counters = self.0.borrow_mut();
+        let selector_count = counters.selector_match_counts.len();
+
+        *counters = Counters::default();
+        counters.selector_match_counts.resize(selector_count, 0);
+    }
+
+    #[inline]
+    pub(crate) fn init_selector_match_counts(&self, selector_count: usize) {
+        let mut counters = self.0.borrow_mut();
+
+        if counters.selector_match_counts.len() < selector_count {
+            counters.selector_match_counts.resize(selector_count, 0);
+        }
+    }
+
+    #[inline]
+    pub(crate) fn record_bytes_in(&self, byte_count: usize) {
+        self.0.borrow_mut().bytes_in += byte_count;
+    }
+
+    #[inline]
+    pub(crate) fn record_bytes_out(&self, byte_count: usize) {
+        self.0.borrow_mut().bytes_out += byte_count;
+    }
+
+    #[inline]
+    pub(crate) fn record_lexed_token(&self) {
+        self.0.borrow_mut().lexed_token_count += 1;
+    }
+
+    #[inline]
+    pub(crate) fn record_scanned_tag(&self) {
+        self.0.borrow_mut().scanned_tag_count += 1;
+    }
+
+    // NOTE: internal selectors (e.g. the one for the `rewrite_meta_charset` setting) follow
+    // the selectors specified by the user, so their matches are not counted.
+    #[inline]
+    pub(crate) fn record_selector_match(&self, selector_idx: usize) {
+        if let Some(count) = self
+            .0
+            .borrow_mut()
+            .selector_match_counts
+            .get_mut(selector_idx)
+        {
+            *count += 1;
+        }
+    }
+
+    #[inline]
+    pub(crate) fn record_handler_invocations(&self, count: usize) {
+        self.0.borrow_mut().handler_invocation_count += count;
+    }
+
+    #[inline]
+    pub(crate) fn record_memory_usage(&self, usage: usize) {
+        let mut counters = self.0.borrow_mut();
+
+        if usage > counters.peak_memory_usage {
+            counters.peak_memory_usage = usage;
+        }
+    }
+
+    #[inline]
+    pub(crate) fn record_buffering(&self, buffered_byte_count: usize) {
+        let mut counters = self.0.borrow_mut();
+
+        counters.buffering_count += 1;
+
+        if buffered_byte_count > counters.largest_buffered_tag_size {
+            counters.largest_buffered_tag_size = buffered_byte_count;
+        }
+    }
+}
@@ -0,0 +1,474 @@
+use super::{HtmlRewriter, RewritingError, Settings};
+use crate::base::Shared;
+use crate::transform_stream::OutputSink;
+use std::io::{self, Read, Write};
+
+const READ_BUFFER_SIZE: usize = 8192;
+
+pub(super) struct SharedOutputBuffer(pub Shared<Vec<u8>>);
+
+impl OutputSink for SharedOutputBuffer {
+    #[inline]
+    fn handle_chunk(&mut self, chunk: &[u8]) {
+        self.0.borrow_mut().extend_from_slice(chunk);
+    }
+}
+
+/// Converts the error into an [`io::Error`] of the [`Other`] kind. The original
+/// error can be obtained with [`get_ref`] or [`into_inner`].
+///
+/// [`io::Error`]: https://doc.rust-lang.org/std/io/struct.Error.html
+/// [`Other`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.Other
+/// [`get_ref`]: https://doc.rust-lang.org/std/io/struct.Error.html#method.get_ref
+/// [`into_inner`]: https://doc.rust-lang.org/std/io/struct.Error.html#method.into_inner
+impl From<RewritingError> for io::Error {
+    #[inline]
+    fn from(err: RewritingError) -> Self {
+        io::Error::new(io::ErrorKind::Other, err)
+    }
+}
+
+#[inline]
+pub(super) fn to_io_error(err: RewritingError) -> io::Error {
+    io::Error::from(err)
+}
+
+#[inline]
+pub(super) fn closed_error() -> io::Error {
+    io::Error::new(io::ErrorKind::Other, "The rewriter has been closed.")
+}
+
+#[inline]
+fn poisoned_error() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Other,
+        "Attempt to use the HtmlRewriter after a fatal error.",
+    )
+}
+
+/// An [`OutputSink`] that writes the rewriter's output to an [`io::Write`] implementor.
+///
+/// [`HtmlRewriter`] with this output sink implements [`io::Write`] itself, so it can be used
+/// in `std::io` pipelines. Since output sinks can't fail, the first error produced by the
+/// underlying writer is stored and then returned from the next [`io::Write`] method call or from
+/// [`finish`].
+///
+/// [`OutputSink`]: trait.OutputSink.html
+/// [`HtmlRewriter`]: struct.HtmlRewriter.html
+/// [`io::Write`]: https://doc.rust-lang.org/std/io/trait.Write.html
+/// [`finish`]: struct.HtmlRewriter.html#method.finish
+///
+/// # Example
+/// ```
+/// use lol_html::{element, HtmlRewriter, IoOutputSink, Settings};
+/// use std::io::{self, Write};
+///
+/// let mut rewriter = HtmlRewriter::new(
+///     Settings {
+///         element_content_handlers: vec![element!("b", |el| {
+///             el.set_tag_name("strong").unwrap();
+///
+///             Ok(())
+///         })],
+///         ..Settings::default()
+///     },
+///     IoOutputSink::new(vec![]),
+/// );
+///
+/// io::copy(&mut &b"<b>Hello</b>"[..], &mut rewriter).unwrap();
+///
+/// let output = rewriter.finish().unwrap();
+///
+/// assert_eq!(String::from_utf8(output).unwrap(), "<strong>Hello</strong>");
+/// ```
+#[derive(Debug)]
+pub struct IoOutputSink<W: Write> {
+    writer: Option<W>,
+    error: Option<io::Error>,
+}
+
+impl<W: Write> IoOutputSink<W> {
+    /// Creates a new output sink writing to the `writer`.
+    #[inline]
+    pub fn new(writer: W) -> Self {
+        IoOutputSink {
+            writer: Some(writer),
+            error: None,
+        }
+    }
+
+    #[inline]
+    fn take_error(&mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<W: Write> OutputSink for IoOutputSink<W> {
+    fn handle_chunk(&mut self, chunk: &[u8]) {
+        // NOTE: skip the finalizing chunk and don't write anything
+        // after the first error.
+        if chunk.is_empty() || self.error.is_some() {
+            return;
+        }
+
+        if let Some(ref mut writer) = self.writer {
+            if let Err(err) = writer.write_all(chunk) {
+                self.error = Some(err);
+            }
+        }
+    }
+}
+
+impl<W: Write> HtmlRewriter<'_, IoOutputSink<W>> {
+    fn write_io(&mut self, data: &[u8]) -> io::Result<()> {
+        if self.poisoned {
+            return Err(poisoned_error());
+        }
+
+        let res = self
+            .write_input(data)
+            .map_err(to_io_error)
+            .and_then(|_| self.with_output_sink(IoOutputSink::take_error));
+
+        if res.is_err() {
+            self.poisoned = true;
+        }
+
+        res
+    }
+
+    /// Finalizes the rewriting process, flushes the underlying writer and returns it.
+    ///
+    /// This is an [`io::Error`]-returning counterpart of [`end`] that should be called
+    /// once the last chunk of the input is written.
+    ///
+    /// [`io::Error`]: https://doc.rust-lang.org/std/io/struct.Error.html
+    /// [`end`]: struct.HtmlRewriter.html#method.end
+    pub fn finish(mut self) -> io::Result<W> {
+        if self.poisoned {
+            return Err(poisoned_error());
+        }
+
+        self.end_input().map_err(to_io_error)?;
+
+        self.with_output_sink(|sink| {
+            sink.take_error()?;
+
+            // NOTE: the writer is taken only once, since `finish` consumes the rewriter.
+            let mut writer = sink.writer.take().expect("Writer should be available");
+
+            writer.flush()?;
+
+            Ok(writer)
+        })
+    }
+}
+
+/// Writes the input to the rewriter.
+///
+/// Rewriting errors are converted to [`io::Error`]s. Note that [`flush`] flushes only the
+/// underlying writer: the rewriter may still hold a part of the written input if it's
+/// not sufficient to produce the output.
+///
+/// [`io::Error`]: https://doc.rust-lang.org/std/io/struct.Error.html
+/// [`flush`]: https://doc.rust-lang.org/std/io/trait.Write.html#tymethod.flush
+impl<W: Write> Write for HtmlRewriter<'_, IoOutputSink<W>> {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_io(buf).map(|_| buf.len())
+    }
+
+    #[inline]
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.write_io(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.poisoned {
+            return Err(poisoned_error());
+        }
+
+        self.with_output_sink(|sink| {
+            sink.take_error()?;
+
+            match sink.writer {
+                Some(ref mut writer) => writer.flush(),
+                None => Ok(()),
+            }
+        })
+    }
+}
+
+/// A reader that rewrites the HTML read from the underlying reader.
+///
+/// The input is read from the underlying reader in chunks and the rewritten output
+/// is yielded as you read from the `RewritingReader`. Rewriting errors are converted
+/// to [`io::Error`]s.
+///
+/// [`io::Error`]: https://doc.rust-lang.org/std/io/struct.Error.html
+///
+/// # Example
+/// ```
+/// use lol_html::{element, RewritingReader, Settings};
+/// use std::io::Read;
+///
+/// let mut reader = RewritingReader::new(
+///     Settings {
+///         element_content_handlers: vec![element!("b", |el| {
+///             el.set_tag_name("strong").unwrap();
+///
+///             Ok(())
+///         })],
+///         ..Settings::default()
+///     },
+///     &b"<b>Hello</b>"[..],
+/// );
+///
+/// let mut output = String::new();
+///
+/// reader.read_to_string(&mut output).unwrap();
+///
+/// assert_eq!(output, "<strong>Hello</strong>");
+/// ```
+pub struct RewritingReader<'h, R: Read> {
+    reader: R,
+    rewriter: Option<HtmlRewriter<'h, SharedOutputBuffer>>,
+    output: Shared<Vec<u8>>,
+    output_pos: usize,
+    input_buffer: Box<[u8]>,
+    finished: bool,
+}
+
+impl<'h, R: Read> RewritingReader<'h, R> {
+    /// Constructs a new reader that rewrites the input from the `reader` with
+    /// the provided `settings`.
+    pub fn new<'s>(settings: Settings<'h, 's>, reader: R) -> Self {
+        let output = Shared::new(Vec::default());
+        let rewriter = HtmlRewriter::new(settings, SharedOutputBuffer(Shared::clone(&output)));
+
+        RewritingReader {
+            reader,
+            rewriter: Some(rewriter),
+            output,
+            output_pos: 0,
+            input_buffer: vec![0; READ_BUFFER_SIZE].into(),
+            finished: false,
+        }
+    }
+
+    /// Returns the underlying reader.
+    #[inline]
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn rewrite_next_chunk(&mut self) -> io::Result<()> {
+        let rewriter = match self.rewriter.as_mut() {
+            Some(rewriter) => rewriter,
+            None => return Err(closed_error()),
+        };
+
+        let byte_count = self.reader.read(&mut self.input_buffer)?;
+
+        let res = if byte_count == 0 {
+            self.finished = true;
+            self.rewriter.take().map_or(Ok(()), HtmlRewriter::end)
+        } else {
+            rewriter.write(&self.input_buffer[..byte_count])
+        };
+
+        if let Err(err) = res {
+            self.rewriter = None;
+
+            return Err(to_io_error(err));
+        }
+
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for RewritingReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            {
+                let mut output = self.output.borrow_mut();
+
+                if self.output_pos < output.len() {
+                    let available = &output[self.output_pos..];
+                    let byte_count = available.len().min(buf.len());
+
+                    buf[..byte_count].copy_from_slice(&available[..byte_count]);
+                    self.output_pos += byte_count;
+
+                    return Ok(byte_count);
+                }
+
+                output.clear();
+                self.output_pos = 0;
+            }
+
+            if self.finished {
+                return Ok(0);
+            }
+
+            self.rewrite_next_chunk()?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::html_content::ContentType;
+    use crate::{element, text};
+
+    fn settings() -> Settings<'static, 'static> {
+        Settings {
+            element_content_handlers: vec![
+                element!("b", |el| {
+                    el.set_tag_name("strong").unwrap();
+
+                    Ok(())
+                }),
+                text!("i", |t| {
+                    if t.last_in_text_node() {
+                        t.after("!", ContentType::Text);
+                    }
+
+                    Ok(())
+                }),
+            ],
+            ..Settings::default()
+        }
+    }
+
+    fn failing_settings() -> Settings<'static, 'static> {
+        Settings {
+            element_content_handlers: vec![element!("div", |_| Err("Oops".into()))],
+            ..Settings::default()
+        }
+    }
+
+    struct FailingWriter;
+
+    impl Write for FailingWriter {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::new(io::ErrorKind::BrokenPipe, "Broken"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // NOTE: yields the input one byte at a time.
+    struct ByteReader<'i>(&'i [u8]);
+
+    impl Read for ByteReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.0.is_empty() || buf.is_empty() {
+                return Ok(0);
+            }
+
+            buf[0] = self.0[0];
+            self.0 = &self.0[1..];
+
+            Ok(1)
+        }
+    }
+
+    #[test]
+    fn rewriter_as_io_write() {
+        let mut rewriter = HtmlRewriter::new(settings(), IoOutputSink::new(vec![]));
+
+        for chunk in &["<b>Hello", "</b> <", "i>world</i>"] {
+            rewriter.write_all(chunk.as_bytes()).unwrap();
+        }
+
+        rewriter.flush().unwrap();
+
+        let output = rewriter.finish().unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "<strong>Hello</strong> <i>world!</i>"
+        );
+    }
+
+    #[test]
+    fn rewriting_error_to_io_error() {
+        let mut rewriter = HtmlRewriter::new(failing_settings(), IoOutputSink::new(vec![]));
+        let err = rewriter.write_all(b"<div>").unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::Other);
+
+        let inner = err.get_ref().unwrap();
+
+        match inner.downcast_ref::<RewritingError>() {
+            Some(RewritingError::ContentHandlerError(e)) => assert_eq!(e.to_string(), "Oops"),
+            _ => panic!("Expected content handler error"),
+        }
+
+        // NOTE: the rewriter is poisoned now, but it shouldn't panic.
+        assert!(rewriter.write_all(b"foo").is_err());
+        assert!(rewriter.finish().is_err());
+    }
+
+    #[test]
+    fn writer_error() {
+        let mut rewriter = HtmlRewriter::new(settings(), IoOutputSink::new(FailingWriter));
+        let err = rewriter.write_all(b"<b>Hello</b>").unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+    }
+
+    #[test]
+    fn rewriting_reader() {
+        let mut output = String::new();
+        let html = "<b>Hello</b> <i>world</i>";
+
+        RewritingReader::new(settings(), html.as_bytes())
+            .read_to_string(&mut output)
+            .unwrap();
+
+        assert_eq!(output, "<strong>Hello</strong> <i>world!</i>");
+
+        let mut output = vec![];
+        let mut reader = RewritingReader::new(settings(), ByteReader(html.as_bytes()));
+        let mut buf = [0; 3];
+
+        loop {
+            let byte_count = reader.read(&mut buf).unwrap();
+
+            if byte_count == 0 {
+                break;
+            }
+
+            output.extend_from_slice(&buf[..byte_count]);
+        }
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "<strong>Hello</strong> <i>world!</i>"
+        );
+    }
+
+    #[test]
+    fn rewriting_reader_error() {
+        let mut reader = RewritingReader::new(failing_settings(), &b"<div>"[..]);
+        let err = reader.read(&mut [0; 16]).unwrap_err();
+
+        assert!(err
+            .get_ref()
+            .and_then(|e| e.downcast_ref::<RewritingError>())
+            .is_some());
+
+        assert_eq!(
+            reader.read(&mut [0; 16]).unwrap_err().to_string(),
+            "The rewriter has been closed."
+        );
+    }
+}
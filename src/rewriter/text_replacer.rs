@@ -0,0 +1,625 @@
+use super::handlers_dispatcher::TextBuffer;
+use super::settings::{TextHandler, TextNodeHandler};
+use crate::base::{MaybeSend, Range, Shared};
+use crate::rewritable_units::{content_to_bytes, ContentType, TextChunk, TextNode};
+use encoding_rs::UTF_8;
+use regex_automata::{dense, DenseDFA, ErrorKind, DFA};
+use std::collections::HashSet;
+use std::str;
+use thiserror::Error;
+
+/// An error that occures when an invalid pattern is provided to [`TextPattern`].
+///
+/// [`TextPattern`]: ../struct.TextPattern.html
+#[derive(Error, Debug, PartialEq, Clone)]
+pub enum TextPatternError {
+    /// The provided value is not a valid regular expression.
+    #[error("Invalid regular expression: {0}")]
+    InvalidRegex(String),
+
+    /// The regular expression uses a feature that can't be matched in a streaming fashion
+    /// (e.g. `^` and `$` anchors or word boundary assertions).
+    #[error("Unsupported regular expression: {0}")]
+    UnsupportedRegex(String),
+}
+
+impl From<regex_automata::Error> for TextPatternError {
+    fn from(err: regex_automata::Error) -> Self {
+        match err.kind() {
+            ErrorKind::Unsupported(msg) => TextPatternError::UnsupportedRegex(msg.to_owned()),
+            _ => TextPatternError::InvalidRegex(err.to_string()),
+        }
+    }
+}
+
+/// A pattern searched for in text by [`ElementContentHandlers::replace_text`].
+///
+/// The pattern is matched against the text as it appears in the input, i.e. character references
+/// in the text are not decoded. Empty matches are ignored.
+///
+/// [`ElementContentHandlers::replace_text`]: struct.ElementContentHandlers.html#method.replace_text
+#[derive(Clone, Debug)]
+pub struct TextPattern {
+    dfa: DenseDFA<Vec<usize>, usize>,
+}
+
+impl TextPattern {
+    /// Creates a pattern from a regular expression.
+    ///
+    /// The [syntax] of the `regex` crate is supported, except for anchors and word boundary
+    /// assertions. If several matches are possible at the same position, the one that would be
+    /// preferred by a backtracking regex engine wins, e.g. `a|ab` matches `a` in `ab`.
+    ///
+    /// [syntax]: https://docs.rs/regex/1/regex/#syntax
+    pub fn regex(regex: &str) -> Result<Self, TextPatternError> {
+        let dfa = dense::Builder::new()
+            .anchored(true)
+            .minimize(true)
+            .build(regex)?;
+
+        Ok(TextPattern { dfa })
+    }
+
+    /// Creates a pattern matching any of the `literals`.
+    ///
+    /// If several literals match at the same position, the longest one wins.
+    pub fn literals<S: AsRef<str>>(literals: &[S]) -> Self {
+        let regex = literals
+            .iter()
+            .map(|l| escape_regex(l.as_ref()))
+            .collect::<Vec<_>>()
+            .join("|");
+
+        let dfa = dense::Builder::new()
+            .anchored(true)
+            .longest_match(true)
+            .build(&regex)
+            .expect("Escaped literals should always form a valid regular expression");
+
+        TextPattern { dfa }
+    }
+}
+
+fn escape_regex(literal: &str) -> String {
+    let mut escaped = String::with_capacity(literal.len());
+
+    for ch in literal.chars() {
+        if is_meta_character(ch) {
+            escaped.push('\\');
+        }
+
+        escaped.push(ch);
+    }
+
+    escaped
+}
+
+#[inline]
+fn is_meta_character(ch: char) -> bool {
+    matches!(
+        ch,
+        '\\' | '.' | '+' | '*' | '?' | '(' | ')' | '|' | '[' | ']' | '{' | '}' | '^' | '$'
+    )
+}
+
+// NOTE: a position in the text where a match can start, which is still live in the DFA
+// at the scanned position.
+struct MatchCandidate {
+    start: usize,
+    state: usize,
+    match_end: Option<usize>,
+}
+
+// NOTE: instead of running the anchored DFA from each position of the text, the DFA is run for
+// all the live candidates at once and the search is resumed where it has stopped in the previous
+// chunk. Candidates in the same DFA state behave identically, so only the earliest of them is
+// kept, and all the candidates that follow a candidate with a match are dropped, as the leftmost
+// match wins. Thus, there are never more candidates than DFA states, and each byte of the text
+// is scanned once, except for the bytes that follow a match and precede the position where
+// the DFA has ruled out the match's extension.
+#[derive(Default)]
+struct MatchSearch {
+    candidates: Vec<MatchCandidate>,
+    pos: usize,
+    seen_states: HashSet<usize>,
+}
+
+impl MatchSearch {
+    fn reset(&mut self) {
+        self.candidates.clear();
+        self.pos = 0;
+    }
+
+    fn step(&mut self, pattern: &TextPattern, byte: u8) {
+        let dfa = &pattern.dfa;
+        let has_match = self.candidates.iter().any(|c| c.match_end.is_some());
+
+        // NOTE: matches are valid UTF-8 strings, so they can't start with a continuation byte.
+        if !has_match && byte & 0xC0 != 0x80 {
+            self.candidates.push(MatchCandidate {
+                start: self.pos,
+                state: dfa.start_state(),
+                match_end: None,
+            });
+        }
+
+        self.pos += 1;
+
+        for candidate in &mut self.candidates {
+            if !dfa.is_dead_state(candidate.state) {
+                candidate.state = dfa.next_state(candidate.state, byte);
+
+                if dfa.is_match_state(candidate.state) {
+                    candidate.match_end = Some(self.pos);
+                }
+            }
+        }
+
+        let seen_states = &mut self.seen_states;
+        let mut found_match = false;
+
+        seen_states.clear();
+
+        self.candidates.retain(|c| {
+            if found_match {
+                false
+            } else if c.match_end.is_some() {
+                found_match = true;
+                true
+            } else {
+                !dfa.is_dead_state(c.state) && seen_states.insert(c.state)
+            }
+        });
+    }
+
+    // NOTE: the match of the earliest candidate is final once the candidate is dead or
+    // the end of the text node is reached.
+    fn next_match(&mut self, pattern: &TextPattern, text: &[u8], last: bool) -> Option<Range> {
+        loop {
+            let ended = last && self.pos == text.len();
+
+            if ended {
+                self.candidates.retain(|c| c.match_end.is_some());
+            }
+
+            if let Some(candidate) = self.candidates.first() {
+                if let Some(end) = candidate.match_end {
+                    if ended || pattern.dfa.is_dead_state(candidate.state) {
+                        let start = candidate.start;
+
+                        // NOTE: the search is restarted at the end of the match.
+                        self.candidates.clear();
+                        self.pos = end;
+
+                        return Some(Range { start, end });
+                    }
+                }
+            }
+
+            if self.pos == text.len() {
+                return None;
+            }
+
+            self.step(pattern, text[self.pos]);
+        }
+    }
+
+    #[inline]
+    fn held_back_start(&self) -> usize {
+        self.candidates.first().map_or(self.pos, |c| c.start)
+    }
+
+    // NOTE: moves the search to the text from which the first `len` bytes are removed.
+    fn shift(&mut self, len: usize) {
+        self.pos -= len;
+
+        for candidate in &mut self.candidates {
+            candidate.start -= len;
+            candidate.match_end = candidate.match_end.map(|end| end - len);
+        }
+    }
+}
+
+pub(crate) struct TextReplacer<'h> {
+    pattern: TextPattern,
+    replacement: Box<dyn_maybe_send!(FnMut(&str) -> String + 'h)>,
+    content_type: ContentType,
+    search: MatchSearch,
+}
+
+impl<'h> TextReplacer<'h> {
+    pub fn new(
+        pattern: TextPattern,
+        replacement: impl FnMut(&str) -> String + MaybeSend + 'h,
+        content_type: ContentType,
+    ) -> Self {
+        TextReplacer {
+            pattern,
+            replacement: Box::new(replacement),
+            content_type,
+            search: MatchSearch::default(),
+        }
+    }
+
+    fn push_replacement(&mut self, output: &mut String, matched: &str) {
+        let replacement = (self.replacement)(matched);
+
+        // NOTE: the output replaces the chunk as HTML, so the text replacement is escaped
+        // the same way as text content of the mutations.
+        content_to_bytes(&replacement, self.content_type, UTF_8, &mut |c| {
+            output.push_str(str::from_utf8(c).expect("Replacement should be a valid UTF-8 string"));
+        });
+    }
+
+    // NOTE: the `text` should consist of the text held back from the previous call, followed
+    // by the new text. Returns the length of the text that should be held back until the next
+    // chunk.
+    fn replace(&mut self, text: &str, output: &mut String, last: bool) -> usize {
+        let mut emitted = 0;
+
+        while let Some(m) = self.search.next_match(&self.pattern, text.as_bytes(), last) {
+            output.push_str(&text[emitted..m.start]);
+            self.push_replacement(output, &text[m.start..m.end]);
+            emitted = m.end;
+        }
+
+        let held_back_start = self.search.held_back_start();
+
+        output.push_str(&text[emitted..held_back_start]);
+        self.search.shift(held_back_start);
+
+        text.len() - held_back_start
+    }
+}
+
+// NOTE: text replacers are implemented on top of text handlers: the chunk is replaced with
+// its text, in which the matches are substituted, except for the suffix that can be a beginning
+// of a match. The suffix is buffered and prepended to the next chunk of the text node.
+pub(super) fn text_replacing_handler<'h>(
+    mut text_handler: Option<TextHandler<'h>>,
+    mut replacer: TextReplacer<'h>,
+    buffer: Shared<TextBuffer>,
+) -> TextHandler<'h> {
+    Box::new(move |chunk: &mut TextChunk| {
+        if let Some(ref mut handler) = text_handler {
+            handler(chunk)?;
+        }
+
+        let mut buffer = buffer.borrow_mut();
+        let pending = &mut buffer.bytes;
+        let had_pending = !pending.is_empty();
+
+        // NOTE: the buffer can be cleared by the dispatcher if the rewriter is reset in the middle
+        // of a text node, and it is always empty once the search is complete.
+        if !had_pending {
+            replacer.search.reset();
+        }
+
+        // NOTE: the text of the chunk that has been replaced or removed by the handler is not
+        // searched for matches, but the text held back from the previous chunks is inserted
+        // before it, as the matches can't be completed anymore.
+        if chunk.removed() {
+            if had_pending {
+                let text =
+                    str::from_utf8(pending).expect("Buffered text should be a valid UTF-8 string");
+                let mut output = String::new();
+
+                replacer.replace(text, &mut output, true);
+                chunk.prepend_before(&output, ContentType::Html);
+                pending.drain(..);
+            }
+
+            return Ok(());
+        }
+
+        pending.extend_from_slice(chunk.as_str().as_bytes())?;
+
+        let text = str::from_utf8(pending).expect("Buffered text should be a valid UTF-8 string");
+        let mut output = String::new();
+        let held_back_len = replacer.replace(text, &mut output, chunk.last_in_text_node());
+        let emitted_len = text.len() - held_back_len;
+
+        // NOTE: leave the chunk intact if there is nothing to replace in it.
+        if had_pending || output != text {
+            chunk.replace(&output, ContentType::Html);
+        }
+
+        pending.drain(..emitted_len);
+
+        Ok(())
+    })
+}
+
+// NOTE: text node handlers already buffer whole text nodes, so the replacements are made in
+// the text node before the handler is invoked.
+pub(super) fn text_replacing_text_node_handler<'h>(
+    mut text_node_handler: TextNodeHandler<'h>,
+    mut replacer: TextReplacer<'h>,
+) -> TextNodeHandler<'h> {
+    Box::new(move |text_node: &mut TextNode| {
+        let mut output = String::new();
+
+        replacer.replace(text_node.as_str(), &mut output, true);
+
+        if output != text_node.as_str() {
+            text_node.replace(&output, ContentType::Html);
+        }
+
+        text_node_handler(text_node)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::*;
+    use crate::*;
+    use std::borrow::Cow;
+    use std::cell::RefCell;
+
+    fn replace_in_chunks(
+        chunks: &[&str],
+        handlers: ElementContentHandlers,
+        max_allowed_memory_usage: usize,
+    ) -> Result<Vec<String>, RewritingError> {
+        let output = RefCell::new(vec![]);
+
+        {
+            let mut rewriter = HtmlRewriter::new(
+                Settings {
+                    element_content_handlers: vec![(Cow::Owned("div".parse().unwrap()), handlers)],
+                    memory_settings: MemorySettings {
+                        max_allowed_memory_usage,
+                        preallocated_parsing_buffer_size: 0,
+                    },
+                    ..Settings::default()
+                },
+                |c: &[u8]| {
+                    output
+                        .borrow_mut()
+                        .push(String::from_utf8(c.to_vec()).unwrap())
+                },
+            );
+
+            // NOTE: mark the output produced for each chunk of the input.
+            for chunk in chunks {
+                rewriter.write(chunk.as_bytes())?;
+                output.borrow_mut().push("|".into());
+            }
+
+            rewriter.end()?;
+        }
+
+        Ok(output.into_inner())
+    }
+
+    fn replace(chunks: &[&str], pattern: TextPattern, content_type: ContentType) -> String {
+        let handlers = ElementContentHandlers::default().replace_text(
+            pattern,
+            |m| format!("<{}>", m),
+            content_type,
+        );
+
+        replace_in_chunks(chunks, handlers, std::usize::MAX)
+            .unwrap()
+            .concat()
+    }
+
+    #[test]
+    fn invalid_regex() {
+        assert!(matches!(
+            TextPattern::regex("(foo").unwrap_err(),
+            TextPatternError::InvalidRegex(_)
+        ));
+
+        assert!(matches!(
+            TextPattern::regex("^foo").unwrap_err(),
+            TextPatternError::UnsupportedRegex(_)
+        ));
+
+        assert!(matches!(
+            TextPattern::regex(r"\bfoo").unwrap_err(),
+            TextPatternError::UnsupportedRegex(_)
+        ));
+    }
+
+    #[test]
+    fn escaped_literals() {
+        let pattern = TextPattern::literals(&["a.b", "(c)", "[d]*", "e|f"]);
+
+        assert_eq!(
+            replace(
+                &["<div>a.b (c) [d]* e|f axb ddd</div>"],
+                pattern,
+                ContentType::Html
+            ),
+            "<div><a.b> <(c)> <[d]*> <e|f> axb ddd</div>|"
+        );
+    }
+
+    #[test]
+    fn regex_across_chunks() {
+        let pattern = TextPattern::regex(r"\d{3}-\d{4}").unwrap();
+
+        assert_eq!(
+            replace(
+                &["<div>555-01", "00 and 5", "5", "5-0", "199</div>"],
+                pattern.clone(),
+                ContentType::Html
+            ),
+            "<div>|<555-0100> and |||<555-0199></div>|"
+        );
+
+        assert_eq!(
+            replace(&["<div>555-01", "0</div>"], pattern, ContentType::Html),
+            "<div>|555-010</div>|"
+        );
+    }
+
+    #[test]
+    fn regex_preferred_match() {
+        assert_eq!(
+            replace(
+                &["<div>a", "bc</div>"],
+                TextPattern::regex("a|ab").unwrap(),
+                ContentType::Html
+            ),
+            "<div>|<a>bc</div>|"
+        );
+
+        assert_eq!(
+            replace(
+                &["<div>aaa", "ab</div>"],
+                TextPattern::regex("a+b").unwrap(),
+                ContentType::Html
+            ),
+            "<div>|<aaaab></div>|"
+        );
+    }
+
+    #[test]
+    fn literals_across_chunks() {
+        let pattern = TextPattern::literals(&["foo", "foobar", "bar"]);
+
+        assert_eq!(
+            replace(
+                &["<div>xfo", "obaz fooba", "r ba", "r</div>"],
+                pattern,
+                ContentType::Html
+            ),
+            "<div>x|<foo>baz |<foobar> |<bar></div>|"
+        );
+    }
+
+    #[test]
+    fn minimal_holdback() {
+        let pattern = TextPattern::literals(&["abc"]);
+
+        assert_eq!(
+            replace(&["<div>xxab", "x", "yyy</div>"], pattern, ContentType::Html),
+            "<div>xx|abx|yyy</div>|"
+        );
+    }
+
+    #[test]
+    fn non_ascii_text() {
+        let pattern = TextPattern::regex("ми.").unwrap();
+
+        assert_eq!(
+            replace(&["<div>Привет, м", "ир</div>"], pattern, ContentType::Html),
+            "<div>Привет, |<мир></div>|"
+        );
+    }
+
+    #[test]
+    fn text_content_type() {
+        let pattern = TextPattern::literals(&["&"]);
+
+        assert_eq!(
+            replace(&["<div>a & b</div>"], pattern, ContentType::Text),
+            "<div>a &lt;&amp;&gt; b</div>|"
+        );
+    }
+
+    #[test]
+    fn only_matched_elements() {
+        let pattern = TextPattern::literals(&["foo"]);
+
+        assert_eq!(
+            replace(&["foo<div>foo</div>foo"], pattern, ContentType::Html),
+            "foo<div><foo></div>foo|"
+        );
+    }
+
+    #[test]
+    fn with_text_handler() {
+        let mut chunks = vec![];
+
+        let handlers = ElementContentHandlers::default()
+            .text(|c| {
+                chunks.push(c.as_str().to_string());
+                Ok(())
+            })
+            .replace_text(
+                TextPattern::literals(&["foo"]),
+                |_| "bar".into(),
+                ContentType::Text,
+            );
+
+        let output = replace_in_chunks(&["<div>fo", "o</div>"], handlers, std::usize::MAX);
+
+        assert_eq!(output.unwrap().concat(), "<div>|bar</div>|");
+        assert_eq!(chunks, vec!["fo", "o", ""]);
+    }
+
+    #[test]
+    fn with_replacing_text_handler() {
+        let handlers = ElementContentHandlers::default()
+            .text(|c| {
+                if c.as_str() == "o bar" {
+                    c.before("<before>", ContentType::Html);
+                    c.replace("<replaced>", ContentType::Html);
+                }
+
+                Ok(())
+            })
+            .replace_text(
+                TextPattern::literals(&["foo"]),
+                |_| "baz".into(),
+                ContentType::Text,
+            );
+
+        let output = replace_in_chunks(
+            &["<div>foo f", "o bar", " foo</div>"],
+            handlers,
+            std::usize::MAX,
+        );
+
+        assert_eq!(
+            output.unwrap().concat(),
+            "<div>baz |f<before><replaced>| baz</div>|"
+        );
+    }
+
+    #[test]
+    fn with_text_node_handler() {
+        let mut texts = vec![];
+
+        let handlers = ElementContentHandlers::default()
+            .text_node(|t| {
+                texts.push(t.as_str().to_string());
+                assert!(t.removed());
+                t.before("[", ContentType::Text);
+                Ok(())
+            })
+            .replace_text(
+                TextPattern::literals(&["foo"]),
+                |_| "bar".into(),
+                ContentType::Text,
+            );
+
+        let output = replace_in_chunks(&["<div>foo f", "oo</div>"], handlers, std::usize::MAX);
+
+        assert_eq!(output.unwrap().concat(), "<div>|[bar bar</div>|");
+        assert_eq!(texts, vec!["foo foo"]);
+    }
+
+    #[test]
+    fn memory_limit_exceeded() {
+        const MAX: usize = 100;
+
+        let handlers = ElementContentHandlers::default().replace_text(
+            TextPattern::regex("a.*b").unwrap(),
+            |_| "".into(),
+            ContentType::Text,
+        );
+
+        let text = "a".repeat(MAX);
+        let err = replace_in_chunks(&["<div>", &text, &text], handlers, MAX).unwrap_err();
+
+        match err {
+            RewritingError::MemoryLimitExceeded(e) => assert_eq!(e, MemoryLimitExceededError),
+            _ => panic!("{}", err),
+        }
+    }
+}
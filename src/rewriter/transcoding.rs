@@ -0,0 +1,279 @@
+use super::RewritingStats;
+use crate::transform_stream::OutputSink;
+use encoding_rs::{CoderResult, Decoder, Encoding, UTF_16BE, UTF_16LE};
+use std::mem;
+
+// NOTE: encoders of the Encoding Standard can't produce UTF-16, so we encode it ourselves.
+enum Encoder {
+    Utf16 { big_endian: bool },
+    Standard(encoding_rs::Encoder),
+}
+
+impl Encoder {
+    fn new(encoding: &'static Encoding) -> Self {
+        let encoding = output_encoding(encoding);
+
+        if encoding == UTF_16LE || encoding == UTF_16BE {
+            Encoder::Utf16 {
+                big_endian: encoding == UTF_16BE,
+            }
+        } else {
+            Encoder::Standard(encoding.new_encoder())
+        }
+    }
+
+    fn encode(&mut self, text: &str, last: bool, output: &mut Vec<u8>) {
+        match self {
+            Encoder::Utf16 { big_endian } => {
+                for code_unit in text.encode_utf16() {
+                    if *big_endian {
+                        output.extend_from_slice(&code_unit.to_be_bytes());
+                    } else {
+                        output.extend_from_slice(&code_unit.to_le_bytes());
+                    }
+                }
+            }
+            Encoder::Standard(encoder) => {
+                let mut text = text;
+
+                loop {
+                    let capacity = encoder
+                        .max_buffer_length_from_utf8_if_no_unmappables(text.len())
+                        .expect("Output buffer length should not overflow");
+
+                    output.reserve(capacity);
+
+                    let (result, read, _) = encoder.encode_from_utf8_to_vec(text, output, last);
+
+                    text = &text[read..];
+
+                    if let CoderResult::InputEmpty = result {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Returns the encoding the output in the given `encoding` is actually produced in.
+///
+/// NOTE: unlike the Encoding Standard, we support UTF-16 for the output.
+pub(super) fn output_encoding(encoding: &'static Encoding) -> &'static Encoding {
+    if encoding == UTF_16LE || encoding == UTF_16BE {
+        encoding
+    } else {
+        encoding.output_encoding()
+    }
+}
+
+fn decode(decoder: &mut Decoder, input: &[u8], last: bool, output: &mut String) {
+    let mut input = input;
+
+    loop {
+        let capacity = decoder
+            .max_utf8_buffer_length(input.len())
+            .expect("Output buffer length should not overflow");
+
+        output.reserve(capacity);
+
+        let (result, read, _) = decoder.decode_to_string(input, output, last);
+
+        input = &input[read..];
+
+        if let CoderResult::InputEmpty = result {
+            break;
+        }
+    }
+}
+
+/// Transcodes the input in the given encoding into UTF-8. Code units split between
+/// the chunks of the input are buffered by the decoder.
+pub(super) struct InputTranscoder {
+    decoder: Decoder,
+    output: String,
+}
+
+impl InputTranscoder {
+    #[inline]
+    pub fn new(encoding: &'static Encoding) -> Self {
+        InputTranscoder {
+            // NOTE: the BOM is preserved, so it can be transcoded back to the output.
+            decoder: encoding.new_decoder_without_bom_handling(),
+            output: String::default(),
+        }
+    }
+
+    #[inline]
+    pub fn encoding(&self) -> &'static Encoding {
+        self.decoder.encoding()
+    }
+
+    pub fn transcode(&mut self, input: &[u8], last: bool) -> &[u8] {
+        self.output.clear();
+
+        decode(&mut self.decoder, input, last, &mut self.output);
+
+        self.output.as_bytes()
+    }
+}
+
+/// Transcodes the output of the rewriter from one encoding into another.
+pub(super) struct OutputTranscoder {
+    decoder: Decoder,
+    encoder: Encoder,
+    decoded: String,
+    encoded: Vec<u8>,
+}
+
+impl OutputTranscoder {
+    #[inline]
+    pub fn new(from: &'static Encoding, to: &'static Encoding) -> Self {
+        OutputTranscoder {
+            decoder: from.new_decoder_without_bom_handling(),
+            encoder: Encoder::new(to),
+            decoded: String::default(),
+            encoded: Vec::default(),
+        }
+    }
+
+    fn transcode(&mut self, chunk: &[u8], output_sink: &mut dyn OutputSink) {
+        let last = chunk.is_empty();
+
+        self.decoded.clear();
+        self.encoded.clear();
+
+        decode(&mut self.decoder, chunk, last, &mut self.decoded);
+        self.encoder.encode(&self.decoded, last, &mut self.encoded);
+
+        // NOTE: an empty chunk is the finalizing chunk for the output sink,
+        // so we emit it only once everything else is flushed.
+        if !self.encoded.is_empty() {
+            output_sink.handle_chunk(&self.encoded);
+        }
+
+        if last {
+            output_sink.handle_chunk(&[]);
+        }
+    }
+}
+
+/// An output sink wrapper that transcodes the output if it's required.
+pub(super) struct TranscodingOutputSink<O: OutputSink> {
+    inner: O,
+    transcoder: Option<OutputTranscoder>,
+    stats: Option<RewritingStats>,
+}
+
+impl<O: OutputSink> TranscodingOutputSink<O> {
+    #[inline]
+    pub fn new(inner: O, stats: Option<RewritingStats>) -> Self {
+        TranscodingOutputSink {
+            inner,
+            transcoder: None,
+            stats,
+        }
+    }
+
+    #[inline]
+    pub fn set_transcoder(&mut self, transcoder: Option<OutputTranscoder>) {
+        self.transcoder = transcoder;
+    }
+
+    #[inline]
+    pub fn inner_mut(&mut self) -> &mut O {
+        &mut self.inner
+    }
+
+    #[inline]
+    pub fn replace_inner(&mut self, inner: O) -> O {
+        mem::replace(&mut self.inner, inner)
+    }
+}
+
+impl<O: OutputSink> OutputSink for TranscodingOutputSink<O> {
+    #[inline]
+    fn handle_chunk(&mut self, chunk: &[u8]) {
+        match self.stats {
+            Some(ref stats) => {
+                let inner = &mut self.inner;
+
+                let mut output_sink = |c: &[u8]| {
+                    stats.record_bytes_out(c.len());
+                    inner.handle_chunk(c);
+                };
+
+                match self.transcoder {
+                    Some(ref mut transcoder) => transcoder.transcode(chunk, &mut output_sink),
+                    None => output_sink(chunk),
+                }
+            }
+            None => match self.transcoder {
+                Some(ref mut transcoder) => transcoder.transcode(chunk, &mut self.inner),
+                None => self.inner.handle_chunk(chunk),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding_rs::{ISO_2022_JP, UTF_8};
+
+    #[test]
+    fn split_code_units() {
+        let input = "Привет, мир!"
+            .encode_utf16()
+            .flat_map(|c| c.to_le_bytes().to_vec())
+            .collect::<Vec<_>>();
+
+        let mut transcoder = InputTranscoder::new(UTF_16LE);
+        let mut output = Vec::default();
+
+        for byte in input.chunks(1) {
+            output.extend_from_slice(transcoder.transcode(byte, false));
+        }
+
+        output.extend_from_slice(transcoder.transcode(&[], true));
+
+        assert_eq!(String::from_utf8(output).unwrap(), "Привет, мир!");
+    }
+
+    #[test]
+    fn output_transcoding() {
+        for &encoding in &[UTF_16LE, UTF_16BE, ISO_2022_JP] {
+            let text = "<p>こんにちは</p>";
+            let stats = RewritingStats::new();
+            let mut output = Vec::default();
+
+            {
+                let mut sink = TranscodingOutputSink::new(
+                    |c: &[u8]| output.push(c.to_vec()),
+                    Some(RewritingStats::clone(&stats)),
+                );
+
+                sink.set_transcoder(Some(OutputTranscoder::new(UTF_8, encoding)));
+
+                for byte in text.as_bytes().chunks(1) {
+                    sink.handle_chunk(byte);
+                }
+
+                sink.handle_chunk(&[]);
+            }
+
+            let (last, chunks) = output.split_last().unwrap();
+
+            assert!(last.is_empty());
+            assert!(chunks.iter().all(|c| !c.is_empty()));
+
+            let output = output.concat();
+            let (decoded, had_errors) = encoding.decode_without_bom_handling(&output);
+
+            assert_eq!(stats.bytes_out(), output.len());
+
+            assert!(!had_errors);
+            assert_eq!(decoded, text);
+        }
+    }
+}
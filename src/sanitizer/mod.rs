@@ -0,0 +1,393 @@
+//! Allowlist-based HTML sanitization on top of the rewriter.
+//!
+//! [`Sanitizer`] produces content handlers that remove everything that is not allowed by
+//! a [`SanitizerPolicy`] from the rewriter's output. The sanitization is streaming, as any
+//! other rewriting.
+//!
+//! [`Sanitizer`]: struct.Sanitizer.html
+//! [`SanitizerPolicy`]: struct.SanitizerPolicy.html
+
+mod policy;
+
+use crate::html::{Namespace, TextType};
+use crate::rewritable_units::{ContentType, Element, TextChunk};
+use crate::rewriter::{
+    DocumentContentHandlers, ElementContentHandlers, RewriteStrSettings, RewritingError,
+};
+use crate::selectors_vm::Selector;
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+pub use self::policy::{DisallowedElementAction, SanitizerPolicy};
+
+// NOTE: the content of these elements is not parsed as markup, so it's output as is and
+// should be escaped once the element is removed.
+const RAW_TEXT_TAGS: &[&str] = &[
+    "iframe",
+    "noembed",
+    "noframes",
+    "noscript",
+    "plaintext",
+    "script",
+    "style",
+    "textarea",
+    "title",
+    "xmp",
+];
+
+/// An HTML sanitizer.
+///
+/// The sanitizer provides the content handlers that should be specified in the rewriter's
+/// settings. The handlers should follow all the other handlers, so the sanitizer checks
+/// the attributes set by them. Note that content inserted as HTML by other handlers is
+/// not sanitized.
+///
+/// # Example
+/// ```
+/// use lol_html::{HtmlRewriter, Settings};
+/// use lol_html::sanitizer::Sanitizer;
+///
+/// let sanitizer = Sanitizer::default();
+/// let mut output = vec![];
+///
+/// {
+///     let mut rewriter = HtmlRewriter::new(
+///         Settings {
+///             element_content_handlers: sanitizer.element_content_handlers(),
+///             document_content_handlers: sanitizer.document_content_handlers(),
+///             ..Settings::default()
+///         },
+///         |c: &[u8]| output.extend_from_slice(c),
+///     );
+///
+///     rewriter.write(b"<p onclick='steal()'>Hello <scr").unwrap();
+///     rewriter.write(b"ipt>steal()</script><a href='javascript:steal()'>world</a></p>").unwrap();
+///     rewriter.end().unwrap();
+/// }
+///
+/// assert_eq!(String::from_utf8(output).unwrap(), "<p>Hello <a>world</a></p>");
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Sanitizer {
+    policy: Arc<SanitizerPolicy>,
+}
+
+impl Sanitizer {
+    /// Constructs a new sanitizer with the provided `policy`.
+    #[inline]
+    pub fn new(policy: SanitizerPolicy) -> Self {
+        Sanitizer {
+            policy: Arc::new(policy),
+        }
+    }
+
+    /// Returns the policy of the sanitizer.
+    #[inline]
+    pub fn policy(&self) -> &SanitizerPolicy {
+        &self.policy
+    }
+
+    /// Returns the element content handlers that sanitize the elements and their attributes.
+    pub fn element_content_handlers<'h>(
+        &self,
+    ) -> Vec<(Cow<'static, Selector>, ElementContentHandlers<'h>)> {
+        let policy = Arc::clone(&self.policy);
+
+        let mut handlers = vec![(
+            Cow::Owned("*".parse().unwrap()),
+            ElementContentHandlers::default().element(move |el| {
+                sanitize_element(el, &policy);
+
+                Ok(())
+            }),
+        )];
+
+        if self.policy.disallowed_elements != DisallowedElementAction::Drop {
+            let escaped_tags = RAW_TEXT_TAGS
+                .iter()
+                .filter(|&&tag| {
+                    !self.policy.allowed_tags.contains(tag)
+                        && !self.policy.dropped_tags.contains(tag)
+                })
+                .copied()
+                .collect::<Vec<_>>();
+
+            if !escaped_tags.is_empty() {
+                handlers.push((
+                    Cow::Owned(escaped_tags.join(", ").parse().unwrap()),
+                    ElementContentHandlers::default().text(|t| {
+                        escape_raw_text(t);
+
+                        Ok(())
+                    }),
+                ));
+            }
+        }
+
+        handlers
+    }
+
+    /// Returns the document content handlers that remove comments and the doctype, if required
+    /// by the policy.
+    pub fn document_content_handlers<'h>(&self) -> Vec<DocumentContentHandlers<'h>> {
+        let mut handlers = DocumentContentHandlers::default();
+
+        if self.policy.remove_comments {
+            handlers = handlers.comments(|c| {
+                c.remove();
+
+                Ok(())
+            });
+        }
+
+        if self.policy.remove_doctype {
+            handlers = handlers.doctype(|d| {
+                d.remove();
+
+                Ok(())
+            });
+        }
+
+        vec![handlers]
+    }
+
+    /// Sanitizes the given `html` string.
+    ///
+    /// # Example
+    /// ```
+    /// use lol_html::sanitizer::{DisallowedElementAction, Sanitizer, SanitizerPolicy};
+    ///
+    /// let sanitizer = Sanitizer::new(SanitizerPolicy {
+    ///     disallowed_elements: DisallowedElementAction::Escape,
+    ///     ..SanitizerPolicy::default()
+    /// });
+    ///
+    /// assert_eq!(
+    ///     sanitizer.sanitize_str("<b>Hello</b> <blink>world</blink>").unwrap(),
+    ///     "<b>Hello</b> &lt;blink&gt;world&lt;/blink&gt;"
+    /// );
+    /// ```
+    pub fn sanitize_str(&self, html: &str) -> Result<String, RewritingError> {
+        crate::rewrite_str(
+            html,
+            RewriteStrSettings {
+                element_content_handlers: self.element_content_handlers(),
+                document_content_handlers: self.document_content_handlers(),
+                ..RewriteStrSettings::default()
+            },
+        )
+    }
+}
+
+fn sanitize_element(el: &mut Element, policy: &SanitizerPolicy) {
+    let tag_name = el.tag_name();
+
+    if policy.allowed_tags.contains(&tag_name) {
+        sanitize_attributes(el, &tag_name, policy);
+    } else if policy.dropped_tags.contains(&tag_name) || el.namespace_uri() != Namespace::Html.uri()
+    {
+        el.remove();
+    } else {
+        match policy.disallowed_elements {
+            DisallowedElementAction::Drop => el.remove(),
+            DisallowedElementAction::Unwrap => el.remove_and_keep_content(),
+            DisallowedElementAction::Escape => {
+                let mut start_tag = format!("<{}", tag_name);
+
+                for attr in el.attributes() {
+                    start_tag += &format!(" {}=\"{}\"", attr.name(), attr.decoded_value());
+                }
+
+                start_tag.push('>');
+
+                el.before(&start_tag, ContentType::Text);
+                el.append(&format!("</{}>", tag_name), ContentType::Text);
+                el.remove_and_keep_content();
+            }
+        }
+    }
+}
+
+// NOTE: if any of the attributes with the same name is not allowed, all of them are removed,
+// as it's up to the parser which of them takes effect.
+fn sanitize_attributes(el: &mut Element, tag_name: &str, policy: &SanitizerPolicy) {
+    let removed_attributes = el
+        .attributes()
+        .iter()
+        .filter_map(|attr| {
+            let name = attr.name();
+
+            let allowed = policy.is_attribute_allowed(tag_name, &name)
+                && (!policy.url_attributes.contains(&name)
+                    || policy.is_url_allowed(&attr.decoded_value()));
+
+            if allowed {
+                None
+            } else {
+                Some(name)
+            }
+        })
+        .collect::<HashSet<_>>();
+
+    for name in removed_attributes {
+        while el.has_attribute(&name) {
+            el.remove_attribute(&name);
+        }
+    }
+}
+
+fn escape_raw_text(chunk: &mut TextChunk) {
+    match chunk.text_type() {
+        TextType::Data | TextType::CDataSection => (),
+        _ => {
+            let text = chunk.decoded_text().into_owned();
+
+            chunk.replace(&text, ContentType::Text);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{HtmlRewriter, Settings};
+
+    fn sanitize(html: &str, policy: SanitizerPolicy) -> String {
+        let sanitizer = Sanitizer::new(policy);
+        let mut output = vec![];
+
+        {
+            let mut rewriter = HtmlRewriter::new(
+                Settings {
+                    element_content_handlers: sanitizer.element_content_handlers(),
+                    document_content_handlers: sanitizer.document_content_handlers(),
+                    ..Settings::default()
+                },
+                |c: &[u8]| output.extend_from_slice(c),
+            );
+
+            for chunk in html.as_bytes().chunks(3) {
+                rewriter.write(chunk).unwrap();
+            }
+
+            rewriter.end().unwrap();
+        }
+
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn default_policy() {
+        assert_eq!(
+            sanitize(
+                concat!(
+                    "<!doctype html><p onclick=\"x()\" title='t'>Hi <b>there</b><!-- c -->",
+                    "<script>alert(1)</script><style>p{}</style><custom>kept</custom>",
+                    "<svg><a href=\"/\">dropped</a></svg></p>"
+                ),
+                SanitizerPolicy::default()
+            ),
+            "<p title='t'>Hi <b>there</b>kept</p>"
+        );
+    }
+
+    #[test]
+    fn urls() {
+        assert_eq!(
+            sanitize(
+                concat!(
+                    "<a href=\"javascript:alert(1)\">a</a>",
+                    "<a href=\" JaVaScRiPt:alert(1)\">b</a>",
+                    "<a href=\"java&#x09;script&colon;alert(1)\">c</a>",
+                    "<a href=\"/rel\" title=\"t\">d</a>",
+                    "<a href=\"https://example.com\">e</a>",
+                    "<a href=\"https://example.com\" href=\"javascript:alert(1)\">f</a>",
+                    "<img src=\"data:image/png;base64,AA\" alt=\"x\">"
+                ),
+                SanitizerPolicy::default()
+            ),
+            concat!(
+                "<a>a</a>",
+                "<a>b</a>",
+                "<a>c</a>",
+                "<a href=\"/rel\" title=\"t\">d</a>",
+                "<a href=\"https://example.com\">e</a>",
+                "<a>f</a>",
+                "<img alt=\"x\">"
+            )
+        );
+    }
+
+    #[test]
+    fn raw_text_escaping() {
+        assert_eq!(
+            sanitize(
+                "<textarea>a &amp; <b>x</b></textarea><xmp><img src=x onerror=alert(1)></xmp>",
+                SanitizerPolicy::default()
+            ),
+            "a &amp; &lt;b&gt;x&lt;/b&gt;&lt;img src=x onerror=alert(1)&gt;"
+        );
+    }
+
+    #[test]
+    fn allowed_raw_text_element() {
+        let mut policy = SanitizerPolicy::default();
+
+        policy.allowed_tags.insert("textarea".into());
+
+        assert_eq!(
+            sanitize("<textarea>a &amp; <b>x</b></textarea>", policy),
+            "<textarea>a &amp; <b>x</b></textarea>"
+        );
+    }
+
+    #[test]
+    fn disallowed_element_actions() {
+        let html = "<div><custom x=\"1\">a<i>b</i></custom><br></div>";
+
+        let sanitize_with = |disallowed_elements| {
+            sanitize(
+                html,
+                SanitizerPolicy {
+                    disallowed_elements,
+                    ..SanitizerPolicy::default()
+                },
+            )
+        };
+
+        assert_eq!(
+            sanitize_with(DisallowedElementAction::Drop),
+            "<div><br></div>"
+        );
+
+        assert_eq!(
+            sanitize_with(DisallowedElementAction::Unwrap),
+            "<div>a<i>b</i><br></div>"
+        );
+
+        assert_eq!(
+            sanitize_with(DisallowedElementAction::Escape),
+            "<div>&lt;custom x=\"1\"&gt;a<i>b</i>&lt;/custom&gt;<br></div>"
+        );
+    }
+
+    #[test]
+    fn comments_and_doctype() {
+        let html = "<!doctype html><!-- a --><p><!-- b --></p>";
+
+        assert_eq!(sanitize(html, SanitizerPolicy::default()), "<p></p>");
+
+        assert_eq!(
+            sanitize(
+                html,
+                SanitizerPolicy {
+                    remove_comments: false,
+                    remove_doctype: false,
+                    ..SanitizerPolicy::default()
+                }
+            ),
+            html
+        );
+    }
+}
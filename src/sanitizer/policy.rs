@@ -0,0 +1,307 @@
+use std::collections::{HashMap, HashSet};
+
+const DEFAULT_ALLOWED_TAGS: &[&str] = &[
+    "a",
+    "abbr",
+    "acronym",
+    "area",
+    "article",
+    "aside",
+    "b",
+    "bdi",
+    "bdo",
+    "blockquote",
+    "br",
+    "caption",
+    "center",
+    "cite",
+    "code",
+    "col",
+    "colgroup",
+    "data",
+    "dd",
+    "del",
+    "details",
+    "dfn",
+    "div",
+    "dl",
+    "dt",
+    "em",
+    "figcaption",
+    "figure",
+    "footer",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hgroup",
+    "hr",
+    "i",
+    "img",
+    "ins",
+    "kbd",
+    "li",
+    "map",
+    "mark",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "q",
+    "rp",
+    "rt",
+    "rtc",
+    "ruby",
+    "s",
+    "samp",
+    "small",
+    "span",
+    "strike",
+    "strong",
+    "sub",
+    "summary",
+    "sup",
+    "table",
+    "tbody",
+    "td",
+    "tfoot",
+    "th",
+    "thead",
+    "time",
+    "tr",
+    "tt",
+    "u",
+    "ul",
+    "var",
+    "wbr",
+];
+
+const DEFAULT_ALLOWED_ATTRIBUTES: &[&str] = &["lang", "title"];
+
+const TABLE_CELL_ATTRIBUTES: &[&str] = &["align", "char", "charoff"];
+
+const DEFAULT_ALLOWED_TAG_ATTRIBUTES: &[(&str, &[&str])] = &[
+    ("a", &["href", "hreflang"]),
+    ("bdo", &["dir"]),
+    ("blockquote", &["cite"]),
+    ("col", &["align", "char", "charoff", "span"]),
+    ("colgroup", &["align", "char", "charoff", "span"]),
+    ("del", &["cite", "datetime"]),
+    ("hr", &["align", "size", "width"]),
+    ("img", &["align", "alt", "height", "src", "width"]),
+    ("ins", &["cite", "datetime"]),
+    ("ol", &["start"]),
+    ("q", &["cite"]),
+    ("table", &["align", "char", "charoff", "summary"]),
+    ("tbody", TABLE_CELL_ATTRIBUTES),
+    (
+        "td",
+        &["align", "char", "charoff", "colspan", "headers", "rowspan"],
+    ),
+    ("tfoot", TABLE_CELL_ATTRIBUTES),
+    (
+        "th",
+        &[
+            "abbr", "align", "char", "charoff", "colspan", "headers", "rowspan", "scope",
+        ],
+    ),
+    ("thead", TABLE_CELL_ATTRIBUTES),
+    ("time", &["datetime"]),
+    ("tr", TABLE_CELL_ATTRIBUTES),
+];
+
+const DEFAULT_URL_ATTRIBUTES: &[&str] = &[
+    "action",
+    "background",
+    "cite",
+    "codebase",
+    "data",
+    "formaction",
+    "href",
+    "longdesc",
+    "manifest",
+    "poster",
+    "src",
+    "xlink:href",
+];
+
+const DEFAULT_ALLOWED_URL_SCHEMES: &[&str] = &["http", "https", "mailto"];
+
+const DEFAULT_DROPPED_TAGS: &[&str] = &[
+    "iframe", "noembed", "noframes", "noscript", "script", "style",
+];
+
+#[inline]
+fn to_set(items: &[&str]) -> HashSet<String> {
+    items.iter().map(|&item| item.to_string()).collect()
+}
+
+/// Specifies what the sanitizer does with the elements that are not allowed by the policy.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DisallowedElementAction {
+    /// The element is removed along with its content.
+    Drop,
+    /// The element is removed, but its content is kept.
+    Unwrap,
+    /// The start and end tags of the element are kept as text, so they are displayed
+    /// rather than interpreted as markup.
+    Escape,
+}
+
+/// An allowlist-based sanitization policy.
+///
+/// The default policy allows a conservative set of formatting elements and attributes,
+/// `http`, `https` and `mailto` URLs, unwraps the other elements, drops the content of
+/// `<script>`, `<style>` and other elements whose content can't be displayed as text, and
+/// removes comments and the doctype.
+///
+/// All the tag and attribute names in the policy should be in lowercase, URL schemes should
+/// be specified in lowercase without the trailing colon.
+///
+/// Regardless of the policy, the text content of disallowed elements which is not parsed as
+/// markup (e.g. the content of `<textarea>` or `<xmp>`) is escaped if the element is unwrapped
+/// or escaped, and disallowed elements outside of the HTML namespace (e.g. SVG and MathML) are
+/// dropped along with their content, as their content is parsed differently in HTML.
+#[derive(Clone, Debug)]
+pub struct SanitizerPolicy {
+    /// Specifies the tags of the elements that are kept.
+    pub allowed_tags: HashSet<String>,
+
+    /// Specifies the attributes that are kept on all the allowed elements.
+    pub allowed_attributes: HashSet<String>,
+
+    /// Specifies the attributes that are kept on the allowed elements with the given tags,
+    /// in addition to [`allowed_attributes`].
+    ///
+    /// [`allowed_attributes`]: #structfield.allowed_attributes
+    pub allowed_tag_attributes: HashMap<String, HashSet<String>>,
+
+    /// Specifies the attributes that contain URLs.
+    ///
+    /// Allowed URL attributes are kept only if their URLs have one of the
+    /// [`allowed_url_schemes`] or if the URLs are relative and [`allow_relative_urls`] is `true`.
+    ///
+    /// [`allowed_url_schemes`]: #structfield.allowed_url_schemes
+    /// [`allow_relative_urls`]: #structfield.allow_relative_urls
+    pub url_attributes: HashSet<String>,
+
+    /// Specifies the allowed schemes of URLs in the [`url_attributes`].
+    ///
+    /// [`url_attributes`]: #structfield.url_attributes
+    pub allowed_url_schemes: HashSet<String>,
+
+    /// Specifies whether relative URLs are allowed in the [`url_attributes`].
+    ///
+    /// [`url_attributes`]: #structfield.url_attributes
+    pub allow_relative_urls: bool,
+
+    /// Specifies what to do with the elements which tags are not allowed.
+    pub disallowed_elements: DisallowedElementAction,
+
+    /// Specifies the tags of the disallowed elements that are always dropped along with their
+    /// content, regardless of [`disallowed_elements`].
+    ///
+    /// [`disallowed_elements`]: #structfield.disallowed_elements
+    pub dropped_tags: HashSet<String>,
+
+    /// Specifies whether HTML comments are removed.
+    pub remove_comments: bool,
+
+    /// Specifies whether the [document type declaration] is removed.
+    ///
+    /// [document type declaration]: https://developer.mozilla.org/en-US/docs/Glossary/Doctype
+    pub remove_doctype: bool,
+}
+
+impl Default for SanitizerPolicy {
+    fn default() -> Self {
+        SanitizerPolicy {
+            allowed_tags: to_set(DEFAULT_ALLOWED_TAGS),
+            allowed_attributes: to_set(DEFAULT_ALLOWED_ATTRIBUTES),
+            allowed_tag_attributes: DEFAULT_ALLOWED_TAG_ATTRIBUTES
+                .iter()
+                .map(|&(tag, attributes)| (tag.to_string(), to_set(attributes)))
+                .collect(),
+            url_attributes: to_set(DEFAULT_URL_ATTRIBUTES),
+            allowed_url_schemes: to_set(DEFAULT_ALLOWED_URL_SCHEMES),
+            allow_relative_urls: true,
+            disallowed_elements: DisallowedElementAction::Unwrap,
+            dropped_tags: to_set(DEFAULT_DROPPED_TAGS),
+            remove_comments: true,
+            remove_doctype: true,
+        }
+    }
+}
+
+impl SanitizerPolicy {
+    #[inline]
+    pub(super) fn is_attribute_allowed(&self, tag_name: &str, attribute_name: &str) -> bool {
+        self.allowed_attributes.contains(attribute_name)
+            || self
+                .allowed_tag_attributes
+                .get(tag_name)
+                .map_or(false, |attributes| attributes.contains(attribute_name))
+    }
+
+    pub(super) fn is_url_allowed(&self, url: &str) -> bool {
+        // NOTE: browsers strip leading and trailing C0 control characters and spaces from URLs,
+        // as well as ASCII tabs and newlines anywhere in them.
+        let url: String = url
+            .trim_matches(|c: char| c <= ' ')
+            .chars()
+            .filter(|&c| c != '\t' && c != '\n' && c != '\r')
+            .collect();
+
+        match url.find(|c: char| c == ':' || c == '/' || c == '?' || c == '#') {
+            Some(idx) if url[idx..].starts_with(':') => self
+                .allowed_url_schemes
+                .contains(&url[..idx].to_ascii_lowercase()),
+            _ => self.allow_relative_urls,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn url_schemes() {
+        let policy = SanitizerPolicy::default();
+
+        for &url in &[
+            "https://example.com",
+            "HTTP://example.com",
+            "  mailto:foo@example.com ",
+            "/path:with:colons",
+            "?q=a:b",
+            "#a:b",
+            "",
+        ] {
+            assert!(policy.is_url_allowed(url), "{}", url);
+        }
+
+        for &url in &[
+            "javascript:alert(1)",
+            " JaVaScRiPt:alert(1)",
+            "java\tscript:alert(1)",
+            "\u{1}javascript:alert(1)",
+            "data:text/html,<script>alert(1)</script>",
+            "vbscript:foo",
+            ":foo",
+        ] {
+            assert!(!policy.is_url_allowed(url), "{:?}", url);
+        }
+
+        let policy = SanitizerPolicy {
+            allow_relative_urls: false,
+            ..SanitizerPolicy::default()
+        };
+
+        assert!(!policy.is_url_allowed("/foo"));
+        assert!(policy.is_url_allowed("https://example.com/foo"));
+    }
+}
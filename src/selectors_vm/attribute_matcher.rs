@@ -1,8 +1,8 @@
 use super::compiler::AttrExprOperands;
 use crate::base::Bytes;
-use crate::html::Namespace;
+use crate::html::{decode_attribute_value, Namespace};
 use crate::parser::{AttributeOutline, SharedAttributeBuffer};
-use encoding_rs::UTF_8;
+use encoding_rs::{Encoding, UTF_8};
 use lazy_static::lazy_static;
 use lazycell::LazyCell;
 use memchr::{memchr, memchr2};
@@ -26,17 +26,24 @@ pub struct AttributeMatcher<'i> {
     id: MemoizedAttrValue<'i>,
     class: MemoizedAttrValue<'i>,
     is_html_element: bool,
+    decoding_encoding: Option<&'static Encoding>,
 }
 
 impl<'i> AttributeMatcher<'i> {
     #[inline]
-    pub fn new(input: &'i Bytes<'i>, attributes: SharedAttributeBuffer, ns: Namespace) -> Self {
+    pub fn new(
+        input: &'i Bytes<'i>,
+        attributes: SharedAttributeBuffer,
+        ns: Namespace,
+        decoding_encoding: Option<&'static Encoding>,
+    ) -> Self {
         AttributeMatcher {
             input,
             attributes,
             id: LazyCell::default(),
             class: LazyCell::default(),
             is_html_element: ns == Namespace::Html,
+            decoding_encoding,
         }
     }
 
@@ -65,8 +72,18 @@ impl<'i> AttributeMatcher<'i> {
 
     #[inline]
     fn get_value(&self, lowercased_name: &Bytes) -> Option<Bytes<'i>> {
-        self.find(lowercased_name)
-            .map(|a| self.input.slice(a.value))
+        self.find(lowercased_name).map(|a| {
+            let value = self.input.slice(a.value);
+
+            match self.decoding_encoding {
+                Some(encoding) if memchr(b'&', &value).is_some() => {
+                    let decoded = decode_attribute_value(&value.as_string(encoding)).into_owned();
+
+                    Bytes::from_str(&decoded, encoding).into_owned()
+                }
+                _ => value,
+            }
+        })
     }
 
     #[inline]
@@ -4,17 +4,21 @@ use super::program::{
 };
 use super::{Ast, AstNode, Expr, AttributeComparisonExpr, OnTagNameExpr, OnAttributesExpr, Predicate, SelectorState};
 use crate::base::{Bytes, HasReplacementsError};
-use crate::html::LocalName;
+use crate::html::{LocalName, LocalNameHash};
 use encoding_rs::Encoding;
 use selectors::attr::{ParsedCaseSensitivity, AttrSelectorOperator};
 use std::fmt::Debug;
 use std::hash::Hash;
 use std::iter;
 
+// NOTE: compiled expressions are always `Send + Sync`, so compiled programs
+// can be shared between rewriters running on different threads.
+
 /// An expression using only the tag name of an element.
-pub type CompiledLocalNameExpr = Box<dyn Fn(&SelectorState, &LocalName) -> bool>;
+pub type CompiledLocalNameExpr = Box<dyn Fn(&SelectorState, &LocalName) -> bool + Send + Sync>;
 /// An expression using the attributes of an element.
-pub type CompiledAttributeExpr = Box<dyn Fn(&SelectorState, &AttributeMatcher) -> bool>;
+pub type CompiledAttributeExpr =
+    Box<dyn Fn(&SelectorState, &AttributeMatcher) -> bool + Send + Sync>;
 
 #[derive(Default)]
 struct ExprSet {
@@ -30,7 +34,7 @@ pub struct AttrExprOperands {
 
 impl Expr<OnTagNameExpr> {
     #[inline]
-    pub fn compile_expr<F: Fn(&SelectorState, &LocalName) -> bool + 'static>(&self, f: F) -> CompiledLocalNameExpr {
+    pub fn compile_expr<F: Fn(&SelectorState, &LocalName) -> bool + Send + Sync + 'static>(&self, f: F) -> CompiledLocalNameExpr {
         if self.negation {
             Box::new(move |s, a| !f(s, a))
         } else {
@@ -80,7 +84,7 @@ impl Compilable for Expr<OnTagNameExpr> {
 
 impl Expr<OnAttributesExpr> {
     #[inline]
-    pub fn compile_expr<F: Fn(&SelectorState, &AttributeMatcher) -> bool + 'static>(&self, f: F) -> CompiledAttributeExpr {
+    pub fn compile_expr<F: Fn(&SelectorState, &AttributeMatcher) -> bool + Send + Sync + 'static>(&self, f: F) -> CompiledAttributeExpr {
         if self.negation {
             Box::new(move |s, a| !f(s, a))
         } else {
@@ -183,6 +187,16 @@ where
     ) -> Instruction<P> {
         let mut exprs = ExprSet::default();
 
+        let required_local_name = on_tag_name_exprs
+            .iter()
+            .find_map(|e| match &e.simple_expr {
+                OnTagNameExpr::LocalName(name) if !e.negation => {
+                    Some(LocalNameHash::from(name.as_str()))
+                }
+                _ => None,
+            })
+            .unwrap_or_default();
+
         on_tag_name_exprs.iter().for_each(|c| c.compile(self.encoding, &mut exprs, enable_nth_of_type));
         on_attr_exprs.iter().for_each(|c| c.compile(self.encoding, &mut exprs, enable_nth_of_type));
 
@@ -197,6 +211,7 @@ where
             associated_branch: branch,
             local_name_exprs: local_name_exprs.into(),
             attribute_exprs: attribute_exprs.into(),
+            required_local_name,
         }
     }
 
@@ -332,7 +347,7 @@ mod tests {
             Token::StartTag(t) => {
                 let (input, attrs) = t.raw_attributes();
                 let tag_name = t.name();
-                let attr_matcher = AttributeMatcher::new(input, attrs, Namespace::Html);
+                let attr_matcher = AttributeMatcher::new(input, attrs, Namespace::Html, None);
                 let local_name =
                     LocalName::from_str_without_replacements(&tag_name, encoding).unwrap();
 
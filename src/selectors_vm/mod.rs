@@ -8,10 +8,12 @@ mod stack;
 
 use self::program::AddressRange;
 use self::stack::StackDirective;
-use crate::html::{LocalName, Namespace};
+use crate::base::MaybeSend;
+use crate::html::{LocalName, LocalNameHash, Namespace, Tag};
 use crate::memory::{MemoryLimitExceededError, SharedMemoryLimiter};
 use crate::transform_stream::AuxStartTagInfo;
 use encoding_rs::Encoding;
+use std::sync::Arc;
 
 pub use self::ast::*;
 pub use self::attribute_matcher::AttributeMatcher;
@@ -19,7 +21,7 @@ pub use self::compiler::Compiler;
 pub use self::error::SelectorError;
 pub use self::parser::Selector;
 pub use self::program::{ExecutionBranch, Program, TryExecResult};
-pub use self::stack::{ElementData, Stack, StackItem, ChildCounter};
+pub use self::stack::{ChildCounter, ElementData, Stack, StackItem};
 
 pub struct MatchInfo<P> {
     pub payload: P,
@@ -27,11 +29,13 @@ pub struct MatchInfo<P> {
 }
 
 pub type AuxStartTagInfoRequest<E, P> = Box<
-    dyn FnOnce(
-        &mut SelectorMatchingVm<E>,
-        AuxStartTagInfo,
-        &mut dyn FnMut(MatchInfo<P>),
-    ) -> Result<(), MemoryLimitExceededError>,
+    dyn_maybe_send!(
+        FnOnce(
+            &mut SelectorMatchingVm<E>,
+            AuxStartTagInfo,
+            &mut dyn FnMut(MatchInfo<P>),
+        ) -> Result<(), MemoryLimitExceededError>
+    ),
 >;
 
 pub enum VmError<E: ElementData, MatchPayload> {
@@ -134,24 +138,116 @@ macro_rules! aux_info_request {
     };
 }
 
+/// Tracks the elements that are created by HTML parser only once per document: their start
+/// tags are ignored afterwards (with their attributes added to the existing element).
+#[derive(Default, Copy, Clone, PartialEq, Eq)]
+struct SingletonElements {
+    html: bool,
+    head: bool,
+    body: bool,
+}
+
+impl SingletonElements {
+    #[inline]
+    fn track_start_tag(&mut self, local_name: &LocalName) {
+        if *local_name == Tag::Html {
+            self.html = true;
+        } else if *local_name == Tag::Head {
+            self.head = true;
+        } else if *local_name == Tag::Body {
+            // NOTE: `head` start tags are ignored in the document's body as well.
+            self.head = true;
+            self.body = true;
+        }
+    }
+
+    #[inline]
+    fn can_be_created(&self, local_name: LocalNameHash) -> bool {
+        !((self.html && local_name == Tag::Html)
+            || (self.head && local_name == Tag::Head)
+            || (self.body && local_name == Tag::Body))
+    }
+}
+
 pub struct SelectorMatchingVm<E: ElementData> {
-    program: Program<E::MatchPayload>,
+    program: Arc<Program<E::MatchPayload>>,
     stack: Stack<E>,
+    attr_decoding_encoding: Option<&'static Encoding>,
+    singleton_elements: SingletonElements,
+    entry_points_can_match: bool,
 }
 
 impl<E: ElementData> SelectorMatchingVm<E> {
+    /// Creates a VM executing a compiled program that can be shared between VMs.
+    ///
+    /// If `attr_decoding_encoding` is specified, attribute values are decoded using this
+    /// encoding and matched with decoded character references.
     #[inline]
     pub fn new(
-        ast: Ast<E::MatchPayload>,
-        encoding: &'static Encoding,
+        program: Arc<Program<E::MatchPayload>>,
         memory_limiter: SharedMemoryLimiter,
+        attr_decoding_encoding: Option<&'static Encoding>,
     ) -> Self {
-        let program = Compiler::new(encoding).compile(ast);
         let enable_nth_of_type = program.enable_nth_of_type;
+        let entry_points_can_match = !program.entry_points.is_empty();
 
         SelectorMatchingVm {
             program,
             stack: Stack::new(memory_limiter, enable_nth_of_type),
+            attr_decoding_encoding,
+            singleton_elements: SingletonElements::default(),
+            entry_points_can_match,
+        }
+    }
+
+    /// Returns the VM to its initial state, so it can be used for another document.
+    pub fn reset(&mut self) {
+        self.stack.clear();
+        self.singleton_elements = SingletonElements::default();
+        self.entry_points_can_match = !self.program.entry_points.is_empty();
+    }
+
+    /// Returns `false` if none of the selectors can match any element in the rest of the document.
+    ///
+    /// NOTE: the only elements that are known to never appear again are the `html`, `head`
+    /// and `body` elements: HTML parser ignores their start tags once they have been created.
+    /// So, an instruction can't match anymore if it requires the tag name of such an element.
+    /// The rest of the elements can be matched only by the entry points or by the jumps of the
+    /// elements on the stack, so if none of these instructions can match, none of the
+    /// selectors can (e.g. `html > head > meta` once the `head` element is closed).
+    ///
+    /// The VM itself still matches such start tags, so the result only tells if the rest of the
+    /// input can be passed through without rewriting.
+    pub fn can_match_further(&self) -> bool {
+        self.entry_points_can_match
+            || self.stack.items().iter().any(|item| {
+                item.jumps
+                    .iter()
+                    .chain(item.hereditary_jumps.iter())
+                    .any(|addr_range| self.can_match_in(addr_range))
+            })
+    }
+
+    #[inline]
+    fn can_match_in(&self, addr_range: &AddressRange) -> bool {
+        self.program.instructions[addr_range.clone()]
+            .iter()
+            .any(|instr| {
+                self.singleton_elements
+                    .can_be_created(instr.required_local_name)
+            })
+    }
+
+    #[inline]
+    fn track_singleton_elements(&mut self, local_name: &LocalName, ns: Namespace) {
+        if ns == Namespace::Html {
+            let prev_state = self.singleton_elements;
+
+            self.singleton_elements.track_start_tag(local_name);
+
+            if self.singleton_elements != prev_state {
+                self.entry_points_can_match = self.can_match_in(&self.program.entry_points);
+            }
         }
     }
 
@@ -163,6 +259,8 @@ impl<E: ElementData> SelectorMatchingVm<E> {
     ) -> Result<(), VmError<E, E::MatchPayload>> {
         use StackDirective::*;
 
+        self.track_singleton_elements(&local_name, ns);
+
         self.stack.add_child(&local_name);
 
         let mut ctx = ExecutionCtx::new(local_name, ns);
@@ -204,7 +302,12 @@ impl<E: ElementData> SelectorMatchingVm<E> {
         aux_info: AuxStartTagInfo,
         match_handler: &mut dyn FnMut(MatchInfo<E::MatchPayload>),
     ) -> Result<(), MemoryLimitExceededError> {
-        let attr_matcher = AttributeMatcher::new(aux_info.input, aux_info.attr_buffer, ns);
+        let attr_matcher = AttributeMatcher::new(
+            aux_info.input,
+            aux_info.attr_buffer,
+            ns,
+            self.attr_decoding_encoding,
+        );
 
         ctx.with_content = !aux_info.self_closing;
 
@@ -232,7 +335,7 @@ impl<E: ElementData> SelectorMatchingVm<E> {
         Ok(())
     }
 
-    fn bailout<T: 'static>(
+    fn bailout<T: MaybeSend + 'static>(
         ctx: ExecutionCtx<E>,
         bailout: Bailout<T>,
         recovery_point_handler: RecoveryPointHandler<T, E, E::MatchPayload>,
@@ -240,7 +343,12 @@ impl<E: ElementData> SelectorMatchingVm<E> {
         let mut ctx = ctx.into_owned();
 
         aux_info_request!(move |this, aux_info, match_handler| {
-            let attr_matcher = AttributeMatcher::new(aux_info.input, aux_info.attr_buffer, ctx.ns);
+            let attr_matcher = AttributeMatcher::new(
+                aux_info.input,
+                aux_info.attr_buffer,
+                ctx.ns,
+                this.attr_decoding_encoding,
+            );
 
             this.complete_instr_execution_with_attrs(
                 bailout.at_addr,
@@ -375,17 +483,17 @@ impl<E: ElementData> SelectorMatchingVm<E> {
         let state = self.stack.build_state(&ctx.stack_item.local_name);
 
         for addr in addr_range {
-            match self.program.instructions[addr].try_exec_without_attrs(&state, &ctx.stack_item.local_name) {
-                TryExecResult::Branch(branch) => {
-                    ctx.add_execution_branch(branch, match_handler)
-                }
+            match self.program.instructions[addr]
+                .try_exec_without_attrs(&state, &ctx.stack_item.local_name)
+            {
+                TryExecResult::Branch(branch) => ctx.add_execution_branch(branch, match_handler),
                 TryExecResult::AttributesRequired => {
                     return Err(Bailout {
                         at_addr: addr,
                         recovery_point: addr - start + 1,
                     });
-                },
-                _ => ()
+                }
+                _ => (),
             }
         }
 
@@ -543,11 +651,11 @@ mod tests {
     use crate::errors::RewritingError;
     use crate::html::Namespace;
     use crate::memory::MemoryLimiter;
-    use crate::rewritable_units::{DocumentEnd, Token, TokenCaptureFlags};
+    use crate::rewritable_units::{DocumentEnd, DocumentStart, Token, TokenCaptureFlags};
     use crate::transform_stream::{
         StartTagHandlingResult, TransformController, TransformStream, TransformStreamSettings,
     };
-    use encoding_rs::UTF_8;
+    use encoding_rs::{Encoding, UTF_8};
     use hashbrown::{HashMap, HashSet};
 
     struct Expectation {
@@ -605,6 +713,10 @@ mod tests {
                 TokenCaptureFlags::all()
             }
 
+            fn handle_start(&mut self, _: &mut DocumentStart) -> Result<(), RewritingError> {
+                Ok(())
+            }
+
             fn handle_end(&mut self, _: &mut DocumentEnd) -> Result<(), RewritingError> {
                 Ok(())
             }
@@ -626,6 +738,7 @@ mod tests {
             encoding,
             memory_limiter: MemoryLimiter::new_shared(2048),
             strict: true,
+            stats: None,
         });
 
         transform_stream.write(&*html).unwrap();
@@ -660,8 +773,11 @@ mod tests {
             }
 
             let memory_limiter = MemoryLimiter::new_shared(2048);
-            let vm: SelectorMatchingVm<TestElementData> =
-                SelectorMatchingVm::new(ast, UTF_8, memory_limiter);
+            let vm: SelectorMatchingVm<TestElementData> = SelectorMatchingVm::new(
+                Arc::new(Compiler::new(UTF_8).compile(ast)),
+                memory_limiter,
+                None,
+            );
 
             vm
         }};
@@ -1728,4 +1844,143 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn can_match_further() {
+        let mut vm = create_vm!(&["head > meta", "head title"]);
+
+        let no_match = Expectation {
+            should_bailout: false,
+            should_match_with_content: true,
+            matched_payload: set![],
+        };
+
+        // Stack after:
+        // - <html>
+        exec_for_start_tag_and_assert!(vm, "<html>", Namespace::Html, no_match);
+
+        // Stack after:
+        // - <html>
+        // - <head>
+        exec_for_start_tag_and_assert!(vm, "<head>", Namespace::Html, no_match);
+
+        // Stack after:
+        // - <html>
+        // - <head>
+        // - <title> (1)
+        exec_for_start_tag_and_assert!(
+            vm,
+            "<title>",
+            Namespace::Html,
+            Expectation {
+                should_bailout: false,
+                should_match_with_content: true,
+                matched_payload: set![1],
+            }
+        );
+
+        // Stack after:
+        // - <html>
+        // - <head>
+        exec_for_end_tag_and_assert!(vm, "</title>", map![(1, 1)]);
+
+        assert!(vm.can_match_further());
+
+        // Stack after:
+        // - <html>
+        exec_for_end_tag_and_assert!(vm, "</head>", map![]);
+
+        assert!(!vm.can_match_further());
+
+        // NOTE: the VM still matches `head` start tags that are ignored by HTML parser.
+        // Stack after:
+        // - <html>
+        // - <head>
+        exec_for_start_tag_and_assert!(vm, "<head>", Namespace::Html, no_match);
+
+        assert!(vm.can_match_further());
+
+        // Stack after:
+        // - <html>
+        // - <head>
+        exec_for_start_tag_and_assert!(
+            vm,
+            "<meta>",
+            Namespace::Html,
+            Expectation {
+                should_bailout: false,
+                should_match_with_content: false,
+                matched_payload: set![0],
+            }
+        );
+
+        // Stack after:
+        // - <html>
+        exec_for_end_tag_and_assert!(vm, "</head>", map![]);
+
+        assert!(!vm.can_match_further());
+    }
+
+    #[test]
+    fn can_match_further_with_jumps_on_stack() {
+        let mut vm = create_vm!(&["html > head > meta", "html title", "body > div"]);
+
+        let no_match = Expectation {
+            should_bailout: false,
+            should_match_with_content: true,
+            matched_payload: set![],
+        };
+
+        // Stack after:
+        // - <html>
+        exec_for_start_tag_and_assert!(vm, "<html>", Namespace::Html, no_match);
+
+        assert!(vm.can_match_further());
+
+        // Stack after:
+        // - <html>
+        // - <head>
+        exec_for_start_tag_and_assert!(vm, "<head>", Namespace::Html, no_match);
+
+        // Stack after:
+        // - <html>
+        exec_for_end_tag_and_assert!(vm, "</head>", map![]);
+
+        assert!(vm.can_match_further());
+
+        // Stack after:
+        // - <html>
+        // - <body>
+        exec_for_start_tag_and_assert!(vm, "<body>", Namespace::Html, no_match);
+
+        assert!(vm.can_match_further());
+
+        // Stack after:
+        // - <html>
+        exec_for_end_tag_and_assert!(vm, "</body>", map![]);
+
+        // NOTE: `title` can still be matched as a descendant of `html`.
+        assert!(vm.can_match_further());
+
+        // Stack after: empty
+        exec_for_end_tag_and_assert!(vm, "</html>", map![]);
+
+        assert!(!vm.can_match_further());
+    }
+
+    #[test]
+    fn can_match_further_with_reachable_selectors() {
+        let mut vm = create_vm!(&["head > meta", "body"]);
+
+        let no_match = Expectation {
+            should_bailout: false,
+            should_match_with_content: true,
+            matched_payload: set![],
+        };
+
+        exec_for_start_tag_and_assert!(vm, "<head>", Namespace::Html, no_match);
+        exec_for_end_tag_and_assert!(vm, "</head>", map![]);
+
+        assert!(vm.can_match_further());
+    }
 }
@@ -206,3 +206,12 @@ impl FromStr for Selector {
         Ok(Selector(SelectorsParser::parse(selector)?))
     }
 }
+
+impl Selector {
+    /// Returns `true` if the selector contains only ASCII characters, so its compiled
+    /// form is the same for all ASCII-compatible encodings.
+    #[inline]
+    pub(crate) fn is_ascii(&self) -> bool {
+        self.0.to_css_string().is_ascii()
+    }
+}
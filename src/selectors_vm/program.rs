@@ -1,7 +1,7 @@
 use super::SelectorState;
 use super::attribute_matcher::AttributeMatcher;
 use super::compiler::{CompiledAttributeExpr, CompiledLocalNameExpr};
-use crate::html::LocalName;
+use crate::html::{LocalName, LocalNameHash};
 use hashbrown::HashSet;
 use std::hash::Hash;
 use std::ops::Range;
@@ -38,6 +38,9 @@ where
     pub associated_branch: ExecutionBranch<P>,
     pub local_name_exprs: Box<[CompiledLocalNameExpr]>,
     pub attribute_exprs: Box<[CompiledAttributeExpr]>,
+    /// The hash of the tag name required by the instruction. It's empty if the instruction
+    /// doesn't require a particular tag name or if the tag name can't be hashed.
+    pub required_local_name: LocalNameHash,
 }
 
 impl<P> Instruction<P>
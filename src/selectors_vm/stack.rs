@@ -1,6 +1,7 @@
 use super::SelectorState;
 use super::program::AddressRange;
 use super::ast::NthChild;
+use crate::base::MaybeSend;
 use crate::html::{LocalName, Namespace, Tag};
 use crate::memory::{LimitedVec, MemoryLimitExceededError, SharedMemoryLimiter};
 // use hashbrown for raw entry, switch back to std once it stablizes there
@@ -24,7 +25,7 @@ fn is_void_element(local_name: &LocalName) -> bool {
     )
 }
 
-pub trait ElementData: Default + 'static {
+pub trait ElementData: Default + MaybeSend + 'static {
     type MatchPayload: PartialEq + Eq + Copy + Debug + Hash + 'static;
 
     fn matched_payload_mut(&mut self) -> &mut HashSet<Self::MatchPayload>;
@@ -201,6 +202,17 @@ impl<E: ElementData> Stack<E> {
         }
     }
 
+    /// Removes all the items and resets child counters, retaining the allocated memory.
+    pub fn clear(&mut self) {
+        self.root_child_counter = ChildCounter::default();
+
+        if let Some(counters) = &mut self.typed_child_counters {
+            counters.0.clear();
+        }
+
+        self.items.drain(..);
+    }
+
     /// Adds a child to child counters. Called before pushing the element to the stack.
     pub fn add_child<'i>(&mut self, name: &LocalName<'i>) {
         match self.items.last_mut() {
@@ -1,16 +1,16 @@
 use super::*;
-use crate::base::{Bytes, Range};
+use crate::base::{Bytes, Range, Shared};
 use crate::html::{LocalName, Namespace};
 use crate::parser::{
     Lexeme, LexemeSink, NonTagContentLexeme, ParserDirective, ParserOutputSink, TagHintSink,
     TagLexeme, TagTokenOutline,
 };
 use crate::rewritable_units::{
-    DocumentEnd, Serialize, ToToken, Token, TokenCaptureFlags, TokenCapturer, TokenCapturerEvent,
+    DocumentEnd, DocumentStart, SerializeWithMutations, SourceLocation, SourceLocationTracker,
+    ToToken, Token, TokenCaptureFlags, TokenCapturer, TokenCapturerEvent, TokenStart,
 };
-use crate::rewriter::RewritingError;
-use encoding_rs::Encoding;
-use std::rc::Rc;
+use crate::rewriter::{RewritingError, RewritingStats};
+use encoding_rs::{Encoding, UTF_8};
 
 use TagTokenOutline::*;
 
@@ -20,8 +20,11 @@ pub struct AuxStartTagInfo<'i> {
     pub self_closing: bool,
 }
 
-type AuxStartTagInfoRequest<C> =
-    Box<dyn FnOnce(&mut C, AuxStartTagInfo<'_>) -> Result<TokenCaptureFlags, RewritingError>>;
+type AuxStartTagInfoRequest<C> = Box<
+    dyn_maybe_send!(
+        FnOnce(&mut C, AuxStartTagInfo<'_>) -> Result<TokenCaptureFlags, RewritingError>
+    ),
+>;
 
 pub enum DispatcherError<C> {
     InfoRequest(AuxStartTagInfoRequest<C>),
@@ -35,8 +38,37 @@ pub trait TransformController: Sized {
     fn handle_start_tag(&mut self, name: LocalName, ns: Namespace) -> StartTagHandlingResult<Self>;
     fn handle_end_tag(&mut self, name: LocalName) -> TokenCaptureFlags;
     fn handle_token(&mut self, token: &mut Token) -> Result<(), RewritingError>;
+    fn handle_start(&mut self, document_start: &mut DocumentStart) -> Result<(), RewritingError>;
     fn handle_end(&mut self, document_end: &mut DocumentEnd) -> Result<(), RewritingError>;
     fn should_emit_content(&self) -> bool;
+
+    // NOTE: content that is not emitted can be captured instead of being removed.
+    fn content_capture(&self) -> Option<usize> {
+        None
+    }
+
+    // NOTE: the content that can't be captured is passed through to the `output_handler`.
+    fn capture_content(
+        &mut self,
+        _capture_idx: usize,
+        _chunk: &[u8],
+        _output_handler: &mut dyn FnMut(&[u8]),
+    ) -> Result<(), RewritingError> {
+        Ok(())
+    }
+
+    fn take_captured_content(&mut self) -> Vec<u8> {
+        Vec::default()
+    }
+
+    // NOTE: once this returns `true` the rest of the input is passed through as is.
+    fn should_stop_rewriting(&self) -> bool {
+        false
+    }
+
+    // NOTE: the controller should return to its initial state, so it can be used
+    // for another document.
+    fn reset(&mut self) {}
 }
 
 /// Defines an interface for the [`HtmlRewriter`]'s output.
@@ -72,7 +104,15 @@ where
     got_flags_from_hint: bool,
     pending_element_aux_info_req: Option<AuxStartTagInfoRequest<C>>,
     emission_enabled: bool,
+    content_capture: Option<usize>,
     encoding: &'static Encoding,
+    source_location_tracker: SourceLocationTracker,
+    pending_text_location: SourceLocation,
+    document_started: bool,
+    document_start_content: Option<Vec<u8>>,
+    rewriting_stopped: bool,
+    output_enabled: bool,
+    stats: Option<RewritingStats>,
 }
 
 impl<C, O> Dispatcher<C, O>
@@ -80,7 +120,12 @@ where
     C: TransformController,
     O: OutputSink,
 {
-    pub fn new(transform_controller: C, output_sink: O, encoding: &'static Encoding) -> Self {
+    pub fn new(
+        transform_controller: C,
+        output_sink: O,
+        encoding: &'static Encoding,
+        stats: Option<RewritingStats>,
+    ) -> Self {
         let initial_capture_flags = transform_controller.initial_capture_flags();
 
         Dispatcher {
@@ -91,22 +136,178 @@ where
             got_flags_from_hint: false,
             pending_element_aux_info_req: None,
             emission_enabled: true,
+            content_capture: None,
             encoding,
+            source_location_tracker: SourceLocationTracker::default(),
+            pending_text_location: SourceLocation::default(),
+            document_started: false,
+            document_start_content: None,
+            rewriting_stopped: false,
+            output_enabled: true,
+            stats,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.transform_controller.reset();
+
+        let initial_capture_flags = self.transform_controller.initial_capture_flags();
+
+        self.remaining_content_start = 0;
+        self.token_capturer.reset(initial_capture_flags);
+        self.got_flags_from_hint = false;
+        self.pending_element_aux_info_req = None;
+        self.emission_enabled = true;
+        self.content_capture = None;
+        self.source_location_tracker = SourceLocationTracker::default();
+        self.pending_text_location = SourceLocation::default();
+        self.document_started = false;
+        self.document_start_content = None;
+        self.rewriting_stopped = false;
+    }
+
+    // NOTE: once the output is disabled the tokens are still produced for the transform
+    // controller, but they are not serialized and the input is not emitted to the output
+    // sink. The output sink receives only the finalizing chunk.
+    #[inline]
+    pub fn disable_output(&mut self) {
+        self.output_enabled = false;
+    }
+
+    #[inline]
+    pub fn output_sink_mut(&mut self) -> &mut O {
+        &mut self.output_sink
+    }
+
+    #[inline]
+    pub fn transform_controller_mut(&mut self) -> &mut C {
+        &mut self.transform_controller
+    }
+
+    // NOTE: the encoding can be changed only before any input is processed.
+    #[inline]
+    pub fn set_encoding(&mut self, encoding: &'static Encoding) {
+        self.encoding = encoding;
+        self.token_capturer.set_encoding(encoding);
+    }
+
+    /// Emits the content prepended to the document and returns the number of bytes of the `input`
+    /// that were emitted with it. Returns `None` if more input is required to tell whether
+    /// the `input` starts with a byte order mark.
+    pub fn start(&mut self, input: &[u8], last: bool) -> Result<Option<usize>, RewritingError> {
+        const BOM: &[u8] = b"\xEF\xBB\xBF";
+
+        if self.document_started {
+            return Ok(Some(0));
+        }
+
+        // NOTE: the content is kept between the calls if the beginning of the input is incomplete.
+        let content = match self.document_start_content.take() {
+            Some(content) => content,
+            None => {
+                let mut document_start = DocumentStart::new(self.encoding);
+
+                self.transform_controller
+                    .handle_start(&mut document_start)?;
+
+                document_start.into_content()
+            }
+        };
+
+        // NOTE: the content is inserted after the byte order mark, so we don't split
+        // the input if there is nothing to insert.
+        let bom_len = if content.is_empty() || self.encoding != UTF_8 {
+            0
+        } else if input.starts_with(BOM) {
+            BOM.len()
+        } else if BOM.starts_with(input) && !last {
+            self.document_start_content = Some(content);
+
+            return Ok(None);
+        } else {
+            0
+        };
+
+        if bom_len > 0 {
+            if self.output_enabled {
+                self.output_sink.handle_chunk(&input[..bom_len]);
+            }
+
+            self.source_location_tracker.finish_chunk(input, bom_len);
+        }
+
+        if self.output_enabled && !content.is_empty() {
+            self.output_sink.handle_chunk(&content);
         }
+
+        self.document_started = true;
+
+        Ok(Some(bom_len))
     }
 
-    pub fn flush_remaining_input(&mut self, input: &[u8], consumed_byte_count: usize) {
+    pub fn flush_remaining_input(
+        &mut self,
+        input: &[u8],
+        consumed_byte_count: usize,
+    ) -> Result<(), RewritingError> {
         let output = &input[self.remaining_content_start..consumed_byte_count];
 
-        if self.emission_enabled && !output.is_empty() {
-            self.output_sink.handle_chunk(&output);
+        if !output.is_empty() {
+            emit_content(
+                output,
+                self.emission_enabled && self.output_enabled,
+                self.output_enabled,
+                self.content_capture,
+                &mut self.transform_controller,
+                &mut self.output_sink,
+            )?;
         }
 
         self.remaining_content_start = 0;
+
+        self.source_location_tracker
+            .finish_chunk(input, consumed_byte_count);
+
+        Ok(())
+    }
+
+    #[inline]
+    pub fn rewriting_stopped(&self) -> bool {
+        self.rewriting_stopped
+    }
+
+    /// Emits the part of the `input` that follows the content that was emitted before
+    /// the rewriting has been stopped.
+    pub fn pass_through_remaining_input(&mut self, input: &[u8]) {
+        let output = &input[self.remaining_content_start..];
+
+        if self.output_enabled && !output.is_empty() {
+            self.output_sink.handle_chunk(output);
+        }
+
+        self.remaining_content_start = 0;
+
+        self.source_location_tracker
+            .finish_chunk(input, input.len());
+    }
+
+    #[inline]
+    pub fn pass_through(&mut self, input: &[u8]) {
+        if self.output_enabled && !input.is_empty() {
+            self.output_sink.handle_chunk(input);
+        }
     }
 
     pub fn finish(&mut self, input: &[u8]) -> Result<(), RewritingError> {
-        self.flush_remaining_input(input, input.len());
+        self.flush_remaining_input(input, input.len())?;
+
+        // NOTE: the content of the elements that are not closed by the end of the input
+        // is emitted as is.
+        let captured_content = self.transform_controller.take_captured_content();
+
+        if self.output_enabled && !captured_content.is_empty() {
+            self.output_sink.handle_chunk(&captured_content);
+        }
 
         let mut document_end = DocumentEnd::new(&mut self.output_sink, self.encoding);
 
@@ -127,9 +328,13 @@ where
     {
         let transform_controller = &mut self.transform_controller;
         let output_sink = &mut self.output_sink;
-        let emission_enabled = self.emission_enabled;
+        let emission_enabled = self.emission_enabled && self.output_enabled;
+        let output_enabled = self.output_enabled;
+        let content_capture = self.content_capture;
         let lexeme_range = lexeme.raw_range();
         let remaining_content_start = self.remaining_content_start;
+        let source_location_tracker = &mut self.source_location_tracker;
+        let pending_text_location = self.pending_text_location;
         let mut lexeme_consumed = false;
 
         self.token_capturer.feed(lexeme, |event| {
@@ -142,18 +347,37 @@ where
 
                     lexeme_consumed = true;
 
-                    if emission_enabled && chunk.len() > 0 {
-                        output_sink.handle_chunk(&chunk);
+                    if chunk.len() > 0 {
+                        emit_content(
+                            &chunk,
+                            emission_enabled,
+                            output_enabled,
+                            content_capture,
+                            transform_controller,
+                            output_sink,
+                        )?;
                     }
                 }
-                TokenCapturerEvent::TokenProduced(mut token) => {
+                TokenCapturerEvent::TokenProduced(mut token, start) => {
                     trace!(@output token);
 
+                    let source_location = match start {
+                        TokenStart::InLexeme(offset) => source_location_tracker
+                            .location_at(lexeme.input(), lexeme_range.start + offset),
+                        TokenStart::AfterPreviousText => pending_text_location,
+                    };
+
+                    token.set_source_location(source_location);
                     transform_controller.handle_token(&mut token)?;
 
-                    if emission_enabled {
-                        token.to_bytes(&mut |c| output_sink.handle_chunk(c));
-                    }
+                    emit_token(
+                        &mut token,
+                        emission_enabled,
+                        output_enabled,
+                        content_capture,
+                        transform_controller,
+                        output_sink,
+                    )?;
                 }
             }
             Ok(())
@@ -163,9 +387,29 @@ where
             self.remaining_content_start = lexeme_range.end;
         }
 
+        // NOTE: the raw text that hasn't been produced yet can be carried over to the next
+        // input chunk, so its location is calculated while the current chunk is available.
+        if let Some(offset) = self.token_capturer.take_pending_text_offset() {
+            self.pending_text_location = self
+                .source_location_tracker
+                .location_at(lexeme.input(), lexeme_range.start + offset);
+        }
+
         Ok(())
     }
 
+    // NOTE: the rewriting can be stopped only on the tag boundaries: the pending text
+    // is flushed to the text handlers at this point, so none of them is left with
+    // an incomplete text node.
+    #[inline]
+    fn try_stop_rewriting(&mut self) -> bool {
+        if self.transform_controller.should_stop_rewriting() {
+            self.rewriting_stopped = true;
+        }
+
+        self.rewriting_stopped
+    }
+
     #[inline]
     fn get_next_parser_directive(&self) -> ParserDirective {
         if self.token_capturer.has_captures() {
@@ -187,7 +431,7 @@ where
                     &mut self.transform_controller,
                     AuxStartTagInfo {
                         input,
-                        attr_buffer: Rc::clone($attributes),
+                        attr_buffer: Shared::clone($attributes),
                         self_closing: $self_closing,
                     },
                 )
@@ -255,19 +499,29 @@ where
 
     #[inline]
     fn flush_pending_captured_text(&mut self) -> Result<(), RewritingError> {
+        let source_location = self.pending_text_location;
         let transform_controller = &mut self.transform_controller;
         let output_sink = &mut self.output_sink;
-        let emission_enabled = self.emission_enabled;
+        let emission_enabled = self.emission_enabled && self.output_enabled;
+        let output_enabled = self.output_enabled;
+        let content_capture = self.content_capture;
 
         self.token_capturer.flush_pending_text(&mut |event| {
-            if let TokenCapturerEvent::TokenProduced(mut token) = event {
+            // NOTE: the flushed text continues the previously produced text.
+            if let TokenCapturerEvent::TokenProduced(mut token, _) = event {
                 trace!(@output token);
 
+                token.set_source_location(source_location);
                 transform_controller.handle_token(&mut token)?;
 
-                if emission_enabled {
-                    token.to_bytes(&mut |c| output_sink.handle_chunk(c));
-                }
+                emit_token(
+                    &mut token,
+                    emission_enabled,
+                    output_enabled,
+                    content_capture,
+                    transform_controller,
+                    output_sink,
+                )?;
             }
 
             Ok(())
@@ -277,17 +531,79 @@ where
     }
 
     #[inline]
-    fn should_stop_removing_element_content(&self) -> bool {
-        !self.emission_enabled && self.transform_controller.should_emit_content()
+    fn should_stop_removing_or_capturing_element_content(&self) -> bool {
+        (!self.emission_enabled && self.transform_controller.should_emit_content())
+            || self.content_capture != self.transform_controller.content_capture()
+    }
+
+    #[inline]
+    fn update_content_emission(&mut self) {
+        self.emission_enabled = self.transform_controller.should_emit_content();
+        self.content_capture = self.transform_controller.content_capture();
     }
 }
 
+#[inline]
+fn emit_content<C: TransformController, O: OutputSink>(
+    chunk: &[u8],
+    emission_enabled: bool,
+    output_enabled: bool,
+    content_capture: Option<usize>,
+    transform_controller: &mut C,
+    output_sink: &mut O,
+) -> Result<(), RewritingError> {
+    if emission_enabled {
+        output_sink.handle_chunk(chunk);
+    } else if let Some(capture_idx) = content_capture {
+        transform_controller.capture_content(capture_idx, chunk, &mut |c| {
+            if output_enabled {
+                output_sink.handle_chunk(c);
+            }
+        })?;
+    }
+
+    Ok(())
+}
+
+#[inline]
+fn emit_token<C: TransformController, O: OutputSink>(
+    token: &mut Token,
+    emission_enabled: bool,
+    output_enabled: bool,
+    content_capture: Option<usize>,
+    transform_controller: &mut C,
+    output_sink: &mut O,
+) -> Result<(), RewritingError> {
+    let mut result = Ok(());
+
+    if emission_enabled || content_capture.is_some() {
+        token.to_bytes(&mut |c| {
+            if result.is_ok() {
+                result = emit_content(
+                    c,
+                    emission_enabled,
+                    output_enabled,
+                    content_capture,
+                    transform_controller,
+                    output_sink,
+                );
+            }
+        })?;
+    }
+
+    result
+}
+
 impl<C, O> LexemeSink for Dispatcher<C, O>
 where
     C: TransformController,
     O: OutputSink,
 {
     fn handle_tag(&mut self, lexeme: &TagLexeme) -> Result<ParserDirective, RewritingError> {
+        if let Some(ref stats) = self.stats {
+            stats.record_lexed_token();
+        }
+
         // NOTE: flush pending text before reporting tag to the transform controller.
         // Otherwise, transform controller can enable or disable text handlers too early.
         // In case of start tag, newly matched element text handlers
@@ -297,21 +613,49 @@ where
 
         if self.got_flags_from_hint {
             self.got_flags_from_hint = false;
+        } else if self.pending_element_aux_info_req.is_none() && self.try_stop_rewriting() {
+            // NOTE: the tag is not reported to the transform controller yet, so we can
+            // stop right before it.
+            return Ok(ParserDirective::Stop);
         } else {
             self.adjust_capture_flags_for_tag_lexeme(lexeme)?;
         }
 
         if let TagTokenOutline::EndTag { .. } = lexeme.token_outline() {
-            if self.should_stop_removing_element_content() {
-                self.emission_enabled = true;
-                self.remaining_content_start = lexeme.raw_range().start;
+            if self.should_stop_removing_or_capturing_element_content() {
+                let content_end = lexeme.raw_range().start;
+
+                // NOTE: the captured content should be complete before the end tag
+                // is passed to the handlers.
+                if self.content_capture.is_some() && self.remaining_content_start < content_end {
+                    let content = lexeme.input().slice(Range {
+                        start: self.remaining_content_start,
+                        end: content_end,
+                    });
+
+                    emit_content(
+                        &content,
+                        false,
+                        self.output_enabled,
+                        self.content_capture,
+                        &mut self.transform_controller,
+                        &mut self.output_sink,
+                    )?;
+                }
+
+                self.update_content_emission();
+                self.remaining_content_start = content_end;
             }
         }
 
         self.try_produce_token_from_lexeme(lexeme)?;
-        self.emission_enabled = self.transform_controller.should_emit_content();
+        self.update_content_emission();
 
-        Ok(self.get_next_parser_directive())
+        if self.try_stop_rewriting() {
+            Ok(ParserDirective::Stop)
+        } else {
+            Ok(self.get_next_parser_directive())
+        }
     }
 
     #[inline]
@@ -319,6 +663,10 @@ where
         &mut self,
         lexeme: &NonTagContentLexeme,
     ) -> Result<(), RewritingError> {
+        if let Some(ref stats) = self.stats {
+            stats.record_lexed_token();
+        }
+
         self.try_produce_token_from_lexeme(lexeme)
     }
 }
@@ -333,6 +681,14 @@ where
         name: LocalName,
         ns: Namespace,
     ) -> Result<ParserDirective, RewritingError> {
+        if let Some(ref stats) = self.stats {
+            stats.record_scanned_tag();
+        }
+
+        if self.try_stop_rewriting() {
+            return Ok(ParserDirective::Stop);
+        }
+
         match self.transform_controller.handle_start_tag(name, ns) {
             Ok(flags) => {
                 Ok(self.apply_capture_flags_from_hint_and_get_next_parser_directive(flags))
@@ -348,15 +704,23 @@ where
     }
 
     fn handle_end_tag_hint(&mut self, name: LocalName) -> Result<ParserDirective, RewritingError> {
+        if let Some(ref stats) = self.stats {
+            stats.record_scanned_tag();
+        }
+
         self.flush_pending_captured_text()?;
 
+        if self.try_stop_rewriting() {
+            return Ok(ParserDirective::Stop);
+        }
+
         let mut flags = self.transform_controller.handle_end_tag(name);
 
-        // NOTE: if emission was disabled (i.e. we've been removing element content)
+        // NOTE: if emission was disabled (i.e. we've been removing or capturing element content)
         // we need to request the end tag lexeme, to ensure that we have it.
         // Otherwise, if we have unfinished end tag in the end of input we'll emit
         // it where we shouldn't.
-        if self.should_stop_removing_element_content() {
+        if self.should_stop_removing_or_capturing_element_content() {
             flags |= TokenCaptureFlags::NEXT_END_TAG;
         }
 
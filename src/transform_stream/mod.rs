@@ -1,12 +1,11 @@
 mod dispatcher;
 
 use self::dispatcher::Dispatcher;
+use crate::base::Shared;
 use crate::memory::{Arena, SharedMemoryLimiter};
 use crate::parser::{Parser, ParserDirective, SharedAttributeBuffer};
-use crate::rewriter::RewritingError;
+use crate::rewriter::{RewritingError, RewritingStats};
 use encoding_rs::Encoding;
-use std::cell::RefCell;
-use std::rc::Rc;
 
 pub use self::dispatcher::{
     AuxStartTagInfo, DispatcherError, OutputSink, StartTagHandlingResult, TransformController,
@@ -23,6 +22,7 @@ where
     pub memory_limiter: SharedMemoryLimiter,
     pub encoding: &'static Encoding,
     pub strict: bool,
+    pub stats: Option<RewritingStats>,
 }
 
 pub struct TransformStream<C, O>
@@ -30,10 +30,11 @@ where
     C: TransformController,
     O: OutputSink,
 {
-    dispatcher: Rc<RefCell<Dispatcher<C, O>>>,
+    dispatcher: Shared<Dispatcher<C, O>>,
     parser: Parser<Dispatcher<C, O>>,
     buffer: Arena,
     has_buffered_data: bool,
+    stats: Option<RewritingStats>,
 }
 
 impl<C, O> TransformStream<C, O>
@@ -42,21 +43,15 @@ where
     O: OutputSink,
 {
     pub fn new(settings: TransformStreamSettings<C, O>) -> Self {
-        let initial_parser_directive = if settings
-            .transform_controller
-            .initial_capture_flags()
-            .is_empty()
-        {
-            ParserDirective::WherePossibleScanForTagsOnly
-        } else {
-            ParserDirective::Lex
-        };
+        let initial_parser_directive =
+            Self::initial_parser_directive(&settings.transform_controller);
 
-        let dispatcher = Rc::new(RefCell::new(Dispatcher::new(
+        let dispatcher = Shared::new(Dispatcher::new(
             settings.transform_controller,
             settings.output_sink,
             settings.encoding,
-        )));
+            settings.stats.clone(),
+        ));
 
         let buffer = Arena::new(
             settings.memory_limiter,
@@ -70,9 +65,34 @@ where
             parser,
             buffer,
             has_buffered_data: false,
+            stats: settings.stats,
+        }
+    }
+
+    fn initial_parser_directive(transform_controller: &C) -> ParserDirective {
+        if transform_controller.initial_capture_flags().is_empty() {
+            ParserDirective::WherePossibleScanForTagsOnly
+        } else {
+            ParserDirective::Lex
         }
     }
 
+    /// Returns the stream to its initial state, so it can be used for another document.
+    /// The parsing buffer retains the memory allocated for it.
+    pub fn reset(&mut self) {
+        let initial_parser_directive = {
+            let mut dispatcher = self.dispatcher.borrow_mut();
+
+            dispatcher.reset();
+
+            Self::initial_parser_directive(dispatcher.transform_controller_mut())
+        };
+
+        self.parser.reset(initial_parser_directive);
+        self.buffer.reset();
+        self.has_buffered_data = false;
+    }
+
     fn buffer_blocked_bytes(
         &mut self,
         data: &[u8],
@@ -88,6 +108,10 @@ where
             self.has_buffered_data = true;
         }
 
+        if let Some(ref stats) = self.stats {
+            stats.record_buffering(self.buffer.bytes().len());
+        }
+
         trace!(@buffer self.buffer);
 
         Ok(())
@@ -96,6 +120,13 @@ where
     pub fn write(&mut self, data: &[u8]) -> Result<(), RewritingError> {
         trace!(@write data);
 
+        // NOTE: once the rewriting is stopped the input is passed through as is.
+        if self.dispatcher.borrow().rewriting_stopped() {
+            self.dispatcher.borrow_mut().pass_through(data);
+
+            return Ok(());
+        }
+
         let chunk = if self.has_buffered_data {
             self.buffer
                 .append(data)
@@ -108,11 +139,32 @@ where
 
         trace!(@chunk chunk);
 
-        let consumed_byte_count = self.parser.parse(chunk, false)?;
+        let start = self.dispatcher.borrow_mut().start(chunk, false)?;
+
+        // NOTE: the beginning of the input is buffered until the rewriter can tell
+        // whether it starts with a byte order mark.
+        let start = match start {
+            Some(start) => start,
+            None => return self.buffer_blocked_bytes(data, 0),
+        };
+
+        let consumed_byte_count = start + self.parser.parse(&chunk[start..], false)?;
+
+        if self.dispatcher.borrow().rewriting_stopped() {
+            // NOTE: the rest of the chunk, including the buffered part of it,
+            // is passed through without parsing.
+            self.dispatcher
+                .borrow_mut()
+                .pass_through_remaining_input(&chunk[start..]);
+
+            self.has_buffered_data = false;
+
+            return Ok(());
+        }
 
         self.dispatcher
             .borrow_mut()
-            .flush_remaining_input(&chunk, consumed_byte_count);
+            .flush_remaining_input(&chunk[start..], consumed_byte_count - start)?;
 
         if consumed_byte_count < chunk.len() {
             self.buffer_blocked_bytes(data, consumed_byte_count)?;
@@ -126,6 +178,10 @@ where
     pub fn end(&mut self) -> Result<(), RewritingError> {
         trace!(@end);
 
+        if self.dispatcher.borrow().rewriting_stopped() {
+            return self.dispatcher.borrow_mut().finish(&[]);
+        }
+
         let chunk = if self.has_buffered_data {
             self.buffer.bytes()
         } else {
@@ -134,8 +190,45 @@ where
 
         trace!(@chunk chunk);
 
+        let start = self
+            .dispatcher
+            .borrow_mut()
+            .start(chunk, true)?
+            .expect("Document should be started with the last chunk");
+
+        let chunk = &chunk[start..];
+
         self.parser.parse(chunk, true)?;
-        self.dispatcher.borrow_mut().finish(chunk)
+
+        let mut dispatcher = self.dispatcher.borrow_mut();
+
+        if dispatcher.rewriting_stopped() {
+            dispatcher.pass_through_remaining_input(chunk);
+
+            return dispatcher.finish(&[]);
+        }
+
+        dispatcher.finish(chunk)
+    }
+
+    #[inline]
+    pub fn disable_output(&mut self) {
+        self.dispatcher.borrow_mut().disable_output();
+    }
+
+    #[inline]
+    pub fn with_output_sink<T>(&self, f: impl FnOnce(&mut O) -> T) -> T {
+        f(self.dispatcher.borrow_mut().output_sink_mut())
+    }
+
+    #[inline]
+    pub fn with_transform_controller<T>(&self, f: impl FnOnce(&mut C) -> T) -> T {
+        f(self.dispatcher.borrow_mut().transform_controller_mut())
+    }
+
+    #[inline]
+    pub fn set_encoding(&mut self, encoding: &'static Encoding) {
+        self.dispatcher.borrow_mut().set_encoding(encoding);
     }
 
     #[cfg(feature = "integration_test")]
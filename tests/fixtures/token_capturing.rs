@@ -7,7 +7,7 @@ use lol_html::{
     TransformStream, Namespace, TransformStreamSettings, MemoryLimiter
 };
 use lol_html::errors::RewritingError;
-use lol_html::html_content::{DocumentEnd, TextType};
+use lol_html::html_content::{DocumentEnd, DocumentStart, TextType};
 use lol_html::test_utils::Output;
 
 macro_rules! expect_eql {
@@ -76,6 +76,10 @@ impl TransformController for TestTransformController<'_> {
         Ok(())
     }
 
+    fn handle_start(&mut self, _: &mut DocumentStart) -> Result<(), RewritingError> {
+        Ok(())
+    }
+
     fn handle_end(&mut self, _: &mut DocumentEnd) -> Result<(), RewritingError> {
         Ok(())
     }
@@ -107,7 +111,8 @@ pub fn parse(
             preallocated_parsing_buffer_size: 0,
             memory_limiter,
             encoding: encoding.into(),
-            strict: true
+            strict: true,
+            stats: None
         }
     );
 
@@ -95,6 +95,10 @@ fn main() {
         None => return,
     };
 
+    tracing_subscriber::fmt()
+        .with_max_level(tracing_subscriber::filter::LevelFilter::TRACE)
+        .init();
+
     let html = matches.free.first().unwrap();
     let tag_hint_mode = matches.opt_present("H");
 
@@ -105,6 +109,7 @@ fn main() {
         memory_limiter: MemoryLimiter::new_shared(2048),
         encoding: UTF_8,
         strict: true,
+        stats: None,
     });
 
     let parser = transform_stream.parser();